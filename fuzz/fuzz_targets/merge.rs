@@ -0,0 +1,18 @@
+//! Merges a generated value with a mutation of it.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use structured_merge_diff::fuzzing::{self, ByteSource};
+
+fuzz_target!(|data: &[u8]| {
+    let pt = fuzzing::fuzz_type();
+    let mut src = ByteSource::new(data);
+    let Ok(lhs) = pt.from_value(fuzzing::arbitrary_value(&pt, &mut src)) else {
+        return;
+    };
+    let Ok(rhs) = pt.from_value(fuzzing::mutate_value(&lhs, &mut src)) else {
+        return;
+    };
+    fuzzing::check_merge(&lhs, &rhs);
+});
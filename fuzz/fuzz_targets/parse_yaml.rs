@@ -0,0 +1,10 @@
+//! Parses arbitrary input as YAML of the fuzz type and checks what parses.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use structured_merge_diff::fuzzing;
+
+fuzz_target!(|data: &[u8]| {
+    fuzzing::check_parse(data);
+});
@@ -0,0 +1,14 @@
+//! Checks set identities and FieldsV1 round trips on generated sets.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use structured_merge_diff::fuzzing::{self, ByteSource};
+
+fuzz_target!(|data: &[u8]| {
+    let mut src = ByteSource::new(data);
+    let a = fuzzing::arbitrary_set(&mut src);
+    let b = fuzzing::arbitrary_set(&mut src);
+    fuzzing::check_set_round_trip(&a);
+    fuzzing::check_set_algebra(&a, &b);
+});
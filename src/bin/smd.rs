@@ -8,8 +8,8 @@ use std::io::{self, Write};
 use std::path::PathBuf;
 use std::process::ExitCode;
 
-use structured_merge_diff::typed::Parser as SchemaParser;
-use structured_merge_diff::value;
+use structured_merge_diff::fieldpath::{GraphFormat, ManagedFields};
+use structured_merge_diff::typed::{OutputOptions, Parser as SchemaParser};
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
@@ -30,11 +30,15 @@ OPTIONS:
 COMMANDS:
     list-types               List all types in the schema
     validate <FILE>          Validate a YAML/JSON file against the schema
-    merge --lhs <FILE> --rhs <FILE>
-                             Merge two YAML/JSON files
+    merge --lhs <FILE> --rhs <FILE> [--sort-sets]
+                             Merge two YAML/JSON files, optionally
+                             emitting set items in sorted order
     compare --lhs <FILE> --rhs <FILE>
                              Compare two YAML/JSON files
     fieldset <FILE>          Build a fieldset from a YAML/JSON file
+    fieldset --graph <dot|json> <FILE>
+                             Render serialized managed fields as an
+                             ownership graph
 "#,
         VERSION
     );
@@ -56,9 +60,9 @@ struct Cli {
 enum Command {
     ListTypes,
     Validate { file: PathBuf },
-    Merge { lhs: PathBuf, rhs: PathBuf },
+    Merge { lhs: PathBuf, rhs: PathBuf, sort_sets: bool },
     Compare { lhs: PathBuf, rhs: PathBuf },
-    Fieldset { file: PathBuf },
+    Fieldset { file: PathBuf, graph: Option<GraphFormat> },
 }
 
 fn parse_args() -> Result<Cli, String> {
@@ -116,6 +120,7 @@ fn parse_args() -> Result<Cli, String> {
             "merge" => {
                 let mut lhs: Option<PathBuf> = None;
                 let mut rhs: Option<PathBuf> = None;
+                let mut sort_sets = false;
                 i += 1;
                 while i < args.len() {
                     match args[i].as_str() {
@@ -133,6 +138,9 @@ fn parse_args() -> Result<Cli, String> {
                             }
                             rhs = Some(PathBuf::from(&args[i]));
                         }
+                        "--sort-sets" => {
+                            sort_sets = true;
+                        }
                         _ => {
                             i -= 1;
                             break;
@@ -142,7 +150,11 @@ fn parse_args() -> Result<Cli, String> {
                 }
                 match (lhs, rhs) {
                     (Some(l), Some(r)) => {
-                        command = Some(Command::Merge { lhs: l, rhs: r });
+                        command = Some(Command::Merge {
+                            lhs: l,
+                            rhs: r,
+                            sort_sets,
+                        });
                     }
                     _ => {
                         return Err("merge requires --lhs and --rhs arguments".to_string());
@@ -186,12 +198,23 @@ fn parse_args() -> Result<Cli, String> {
                 }
             }
             "fieldset" => {
+                let mut graph = None;
                 i += 1;
+                if i < args.len() && args[i] == "--graph" {
+                    i += 1;
+                    graph = match args.get(i).map(String::as_str) {
+                        Some("dot") => Some(GraphFormat::Dot),
+                        Some("json") => Some(GraphFormat::Json),
+                        _ => return Err("--graph requires dot or json".to_string()),
+                    };
+                    i += 1;
+                }
                 if i >= args.len() {
                     return Err("Missing file argument for fieldset".to_string());
                 }
                 command = Some(Command::Fieldset {
                     file: PathBuf::from(&args[i]),
+                    graph,
                 });
             }
             arg => {
@@ -264,15 +287,19 @@ fn run(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
         Command::Validate { file } => {
             validate(&parser, &type_name, &file, &mut output)?;
         }
-        Command::Merge { lhs, rhs } => {
-            merge(&parser, &type_name, &lhs, &rhs, &mut output)?;
+        Command::Merge { lhs, rhs, sort_sets } => {
+            let options = OutputOptions { sort_sets };
+            merge(&parser, &type_name, &lhs, &rhs, options, &mut output)?;
         }
         Command::Compare { lhs, rhs } => {
             compare(&parser, &type_name, &lhs, &rhs, &mut output)?;
         }
-        Command::Fieldset { file } => {
+        Command::Fieldset { file, graph: None } => {
             fieldset(&parser, &type_name, &file, &mut output)?;
         }
+        Command::Fieldset { file, graph: Some(format) } => {
+            fieldset_graph(&file, format, &mut output)?;
+        }
     }
 
     Ok(())
@@ -324,6 +351,7 @@ fn merge(
     type_name: &str,
     lhs_file: &PathBuf,
     rhs_file: &PathBuf,
+    options: OutputOptions,
     output: &mut dyn Write,
 ) -> Result<(), Box<dyn std::error::Error>> {
     let lhs_content = fs::read_to_string(lhs_file)
@@ -344,7 +372,7 @@ fn merge(
     let merged = lhs.merge(&rhs)
         .map_err(|e| format!("Merge failed: {}", e))?;
 
-    let yaml = value::to_yaml(merged.value())
+    let yaml = merged.to_yaml_with(options)
         .map_err(|e| format!("Failed to serialize result: {}", e))?;
 
     write!(output, "{}", yaml)?;
@@ -431,3 +459,22 @@ fn fieldset(
 
     Ok(())
 }
+
+fn fieldset_graph(
+    file: &PathBuf,
+    format: GraphFormat,
+    output: &mut dyn Write,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let content = fs::read_to_string(file)
+        .map_err(|e| format!("Failed to read file {:?}: {}", file, e))?;
+
+    let managers: ManagedFields = serde_yaml::from_str(&content)
+        .map_err(|e| format!("Failed to parse managed fields: {}", e))?;
+
+    let graph = managers.to_graph(format)
+        .map_err(|e| format!("Failed to build graph: {}", e))?;
+
+    writeln!(output, "{}", graph.trim_end())?;
+
+    Ok(())
+}
@@ -0,0 +1,292 @@
+//! Checks that a schema gives server-side apply sane behavior.
+//!
+//! Schema choices such as list types, keys and atomic maps decide how
+//! applies merge and who owns what. A CRD author can run operations typical
+//! of their resource through run, which checks at every step that:
+//!
+//! - applying the same config again changes nothing,
+//! - a manager applying the fields it owns, extracted from the result,
+//!   changes nothing,
+//! - a conflict is symmetric: once the applier forces its way through, the
+//!   manager it conflicted with re-applying its fields conflicts back.
+//!
+//! ```
+//! use structured_merge_diff::conformance;
+//! use structured_merge_diff::scenario::Op;
+//! use structured_merge_diff::typed::Parser;
+//!
+//! let pt = Parser::new("types:\n- name: root\n  map:\n    elementType:\n      scalar: string\n")
+//!     .unwrap()
+//!     .type_by_name("root");
+//! let report = conformance::run(
+//!     &pt,
+//!     &[Op::apply("kubectl", "v1", r#"{"a": "x"}"#), Op::apply("other", "v1", r#"{"a": "y"}"#)],
+//! );
+//! assert!(report.passed(), "{}", report);
+//! ```
+
+use crate::fieldpath::ManagedFields;
+use crate::merge::{ApplyError, Conflicts, UpdateError, Updater};
+use crate::scenario::{same, Op, OpKind};
+use crate::typed::{ParseableType, TypedValue};
+use std::fmt;
+
+/// Check is a property run verifies.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Check {
+    /// The operation ran, or failed only with conflicts.
+    Operation,
+    /// Running a successful operation again changes nothing.
+    Idempotence,
+    /// Applying the fields a manager owns, extracted from the object,
+    /// changes nothing.
+    ExtractRoundTrip,
+    /// A manager conflicted with conflicts back once overridden.
+    ConflictSymmetry,
+}
+
+impl fmt::Display for Check {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Check::Operation => write!(f, "operation"),
+            Check::Idempotence => write!(f, "idempotence"),
+            Check::ExtractRoundTrip => write!(f, "extract round trip"),
+            Check::ConflictSymmetry => write!(f, "conflict symmetry"),
+        }
+    }
+}
+
+/// Failure is a check that failed after an operation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Failure {
+    /// The index of the operation.
+    pub op: usize,
+    pub check: Check,
+    pub message: String,
+}
+
+/// Report lists the checks run and those that failed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+    /// The number of checks run.
+    pub checks: usize,
+    pub failures: Vec<Failure>,
+}
+
+impl Report {
+    /// Returns true if every check passed.
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    fn record(&mut self, op: usize, check: Check, result: Result<(), String>) {
+        self.checks += 1;
+        if let Err(message) = result {
+            self.failures.push(Failure { op, check, message });
+        }
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} checks, {} failed", self.checks, self.failures.len())?;
+        for failure in &self.failures {
+            write!(f, "\nop {}: {}: {}", failure.op, failure.check, failure.message)?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs the operations in order on objects of type `pt`, starting from an
+/// empty object, with a default Updater, and checks them.
+pub fn run(pt: &ParseableType, ops: &[Op]) -> Report {
+    run_with_updater(pt, &Updater::builder().build(), ops)
+}
+
+/// Runs the operations as run does, with `updater`.
+pub fn run_with_updater(pt: &ParseableType, updater: &Updater, ops: &[Op]) -> Report {
+    let mut report = Report::default();
+    let Ok(empty) = pt.from_yaml("{}") else {
+        report.record(0, Check::Operation, Err("the type isn't a map".to_string()));
+        return report;
+    };
+    let mut live = empty.empty();
+    let mut managers = ManagedFields::new();
+
+    for (i, op) in ops.iter().enumerate() {
+        let config = match pt.from_yaml(&op.object) {
+            Ok(config) => config,
+            Err(e) => {
+                report.record(i, Check::Operation, Err(e.to_string()));
+                continue;
+            }
+        };
+        match op.run(updater, &live, &config, &mut managers) {
+            Ok(result) => {
+                report.record(i, Check::Operation, Ok(()));
+                report.record(
+                    i,
+                    Check::Idempotence,
+                    idempotence(updater, op, &result, &config, &managers),
+                );
+                if op.kind != OpKind::Update {
+                    let round_trip = extract_round_trip(updater, op, &result, &managers);
+                    report.record(i, Check::ExtractRoundTrip, round_trip);
+                }
+                live = result;
+            }
+            Err(UpdateError::Conflicts(conflicts)) => {
+                report.record(i, Check::Operation, Ok(()));
+                if op.kind == OpKind::Apply {
+                    let symmetry = conflict_symmetry(updater, op, &live, &config, &managers, &conflicts);
+                    report.record(i, Check::ConflictSymmetry, symmetry);
+                }
+            }
+            Err(e) => report.record(i, Check::Operation, Err(e.to_string())),
+        }
+    }
+    report
+}
+
+/// Checks that running `op` again on its result changes nothing.
+fn idempotence(
+    updater: &Updater,
+    op: &Op,
+    result: &TypedValue,
+    config: &TypedValue,
+    managers: &ManagedFields,
+) -> Result<(), String> {
+    let mut again = managers.clone();
+    let rerun = op.run(updater, result, config, &mut again).map_err(|e| e.to_string())?;
+    if !same(&rerun, result) || again != *managers {
+        return Err(format!(
+            "running again changed the object to {:?} and managers to {}",
+            rerun.value(),
+            again
+        ));
+    }
+    Ok(())
+}
+
+/// Checks that the manager of `op` applying the fields it owns changes
+/// nothing.
+fn extract_round_trip(updater: &Updater, op: &Op, result: &TypedValue, managers: &ManagedFields) -> Result<(), String> {
+    let Some(owned) = managers.get(&op.manager) else {
+        return Ok(());
+    };
+    let extracted = result.extract_items(owned.set());
+    let mut again = managers.clone();
+    let reapplied = updater
+        .apply(result, &extracted, &op.api_version, &mut again, &op.manager, false)
+        .map_err(|e| format!("applying the extracted fields {:?} failed: {}", extracted.value(), e))?;
+    if !same(&reapplied, result) || again != *managers {
+        return Err(format!(
+            "applying the extracted fields {:?} changed the object to {:?} and managers to {}",
+            extracted.value(),
+            reapplied.value(),
+            again
+        ));
+    }
+    Ok(())
+}
+
+/// Checks that the appliers `op` conflicted with conflict with it in turn
+/// once it forced its apply.
+fn conflict_symmetry(
+    updater: &Updater,
+    op: &Op,
+    live: &TypedValue,
+    config: &TypedValue,
+    managers: &ManagedFields,
+    conflicts: &Conflicts,
+) -> Result<(), String> {
+    let mut forced_managers = managers.clone();
+    let forced = updater
+        .apply(live, config, &op.api_version, &mut forced_managers, &op.manager, true)
+        .map_err(|e| format!("forcing the apply failed: {}", e))?;
+    for conflict in conflicts.iter() {
+        let Some(owned) = managers.get(&conflict.manager).filter(|vs| vs.applied) else {
+            continue;
+        };
+        let own_config = live.extract_items(owned.set());
+        let mut again = forced_managers.clone();
+        let back = updater.apply(
+            &forced,
+            &own_config,
+            &owned.api_version,
+            &mut again,
+            &conflict.manager,
+            false,
+        );
+        let conflicts_back = match back {
+            Err(ApplyError::Conflicts(back)) => back,
+            _ => Conflicts::new(),
+        };
+        if !conflicts_back
+            .iter()
+            .any(|c| c.manager == op.manager && c.path == conflict.path)
+        {
+            return Err(format!(
+                "{} re-applying its fields doesn't conflict with {} at {}",
+                conflict.manager, op.manager, conflict.path
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::typed::Parser;
+
+    #[test]
+    fn test_conformance() {
+        let pt = Parser::new(
+            r#"types:
+- name: root
+  map:
+    fields:
+    - name: replicas
+      type:
+        scalar: numeric
+    - name: ports
+      type:
+        list:
+          elementType:
+            map:
+              fields:
+              - name: port
+                type:
+                  scalar: numeric
+              - name: protocol
+                type:
+                  scalar: string
+          elementRelationship: associative
+          keys: [port]
+"#,
+        )
+        .unwrap()
+        .type_by_name("root");
+        let ops = [
+            Op::apply("kubectl", "v1", r#"{"replicas": 1, "ports": [{"port": 80}]}"#),
+            Op::update("hpa", "v1", r#"{"replicas": 3, "ports": [{"port": 80}]}"#),
+            Op::apply(
+                "kubectl",
+                "v1",
+                r#"{"replicas": 3, "ports": [{"port": 80}, {"port": 443}]}"#,
+            ),
+            Op::apply("helm", "v1", r#"{"ports": [{"port": 443, "protocol": "TCP"}]}"#),
+            Op::apply("other", "v1", r#"{"ports": [{"port": 443, "protocol": "UDP"}]}"#),
+            Op::force_apply("other", "v1", r#"{"ports": [{"port": 443, "protocol": "UDP"}]}"#),
+        ];
+        let report = run(&pt, &ops);
+        assert!(report.passed(), "{}", report);
+        assert_eq!(report.checks, 16);
+
+        let report = run(&pt, &[Op::apply("kubectl", "v1", r#"{"replicas": "many"}"#)]);
+        assert_eq!(report.failures.len(), 1);
+        assert_eq!(report.failures[0].check, Check::Operation);
+    }
+}
@@ -0,0 +1,210 @@
+//! Fault injection, enabled by the `fault-injection` feature.
+//!
+//! FaultyConverter fails chosen Converter calls, and fail_resolution makes
+//! schemas fail to resolve a named type, so that consumers can test how
+//! their retries and error reporting handle failures in the middle of a
+//! merge.
+
+use crate::fieldpath::{APIVersion, Set};
+use crate::merge::{ConversionError, ConversionErrorKind, Converter};
+use crate::schema::TypeRef;
+use crate::typed::TypedValue;
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::sync::{Mutex, PoisonError};
+
+/// FaultyConverter wraps a converter and fails the chosen calls to convert
+/// and convert_set, which are numbered together from 0.
+pub struct FaultyConverter<C> {
+    inner: C,
+    failing_calls: BTreeSet<usize>,
+    failing_versions: BTreeSet<String>,
+    kind: ConversionErrorKind,
+    calls: Mutex<usize>,
+}
+
+impl<C: Converter> FaultyConverter<C> {
+    /// Returns a converter that passes every call to `inner`.
+    pub fn new(inner: C) -> Self {
+        FaultyConverter {
+            inner,
+            failing_calls: BTreeSet::new(),
+            failing_versions: BTreeSet::new(),
+            kind: ConversionErrorKind::Internal,
+            calls: Mutex::new(0),
+        }
+    }
+
+    /// Fails the call with the given number.
+    pub fn fail_call(mut self, call: usize) -> Self {
+        self.failing_calls.insert(call);
+        self
+    }
+
+    /// Fails every conversion to `version`.
+    pub fn fail_version(mut self, version: impl Into<String>) -> Self {
+        self.failing_versions.insert(version.into());
+        self
+    }
+
+    /// Sets the kind of the injected errors, Internal by default. Errors
+    /// of kind MissingVersion are usually skipped instead of failing.
+    pub fn kind(mut self, kind: ConversionErrorKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    /// Returns the number of calls made so far.
+    pub fn calls(&self) -> usize {
+        *self.calls.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Counts a call to `version` and returns the error to inject, if any.
+    fn fault(&self, version: &APIVersion) -> Option<ConversionError> {
+        let mut calls = self.calls.lock().unwrap_or_else(PoisonError::into_inner);
+        let call = *calls;
+        *calls += 1;
+        if !self.failing_calls.contains(&call) && !self.failing_versions.contains(version.as_str()) {
+            return None;
+        }
+        Some(ConversionError::new(self.kind, format!("injected failure of conversion call {} to {}", call, version)))
+    }
+}
+
+impl<C: Converter> Converter for FaultyConverter<C> {
+    fn convert(&self, obj: &TypedValue, version: &APIVersion) -> Result<TypedValue, ConversionError> {
+        match self.fault(version) {
+            Some(err) => Err(err),
+            None => self.inner.convert(obj, version),
+        }
+    }
+
+    fn convert_set(&self, set: &Set, from: &APIVersion, to: &APIVersion) -> Option<Result<Set, ConversionError>> {
+        let result = self.inner.convert_set(set, from, to)?;
+        match self.fault(to) {
+            Some(err) => Some(Err(err)),
+            None => Some(result),
+        }
+    }
+}
+
+thread_local! {
+    /// The named types that fail to resolve, with the number of
+    /// resolutions left before they do.
+    static RESOLUTION_FAULTS: RefCell<Vec<(String, usize)>> = const { RefCell::new(Vec::new()) };
+}
+
+/// ResolutionFault makes resolving a named type fail until it's dropped.
+#[must_use = "the fault is removed when the guard is dropped"]
+pub struct ResolutionFault {
+    name: String,
+}
+
+impl Drop for ResolutionFault {
+    fn drop(&mut self) {
+        RESOLUTION_FAULTS.with(|faults| faults.borrow_mut().retain(|(name, _)| *name != self.name));
+    }
+}
+
+/// Makes every schema on the current thread fail to resolve the named type
+/// after resolving it `after` more times, as if the type were missing,
+/// until the returned guard is dropped.
+///
+/// Validation reports types that fail to resolve as errors, while
+/// operations on validated values skip them like types missing from the
+/// schema.
+pub fn fail_resolution(name: impl Into<String>, after: usize) -> ResolutionFault {
+    let name = name.into();
+    RESOLUTION_FAULTS.with(|faults| {
+        let mut faults = faults.borrow_mut();
+        faults.retain(|(n, _)| *n != name);
+        faults.push((name.clone(), after));
+    });
+    ResolutionFault { name }
+}
+
+/// Returns true if resolving `tr` should fail, counting the resolution.
+pub(crate) fn resolution_fails(tr: &TypeRef) -> bool {
+    let Some(name) = &tr.named_type else {
+        return false;
+    };
+    RESOLUTION_FAULTS.with(|faults| {
+        let mut faults = faults.borrow_mut();
+        match faults.iter_mut().find(|(n, _)| n == name) {
+            Some((_, 0)) => true,
+            Some((_, after)) => {
+                *after -= 1;
+                false
+            }
+            None => false,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merge::{ApplyError, Updater};
+    use crate::typed::{deduced_parseable_type, Parser};
+
+    struct Identity;
+
+    impl Converter for Identity {
+        fn convert(&self, obj: &TypedValue, _version: &APIVersion) -> Result<TypedValue, ConversionError> {
+            Ok(obj.clone())
+        }
+    }
+
+    #[test]
+    fn test_faulty_converter() {
+        let pt = deduced_parseable_type();
+        let live = pt.from_yaml(r#"{"a": 1}"#).unwrap();
+        let config = pt.from_yaml(r#"{"a": 2}"#).unwrap();
+        let managers = crate::managed_fields! {
+            "old" => { version: "v2", applied: false, paths: [["f:a"]] },
+        };
+        let v1 = APIVersion::new("v1");
+
+        let updater = Updater::builder()
+            .converter(Box::new(FaultyConverter::new(Identity).fail_version("v2")))
+            .build();
+        let err = updater
+            .apply(&live, &config, &v1, &mut managers.clone(), "new", false)
+            .unwrap_err();
+        let ApplyError::ConversionError(err) = err else {
+            panic!("unexpected error {}", err);
+        };
+        assert!(err.message.starts_with("injected failure of conversion call"));
+
+        // Missing versions are skipped
+        let updater = Updater::builder()
+            .converter(Box::new(
+                FaultyConverter::new(Identity)
+                    .fail_version("v2")
+                    .kind(ConversionErrorKind::MissingVersion),
+            ))
+            .build();
+        assert!(updater.apply(&live, &config, &v1, &mut managers.clone(), "new", true).is_ok());
+    }
+
+    #[test]
+    fn test_fail_resolution() {
+        let pt = Parser::new(
+            "types:\n- name: root\n  map:\n    fields:\n    - name: spec\n      type:\n        namedType: spec\n- name: spec\n  map:\n    fields:\n    - name: replicas\n      type:\n        scalar: numeric\n",
+        )
+        .unwrap()
+        .type_by_name("root");
+        let yaml = r#"{"spec": {"replicas": 1}}"#;
+
+        let fault = fail_resolution("spec", 0);
+        assert!(pt.from_yaml(yaml).is_err());
+        drop(fault);
+        assert!(pt.from_yaml(yaml).is_ok());
+
+        // Parsing resolves the type once, so the second parse fails
+        let _fault = fail_resolution("spec", 1);
+        assert!(pt.from_yaml(yaml).is_ok());
+        let err = pt.from_yaml(yaml).unwrap_err();
+        assert!(err.message.contains("no type found matching: spec"), "{}", err.message);
+    }
+}
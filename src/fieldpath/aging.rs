@@ -0,0 +1,121 @@
+//! When managers last set the fields they own, enabled by the
+//! `ownership-aging` feature.
+//!
+//! ManagedFields only record what each manager owns. FieldAges keeps, next
+//! to them, the time or revision at which each owned field was last set, so
+//! that cleanup jobs can find fields their managers stopped re-asserting.
+//! It holds an entry per owned path, which is why it is opt-in.
+
+use super::{ManagedFields, Path, Set};
+use std::collections::{BTreeMap, HashMap};
+
+/// FieldAges maps the fields each manager owns to when the manager last
+/// set them, as a timestamp, a resource version or any other ordered stamp.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldAges<T> {
+    managers: BTreeMap<String, HashMap<Path, T>>,
+}
+
+impl<T> Default for FieldAges<T> {
+    fn default() -> Self {
+        FieldAges {
+            managers: BTreeMap::new(),
+        }
+    }
+}
+
+impl<T: Clone + Ord> FieldAges<T> {
+    /// Creates an empty FieldAges.
+    pub fn new() -> Self {
+        FieldAges::default()
+    }
+
+    /// Records that `manager` set the fields of `asserted` at `at`, after an
+    /// operation left ownership as `managers` records it. An apply asserts
+    /// all the fields of its config and an update those it changed.
+    ///
+    /// Fields no longer owned are forgotten. Owned fields without an age,
+    /// such as those owned before tracking started, are given `at`, so that
+    /// they are never taken for stale before they had a chance to be set.
+    pub fn record(&mut self, managers: &ManagedFields, manager: &str, asserted: &Set, at: T) {
+        let entry = self.managers.entry(manager.to_string()).or_default();
+        asserted.iterate(|path| {
+            entry.insert(path.clone(), at.clone());
+        });
+
+        self.managers.retain(|name, _| managers.contains(name));
+        for (name, vs) in managers.iter() {
+            let ages = self.managers.entry(name.clone()).or_default();
+            ages.retain(|path, _| vs.set().has(path));
+            vs.set().iterate(|path| {
+                if !ages.contains_key(path) {
+                    ages.insert(path.clone(), at.clone());
+                }
+            });
+        }
+    }
+
+    /// Returns when `manager` last set the field at `path`.
+    pub fn last_set(&self, manager: &str, path: &Path) -> Option<&T> {
+        self.managers.get(manager)?.get(path)
+    }
+
+    /// Returns the fields of each manager that were last set before
+    /// `cutoff`, leaving out managers without any.
+    pub fn stale(&self, cutoff: &T) -> BTreeMap<String, Set> {
+        let mut stale = BTreeMap::new();
+        for (manager, ages) in &self.managers {
+            let mut set = Set::new();
+            for (path, at) in ages {
+                if at < cutoff {
+                    set.insert(path);
+                }
+            }
+            if !set.is_empty() {
+                stale.insert(manager.clone(), set);
+            }
+        }
+        stale
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fieldpath::PathElement;
+
+    #[test]
+    fn test_field_ages() {
+        let spec =
+            |name: &str| Path::from_elements(vec![PathElement::field_name("spec"), PathElement::field_name(name)]);
+        let set = |paths: &[Path]| {
+            let mut set = Set::new();
+            for path in paths {
+                set.insert(path);
+            }
+            set
+        };
+        let mut ages = FieldAges::new();
+
+        // Fields owned before tracking count as set at the first record
+        let managers = ManagedFields::builder()
+            .applied("kubectl", "apps/v1", [spec("replicas"), spec("paused")])
+            .updated("hpa", "apps/v1", [spec("minReadySeconds")])
+            .build();
+        ages.record(&managers, "kubectl", &set(&[spec("replicas"), spec("paused")]), 10);
+        assert_eq!(ages.last_set("hpa", &spec("minReadySeconds")), Some(&10));
+
+        // Re-asserted fields get younger, and fields given up are forgotten
+        let managers = ManagedFields::builder()
+            .applied("kubectl", "apps/v1", [spec("replicas")])
+            .updated("hpa", "apps/v1", [spec("minReadySeconds")])
+            .build();
+        ages.record(&managers, "kubectl", &set(&[spec("replicas")]), 20);
+        assert_eq!(ages.last_set("kubectl", &spec("replicas")), Some(&20));
+        assert_eq!(ages.last_set("kubectl", &spec("paused")), None);
+
+        let stale = ages.stale(&15);
+        assert_eq!(stale.len(), 1);
+        assert_eq!(stale.get("hpa"), Some(&set(&[spec("minReadySeconds")])));
+    }
+}
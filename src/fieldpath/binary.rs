@@ -0,0 +1,351 @@
+//! Compact binary encoding for Sets.
+//!
+//! The encoding starts with a magic header and a version byte, followed by
+//! the set tree. Every level lists its members and then its children, both
+//! in sorted order, so equal sets always encode to the same bytes. Integers
+//! are LEB128 varints, with signed values zigzag-encoded.
+
+#![cfg_attr(not(test), deny(clippy::indexing_slicing, clippy::string_slice))]
+
+use super::path::{Path, PathElement};
+use super::serialize::SerializeError;
+use super::set::Set;
+use crate::value::{Field, FieldList, Map, Value};
+
+const MAGIC: &[u8; 4] = b"SMDS";
+
+/// The current version of the binary Set encoding.
+pub const BINARY_SET_VERSION: u8 = 1;
+
+const PE_FIELD: u8 = 0;
+const PE_KEY: u8 = 1;
+const PE_VALUE: u8 = 2;
+const PE_INDEX: u8 = 3;
+/// Marks a wildcard level, listed first among its members.
+const PE_WILDCARD: u8 = 4;
+const PE_EXTENSION: u8 = 5;
+
+const V_NULL: u8 = 0;
+const V_FALSE: u8 = 1;
+const V_TRUE: u8 = 2;
+const V_INT: u8 = 3;
+const V_FLOAT: u8 = 4;
+const V_STRING: u8 = 5;
+const V_LIST: u8 = 6;
+const V_MAP: u8 = 7;
+
+impl Set {
+    /// Encodes the Set in the compact binary format.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(MAGIC);
+        out.push(BINARY_SET_VERSION);
+        write_set(&mut out, self);
+        out
+    }
+
+    /// Decodes a Set from the compact binary format.
+    pub fn from_bytes(data: &[u8]) -> Result<Set, SerializeError> {
+        let mut r = Reader { data, pos: 0 };
+        if r.take(MAGIC.len())? != MAGIC {
+            return Err(SerializeError::new("not a binary set encoding"));
+        }
+        let version = r.byte()?;
+        if version != BINARY_SET_VERSION {
+            return Err(SerializeError::new(format!(
+                "unsupported binary set version: {}",
+                version
+            )));
+        }
+        let set = r.set()?;
+        if r.pos != data.len() {
+            return Err(SerializeError::new("trailing bytes after binary set"));
+        }
+        Ok(set)
+    }
+}
+
+fn write_set(out: &mut Vec<u8>, set: &Set) {
+    let wildcard = set.has_wildcard(&Path::new());
+    write_len(out, set.members.len() + usize::from(wildcard));
+    if wildcard {
+        out.push(PE_WILDCARD);
+    }
+    for pe in set.members.iter() {
+        write_path_element(out, pe);
+    }
+    write_len(out, set.children.len());
+    for (pe, child) in &set.children {
+        write_path_element(out, pe);
+        write_set(out, child);
+    }
+}
+
+fn write_path_element(out: &mut Vec<u8>, pe: &PathElement) {
+    match pe {
+        PathElement::FieldName(name) => {
+            out.push(PE_FIELD);
+            write_str(out, name);
+        }
+        PathElement::Key(fields) => {
+            out.push(PE_KEY);
+            write_len(out, fields.fields.len());
+            for field in &fields.fields {
+                write_str(out, &field.name);
+                write_value(out, &field.value);
+            }
+        }
+        PathElement::Value(v) => {
+            out.push(PE_VALUE);
+            write_value(out, v);
+        }
+        PathElement::Index(i) => {
+            out.push(PE_INDEX);
+            write_varint(out, zigzag(i64::from(*i)));
+        }
+        PathElement::Extension(kind, v) => {
+            out.push(PE_EXTENSION);
+            write_str(out, kind);
+            write_value(out, v);
+        }
+    }
+}
+
+fn write_value(out: &mut Vec<u8>, v: &Value) {
+    match v {
+        Value::Null => out.push(V_NULL),
+        Value::Bool(false) => out.push(V_FALSE),
+        Value::Bool(true) => out.push(V_TRUE),
+        Value::Int(i) => {
+            out.push(V_INT);
+            write_varint(out, zigzag(*i));
+        }
+        Value::Float(f) => {
+            out.push(V_FLOAT);
+            out.extend_from_slice(&f.to_le_bytes());
+        }
+        Value::String(s) => {
+            out.push(V_STRING);
+            write_str(out, s);
+        }
+        Value::List(items) => {
+            out.push(V_LIST);
+            write_len(out, items.len());
+            for item in items {
+                write_value(out, item);
+            }
+        }
+        Value::Map(m) => {
+            out.push(V_MAP);
+            write_len(out, m.len());
+            for (k, v) in m.iter() {
+                write_str(out, k);
+                write_value(out, v);
+            }
+        }
+    }
+}
+
+fn write_str(out: &mut Vec<u8>, s: &str) {
+    write_len(out, s.len());
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn write_len(out: &mut Vec<u8>, n: usize) {
+    write_varint(out, n as u64);
+}
+
+fn write_varint(out: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        out.push((n as u8) | 0x80);
+        n >>= 7;
+    }
+    out.push(n as u8);
+}
+
+fn zigzag(i: i64) -> u64 {
+    ((i << 1) ^ (i >> 63)) as u64
+}
+
+fn unzigzag(n: u64) -> i64 {
+    ((n >> 1) as i64) ^ -((n & 1) as i64)
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], SerializeError> {
+        let end = self.pos.checked_add(n);
+        let bytes = end
+            .and_then(|end| self.data.get(self.pos..end))
+            .ok_or_else(|| SerializeError::new("unexpected end of binary set"))?;
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, SerializeError> {
+        let [b] = self.take(1)? else {
+            return Err(SerializeError::new("unexpected end of binary set"));
+        };
+        Ok(*b)
+    }
+
+    fn varint(&mut self) -> Result<u64, SerializeError> {
+        let mut n = 0u64;
+        for shift in (0..64).step_by(7) {
+            let b = self.byte()?;
+            n |= u64::from(b & 0x7f) << shift;
+            if b & 0x80 == 0 {
+                return Ok(n);
+            }
+        }
+        Err(SerializeError::new("varint overflow in binary set"))
+    }
+
+    fn len(&mut self) -> Result<usize, SerializeError> {
+        let n = self.varint()?;
+        // Every counted item takes at least one byte, which bounds
+        // allocations on corrupt input.
+        usize::try_from(n)
+            .ok()
+            .filter(|n| *n <= self.data.len() - self.pos)
+            .ok_or_else(|| SerializeError::new("invalid length in binary set"))
+    }
+
+    fn string(&mut self) -> Result<String, SerializeError> {
+        let n = self.len()?;
+        let bytes = self.take(n)?;
+        String::from_utf8(bytes.to_vec())
+            .map_err(|e| SerializeError::new(format!("invalid string in binary set: {}", e)))
+    }
+
+    fn set(&mut self) -> Result<Set, SerializeError> {
+        let mut set = Set::new();
+        for _ in 0..self.len()? {
+            if self.data.get(self.pos) == Some(&PE_WILDCARD) {
+                self.pos += 1;
+                set.insert_wildcard(&Path::new());
+                continue;
+            }
+            set.members.insert(self.path_element()?);
+        }
+        for _ in 0..self.len()? {
+            let pe = self.path_element()?;
+            let child = self.set()?;
+            set.children.insert(pe, child);
+        }
+        Ok(set)
+    }
+
+    fn path_element(&mut self) -> Result<PathElement, SerializeError> {
+        match self.byte()? {
+            PE_FIELD => Ok(PathElement::FieldName(self.string()?)),
+            PE_KEY => {
+                let n = self.len()?;
+                let mut fields = Vec::with_capacity(n);
+                for _ in 0..n {
+                    let name = self.string()?;
+                    let value = self.value()?;
+                    fields.push(Field { name, value });
+                }
+                Ok(PathElement::Key(FieldList { fields }))
+            }
+            PE_VALUE => Ok(PathElement::Value(self.value()?)),
+            PE_INDEX => {
+                let i = i32::try_from(unzigzag(self.varint()?))
+                    .map_err(|_| SerializeError::new("index out of range in binary set"))?;
+                Ok(PathElement::Index(i))
+            }
+            PE_EXTENSION => {
+                let kind = self.string()?;
+                Ok(PathElement::Extension(kind, self.value()?))
+            }
+            tag => Err(SerializeError::new(format!(
+                "unknown path element tag in binary set: {}",
+                tag
+            ))),
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, SerializeError> {
+        match self.byte()? {
+            V_NULL => Ok(Value::Null),
+            V_FALSE => Ok(Value::Bool(false)),
+            V_TRUE => Ok(Value::Bool(true)),
+            V_INT => Ok(Value::Int(unzigzag(self.varint()?))),
+            V_FLOAT => {
+                let mut bytes = [0u8; 8];
+                bytes.copy_from_slice(self.take(8)?);
+                Ok(Value::Float(f64::from_le_bytes(bytes)))
+            }
+            V_STRING => Ok(Value::String(self.string()?)),
+            V_LIST => {
+                let n = self.len()?;
+                let mut items = Vec::with_capacity(n);
+                for _ in 0..n {
+                    items.push(self.value()?);
+                }
+                Ok(Value::List(items))
+            }
+            V_MAP => {
+                let mut map = Map::new();
+                for _ in 0..self.len()? {
+                    let k = self.string()?;
+                    let v = self.value()?;
+                    map.set(k, v);
+                }
+                Ok(Value::Map(map))
+            }
+            tag => Err(SerializeError::new(format!(
+                "unknown value tag in binary set: {}",
+                tag
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_binary_roundtrip() {
+        let examples = vec![
+            r#"{}"#,
+            r#"{"f:a":{".":{},"f:b":{}},"f:c":{}}"#,
+            r#"{"f:aaa":{"k:{\"name\":\"first\",\"port\":-443}":{"i:0":{},"i:-1":{},"v:\"x\"":{},"v:2.5":{},"v:null":{}}}}"#,
+            r#"{"f:list":{"v:[1,{\"a\":true}]":{},"v:{\"b\":false}":{}}}"#,
+        ];
+
+        for example in examples {
+            let set = Set::from_json(example.as_bytes()).unwrap();
+            let bytes = set.to_bytes();
+            assert_eq!(&bytes[..4], MAGIC);
+            assert_eq!(bytes[4], BINARY_SET_VERSION);
+
+            let decoded = Set::from_bytes(&bytes).unwrap();
+            assert_eq!(decoded, set, "roundtrip mismatch for {}", example);
+            assert_eq!(decoded.to_bytes(), bytes);
+        }
+    }
+
+    #[test]
+    fn test_binary_rejects_invalid() {
+        let bytes = Set::from_json(br#"{"f:a":{"f:b":{}}}"#).unwrap().to_bytes();
+
+        assert!(Set::from_bytes(b"JSON{}").is_err());
+        assert!(Set::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+
+        let mut future = bytes.clone();
+        future[4] = BINARY_SET_VERSION + 1;
+        let err = Set::from_bytes(&future).unwrap_err();
+        assert!(err.message.contains("unsupported binary set version"));
+
+        let mut trailing = bytes;
+        trailing.push(0);
+        assert!(Set::from_bytes(&trailing).is_err());
+    }
+}
@@ -0,0 +1,198 @@
+//! The encoded size of managed fields, and trimming them to a budget.
+//!
+//! Managed fields are stored with the object, so objects with many
+//! managers can outgrow the size limit of the store, such as the 1.5MiB of
+//! etcd. Trimming collapses the least valuable entries, the update managers
+//! that wrote the longest ago, into a single entry, as kube-apiserver does
+//! when an object has too many of them. The fields stay owned, only by whom
+//! is lost.
+
+use super::serialize::SerializeError;
+use super::{ManagedFields, VersionedSet};
+use std::fmt;
+use std::io;
+
+/// The manager that trimmed update managers are collapsed into by default,
+/// as in kube-apiserver.
+pub const ANCIENT_CHANGES_MANAGER: &str = "ancient-changes";
+
+/// TrimPolicy chooses the managers trim_to_budget collapses, and what into.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TrimPolicy {
+    /// Update managers from the oldest to the newest, as the timestamps of
+    /// their managed fields entries order them. Update managers left out
+    /// are taken for older than those listed, in order of name.
+    pub oldest_first: Vec<String>,
+    /// The manager collapsed entries are merged into, or
+    /// ANCIENT_CHANGES_MANAGER if None.
+    pub bucket: Option<String>,
+}
+
+/// TrimError is returned when managed fields can't be trimmed to fit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrimError {
+    pub message: String,
+}
+
+impl fmt::Display for TrimError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for TrimError {}
+
+impl From<SerializeError> for TrimError {
+    fn from(e: SerializeError) -> Self {
+        TrimError { message: e.message }
+    }
+}
+
+/// Counts the bytes written to it.
+struct ByteCounter(usize);
+
+impl io::Write for ByteCounter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0 += buf.len();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl ManagedFields {
+    /// Returns the size in bytes of the FieldsV1 JSON of all the managers,
+    /// which is what their managed fields entries take beyond the manager
+    /// names, versions and timestamps.
+    pub fn encoded_size(&self) -> Result<usize, SerializeError> {
+        let mut counter = ByteCounter(0);
+        for (_, vs) in self.iter() {
+            vs.set().to_writer(&mut counter)?;
+        }
+        Ok(counter.0)
+    }
+
+    /// Collapses update managers, the oldest first, into the bucket manager
+    /// of the policy until encoded_size is at most `budget`, and returns the
+    /// managers collapsed, in order.
+    ///
+    /// Applied managers are never collapsed. Neither are update managers at
+    /// another API version than the bucket, which takes that of the first
+    /// manager collapsed into it, since their paths can't be merged without
+    /// conversion. If the managers can't be trimmed to fit, they are left
+    /// as they are and an error is returned.
+    pub fn trim_to_budget(&mut self, budget: usize, policy: &TrimPolicy) -> Result<Vec<String>, TrimError> {
+        let size = self.encoded_size()?;
+        if size <= budget {
+            return Ok(Vec::new());
+        }
+        let bucket = policy.bucket.as_deref().unwrap_or(ANCIENT_CHANGES_MANAGER);
+
+        let is_candidate = |name: &str| name != bucket && self.get(name).is_some_and(|vs| !vs.applied);
+        let mut candidates: Vec<&str> = self
+            .managers()
+            .map(String::as_str)
+            .filter(|name| !policy.oldest_first.iter().any(|listed| listed == name))
+            .collect();
+        candidates.extend(policy.oldest_first.iter().map(String::as_str));
+        candidates.retain(|name| is_candidate(name));
+
+        let mut trimmed = self.clone();
+        let mut collapsed = Vec::new();
+        for name in candidates {
+            let Some(vs) = trimmed.get(name) else {
+                continue;
+            };
+            let merged = match trimmed.get(bucket) {
+                Some(into) if into.api_version != vs.api_version => continue,
+                Some(into) => VersionedSet::new(into.set().union(vs.set()), into.api_version.clone(), into.applied),
+                None => VersionedSet::new(vs.set().clone(), vs.api_version.clone(), false),
+            };
+            trimmed.remove(name);
+            trimmed.insert(bucket, merged);
+            collapsed.push(name.to_string());
+            if trimmed.encoded_size()? <= budget {
+                *self = trimmed;
+                return Ok(collapsed);
+            }
+        }
+        Err(TrimError {
+            message: format!(
+                "managed fields take {} bytes, {} after trimming, over the budget of {}",
+                size,
+                trimmed.encoded_size()?,
+                budget
+            ),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::managed_fields;
+
+    #[test]
+    fn test_trim_to_budget() {
+        let managers = managed_fields! {
+            "kubectl" => { version: "v1", applied: true, paths: [["f:spec", "f:replicas"]] },
+            "hpa" => { version: "v1", applied: false, paths: [["f:spec", "f:replicas"], ["f:spec", "f:paused"]] },
+            "old-controller" => { version: "v1", applied: false, paths: [["f:spec", "f:paused"]] },
+            "beta-controller" => { version: "v1beta1", applied: false, paths: [["f:spec", "f:min"]] },
+        };
+        let json_size = |managers: &ManagedFields| {
+            managers
+                .iter()
+                .map(|(_, vs)| vs.set().to_json().unwrap().len())
+                .sum::<usize>()
+        };
+        let size = managers.encoded_size().unwrap();
+        assert_eq!(size, json_size(&managers));
+
+        // Within the budget, nothing changes
+        let mut trimmed = managers.clone();
+        assert_eq!(trimmed.trim_to_budget(size, &TrimPolicy::default()), Ok(vec![]));
+        assert_eq!(trimmed, managers);
+
+        // The oldest update managers go first, and only as many as needed
+        let mut trimmed = managers.clone();
+        let policy = TrimPolicy {
+            oldest_first: vec![
+                "old-controller".to_string(),
+                "hpa".to_string(),
+                "beta-controller".to_string(),
+            ],
+            bucket: None,
+        };
+        assert_eq!(
+            trimmed.trim_to_budget(size - 1, &policy).unwrap(),
+            vec!["old-controller", "hpa"]
+        );
+        assert_eq!(
+            trimmed,
+            managed_fields! {
+                "kubectl" => { version: "v1", applied: true, paths: [["f:spec", "f:replicas"]] },
+                "ancient-changes" => {
+                    version: "v1",
+                    applied: false,
+                    paths: [["f:spec", "f:replicas"], ["f:spec", "f:paused"]],
+                },
+                "beta-controller" => { version: "v1beta1", applied: false, paths: [["f:spec", "f:min"]] },
+            }
+        );
+
+        // Budgets that can't be met leave the managers as they were. Left
+        // out of the policy, beta-controller goes first and keeps the
+        // managers at v1 out of the bucket.
+        let mut trimmed = managers.clone();
+        assert!(trimmed.trim_to_budget(10, &policy).is_err());
+        let policy = TrimPolicy {
+            oldest_first: vec!["hpa".to_string()],
+            bucket: None,
+        };
+        assert!(trimmed.trim_to_budget(size - 1, &policy).is_err());
+        assert_eq!(trimmed, managers);
+    }
+}
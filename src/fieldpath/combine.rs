@@ -0,0 +1,159 @@
+//! Combining the ownership recorded on divergent copies of an object.
+
+use super::{ManagedFields, VersionedSet};
+use std::collections::BTreeMap;
+use std::time::SystemTime;
+
+/// MergeStrategy decides what ManagedFields::merge keeps for a manager
+/// whose entries differ between the two copies.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum MergeStrategy {
+    /// Keeps the entry that was recorded last. ManagedFields don't record
+    /// times, so they are given per manager for each copy, as found in the
+    /// managedFields entries of the objects. An entry without a time is
+    /// older than any with one, and ties keep the entry of `self`.
+    PreferNewer {
+        ours: BTreeMap<String, SystemTime>,
+        theirs: BTreeMap<String, SystemTime>,
+    },
+    /// Keeps the union of both sets, applied if either entry was. Entries
+    /// at different API versions can't be combined and fail the merge.
+    Union,
+    /// Fails the merge.
+    #[default]
+    Error,
+}
+
+/// MergeError reports the managers whose entries couldn't be combined.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergeError {
+    pub message: String,
+    /// The managers, in order of name.
+    pub managers: Vec<String>,
+}
+
+impl std::fmt::Display for MergeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.message, self.managers.join(", "))
+    }
+}
+
+impl std::error::Error for MergeError {}
+
+impl ManagedFields {
+    /// Combines the ownership recorded on two divergent copies of an object,
+    /// such as a backup and the live object it is restored over.
+    ///
+    /// Managers found in only one copy are kept as they are, as are entries
+    /// that are the same in both. Entries that differ in set, API version or
+    /// "applied" flag are resolved by `strategy`.
+    pub fn merge(&self, other: &ManagedFields, strategy: &MergeStrategy) -> Result<ManagedFields, MergeError> {
+        let mut merged = self.clone();
+        let mut conflicting = Vec::new();
+        for (manager, theirs) in other.iter() {
+            let Some(ours) = self.get(manager) else {
+                merged.insert(manager.clone(), theirs.clone());
+                continue;
+            };
+            if ours == theirs {
+                continue;
+            }
+            match strategy {
+                MergeStrategy::PreferNewer {
+                    ours: our_times,
+                    theirs: their_times,
+                } => {
+                    if their_times.get(manager) > our_times.get(manager) {
+                        merged.insert(manager.clone(), theirs.clone());
+                    }
+                }
+                MergeStrategy::Union if ours.api_version == theirs.api_version => {
+                    merged.insert(
+                        manager.clone(),
+                        VersionedSet::new(
+                            ours.set.union(&theirs.set),
+                            ours.api_version.clone(),
+                            ours.applied || theirs.applied,
+                        ),
+                    );
+                }
+                MergeStrategy::Union | MergeStrategy::Error => conflicting.push(manager.clone()),
+            }
+        }
+
+        if conflicting.is_empty() {
+            Ok(merged)
+        } else {
+            let message = match strategy {
+                MergeStrategy::Union => "managers own fields at different API versions",
+                _ => "managers own different fields in each copy",
+            };
+            Err(MergeError {
+                message: message.to_string(),
+                managers: conflicting,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fieldpath::{Path, PathElement};
+    use std::time::Duration;
+
+    #[test]
+    fn test_managed_fields_merge() {
+        let spec =
+            |name: &str| Path::from_elements(vec![PathElement::field_name("spec"), PathElement::field_name(name)]);
+        let backup = ManagedFields::builder()
+            .applied("kubectl", "apps/v1", [spec("replicas")])
+            .updated("hpa", "apps/v1", [spec("replicas")])
+            .updated("backup-only", "apps/v1", [spec("paused")])
+            .build();
+        let live = ManagedFields::builder()
+            .applied("kubectl", "apps/v1", [spec("template")])
+            .updated("hpa", "apps/v1", [spec("replicas")])
+            .updated("live-only", "apps/v2", [spec("minReadySeconds")])
+            .build();
+
+        let err = backup.merge(&live, &MergeStrategy::Error).unwrap_err();
+        assert_eq!(err.managers, vec!["kubectl".to_string()]);
+
+        let union = backup.merge(&live, &MergeStrategy::Union).unwrap();
+        let expected = ManagedFields::builder()
+            .applied("kubectl", "apps/v1", [spec("replicas"), spec("template")])
+            .updated("hpa", "apps/v1", [spec("replicas")])
+            .updated("backup-only", "apps/v1", [spec("paused")])
+            .updated("live-only", "apps/v2", [spec("minReadySeconds")])
+            .build();
+        assert_eq!(union, expected);
+
+        let t = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let newer = MergeStrategy::PreferNewer {
+            ours: BTreeMap::from([("kubectl".to_string(), t)]),
+            theirs: BTreeMap::from([("kubectl".to_string(), t + Duration::from_secs(60))]),
+        };
+        let merged = backup.merge(&live, &newer).unwrap();
+        assert_eq!(merged.get("kubectl"), live.get("kubectl"));
+        assert_eq!(merged.get("backup-only"), backup.get("backup-only"));
+        assert_eq!(merged.len(), 4);
+
+        // Without times, the entry of self is kept
+        let merged = backup.merge(
+            &live,
+            &MergeStrategy::PreferNewer {
+                ours: BTreeMap::new(),
+                theirs: BTreeMap::new(),
+            },
+        );
+        assert_eq!(merged.unwrap().get("kubectl"), backup.get("kubectl"));
+
+        let v2 = ManagedFields::builder()
+            .updated("hpa", "apps/v2", [spec("replicas")])
+            .build();
+        let err = backup.merge(&v2, &MergeStrategy::Union).unwrap_err();
+        assert_eq!(err.to_string(), "managers own fields at different API versions: hpa");
+    }
+}
@@ -0,0 +1,222 @@
+//! Evaluation of set arithmetic expressions over named sets.
+//!
+//! Expressions combine named sets with union (`∪`, `|` or `+`),
+//! intersection (`∩` or `&`) and difference (`-` or `∖`), grouped with
+//! parentheses. Intersection binds tighter than union and difference, which
+//! associate to the left. Bare names may contain letters, digits and
+//! `_ . : / -` but can't start with `-`, so differences need a space before
+//! the `-`, as in `kubectl - kube-controller-manager`. Other names are
+//! written as JSON strings.
+
+#![cfg_attr(not(test), deny(clippy::indexing_slicing, clippy::string_slice))]
+
+use super::set::Set;
+use std::collections::HashMap;
+use std::fmt;
+
+/// EvalError is returned for malformed expressions and unknown names.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EvalError {
+    pub message: String,
+}
+
+impl EvalError {
+    fn new(message: impl Into<String>) -> Self {
+        EvalError {
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid set expression: {}", self.message)
+    }
+}
+
+impl std::error::Error for EvalError {}
+
+/// Evaluates a set expression such as `ownerA - (ownerB ∪ ownerC)` over
+/// the given sets.
+pub fn eval(expr: &str, sets: &HashMap<String, Set>) -> Result<Set, EvalError> {
+    let mut parser = ExprParser {
+        tokens: tokenize(expr)?,
+        pos: 0,
+        sets,
+    };
+    let result = parser.union()?;
+    match parser.tokens.get(parser.pos) {
+        None => Ok(result),
+        Some(token) => Err(EvalError::new(format!("unexpected {}", token))),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Name(String),
+    Union,
+    Intersection,
+    Difference,
+    Open,
+    Close,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Name(name) => write!(f, "name {:?}", name),
+            Token::Union => f.write_str("'∪'"),
+            Token::Intersection => f.write_str("'∩'"),
+            Token::Difference => f.write_str("'-'"),
+            Token::Open => f.write_str("'('"),
+            Token::Close => f.write_str("')'"),
+        }
+    }
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || matches!(c, '_' | '.' | ':' | '/' | '-')
+}
+
+fn tokenize(expr: &str) -> Result<Vec<Token>, EvalError> {
+    let mut tokens = Vec::new();
+    let mut rest = expr;
+    while let Some(c) = rest.chars().next() {
+        let token = match c {
+            c if c.is_whitespace() => None,
+            '∪' | '|' | '+' => Some(Token::Union),
+            '∩' | '&' => Some(Token::Intersection),
+            '-' | '∖' => Some(Token::Difference),
+            '(' => Some(Token::Open),
+            ')' => Some(Token::Close),
+            '"' => {
+                let mut de = serde_json::Deserializer::from_str(rest).into_iter::<String>();
+                let (name, after) = match (de.next(), rest.get(de.byte_offset()..)) {
+                    (Some(Ok(name)), Some(after)) => (name, after),
+                    _ => return Err(EvalError::new(format!("malformed quoted name at {:?}", rest))),
+                };
+                rest = after;
+                tokens.push(Token::Name(name));
+                continue;
+            }
+            c if is_name_char(c) => {
+                let end = rest.find(|c| !is_name_char(c)).unwrap_or(rest.len());
+                let (name, after) = rest.split_at(end);
+                tokens.push(Token::Name(name.to_string()));
+                rest = after;
+                continue;
+            }
+            c => return Err(EvalError::new(format!("unexpected character {:?}", c))),
+        };
+        tokens.extend(token);
+        rest = rest.split_at(c.len_utf8()).1;
+    }
+    Ok(tokens)
+}
+
+struct ExprParser<'a> {
+    tokens: Vec<Token>,
+    pos: usize,
+    sets: &'a HashMap<String, Set>,
+}
+
+impl ExprParser<'_> {
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn union(&mut self) -> Result<Set, EvalError> {
+        let mut result = self.intersection()?;
+        loop {
+            match self.peek() {
+                Some(Token::Union) => {
+                    self.pos += 1;
+                    result = result.union(&self.intersection()?);
+                }
+                Some(Token::Difference) => {
+                    self.pos += 1;
+                    result = result.difference(&self.intersection()?);
+                }
+                _ => return Ok(result),
+            }
+        }
+    }
+
+    fn intersection(&mut self) -> Result<Set, EvalError> {
+        let mut result = self.operand()?;
+        while self.peek() == Some(&Token::Intersection) {
+            self.pos += 1;
+            result = result.intersection(&self.operand()?);
+        }
+        Ok(result)
+    }
+
+    fn operand(&mut self) -> Result<Set, EvalError> {
+        match self.next() {
+            Some(Token::Name(name)) => self
+                .sets
+                .get(&name)
+                .cloned()
+                .ok_or_else(|| EvalError::new(format!("unknown set {:?}", name))),
+            Some(Token::Open) => {
+                let result = self.union()?;
+                match self.next() {
+                    Some(Token::Close) => Ok(result),
+                    _ => Err(EvalError::new("unclosed '('")),
+                }
+            }
+            Some(token) => Err(EvalError::new(format!("expected a set, found {}", token))),
+            None => Err(EvalError::new("expected a set, found the end")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fieldpath::{Path, PathElement};
+
+    fn set(names: &[&str]) -> Set {
+        let mut set = Set::new();
+        for name in names {
+            set.insert(&Path::from_elements(vec![PathElement::field_name(*name)]));
+        }
+        set
+    }
+
+    #[test]
+    fn test_eval() {
+        let sets: HashMap<String, Set> = [
+            ("a", set(&["x", "y", "z"])),
+            ("b", set(&["y"])),
+            ("c", set(&["z", "w"])),
+            ("kube-controller-manager", set(&["w"])),
+            ("with space", set(&["x"])),
+        ]
+        .into_iter()
+        .map(|(name, set)| (name.to_string(), set))
+        .collect();
+        let eval = |expr: &str| eval(expr, &sets);
+
+        assert_eq!(eval("a - (b ∪ c)").unwrap(), set(&["x"]));
+        assert_eq!(eval("a - b | c").unwrap(), set(&["x", "z", "w"]));
+        assert_eq!(eval("a - b + c & kube-controller-manager").unwrap(), set(&["x", "z", "w"]));
+        assert_eq!(eval("a∩c").unwrap(), set(&["z"]));
+        assert_eq!(eval("c ∖ kube-controller-manager").unwrap(), set(&["z"]));
+        assert_eq!(eval(r#"a & "with space""#).unwrap(), set(&["x"]));
+        assert_eq!(eval("((b))").unwrap(), set(&["y"]));
+
+        assert_eq!(eval("a - d").unwrap_err().message, r#"unknown set "d""#);
+        assert_eq!(eval("a -").unwrap_err().message, "expected a set, found the end");
+        assert_eq!(eval("(a").unwrap_err().message, "unclosed '('");
+        assert_eq!(eval("a b").unwrap_err().message, r#"unexpected name "b""#);
+        assert_eq!(eval("a ^ b").unwrap_err().to_string(), "invalid set expression: unexpected character '^'");
+        assert!(eval(r#""a"#).is_err());
+    }
+}
@@ -0,0 +1,206 @@
+//! Ownership as CSV, one row per field owned, for analytics.
+//!
+//! Platform teams load the ownership of many objects into a data warehouse
+//! to find, say, which controllers fight over which fields. Each row is an
+//! object, named by the caller, a manager, a path it owns, and the API
+//! version and operation of its entry:
+//!
+//! ```text
+//! object,manager,path,version,applied
+//! default/web,kubectl,.spec.replicas,apps/v1,true
+//! ```
+//!
+//! Paths are rendered as Path displays them, and quoted as RFC 4180 asks
+//! when they hold commas or quotes. Managers owning nothing have no rows,
+//! and wildcards are left out.
+
+use super::{APIVersion, ManagedFields, Path, SerializeError, Set, VersionedSet};
+use std::collections::BTreeMap;
+use std::io;
+
+/// The header row of ownership CSV.
+pub const OWNERSHIP_CSV_HEADER: &str = "object,manager,path,version,applied";
+
+/// OwnershipCsvWriter writes the ownership of objects as CSV rows.
+pub struct OwnershipCsvWriter<W: io::Write> {
+    writer: W,
+}
+
+fn io_error(e: io::Error) -> SerializeError {
+    SerializeError::new(format!("I/O error: {}", e))
+}
+
+/// Quotes a CSV field if it needs to be.
+fn quote(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+impl<W: io::Write> OwnershipCsvWriter<W> {
+    /// Returns a writer to `writer`, writing the header row.
+    pub fn new(mut writer: W) -> Result<Self, SerializeError> {
+        writeln!(writer, "{}", OWNERSHIP_CSV_HEADER).map_err(io_error)?;
+        Ok(OwnershipCsvWriter { writer })
+    }
+
+    /// Writes a row per path owned by each manager of `object`.
+    pub fn write(&mut self, object: &str, managers: &ManagedFields) -> Result<(), SerializeError> {
+        let object = quote(object);
+        for (manager, vs) in managers.iter() {
+            let prefix = format!("{},{},", object, quote(manager));
+            let suffix = format!(",{},{}", quote(vs.api_version.as_str()), vs.applied);
+            let mut result = Ok(());
+            vs.set().iterate(|path| {
+                if result.is_ok() {
+                    result = writeln!(self.writer, "{}{}{}", prefix, quote(&path.to_string()), suffix);
+                }
+            });
+            result.map_err(io_error)?;
+        }
+        Ok(())
+    }
+
+    /// Returns the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Splits CSV into records of fields, unquoting them.
+fn records(input: &str) -> Result<Vec<Vec<String>>, SerializeError> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut chars = input.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if field.is_empty() => loop {
+                match chars.next() {
+                    Some('"') if chars.peek() == Some(&'"') => {
+                        chars.next();
+                        field.push('"');
+                    }
+                    Some('"') => break,
+                    Some(c) => field.push(c),
+                    None => return Err(SerializeError::new("unterminated quoted field in CSV")),
+                }
+            },
+            ',' => record.push(std::mem::take(&mut field)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            c => field.push(c),
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// Reads ownership CSV back into the managed fields of each object.
+///
+/// Rows of the same manager of an object must agree on its API version
+/// and operation.
+pub fn read_ownership_csv<R: io::Read>(mut reader: R) -> Result<BTreeMap<String, ManagedFields>, SerializeError> {
+    let mut input = String::new();
+    reader.read_to_string(&mut input).map_err(io_error)?;
+    let mut rows = records(&input)?.into_iter();
+    match rows.next() {
+        Some(header) if header.join(",") == OWNERSHIP_CSV_HEADER => {}
+        _ => {
+            return Err(SerializeError::new(format!(
+                "missing CSV header {:?}",
+                OWNERSHIP_CSV_HEADER
+            )))
+        }
+    }
+
+    let mut entries: BTreeMap<(String, String), (Set, APIVersion, bool)> = BTreeMap::new();
+    for (i, row) in rows.enumerate() {
+        let line = i + 2;
+        let [object, manager, path, version, applied] = <[String; 5]>::try_from(row)
+            .map_err(|row| SerializeError::new(format!("row {}: expected 5 fields, got {}", line, row.len())))?;
+        let path = Path::parse(&path).map_err(|e| SerializeError::new(format!("row {}: {}", line, e)))?;
+        let applied = match applied.as_str() {
+            "true" => true,
+            "false" => false,
+            _ => {
+                return Err(SerializeError::new(format!(
+                    "row {}: invalid applied {:?}",
+                    line, applied
+                )))
+            }
+        };
+        let version = APIVersion::new(version);
+        let (set, entry_version, entry_applied) = entries
+            .entry((object, manager))
+            .or_insert_with(|| (Set::new(), version.clone(), applied));
+        if *entry_version != version || *entry_applied != applied {
+            return Err(SerializeError::new(format!(
+                "row {}: version or operation differs from earlier rows of the manager",
+                line
+            )));
+        }
+        set.insert(&path);
+    }
+
+    let mut objects: BTreeMap<String, ManagedFields> = BTreeMap::new();
+    for ((object, manager), (set, version, applied)) in entries {
+        objects
+            .entry(object)
+            .or_default()
+            .insert(manager, VersionedSet::new(set, version, applied));
+    }
+    Ok(objects)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::managed_fields;
+
+    #[test]
+    fn test_ownership_csv() {
+        let web = managed_fields! {
+            "kubectl" => { version: "apps/v1", applied: true, paths: [["f:spec", "f:replicas"]] },
+            "controller" => {
+                version: "apps/v1",
+                applied: false,
+                paths: [["f:spec", "f:containers", r#"k:{"name":"a,b"}"#, "f:image"]],
+            },
+        };
+        let db = managed_fields! {
+            "helm" => { version: "v1", applied: true, paths: [["f:data", "f:key"]] },
+        };
+
+        let mut writer = OwnershipCsvWriter::new(Vec::new()).unwrap();
+        writer.write("default/web", &web).unwrap();
+        writer.write("db \"primary\"", &db).unwrap();
+        let csv = String::from_utf8(writer.into_inner()).unwrap();
+        assert_eq!(
+            csv.lines().collect::<Vec<_>>(),
+            vec![
+                OWNERSHIP_CSV_HEADER,
+                r#"default/web,controller,".spec.containers[name=""a,b""].image",apps/v1,false"#,
+                "default/web,kubectl,.spec.replicas,apps/v1,true",
+                r#""db ""primary""",helm,.data.key,v1,true"#,
+            ]
+        );
+
+        let objects = read_ownership_csv(csv.as_bytes()).unwrap();
+        assert_eq!(objects.len(), 2);
+        assert_eq!(objects["default/web"], web);
+        assert_eq!(objects["db \"primary\""], db);
+
+        let conflicting = format!("{}\nx,m,.a,v1,true\nx,m,.b,v2,true\n", OWNERSHIP_CSV_HEADER);
+        assert!(read_ownership_csv(conflicting.as_bytes()).is_err());
+        assert!(read_ownership_csv("x,m,.a,v1,true\n".as_bytes()).is_err());
+    }
+}
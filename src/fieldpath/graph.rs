@@ -0,0 +1,145 @@
+//! Export of field ownership as a graph for visualization.
+
+use super::serialize::{serialize_path_element, SerializeError};
+use super::ManagedFields;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+/// GraphFormat selects the output of ManagedFields::to_graph.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum GraphFormat {
+    /// A Graphviz digraph with one node per field, labelled with its owners.
+    #[default]
+    Dot,
+    /// A nested JSON tree keyed by serialized path element, in which each
+    /// node is `{"managers": [...], "children": {...}}`.
+    Json,
+}
+
+#[derive(Default)]
+struct Node {
+    managers: BTreeSet<String>,
+    children: BTreeMap<String, Node>,
+}
+
+impl Node {
+    fn to_json(&self) -> serde_json::Value {
+        let children = self.children.iter().map(|(key, child)| (key.clone(), child.to_json())).collect();
+        serde_json::json!({
+            "managers": self.managers,
+            "children": serde_json::Value::Object(children),
+        })
+    }
+
+    fn write_dot(&self, id: usize, label: &str, next: &mut usize, out: &mut String) {
+        let mut text = label.to_string();
+        for manager in &self.managers {
+            text.push('\n');
+            text.push_str(manager);
+        }
+        let _ = writeln!(out, "  n{} [label={}];", id, dot_quote(&text));
+        for (key, child) in &self.children {
+            *next += 1;
+            let child_id = *next;
+            let _ = writeln!(out, "  n{} -> n{};", id, child_id);
+            child.write_dot(child_id, key, next, out);
+        }
+    }
+}
+
+fn dot_quote(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '\n' => quoted.push_str("\\n"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+impl ManagedFields {
+    /// Renders the object structure covered by the managed fields, with the
+    /// managers owning each field, in the given format. Fields only present
+    /// as parents of owned fields have no managers.
+    pub fn to_graph(&self, format: GraphFormat) -> Result<String, SerializeError> {
+        let mut root = Node::default();
+        for (manager, vs) in self.iter() {
+            let mut err = None;
+            vs.set().iterate(|path| {
+                let mut node = &mut root;
+                for pe in path.iter() {
+                    match serialize_path_element(pe) {
+                        Ok(key) => node = node.children.entry(key).or_default(),
+                        Err(e) => {
+                            err.get_or_insert(e);
+                            return;
+                        }
+                    }
+                }
+                node.managers.insert(manager.clone());
+            });
+            if let Some(e) = err {
+                return Err(e);
+            }
+        }
+        match format {
+            GraphFormat::Dot => {
+                let mut out = String::from("digraph managedFields {\n");
+                root.write_dot(0, ".", &mut 0, &mut out);
+                out.push_str("}\n");
+                Ok(out)
+            }
+            GraphFormat::Json => Ok(root.to_json().to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::managed_fields;
+
+    #[test]
+    fn test_to_graph() {
+        let managers = managed_fields! {
+            "kubectl" => {
+                version: "v1",
+                applied: true,
+                paths: [["f:spec", "f:replicas"], ["f:spec", r#"k:{"name":"a"}"#]],
+            },
+            "hpa" => { version: "v1", applied: false, paths: [["f:spec", "f:replicas"]] },
+        };
+
+        assert_eq!(
+            managers.to_graph(GraphFormat::Json).unwrap(),
+            concat!(
+                r#"{"children":{"f:spec":{"children":{"f:replicas":{"children":{},"managers":["hpa","kubectl"]},"#,
+                r#""k:{\"name\":\"a\"}":{"children":{},"managers":["kubectl"]}},"managers":[]}},"managers":[]}"#
+            )
+        );
+        assert_eq!(
+            managers.to_graph(GraphFormat::Dot).unwrap(),
+            r#"digraph managedFields {
+  n0 [label="."];
+  n0 -> n1;
+  n1 [label="f:spec"];
+  n1 -> n2;
+  n2 [label="f:replicas\nhpa\nkubectl"];
+  n1 -> n3;
+  n3 [label="k:{\"name\":\"a\"}\nkubectl"];
+}
+"#
+        );
+        assert_eq!(
+            ManagedFields::new().to_graph(GraphFormat::Json).unwrap(),
+            r#"{"children":{},"managers":[]}"#
+        );
+    }
+}
@@ -0,0 +1,341 @@
+//! Manager identifiers, construction and serialization of ManagedFields.
+
+use super::serialize::SerializeError;
+use super::{APIVersion, ManagedFields, Path, Set, VersionedSet};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Operation is the kind of request that recorded a manager's fields.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum Operation {
+    #[default]
+    Apply,
+    Update,
+}
+
+impl fmt::Display for Operation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operation::Apply => write!(f, "Apply"),
+            Operation::Update => write!(f, "Update"),
+        }
+    }
+}
+
+/// ManagerPriority is the class of a manager, which decides whose
+/// conflicts come first and which an apply may force. Managers are users
+/// unless tagged otherwise with ManagedFields::set_priority.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[non_exhaustive]
+pub enum ManagerPriority {
+    /// People and their tools, such as kubectl.
+    #[default]
+    User,
+    /// Controllers reconciling the object.
+    Controller,
+    /// The platform itself, such as admission and mutation pipelines.
+    System,
+}
+
+impl fmt::Display for ManagerPriority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ManagerPriority::User => write!(f, "user"),
+            ManagerPriority::Controller => write!(f, "controller"),
+            ManagerPriority::System => write!(f, "system"),
+        }
+    }
+}
+
+/// ManagerIdentifier is the structured form of a ManagedFields key.
+///
+/// Keys are encoded the way kube-apiserver encodes them internally: the JSON
+/// form of the managedFields entry without its time and fields. The API
+/// version is only part of the key for updates.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub struct ManagerIdentifier {
+    pub manager: String,
+    pub operation: Operation,
+    #[serde(rename = "apiVersion", default, skip_serializing_if = "Option::is_none")]
+    pub api_version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subresource: Option<String>,
+}
+
+impl ManagerIdentifier {
+    /// Identifies an applier.
+    pub fn apply(manager: impl Into<String>) -> Self {
+        ManagerIdentifier {
+            manager: manager.into(),
+            operation: Operation::Apply,
+            api_version: None,
+            subresource: None,
+        }
+    }
+
+    /// Identifies an updater writing the given API version.
+    pub fn update(manager: impl Into<String>, api_version: impl Into<String>) -> Self {
+        ManagerIdentifier {
+            manager: manager.into(),
+            operation: Operation::Update,
+            api_version: Some(api_version.into()),
+            subresource: None,
+        }
+    }
+
+    /// Returns this identifier for requests to a subresource.
+    pub fn with_subresource(mut self, subresource: impl Into<String>) -> Self {
+        let subresource = subresource.into();
+        self.subresource = (!subresource.is_empty()).then_some(subresource);
+        self
+    }
+
+    /// Encodes the identifier as a ManagedFields key.
+    pub fn encode(&self) -> String {
+        let mut id = self.clone();
+        if id.operation == Operation::Apply {
+            id.api_version = None;
+        }
+        // Serializing plain strings can't fail.
+        serde_json::to_string(&id).unwrap_or_default()
+    }
+
+    /// Decodes a ManagedFields key.
+    pub fn decode(key: &str) -> Result<Self, SerializeError> {
+        serde_json::from_str(key)
+            .map_err(|e| SerializeError::new(format!("invalid manager identifier {:?}: {}", key, e)))
+    }
+}
+
+impl fmt::Display for ManagerIdentifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.encode())
+    }
+}
+
+/// ManagedFieldsBuilder builds ManagedFields one manager at a time.
+#[derive(Clone, Default)]
+pub struct ManagedFieldsBuilder {
+    managers: ManagedFields,
+}
+
+impl ManagedFieldsBuilder {
+    /// Adds a manager owning `paths` at `version`, replacing any earlier
+    /// entry for the same manager.
+    pub fn manager(
+        mut self,
+        manager: impl Into<String>,
+        version: impl Into<APIVersion>,
+        applied: bool,
+        paths: impl IntoIterator<Item = Path>,
+    ) -> Self {
+        let mut set = Set::new();
+        for path in paths {
+            set.insert(&path);
+        }
+        self.managers.insert(manager, VersionedSet::new(set, version.into(), applied));
+        self
+    }
+
+    /// Adds a manager whose fields were applied.
+    pub fn applied(
+        self,
+        manager: impl Into<String>,
+        version: impl Into<APIVersion>,
+        paths: impl IntoIterator<Item = Path>,
+    ) -> Self {
+        self.manager(manager, version, true, paths)
+    }
+
+    /// Adds a manager whose fields were updated.
+    pub fn updated(
+        self,
+        manager: impl Into<String>,
+        version: impl Into<APIVersion>,
+        paths: impl IntoIterator<Item = Path>,
+    ) -> Self {
+        self.manager(manager, version, false, paths)
+    }
+
+    /// Builds the ManagedFields.
+    pub fn build(self) -> ManagedFields {
+        self.managers
+    }
+}
+
+impl ManagedFields {
+    /// Returns a builder for ManagedFields.
+    pub fn builder() -> ManagedFieldsBuilder {
+        ManagedFieldsBuilder::default()
+    }
+}
+
+/// Builds ManagedFields from a literal. Paths are lists of serialized
+/// path elements, as in FieldsV1:
+///
+/// ```
+/// use structured_merge_diff::managed_fields;
+///
+/// let managers = managed_fields! {
+///     "kubectl" => { version: "v1", applied: true, paths: [["f:spec", "f:replicas"]] },
+///     "controller" => { version: "v1", applied: false, paths: [["f:status"]] },
+/// };
+/// assert_eq!(managers.len(), 2);
+/// ```
+///
+/// Panics if a path element is malformed.
+#[macro_export]
+macro_rules! managed_fields {
+    ($($manager:expr => {
+        version: $version:expr,
+        applied: $applied:expr,
+        paths: [$([$($element:expr),* $(,)?]),* $(,)?] $(,)?
+    }),* $(,)?) => {
+        $crate::fieldpath::ManagedFields::builder()
+            $(.manager(
+                $manager,
+                $version,
+                $applied,
+                [$($crate::fieldpath::Path::from_serialized(&[$($element),*])
+                    .expect("malformed path element in managed_fields!")),*],
+            ))*
+            .build()
+    };
+}
+
+impl Serialize for APIVersion {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for APIVersion {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer).map(APIVersion::new)
+    }
+}
+
+/// The serialized form of a VersionedSet.
+#[derive(Serialize, Deserialize)]
+struct VersionedSetRepr {
+    #[serde(rename = "apiVersion")]
+    api_version: APIVersion,
+    applied: bool,
+    #[serde(rename = "fieldsV1")]
+    set: Set,
+}
+
+impl Serialize for VersionedSet {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let repr = VersionedSetRepr {
+            api_version: self.api_version.clone(),
+            applied: self.applied,
+            set: self.set.clone(),
+        };
+        repr.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for VersionedSet {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = VersionedSetRepr::deserialize(deserializer)?;
+        Ok(VersionedSet::new(repr.set, repr.api_version, repr.applied))
+    }
+}
+
+/// ManagedFields serialize as an object from manager key to versioned set,
+/// ordered by key so snapshots are stable.
+impl Serialize for ManagedFields {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_map(self.iter())
+    }
+}
+
+impl<'de> Deserialize<'de> for ManagedFields {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let entries = BTreeMap::<String, VersionedSet>::deserialize(deserializer)?;
+        let mut managers = ManagedFields::new();
+        for (manager, vs) in entries {
+            managers.insert(manager, vs);
+        }
+        Ok(managers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fieldpath::{Path, PathElement};
+
+    #[test]
+    fn test_managed_fields_builder() {
+        let spec = |name: &str| Path::from_elements(vec![PathElement::field_name("spec"), PathElement::field_name(name)]);
+        let mut expected = ManagedFields::new();
+        let mut set = Set::new();
+        set.insert(&spec("replicas"));
+        set.insert(&spec("template"));
+        expected.insert("kubectl", VersionedSet::new(set, APIVersion::new("apps/v1"), true));
+        expected.insert("hpa", VersionedSet::new(Set::new(), APIVersion::new("apps/v1"), false));
+
+        let built = ManagedFields::builder()
+            .applied("kubectl", "apps/v1", [spec("replicas"), spec("template")])
+            .updated("hpa", "apps/v1", [])
+            .build();
+        assert_eq!(built, expected);
+
+        let literal = crate::managed_fields! {
+            "kubectl" => {
+                version: "apps/v1",
+                applied: true,
+                paths: [["f:spec", "f:replicas"], ["f:spec", "f:template"]],
+            },
+            "hpa" => { version: "apps/v1", applied: false, paths: [] },
+        };
+        assert_eq!(literal, expected);
+
+        let keyed = crate::managed_fields! {
+            "m" => { version: "v1", applied: true, paths: [["f:items", r#"k:{"name":"a"}"#]] },
+        };
+        let key = Path::from_serialized(&["f:items", r#"k:{"name":"a"}"#]).unwrap();
+        assert!(keyed.get("m").unwrap().set().has(&key));
+        assert!(Path::from_serialized(&["x"]).is_err());
+        assert!(crate::managed_fields! {}.is_empty());
+    }
+
+    #[test]
+    fn test_manager_identifier_encoding() {
+        let apply = ManagerIdentifier::apply("kubectl");
+        assert_eq!(apply.encode(), r#"{"manager":"kubectl","operation":"Apply"}"#);
+
+        let update = ManagerIdentifier::update("controller", "apps/v1").with_subresource("status");
+        let key = update.encode();
+        assert_eq!(
+            key,
+            r#"{"manager":"controller","operation":"Update","apiVersion":"apps/v1","subresource":"status"}"#
+        );
+        assert_eq!(ManagerIdentifier::decode(&key).unwrap(), update);
+        assert!(ManagerIdentifier::decode("Bob").is_err());
+    }
+
+    #[test]
+    fn test_managed_fields_serde_roundtrip() {
+        let mut set = Set::new();
+        set.insert(&Path::from_elements(vec![PathElement::field_name("spec"), PathElement::field_name("replicas")]));
+
+        let mut managers = ManagedFields::new();
+        managers.insert(ManagerIdentifier::apply("b").encode(), VersionedSet::new(set.clone(), APIVersion::new("v1"), true));
+        managers.insert(
+            ManagerIdentifier::update("a", "v2").encode(),
+            VersionedSet::new(set, APIVersion::new("v2"), false),
+        );
+
+        let json = serde_json::to_string(&managers).unwrap();
+        assert!(json.starts_with(r#"{"{\"manager\":\"a\""#));
+        assert!(json.contains(r#""fieldsV1":{"f:spec":{"f:replicas":{}}}"#));
+
+        let restored: ManagedFields = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored, managers);
+        assert_eq!(serde_json::to_string(&restored).unwrap(), json);
+    }
+}
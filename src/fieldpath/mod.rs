@@ -2,17 +2,41 @@
 //!
 //! This module tracks which manager owns which fields.
 
+#[cfg(feature = "ownership-aging")]
+mod aging;
+mod binary;
+mod budget;
+mod combine;
+mod eval;
+mod export;
+mod graph;
+mod managers;
 mod path;
 mod pathelementmap;
+mod pattern;
+mod reconcile;
 mod serialize;
 mod set;
-
+mod summary;
+
+#[cfg(feature = "ownership-aging")]
+pub use aging::*;
+pub use binary::BINARY_SET_VERSION;
+pub use budget::*;
+pub use combine::*;
+pub use eval::*;
+pub use export::*;
+pub use graph::*;
+pub use managers::*;
 pub use path::*;
 pub use pathelementmap::*;
+pub use pattern::*;
+pub use reconcile::*;
 pub use serialize::*;
 pub use set::*;
 
-use std::collections::HashMap;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
 use std::fmt;
 
 /// APIVersion represents a version string for field ownership.
@@ -29,6 +53,42 @@ impl APIVersion {
     pub fn as_str(&self) -> &str {
         &self.0
     }
+
+    /// Orders Kubernetes API versions of the same group by release: by major
+    /// version, then alpha before beta before GA, then by number, so that
+    /// v1alpha1 < v1beta1 < v1 < v2alpha1 < v2.
+    ///
+    /// Returns None if the groups differ or a version isn't of that form.
+    pub fn release_cmp(&self, other: &APIVersion) -> Option<Ordering> {
+        let (group, release) = self.release()?;
+        let (other_group, other_release) = other.release()?;
+        (group == other_group).then(|| release.cmp(&other_release))
+    }
+
+    /// Returns the group and the (major, stage, number) of the version,
+    /// with stages 0, 1 and 2 for alpha, beta and GA.
+    fn release(&self) -> Option<(&str, (u32, u8, u32))> {
+        let (group, version) = self.0.rsplit_once('/').unwrap_or(("", &self.0));
+        let version = version.strip_prefix('v')?;
+        let digits = version.find(|c: char| !c.is_ascii_digit()).unwrap_or(version.len());
+        let (major, suffix) = version.split_at(digits);
+        let major = major.parse().ok()?;
+        let (stage, number) = match suffix {
+            "" => (2, 0),
+            rest => {
+                let (stage, number) = if let Some(n) = rest.strip_prefix("alpha") {
+                    (0, n)
+                } else {
+                    (1, rest.strip_prefix("beta")?)
+                };
+                if !number.bytes().all(|b| b.is_ascii_digit()) {
+                    return None;
+                }
+                (stage, number.parse().ok()?)
+            }
+        };
+        Some((group, (major, stage, number)))
+    }
 }
 
 impl From<&str> for APIVersion {
@@ -50,7 +110,7 @@ impl std::fmt::Display for APIVersion {
 }
 
 /// VersionedSet associates a Set with an API version and "applied" flag.
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct VersionedSet {
     pub set: Set,
     pub api_version: APIVersion,
@@ -98,17 +158,27 @@ impl PartialEq for VersionedSet {
 
 impl Eq for VersionedSet {}
 
-/// ManagedFields tracks what each manager owns.
-#[derive(Debug, Clone, Default)]
+/// ManagedFields tracks what each manager owns. Managers are kept sorted
+/// by name, so iteration, serialization and conflicts are deterministic.
+///
+/// ManagedFields format as a map from manager to version, "applied" flag
+/// and set: on one line with `{}` and `{:?}`, indented with `{:#}` and
+/// `{:#?}`.
+///
+/// Managers can be tagged with a priority, which isn't part of their
+/// entries: it is neither serialized nor compared.
+#[derive(Clone, Default)]
 pub struct ManagedFields {
-    managers: HashMap<String, VersionedSet>,
+    managers: BTreeMap<String, VersionedSet>,
+    priorities: BTreeMap<String, ManagerPriority>,
 }
 
 impl ManagedFields {
     /// Creates a new empty ManagedFields.
     pub fn new() -> Self {
         ManagedFields {
-            managers: HashMap::new(),
+            managers: BTreeMap::new(),
+            priorities: BTreeMap::new(),
         }
     }
 
@@ -142,12 +212,41 @@ impl ManagedFields {
         self.managers.remove(manager)
     }
 
+    /// Removes a manager and returns the paths it owned that no other
+    /// manager owns, which are left unowned. Paths are compared as recorded,
+    /// whatever the API versions of the managers. Returns an empty set if
+    /// the manager isn't recorded.
+    pub fn remove_manager_and_report(&mut self, manager: &str) -> Set {
+        let Some(removed) = self.managers.remove(manager) else {
+            return Set::new();
+        };
+        let mut unowned = removed.set.filter(|path| !self.managers.values().any(|vs| vs.set.has(path)));
+        removed.set.wildcards_iterate(|path: &Path| {
+            if !self.managers.values().any(|vs| vs.set.has_wildcard(path)) {
+                unowned.insert_wildcard(path);
+            }
+        });
+        unowned
+    }
+
+    /// Tags a manager with a priority. The tag stays when the manager's
+    /// entry is removed, for when it owns fields again.
+    pub fn set_priority(&mut self, manager: impl Into<String>, priority: ManagerPriority) {
+        self.priorities.insert(manager.into(), priority);
+    }
+
+    /// Returns the priority of a manager, User unless tagged otherwise.
+    pub fn priority(&self, manager: &str) -> ManagerPriority {
+        self.priorities.get(manager).copied().unwrap_or_default()
+    }
+
     /// Returns true if the manager exists.
     pub fn contains(&self, manager: &str) -> bool {
         self.managers.contains_key(manager)
     }
 
-    /// Returns an iterator over managers and their VersionedSets.
+    /// Returns an iterator over managers and their VersionedSets, in order
+    /// of manager name.
     pub fn iter(&self) -> impl Iterator<Item = (&String, &VersionedSet)> {
         self.managers.iter()
     }
@@ -250,13 +349,24 @@ impl Eq for ManagedFields {}
 
 impl fmt::Display for ManagedFields {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        for (manager, vs) in &self.managers {
-            writeln!(f, "{}:", manager)?;
-            writeln!(f, "- Applied: {}", vs.applied)?;
-            writeln!(f, "- APIVersion: {}", vs.api_version)?;
-            writeln!(f, "- Set: {:?}", vs.set)?;
+        f.debug_map().entries(&self.managers).finish()
+    }
+}
+
+impl fmt::Debug for ManagedFields {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl fmt::Debug for VersionedSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.api_version)?;
+        if self.applied {
+            write!(f, " applied")?;
         }
-        Ok(())
+        write!(f, " ")?;
+        fmt::Display::fmt(&self.set, f)
     }
 }
 
@@ -276,6 +386,19 @@ mod tests {
         assert!(vs.set().has(&Path::from_elements(vec![PathElement::field_name("name")])));
     }
 
+    #[test]
+    fn test_release_cmp() {
+        let cmp = |a: &str, b: &str| APIVersion::new(a).release_cmp(&APIVersion::new(b));
+        let ordered = ["v1alpha1", "v1alpha2", "v1beta1", "v1", "v2alpha1", "v2", "v10"];
+        for pair in ordered.windows(2) {
+            assert_eq!(cmp(pair[0], pair[1]), Some(Ordering::Less), "{:?}", pair);
+        }
+        assert_eq!(cmp("apps/v1", "apps/v1beta2"), Some(Ordering::Greater));
+        assert_eq!(cmp("apps/v1", "batch/v1"), None);
+        assert_eq!(cmp("v1", "latest"), None);
+        assert_eq!(cmp("v1gamma1", "v1"), None);
+    }
+
     #[test]
     fn test_managed_fields_basic() {
         let mut mf = ManagedFields::new();
@@ -291,6 +414,33 @@ mod tests {
         assert!(!mf.contains("manager2"));
     }
 
+    #[test]
+    fn test_managed_fields_order() {
+        let mut mf = ManagedFields::new();
+        for manager in ["kubectl", "hpa", "argo", "zz", "controller"] {
+            mf.insert(manager, VersionedSet::new(Set::new(), APIVersion::new("v1"), false));
+        }
+        let managers: Vec<&str> = mf.managers().map(String::as_str).collect();
+        assert_eq!(managers, ["argo", "controller", "hpa", "kubectl", "zz"]);
+        assert!(mf.iter().map(|(m, _)| m).eq(mf.managers()));
+    }
+
+    #[test]
+    fn test_managed_fields_format() {
+        let mut set = Set::new();
+        set.insert(&Path::from_elements(vec![PathElement::field_name("name")]));
+        let mut mf = ManagedFields::new();
+        mf.insert("b", VersionedSet::new(set.clone(), APIVersion::new("v1"), false));
+        mf.insert("a", VersionedSet::new(set, APIVersion::new("v1"), true));
+
+        assert_eq!(format!("{}", mf), r#"{"a": v1 applied {.name}, "b": v1 {.name}}"#);
+        assert_eq!(format!("{:?}", mf), format!("{}", mf));
+        assert_eq!(
+            format!("{:#}", mf),
+            "{\n    \"a\": v1 applied {\n        .name,\n    },\n    \"b\": v1 {\n        .name,\n    },\n}"
+        );
+    }
+
     #[test]
     fn test_managed_fields_equals() {
         let mut set1 = Set::new();
@@ -311,6 +461,24 @@ mod tests {
         assert!(!mf1.equals(&mf3));
     }
 
+    #[test]
+    fn test_remove_manager_and_report() {
+        let field = |name: &str| Path::from_elements(vec![PathElement::field_name(name)]);
+        let mut managers = ManagedFields::builder()
+            .applied("kubectl", "v1", [field("a"), field("b")])
+            .updated("controller", "v1", [field("b")])
+            .build();
+
+        let unowned = managers.remove_manager_and_report("kubectl");
+        assert_eq!(unowned.to_string(), "{.a}");
+        assert!(!managers.contains("kubectl"));
+        assert!(managers.remove_manager_and_report("kubectl").is_empty());
+
+        let unowned = managers.remove_manager_and_report("controller");
+        assert_eq!(unowned.to_string(), "{.b}");
+        assert!(managers.is_empty());
+    }
+
     #[test]
     fn test_managed_fields_difference() {
         let mut set1 = Set::new();
@@ -1,7 +1,11 @@
 //! Path element and path types.
 
-use crate::value::{FieldList, Value};
+#![cfg_attr(not(test), deny(clippy::indexing_slicing, clippy::string_slice))]
+
+use super::serialize::{json_to_value, SerializeError};
+use crate::value::{Field, FieldList, Value};
 use std::cmp::Ordering;
+use std::str::FromStr;
 
 /// PathElement represents one level of path navigation.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -14,6 +18,12 @@ pub enum PathElement {
     Value(Value),
     /// Index for array indices.
     Index(i32),
+    /// An addressing mode defined outside this crate, named by its kind and
+    /// carrying its argument, such as a regex selector. Extensions are
+    /// ordered after all other elements, by kind and then argument, and
+    /// serialize as `x:<kind>:<argument JSON>`. The merge algorithms treat
+    /// them as opaque: they match only equal extensions.
+    Extension(String, Value),
 }
 
 impl PathElement {
@@ -37,6 +47,16 @@ impl PathElement {
         PathElement::Index(i)
     }
 
+    /// Creates a new extension path element. The kind may not contain ':',
+    /// which separates it from the argument when rendered or serialized.
+    pub fn extension(kind: impl Into<String>, argument: Value) -> Result<Self, SerializeError> {
+        let kind = kind.into();
+        if kind.contains(':') {
+            return Err(SerializeError::new(format!("extension kind {:?} contains ':'", kind)));
+        }
+        Ok(PathElement::Extension(kind, argument))
+    }
+
     /// Returns true if this is a field name element.
     pub fn is_field_name(&self) -> bool {
         matches!(self, PathElement::FieldName(_))
@@ -49,6 +69,43 @@ impl PathElement {
             _ => None,
         }
     }
+
+    /// Returns the element in canonical numeric form: floats in keys and
+    /// values that have an integral value in the range of i64 become ints,
+    /// so `[page=2.0]` becomes `[page=2]`. Other floats are kept. Schemas
+    /// with NumericKeys::Integral key list items in this form.
+    pub fn with_canonical_numbers(&self) -> PathElement {
+        match self {
+            PathElement::Key(fields) => PathElement::Key(FieldList::with_fields(
+                fields
+                    .fields
+                    .iter()
+                    .map(|f| Field {
+                        name: f.name.clone(),
+                        value: canonical_numbers(&f.value),
+                    })
+                    .collect(),
+            )),
+            PathElement::Value(v) => PathElement::Value(canonical_numbers(v)),
+            pe => pe.clone(),
+        }
+    }
+}
+
+/// Returns the value with integral floats turned into ints, recursively.
+pub(crate) fn canonical_numbers(value: &Value) -> Value {
+    match value {
+        Value::Float(f) if f.fract() == 0.0 && *f >= i64::MIN as f64 && *f < i64::MAX as f64 => Value::Int(*f as i64),
+        Value::List(items) => Value::List(items.iter().map(canonical_numbers).collect()),
+        Value::Map(map) => {
+            let mut out = map.clone();
+            for (name, v) in map.iter() {
+                out.set(name.clone(), canonical_numbers(v));
+            }
+            Value::Map(out)
+        }
+        v => v.clone(),
+    }
 }
 
 impl PartialOrd for PathElement {
@@ -65,6 +122,7 @@ impl Ord for PathElement {
                 PathElement::Key(_) => 1,
                 PathElement::Value(_) => 2,
                 PathElement::Index(_) => 3,
+                PathElement::Extension(..) => 4,
             }
         }
 
@@ -91,6 +149,7 @@ impl Ord for PathElement {
             }
             (PathElement::Value(a), PathElement::Value(b)) => a.cmp(b),
             (PathElement::Index(a), PathElement::Index(b)) => a.cmp(b),
+            (PathElement::Extension(ka, a), PathElement::Extension(kb, b)) => ka.cmp(kb).then_with(|| a.cmp(b)),
             _ => Ordering::Equal,
         }
     }
@@ -187,7 +246,7 @@ impl<'a> IntoIterator for &'a Path {
 impl std::fmt::Display for PathElement {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            PathElement::FieldName(name) => write!(f, ".{}", name),
+            PathElement::FieldName(name) => write!(f, ".{}", format_name(name)),
             PathElement::Key(fields) => {
                 write!(f, "[")?;
                 // Sort fields by name for consistent output
@@ -197,12 +256,13 @@ impl std::fmt::Display for PathElement {
                     if i > 0 {
                         write!(f, ",")?;
                     }
-                    write!(f, "{}={}", field.name, format_value(&field.value))?;
+                    write!(f, "{}={}", format_name(&field.name), format_value(&field.value))?;
                 }
                 write!(f, "]")
             }
             PathElement::Value(v) => write!(f, "[={}]", format_value(v)),
             PathElement::Index(i) => write!(f, "[{}]", i),
+            PathElement::Extension(kind, v) => write!(f, "[~{}:{}]", kind, v),
         }
     }
 }
@@ -213,13 +273,38 @@ fn format_value(v: &Value) -> String {
         Value::Null => "null".to_string(),
         Value::Bool(b) => b.to_string(),
         Value::Int(i) => i.to_string(),
-        Value::Float(f) => f.to_string(),
-        Value::String(s) => format!("\"{}\"", s),
+        // Debug keeps a decimal point or exponent, so floats parse back as floats
+        Value::Float(f) => format!("{:?}", f),
+        Value::String(s) => quote(s),
         Value::List(_) => "[...]".to_string(),
         Value::Map(_) => "{...}".to_string(),
     }
 }
 
+/// Returns true if a field name renders without quotes.
+fn is_plain_name(name: &str) -> bool {
+    !name.is_empty() && name.chars().all(is_plain_char)
+}
+
+fn is_plain_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '$')
+}
+
+/// Formats a field name, quoting names with dots, slashes, unicode or other
+/// characters that would be ambiguous, such as `"example.com/role"`.
+fn format_name(name: &str) -> String {
+    if is_plain_name(name) {
+        name.to_string()
+    } else {
+        quote(name)
+    }
+}
+
+/// Quotes a string with JSON escaping.
+fn quote(s: &str) -> String {
+    serde_json::Value::String(s.to_string()).to_string()
+}
+
 impl std::fmt::Display for Path {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         for element in &self.elements {
@@ -229,6 +314,250 @@ impl std::fmt::Display for Path {
     }
 }
 
+impl Path {
+    /// Parses a path in the form Display renders, such as
+    /// `.spec.containers[name="app"].ports[0]` or
+    /// `.metadata.annotations."example.com/role"`. Keys and set values of
+    /// lists or maps can't be parsed, as they render as `[...]` and `{...}`.
+    pub fn parse(s: &str) -> Result<Path, SerializeError> {
+        let mut chars = s.chars();
+        let mut path = Path::new();
+        while let Some(c) = chars.next() {
+            let mut rest = chars.as_str();
+            let pe = match c {
+                '.' => PathElement::FieldName(parse_name(&mut rest)?),
+                '[' => parse_bracket(&mut rest)?,
+                c => return Err(SerializeError::new(format!("unexpected {:?} in path {:?}", c, s))),
+            };
+            path.push(pe);
+            chars = rest.chars();
+        }
+        Ok(path)
+    }
+}
+
+impl FromStr for Path {
+    type Err = SerializeError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Path::parse(s)
+    }
+}
+
+impl Path {
+    /// Renders the path as an RFC 6901 JSON Pointer such as
+    /// `/spec/containers/0/image`. Paths with keys or set values must be
+    /// converted to indexes first with TypedValue::index_path.
+    pub fn to_json_pointer(&self) -> Result<String, SerializeError> {
+        let mut pointer = String::new();
+        for pe in &self.elements {
+            pointer.push('/');
+            match pe {
+                PathElement::FieldName(name) => pointer.push_str(&name.replace('~', "~0").replace('/', "~1")),
+                PathElement::Index(i) => pointer.push_str(&i.to_string()),
+                _ => return Err(positional_error(self, "a JSON pointer")),
+            }
+        }
+        Ok(pointer)
+    }
+
+    /// Renders the path as a CEL field path, as used by the `fieldPath` of
+    /// validation rules and in ValidatingAdmissionPolicy messages, such as
+    /// `.spec.containers[0]['example.com/role']`. Paths with keys or set
+    /// values must be converted to indexes first with TypedValue::index_path.
+    pub fn to_cel_field_path(&self) -> Result<String, SerializeError> {
+        let mut out = String::new();
+        for pe in &self.elements {
+            match pe {
+                PathElement::FieldName(name) if is_cel_identifier(name) => {
+                    out.push('.');
+                    out.push_str(name);
+                }
+                PathElement::FieldName(name) => {
+                    out.push_str("['");
+                    for c in name.chars() {
+                        if matches!(c, '\\' | '\'') {
+                            out.push('\\');
+                        }
+                        out.push(c);
+                    }
+                    out.push_str("']");
+                }
+                PathElement::Index(i) => out.push_str(&format!("[{}]", i)),
+                _ => return Err(positional_error(self, "a CEL field path")),
+            }
+        }
+        Ok(out)
+    }
+
+    /// Parses a CEL field path rendered by to_cel_field_path. Bracketed
+    /// numbers are indexes and bracketed strings field names.
+    pub fn from_cel_field_path(s: &str) -> Result<Path, SerializeError> {
+        let error = |message: &str| SerializeError::new(format!("{} in CEL field path {:?}", message, s));
+        let mut path = Path::new();
+        let mut chars = s.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '.' => {
+                    let mut name = String::new();
+                    while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_') {
+                        name.push(c);
+                    }
+                    if !is_cel_identifier(&name) {
+                        return Err(error("expected an identifier"));
+                    }
+                    path.push(PathElement::FieldName(name));
+                }
+                '[' if chars.next_if_eq(&'\'').is_some() => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('\\') => name.push(chars.next().ok_or_else(|| error("unterminated string"))?),
+                            Some('\'') => break,
+                            Some(c) => name.push(c),
+                            None => return Err(error("unterminated string")),
+                        }
+                    }
+                    if chars.next() != Some(']') {
+                        return Err(error("expected ']'"));
+                    }
+                    path.push(PathElement::FieldName(name));
+                }
+                '[' => {
+                    let mut digits = String::new();
+                    while let Some(c) = chars.next_if(char::is_ascii_digit) {
+                        digits.push(c);
+                    }
+                    let index = digits.parse().map_err(|_| error("expected an index"))?;
+                    if chars.next() != Some(']') {
+                        return Err(error("expected ']'"));
+                    }
+                    path.push(PathElement::Index(index));
+                }
+                c => return Err(error(&format!("unexpected {:?}", c))),
+            }
+        }
+        Ok(path)
+    }
+}
+
+fn positional_error(path: &Path, form: &str) -> SerializeError {
+    SerializeError::new(format!(
+        "path {} has list keys or set values, which can't be expressed as {}",
+        path, form
+    ))
+}
+
+/// Returns true if the name is a CEL identifier, which field paths write
+/// after a dot.
+fn is_cel_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Parses a JSON string at the start of `rest`, advancing past it.
+fn parse_quoted(rest: &mut &str) -> Result<String, SerializeError> {
+    let mut de = serde_json::Deserializer::from_str(rest).into_iter::<String>();
+    match (de.next(), rest.get(de.byte_offset()..)) {
+        (Some(Ok(s)), Some(after)) => {
+            *rest = after;
+            Ok(s)
+        }
+        _ => Err(SerializeError::new(format!("malformed quoted string at {:?}", rest))),
+    }
+}
+
+fn parse_name(rest: &mut &str) -> Result<String, SerializeError> {
+    if rest.starts_with('"') {
+        return parse_quoted(rest);
+    }
+    let end = rest.find(|c| !is_plain_char(c)).unwrap_or(rest.len());
+    if end == 0 {
+        return Err(SerializeError::new(format!("expected a field name at {:?}", rest)));
+    }
+    let (name, after) = rest.split_at(end);
+    *rest = after;
+    Ok(name.to_string())
+}
+
+fn parse_scalar(rest: &mut &str) -> Result<Value, SerializeError> {
+    if rest.starts_with('"') {
+        return parse_quoted(rest).map(Value::String);
+    }
+    let end = rest.find([',', ']']).unwrap_or(rest.len());
+    let (token, after) = rest.split_at(end);
+    let value = match token {
+        "null" => Value::Null,
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        _ => match (token.parse::<i64>(), token.parse::<f64>()) {
+            (Ok(i), _) => Value::Int(i),
+            (_, Ok(f)) => Value::Float(f),
+            _ => return Err(SerializeError::new(format!("unsupported value {:?} in path", token))),
+        },
+    };
+    *rest = after;
+    Ok(value)
+}
+
+fn expect(rest: &mut &str, c: char) -> Result<(), SerializeError> {
+    match rest.strip_prefix(c) {
+        Some(after) => {
+            *rest = after;
+            Ok(())
+        }
+        None => Err(SerializeError::new(format!("expected {:?} at {:?}", c, rest))),
+    }
+}
+
+/// Parses the inside of `[~kind:json]` after the tilde.
+fn parse_extension(rest: &mut &str) -> Result<PathElement, SerializeError> {
+    let Some((kind, after)) = rest.split_once(':') else {
+        return Err(SerializeError::new(format!("expected an extension kind at {:?}", rest)));
+    };
+    let mut de = serde_json::Deserializer::from_str(after).into_iter::<serde_json::Value>();
+    let (json, after) = match (de.next(), after.get(..de.byte_offset()), after.get(de.byte_offset()..)) {
+        (Some(Ok(_)), Some(json), Some(after)) => (json, after),
+        _ => return Err(SerializeError::new(format!("malformed extension argument at {:?}", after))),
+    };
+    let pe = PathElement::Extension(kind.to_string(), json_to_value(json)?);
+    *rest = after;
+    expect(rest, ']')?;
+    Ok(pe)
+}
+
+/// Parses the inside of `[...]` after the opening bracket.
+fn parse_bracket(rest: &mut &str) -> Result<PathElement, SerializeError> {
+    if let Some(after) = rest.strip_prefix('~') {
+        *rest = after;
+        return parse_extension(rest);
+    }
+    if let Some(after) = rest.strip_prefix('=') {
+        *rest = after;
+        let value = parse_scalar(rest)?;
+        expect(rest, ']')?;
+        return Ok(PathElement::Value(value));
+    }
+    if let Some((index, after)) = rest.split_once(']') {
+        if let Ok(i) = index.parse::<i32>() {
+            *rest = after;
+            return Ok(PathElement::Index(i));
+        }
+    }
+    let mut fields = Vec::new();
+    loop {
+        let name = parse_name(rest)?;
+        expect(rest, '=')?;
+        let value = parse_scalar(rest)?;
+        fields.push(Field { name, value });
+        if expect(rest, ',').is_err() {
+            expect(rest, ']')?;
+            return Ok(PathElement::Key(FieldList::with_fields(fields)));
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -268,6 +597,93 @@ mod tests {
         assert_eq!(format!("{}", path), ".metadata.name");
     }
 
+    #[test]
+    fn test_path_parse_round_trip() {
+        let key = |fields: Vec<(&str, Value)>| {
+            PathElement::key(FieldList::with_fields(
+                fields
+                    .into_iter()
+                    .map(|(name, value)| Field {
+                        name: name.to_string(),
+                        value,
+                    })
+                    .collect(),
+            ))
+        };
+        let cases = [
+            (vec![], ""),
+            (
+                vec![
+                    PathElement::field_name("metadata"),
+                    PathElement::field_name("annotations"),
+                    PathElement::field_name("example.com/role"),
+                ],
+                r#".metadata.annotations."example.com/role""#,
+            ),
+            (
+                vec![
+                    PathElement::field_name("spec"),
+                    PathElement::field_name("containers"),
+                    key(vec![("name", Value::String("a\"b".into()))]),
+                    PathElement::field_name("ports"),
+                    key(vec![("containerPort", Value::Int(80)), ("protocol", Value::String("TCP".into()))]),
+                ],
+                r#".spec.containers[name="a\"b"].ports[containerPort=80,protocol="TCP"]"#,
+            ),
+            (
+                vec![
+                    PathElement::field_name("größe"),
+                    PathElement::field_name(""),
+                    PathElement::field_name("items"),
+                    PathElement::index(-1),
+                    PathElement::index(3),
+                ],
+                r#"."größe"."".items[-1][3]"#,
+            ),
+            (
+                vec![
+                    PathElement::field_name("finalizers"),
+                    PathElement::value(Value::String("kubernetes.io/pvc".into())),
+                    PathElement::value(Value::Bool(true)),
+                    PathElement::value(Value::Float(1.5)),
+                    PathElement::value(Value::Null),
+                    key(vec![("a.b", Value::String("]".into()))]),
+                ],
+                r#".finalizers[="kubernetes.io/pvc"][=true][=1.5][=null]["a.b"="]"]"#,
+            ),
+            (
+                vec![
+                    PathElement::field_name("a"),
+                    PathElement::value(Value::Float(2.0)),
+                    PathElement::value(Value::Float(1e300)),
+                    PathElement::value(Value::Float(-2.5e-8)),
+                    key(vec![("x", Value::Float(-3.0))]),
+                ],
+                ".a[=2.0][=1e300][=-2.5e-8][x=-3.0]",
+            ),
+        ];
+        for (elements, rendered) in cases {
+            let path = Path::from_elements(elements);
+            assert_eq!(path.to_string(), rendered);
+            assert_eq!(Path::parse(rendered).unwrap(), path);
+            assert_eq!(rendered.parse::<Path>().unwrap(), path);
+        }
+
+        for bad in ["spec", ".", ".a[", ".a[x]", ".a[=]", r#"."unterminated"#, ".a[name=1", ".a b"] {
+            assert!(Path::parse(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_path_parse_non_ascii() {
+        for bad in ["é", ".a€", "[é]", ".a[€=1]", "€.a", ".a[=1]ü"] {
+            assert!(Path::parse(bad).is_err(), "{}", bad);
+        }
+        let path = Path::from_elements(vec![PathElement::field_name("é"), PathElement::field_name("a€")]);
+        assert_eq!(path.to_string(), r#"."é"."a€""#);
+        assert_eq!(Path::parse(&path.to_string()).unwrap(), path);
+    }
+
     #[test]
     fn test_path_element_ordering() {
         let a = PathElement::field_name("a");
@@ -278,4 +694,29 @@ mod tests {
         // Field names come before indices
         assert!(a < idx);
     }
+
+    #[test]
+    fn test_path_element_extension() {
+        use crate::fieldpath::{deserialize_path_element, serialize_path_element, Set};
+
+        let regex = PathElement::extension("regex", Value::String("^app-".into())).unwrap();
+        assert!(PathElement::index(i32::MAX) < regex);
+        assert!(regex < PathElement::extension("regex", Value::String("^db-".into())).unwrap());
+        assert!(regex < PathElement::extension("wildcard", Value::Null).unwrap());
+        assert!(PathElement::extension("a:b", Value::Null).is_err());
+
+        let path = Path::from_elements(vec![PathElement::field_name("labels"), regex.clone()]);
+        assert_eq!(path.to_string(), r#".labels[~regex:"^app-"]"#);
+        assert_eq!(Path::parse(&path.to_string()).unwrap(), path);
+
+        let serialized = serialize_path_element(&regex).unwrap();
+        assert_eq!(serialized, r#"x:regex:"^app-""#);
+        assert_eq!(deserialize_path_element(&serialized).unwrap(), regex);
+        assert!(serialize_path_element(&PathElement::Extension("a:b".into(), Value::Null)).is_err());
+
+        let mut set = Set::new();
+        set.insert(&path);
+        assert_eq!(Set::from_json(&set.to_json().unwrap()).unwrap(), set);
+        assert_eq!(Set::from_bytes(&set.to_bytes()).unwrap(), set);
+    }
 }
@@ -37,28 +37,37 @@ impl<T: Clone> PathElementMap<T> {
     /// Inserts a PathElement and associated value into the map.
     /// If the PathElement already exists, the value is replaced.
     pub fn insert(&mut self, pe: PathElement, value: T) {
-        let loc = self.members.iter().position(|m| m.path_element >= pe);
-
-        match loc {
-            None => {
-                self.members.push(PathElementValue {
+        match self.members.binary_search_by(|m| m.path_element.cmp(&pe)) {
+            Ok(idx) => self.members[idx].value = value,
+            Err(idx) => {
+                self.members.insert(idx, PathElementValue {
                     path_element: pe,
                     value,
                 });
             }
-            Some(idx) => {
-                if self.members[idx].path_element == pe {
-                    self.members[idx].value = value;
-                } else {
-                    self.members.insert(idx, PathElementValue {
-                        path_element: pe,
-                        value,
-                    });
-                }
-            }
         }
     }
 
+    /// Inserts all the entries, sorting once instead of once per entry.
+    /// Later entries replace earlier ones and existing entries with the
+    /// same PathElement.
+    pub fn insert_all(&mut self, entries: impl IntoIterator<Item = (PathElement, T)>) {
+        self.members.extend(entries.into_iter().map(|(path_element, value)| PathElementValue {
+            path_element,
+            value,
+        }));
+        // The sort is stable, so entries with the same PathElement stay in
+        // insertion order and the last one's value is kept
+        self.members.sort_by(|a, b| a.path_element.cmp(&b.path_element));
+        self.members.dedup_by(|later, kept| {
+            if later.path_element != kept.path_element {
+                return false;
+            }
+            std::mem::swap(&mut later.value, &mut kept.value);
+            true
+        });
+    }
+
     /// Gets the value associated with the given PathElement.
     /// Returns None if the PathElement is not in the map.
     pub fn get(&self, pe: &PathElement) -> Option<&T> {
@@ -76,6 +85,23 @@ impl<T: Clone> PathElementMap<T> {
             .map(|idx| &mut self.members[idx].value)
     }
 
+    /// Gets the values associated with each of the given PathElements, in
+    /// the same order. Sorted lookups are answered in one pass over the
+    /// map.
+    pub fn get_all<'a>(&'a self, pes: &[PathElement]) -> Vec<Option<&'a T>> {
+        if !pes.is_sorted() {
+            return pes.iter().map(|pe| self.get(pe)).collect();
+        }
+        let mut out = Vec::with_capacity(pes.len());
+        let mut rest = self.members.as_slice();
+        for pe in pes {
+            let idx = rest.partition_point(|m| m.path_element < *pe);
+            rest = &rest[idx..];
+            out.push(rest.first().filter(|m| m.path_element == *pe).map(|m| &m.value));
+        }
+        out
+    }
+
     /// Returns true if the map contains the given PathElement.
     pub fn contains(&self, pe: &PathElement) -> bool {
         self.members
@@ -97,6 +123,12 @@ impl<T: Clone> PathElementMap<T> {
         self.members.iter().map(|m| (&m.path_element, &m.value))
     }
 
+    /// Returns the entries in the map, sorted by PathElement. Unlike iter,
+    /// it knows its length and can be walked from either end.
+    pub fn entries(&self) -> impl ExactSizeIterator<Item = (&PathElement, &T)> + DoubleEndedIterator {
+        self.members.iter().map(|m| (&m.path_element, &m.value))
+    }
+
     /// Returns an iterator over the keys (PathElements) in the map.
     pub fn keys(&self) -> impl Iterator<Item = &PathElement> {
         self.members.iter().map(|m| &m.path_element)
@@ -108,6 +140,37 @@ impl<T: Clone> PathElementMap<T> {
     }
 }
 
+impl<T: Clone> From<Vec<(PathElement, T)>> for PathElementMap<T> {
+    /// Builds a map from pairs. Later pairs replace earlier ones with the
+    /// same PathElement.
+    fn from(pairs: Vec<(PathElement, T)>) -> Self {
+        let mut map = PathElementMap::new(pairs.len());
+        map.insert_all(pairs);
+        map
+    }
+}
+
+impl<T: Clone> FromIterator<(PathElement, T)> for PathElementMap<T> {
+    fn from_iter<I: IntoIterator<Item = (PathElement, T)>>(iter: I) -> Self {
+        let mut map = PathElementMap::new(0);
+        map.insert_all(iter);
+        map
+    }
+}
+
+impl<T: Clone> Extend<(PathElement, T)> for PathElementMap<T> {
+    fn extend<I: IntoIterator<Item = (PathElement, T)>>(&mut self, iter: I) {
+        self.insert_all(iter);
+    }
+}
+
+impl<T> From<PathElementMap<T>> for Vec<(PathElement, T)> {
+    /// Returns the pairs of the map, sorted by PathElement.
+    fn from(map: PathElementMap<T>) -> Self {
+        map.members.into_iter().map(|m| (m.path_element, m.value)).collect()
+    }
+}
+
 /// PathElementValueMap is a specialized map from PathElement to Value.
 pub type PathElementValueMap = PathElementMap<Value>;
 
@@ -176,4 +239,31 @@ mod tests {
             Some(&Value::Int(42))
         );
     }
+
+    #[test]
+    fn test_path_element_value_map_bulk() {
+        let mut map = PathElementValueMap::from(vec![
+            (PathElement::field_name("b"), Value::Int(1)),
+            (PathElement::field_name("a"), Value::Int(2)),
+            (PathElement::field_name("b"), Value::Int(3)),
+        ]);
+        map.insert_all([
+            (PathElement::field_name("c"), Value::Int(4)),
+            (PathElement::field_name("a"), Value::Int(5)),
+        ]);
+        assert_eq!(map.len(), 3);
+
+        let keys = [PathElement::field_name("a"), PathElement::field_name("b"), PathElement::field_name("d")];
+        let found = [Some(&Value::Int(5)), Some(&Value::Int(3)), None];
+        assert_eq!(map.get_all(&keys), found);
+        let reversed: Vec<_> = keys.iter().rev().cloned().collect();
+        assert_eq!(map.get_all(&reversed), found.into_iter().rev().collect::<Vec<_>>());
+
+        assert_eq!(map.entries().len(), 3);
+        assert_eq!(map.entries().next_back().map(|(pe, _)| pe.clone()), Some(PathElement::field_name("c")));
+        let pairs: Vec<(PathElement, Value)> = map.clone().into();
+        assert_eq!(pairs[0], (PathElement::field_name("a"), Value::Int(5)));
+        let collected: PathElementValueMap = pairs.into_iter().collect();
+        assert_eq!(collected.keys().collect::<Vec<_>>(), map.keys().collect::<Vec<_>>());
+    }
 }
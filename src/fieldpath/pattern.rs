@@ -0,0 +1,171 @@
+//! Patterns of paths, for policies over the fields a set touches.
+
+#![cfg_attr(not(test), deny(clippy::indexing_slicing, clippy::string_slice))]
+
+use super::path::{Path, PathElement};
+use super::serialize::SerializeError;
+use std::fmt;
+
+/// PatternElement is one element of a path pattern.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PatternElement {
+    /// Matches this path element only.
+    Exact(PathElement),
+    /// Matches any single path element.
+    Any,
+}
+
+/// SetPattern is a list of path patterns. A pattern matches the paths
+/// that start with elements it matches, so `.metadata.labels` matches every
+/// label and `.spec.containers[*].image` the image of every container.
+///
+/// Patterns parse from the path syntax of Path::parse, with `.*` and `[*]`
+/// standing for any element.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SetPattern {
+    patterns: Vec<Vec<PatternElement>>,
+}
+
+impl SetPattern {
+    /// Creates a pattern that matches no path.
+    pub fn new() -> Self {
+        SetPattern::default()
+    }
+
+    /// Adds a pattern matching `path` and the paths beneath it.
+    pub fn with_path(self, path: &Path) -> Self {
+        self.with_elements(path.iter().cloned().map(PatternElement::Exact).collect())
+    }
+
+    /// Adds a pattern of the given elements.
+    pub fn with_elements(mut self, elements: Vec<PatternElement>) -> Self {
+        self.patterns.push(elements);
+        self
+    }
+
+    /// Parses a pattern, such as `.spec.containers[*].image`, and adds it.
+    pub fn with_pattern(self, pattern: &str) -> Result<Self, SerializeError> {
+        Ok(self.with_elements(parse_pattern(pattern)?))
+    }
+
+    /// Parses the patterns and returns a SetPattern of them all.
+    pub fn parse<S: AsRef<str>>(patterns: impl IntoIterator<Item = S>) -> Result<Self, SerializeError> {
+        patterns
+            .into_iter()
+            .try_fold(SetPattern::new(), |set, pattern| set.with_pattern(pattern.as_ref()))
+    }
+
+    /// Returns true if a pattern matches the path.
+    pub fn matches(&self, path: &Path) -> bool {
+        let elements = path.as_slice();
+        self.patterns.iter().any(|pattern| {
+            pattern.len() <= elements.len()
+                && pattern.iter().zip(elements).all(|(p, pe)| match p {
+                    PatternElement::Exact(expected) => expected == pe,
+                    PatternElement::Any => true,
+                })
+        })
+    }
+}
+
+/// Splits the pattern at its `.*` and `[*]` elements, outside of quoted
+/// names and values, and parses the paths in between.
+fn parse_pattern(pattern: &str) -> Result<Vec<PatternElement>, SerializeError> {
+    let mut elements = Vec::new();
+    let mut segment = String::new();
+    let push_segment = |segment: &mut String, elements: &mut Vec<PatternElement>| {
+        let path = Path::parse(segment)?;
+        elements.extend(path.into_iter().map(PatternElement::Exact));
+        segment.clear();
+        Ok::<(), SerializeError>(())
+    };
+
+    let mut rest = pattern;
+    let mut quoted = false;
+    while let Some(c) = rest.chars().next() {
+        let any = if quoted {
+            None
+        } else if let Some(after) = rest.strip_prefix("[*]") {
+            Some(after)
+        } else {
+            rest.strip_prefix(".*")
+                .filter(|after| !after.starts_with(|c: char| c != '.' && c != '['))
+        };
+        if let Some(after) = any {
+            push_segment(&mut segment, &mut elements)?;
+            elements.push(PatternElement::Any);
+            rest = after;
+            continue;
+        }
+
+        let mut chars = rest.chars();
+        chars.next();
+        match c {
+            '"' => quoted = !quoted,
+            '\\' if quoted => {
+                // Keep the escaped character, which may be a quote
+                segment.push(c);
+                let Some(escaped) = chars.next() else {
+                    break;
+                };
+                segment.push(escaped);
+                rest = chars.as_str();
+                continue;
+            }
+            _ => {}
+        }
+        segment.push(c);
+        rest = chars.as_str();
+    }
+    push_segment(&mut segment, &mut elements)?;
+    Ok(elements)
+}
+
+impl fmt::Display for SetPattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, pattern) in self.patterns.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            for element in pattern {
+                match element {
+                    PatternElement::Exact(pe) => write!(f, "{}", pe)?,
+                    PatternElement::Any => write!(f, "[*]")?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_pattern() {
+        let pattern = SetPattern::parse([
+            ".metadata.labels",
+            r#".spec.containers[*].image"#,
+            r#".metadata.annotations."example.com/*""#,
+        ])
+        .unwrap();
+        let path = |s: &str| Path::parse(s).unwrap();
+
+        assert!(pattern.matches(&path(".metadata.labels")));
+        assert!(pattern.matches(&path(".metadata.labels.app")));
+        assert!(!pattern.matches(&path(".metadata")));
+        assert!(pattern.matches(&path(r#".spec.containers[name="app"].image"#)));
+        assert!(!pattern.matches(&path(r#".spec.containers[name="app"].command"#)));
+        // Quoted stars are names
+        assert!(pattern.matches(&path(r#".metadata.annotations."example.com/*""#)));
+        assert!(!pattern.matches(&path(r#".metadata.annotations."example.com/role""#)));
+
+        assert_eq!(
+            pattern.to_string(),
+            r#".metadata.labels, .spec.containers[*].image, .metadata.annotations."example.com/*""#
+        );
+        assert!(SetPattern::parse([".spec[*"]).is_err());
+        assert!(SetPattern::parse(["é", ".a€.*"]).is_err());
+    }
+}
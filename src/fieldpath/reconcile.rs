@@ -0,0 +1,276 @@
+//! Reconciliation of recorded field sets with schema changes.
+//!
+//! Managers' field sets are recorded against the schema in use when they
+//! wrote. When the schema changes, for example when a CRD makes a struct
+//! atomic, the recorded sets must be brought in line with the new schema
+//! before they are used for conflict detection again.
+
+use super::{Path, PathElement, Set};
+use crate::schema::{Atom, ElementRelationship, Map, Scalar, Schema, TypeRef};
+
+/// ReconcileOptions tunes reconcile_set_with_schema.
+#[derive(Debug, Clone, Default)]
+pub struct ReconcileOptions {
+    /// When a type that the old schema made atomic is granular in the new
+    /// one, keep the owner of the former atomic field owning its subfields
+    /// with a wildcard. By default the owner keeps only the field itself.
+    /// Requires the old schema.
+    pub keep_atomic_ownership: bool,
+    /// Removes fields the new schema doesn't define, unless it preserves
+    /// unknown fields.
+    pub prune_unknown_fields: bool,
+}
+
+/// ReconcileError is returned when a type of the set can't be resolved.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReconcileError {
+    pub message: String,
+}
+
+impl std::fmt::Display for ReconcileError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ReconcileError {}
+
+/// Reconciles a field set recorded against `old_schema` with `new_schema`,
+/// both describing objects of `type_ref`.
+///
+/// Returns the reconciled set, or None if it needs no changes. Fields of
+/// types changed from granular to atomic are collapsed to the atomic field
+/// itself. Fields of types changed from atomic to granular are kept as they
+/// are, unless `keep_atomic_ownership` is set. Without the old schema,
+/// changes are detected from the shape of the set alone.
+///
+/// Apiserver-like consumers should run it on every recorded set whenever a
+/// schema changes.
+pub fn reconcile_set_with_schema(
+    set: &Set,
+    old_schema: Option<&Schema>,
+    new_schema: &Schema,
+    type_ref: &TypeRef,
+    options: &ReconcileOptions,
+) -> Result<Option<Set>, ReconcileError> {
+    let mut reconciler = Reconciler {
+        old_schema,
+        new_schema,
+        options,
+        to_remove: Set::new(),
+        to_add: Set::new(),
+    };
+    let old_type = old_schema.map(|_| type_ref);
+    reconciler.reconcile(set, &Path::new(), type_ref, old_type, false)?;
+
+    if reconciler.to_remove.is_empty() && reconciler.to_add.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(set.recursive_difference(&reconciler.to_remove).union(&reconciler.to_add)))
+}
+
+struct Reconciler<'a> {
+    old_schema: Option<&'a Schema>,
+    new_schema: &'a Schema,
+    options: &'a ReconcileOptions,
+    to_remove: Set,
+    to_add: Set,
+}
+
+impl Reconciler<'_> {
+    /// Reconciles `set`, the part of the set beneath `path`. `is_atomic` is
+    /// true if the path is owned as a leaf.
+    fn reconcile(
+        &mut self,
+        set: &Set,
+        path: &Path,
+        type_ref: &TypeRef,
+        old_type: Option<&TypeRef>,
+        is_atomic: bool,
+    ) -> Result<(), ReconcileError> {
+        let atom = self.new_schema.resolve(type_ref).ok_or_else(|| ReconcileError {
+            message: format!("could not resolve {:?}", type_ref),
+        })?;
+        let old_atom = self.old_schema.zip(old_type).and_then(|(schema, tr)| schema.resolve(tr));
+
+        if let Some(map) = &atom.map {
+            // Deduced types have no atomic or granular tags to reconcile
+            if is_untyped_deduced_map(map) {
+                return Ok(());
+            }
+        } else if atom.list.is_none() {
+            return Ok(());
+        }
+
+        if !is_atomic && is_atomic_atom(&atom) {
+            // Granular to atomic: the owner of any subfield now owns just the
+            // atomic field. Migrations the other way are treated as if the
+            // type had always been granular.
+            self.to_remove.insert(path);
+            self.to_add.insert(path);
+            return Ok(());
+        }
+        if is_atomic
+            && self.options.keep_atomic_ownership
+            && !is_atomic_atom(&atom)
+            && old_atom.as_ref().is_some_and(is_atomic_atom)
+        {
+            self.to_add.insert_wildcard(path);
+        }
+
+        let mut elements: Vec<(PathElement, bool)> = Vec::new();
+        set.children_iterate(|pe| {
+            if !set.members_has(pe) {
+                elements.push((pe.clone(), false));
+            }
+        });
+        set.members_iterate(|pe| elements.push((pe.clone(), true)));
+
+        for (pe, is_member) in elements {
+            let child_type = match (&atom.map, &atom.list) {
+                (Some(map), _) => type_ref_at_path(map, &pe),
+                (_, Some(list)) => Some(list.element_type.clone()),
+                _ => None,
+            };
+            let child_path = path.with(pe.clone());
+            let Some(child_type) = child_type else {
+                if self.options.prune_unknown_fields && !self.new_schema.preserve_unknown_fields() {
+                    self.to_remove.insert(&child_path);
+                }
+                continue;
+            };
+            let old_child_type = old_atom.as_ref().and_then(|old| match (&old.map, &old.list) {
+                (Some(map), _) => type_ref_at_path(map, &pe),
+                (_, Some(list)) => Some(list.element_type.clone()),
+                _ => None,
+            });
+            let empty = Set::new();
+            let child_set = set.children_get(&pe).unwrap_or(&empty);
+            let child_is_atomic = is_member && child_set.is_empty();
+            self.reconcile(child_set, &child_path, &child_type, old_child_type.as_ref(), child_is_atomic)?;
+        }
+        Ok(())
+    }
+}
+
+fn is_atomic_atom(atom: &Atom) -> bool {
+    match (&atom.map, &atom.list) {
+        (Some(map), _) => map.element_relationship == ElementRelationship::Atomic,
+        (_, Some(list)) => list.element_relationship == ElementRelationship::Atomic,
+        _ => false,
+    }
+}
+
+fn type_ref_at_path(map: &Map, pe: &PathElement) -> Option<TypeRef> {
+    let tr = match pe.as_field_name().and_then(|name| map.find_field(name)) {
+        Some(field) => field.field_type.clone(),
+        None => map.element_type.clone(),
+    };
+
+    // An empty reference means the map has no such field
+    if tr.named_type.is_none() && tr.inlined.scalar.is_none() && tr.inlined.list.is_none() && tr.inlined.map.is_none() {
+        None
+    } else {
+        Some(tr)
+    }
+}
+
+/// Returns true if m has no fields defined, but allows untyped elements.
+fn is_untyped_deduced_map(m: &Map) -> bool {
+    is_untyped_deduced_ref(&m.element_type) && m.fields.is_empty()
+}
+
+fn is_untyped_deduced_ref(t: &TypeRef) -> bool {
+    if let Some(ref name) = t.named_type {
+        return name == "__untyped_deduced_";
+    }
+    if let Some(ref scalar) = t.inlined.scalar {
+        return *scalar == Scalar::Untyped;
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schema(atomic: bool) -> Schema {
+        let relationship = if atomic { "atomic" } else { "separable" };
+        serde_yaml::from_str(&format!(
+            r#"types:
+- name: root
+  map:
+    fields:
+    - name: spec
+      type:
+        namedType: spec
+- name: spec
+  map:
+    elementRelationship: {}
+    fields:
+    - name: replicas
+      type:
+        scalar: numeric
+    - name: paused
+      type:
+        scalar: boolean
+"#,
+            relationship
+        ))
+        .unwrap()
+    }
+
+    fn set(paths: &[&[&str]]) -> Set {
+        let mut set = Set::new();
+        for path in paths {
+            set.insert(&Path::from_elements(path.iter().map(|f| PathElement::field_name(*f)).collect()));
+        }
+        set
+    }
+
+    #[test]
+    fn test_reconcile_set_with_schema() {
+        let root = TypeRef {
+            named_type: Some("root".to_string()),
+            ..Default::default()
+        };
+        let (granular, atomic) = (schema(false), schema(true));
+        let defaults = ReconcileOptions::default();
+
+        let owned = set(&[&["spec", "replicas"]]);
+        let collapsed = reconcile_set_with_schema(&owned, Some(&granular), &atomic, &root, &defaults).unwrap();
+        assert_eq!(collapsed, Some(set(&[&["spec"]])));
+        assert_eq!(reconcile_set_with_schema(&owned, None, &atomic, &root, &defaults).unwrap(), collapsed);
+
+        // Atomic to granular keeps the field itself unless asked otherwise
+        let owned = set(&[&["spec"]]);
+        assert_eq!(reconcile_set_with_schema(&owned, Some(&atomic), &granular, &root, &defaults).unwrap(), None);
+        let keep = ReconcileOptions {
+            keep_atomic_ownership: true,
+            ..Default::default()
+        };
+        let expanded = reconcile_set_with_schema(&owned, Some(&atomic), &granular, &root, &keep)
+            .unwrap()
+            .unwrap();
+        let spec = Path::from_elements(vec![PathElement::field_name("spec")]);
+        assert!(expanded.has_wildcard(&spec));
+        assert!(expanded.has(&spec.with(PathElement::field_name("paused"))));
+        assert_eq!(reconcile_set_with_schema(&owned, None, &granular, &root, &keep).unwrap(), None);
+
+        let owned = set(&[&["spec", "replicas"], &["spec", "removed"]]);
+        assert_eq!(reconcile_set_with_schema(&owned, None, &granular, &root, &defaults).unwrap(), None);
+        let prune = ReconcileOptions {
+            prune_unknown_fields: true,
+            ..Default::default()
+        };
+        let pruned = reconcile_set_with_schema(&owned, None, &granular, &root, &prune).unwrap();
+        assert_eq!(pruned, Some(set(&[&["spec", "replicas"]])));
+
+        let missing = TypeRef {
+            named_type: Some("missing".to_string()),
+            ..Default::default()
+        };
+        let err = reconcile_set_with_schema(&owned, None, &granular, &missing, &defaults).unwrap_err();
+        assert!(err.message.starts_with("could not resolve"));
+    }
+}
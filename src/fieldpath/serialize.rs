@@ -1,9 +1,15 @@
 //! Serialization for fieldpath types.
 
-use super::path::PathElement;
+#![cfg_attr(not(test), deny(clippy::indexing_slicing, clippy::string_slice))]
+
+use super::path::{Path, PathElement};
 use super::set::Set;
 use crate::value::{Field, FieldList, Value};
-use serde_json;
+use serde::de::{DeserializeSeed, Error as _, IgnoredAny, MapAccess, Visitor};
+use serde::ser::{Error as _, SerializeMap};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::borrow::Cow;
+use std::io;
 
 /// Error type for serialization/deserialization.
 #[derive(Debug, Clone)]
@@ -34,6 +40,7 @@ impl std::error::Error for SerializeError {}
 /// - Value: "v:json_value"
 /// - Key: "k:{json_object}"
 /// - Index: "i:number"
+/// - Extension: "x:kind:json_value"
 pub fn serialize_path_element(pe: &PathElement) -> Result<String, SerializeError> {
     match pe {
         PathElement::FieldName(name) => Ok(format!("f:{}", name)),
@@ -46,17 +53,21 @@ pub fn serialize_path_element(pe: &PathElement) -> Result<String, SerializeError
             Ok(format!("k:{}", json))
         }
         PathElement::Index(i) => Ok(format!("i:{}", i)),
+        PathElement::Extension(kind, v) => {
+            if kind.contains(':') {
+                return Err(SerializeError::new(format!("extension kind {:?} contains ':'", kind)));
+            }
+            let json = value_to_json(v)?;
+            Ok(format!("x:{}:{}", kind, json))
+        }
     }
 }
 
 /// Deserializes a PathElement from its string representation.
 pub fn deserialize_path_element(s: &str) -> Result<PathElement, SerializeError> {
-    if s.len() < 2 {
-        return Err(SerializeError::new("key must be at least 2 characters long"));
-    }
-
-    let prefix = &s[..2];
-    let content = &s[2..];
+    let (Some(prefix), Some(content)) = (s.get(..2), s.get(2..)) else {
+        return Err(SerializeError::new(format!("malformed key {:?}: no type prefix", s)));
+    };
 
     match prefix {
         "f:" => Ok(PathElement::FieldName(content.to_string())),
@@ -74,6 +85,12 @@ pub fn deserialize_path_element(s: &str) -> Result<PathElement, SerializeError>
                 .map_err(|e| SerializeError::new(format!("invalid index: {}", e)))?;
             Ok(PathElement::Index(i))
         }
+        "x:" => {
+            let Some((kind, json)) = content.split_once(':') else {
+                return Err(SerializeError::new(format!("malformed extension {:?}: no kind", s)));
+            };
+            Ok(PathElement::Extension(kind.to_string(), json_to_value(json)?))
+        }
         _ => Err(SerializeError::new(format!(
             "unknown path element type: {}",
             prefix
@@ -126,7 +143,7 @@ fn field_list_to_json(fields: &FieldList) -> Result<String, SerializeError> {
 }
 
 /// Converts a JSON string to Value.
-fn json_to_value(s: &str) -> Result<Value, SerializeError> {
+pub(super) fn json_to_value(s: &str) -> Result<Value, SerializeError> {
     let json_value: serde_json::Value =
         serde_json::from_str(s).map_err(|e| SerializeError::new(format!("JSON parse error: {}", e)))?;
     Ok(serde_json_to_value(&json_value))
@@ -182,133 +199,204 @@ fn json_to_field_list(s: &str) -> Result<FieldList, SerializeError> {
     }
 }
 
-/// Serializes a Set to JSON bytes.
+impl Path {
+    /// Creates a path from serialized elements such as `f:spec` and
+    /// `k:{"name":"nginx"}`, the keys used in FieldsV1.
+    pub fn from_serialized(elements: &[&str]) -> Result<Path, SerializeError> {
+        let elements = elements.iter().map(|s| deserialize_path_element(s)).collect::<Result<_, _>>()?;
+        Ok(Path::from_elements(elements))
+    }
+}
+
 impl Set {
+    /// Serializes a Set to FieldsV1 JSON bytes.
+    ///
+    /// FieldsV1 has no wildcards, so a set with wildcards is rejected; lower
+    /// them with lower_wildcards first, or use to_json_with_wildcards for
+    /// readers of this crate.
     pub fn to_json(&self) -> Result<Vec<u8>, SerializeError> {
-        let json_obj = self.to_json_object(false)?;
-        serde_json::to_vec(&json_obj).map_err(|e| SerializeError::new(format!("JSON error: {}", e)))
+        serde_json::to_vec(self).map_err(|e| SerializeError::new(format!("JSON error: {}", e)))
     }
 
-    /// Deserializes a Set from JSON bytes.
-    pub fn from_json(data: &[u8]) -> Result<Set, SerializeError> {
-        let json_value: serde_json::Value =
-            serde_json::from_slice(data).map_err(|e| SerializeError::new(format!("JSON parse error: {}", e)))?;
+    /// Serializes a Set to FieldsV1 JSON bytes, encoding wildcards under a
+    /// `w:*` key. The apiserver doesn't accept the key, so the result is only
+    /// for readers of this crate.
+    pub fn to_json_with_wildcards(&self) -> Result<Vec<u8>, SerializeError> {
+        let level = SetLevel {
+            set: self,
+            include_self: false,
+            wildcards: true,
+        };
+        serde_json::to_vec(&level).map_err(|e| SerializeError::new(format!("JSON error: {}", e)))
+    }
 
-        match json_value {
-            serde_json::Value::Object(obj) => Self::from_json_object(obj),
-            _ => Err(SerializeError::new("expected JSON object")),
-        }
+    /// Deserializes a Set from FieldsV1 JSON bytes.
+    pub fn from_json(data: &[u8]) -> Result<Set, SerializeError> {
+        serde_json::from_slice(data).map_err(|e| SerializeError::new(format!("JSON parse error: {}", e)))
     }
 
-    fn to_json_object(&self, include_self: bool) -> Result<serde_json::Map<String, serde_json::Value>, SerializeError> {
-        let mut result = serde_json::Map::new();
+    /// Streams the FieldsV1 JSON encoding of the Set into a writer, without
+    /// building an intermediate document.
+    pub fn to_writer<W: io::Write>(&self, writer: W) -> Result<(), SerializeError> {
+        serde_json::to_writer(writer, self).map_err(|e| SerializeError::new(format!("JSON error: {}", e)))
+    }
 
-        // If we need to mark the current path as being in the set
-        if include_self && !(self.members.is_empty() && self.children.is_empty()) {
-            result.insert(".".to_string(), serde_json::Value::Object(serde_json::Map::new()));
-        }
+    /// Reads a Set from a FieldsV1 JSON stream, without building an
+    /// intermediate document.
+    pub fn from_reader<R: io::Read>(reader: R) -> Result<Set, SerializeError> {
+        serde_json::from_reader(reader).map_err(|e| SerializeError::new(format!("JSON parse error: {}", e)))
+    }
+}
 
-        // Merge members and children in sorted order
-        let mut all_elements: Vec<(&PathElement, Option<&Set>)> = Vec::new();
+/// One level of a Set in FieldsV1 form.
+///
+/// When include_self is set, the path leading to this level is itself a
+/// member and a "." entry is emitted. Wildcards are rejected unless
+/// wildcards is set.
+struct SetLevel<'a> {
+    set: &'a Set,
+    include_self: bool,
+    wildcards: bool,
+}
 
-        // Add members (with None for child set)
-        for member in self.members.iter() {
-            all_elements.push((member, None));
+impl Serialize for SetLevel<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // Entries are emitted in the order of their serialized keys.
+        let mut entries: Vec<(String, &PathElement, Option<&Set>)> =
+            Vec::with_capacity(self.set.members.len() + self.set.children.len());
+        for pe in self.set.members.iter() {
+            if !self.set.children.contains_key(pe) {
+                entries.push((serialize_path_element(pe).map_err(S::Error::custom)?, pe, None));
+            }
         }
-
-        // Add children
-        for (pe, child) in &self.children {
-            all_elements.push((pe, Some(child)));
+        for (pe, child) in &self.set.children {
+            entries.push((serialize_path_element(pe).map_err(S::Error::custom)?, pe, Some(child)));
         }
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
 
-        // Sort by path element
-        all_elements.sort_by(|(a, _), (b, _)| a.cmp(b));
-
-        // Deduplicate and merge where both member and child exist
-        let mut i = 0;
-        while i < all_elements.len() {
-            let (pe, child) = &all_elements[i];
-            let key = serialize_path_element(pe)?;
-
-            // Check if next element is the same path element
-            let has_child_next = i + 1 < all_elements.len()
-                && all_elements[i + 1].0 == *pe
-                && all_elements[i + 1].1.is_some();
-
-            if child.is_some() {
-                // This is a child set
-                let child_obj = child.unwrap().to_json_object(false)?;
-                result.insert(key, serde_json::Value::Object(child_obj));
-            } else if has_child_next {
-                // This is a member but next is the child - emit with include_self=true
-                let child_set = all_elements[i + 1].1.unwrap();
-                let child_obj = child_set.to_json_object(true)?;
-                result.insert(key, serde_json::Value::Object(child_obj));
-                i += 1; // Skip the child entry
-            } else {
-                // Just a member with no children
-                result.insert(key, serde_json::Value::Object(serde_json::Map::new()));
+        let wildcard = self.set.has_wildcard(&Path::new());
+        if wildcard && !self.wildcards {
+            return Err(S::Error::custom("FieldsV1 can't encode wildcards; lower them first"));
+        }
+        let include_self = self.include_self && (!entries.is_empty() || wildcard);
+        let len = entries.len() + usize::from(include_self) + usize::from(wildcard);
+        let mut map = serializer.serialize_map(Some(len))?;
+        if include_self {
+            map.serialize_entry(".", &EmptyObject)?;
+        }
+        for (key, pe, child) in &entries {
+            match child {
+                Some(child) => {
+                    let level = SetLevel {
+                        set: child,
+                        include_self: self.set.members.contains(pe),
+                        wildcards: self.wildcards,
+                    };
+                    map.serialize_entry(key, &level)?;
+                }
+                None => map.serialize_entry(key, &EmptyObject)?,
             }
+        }
+        // Sorts after every path element key
+        if wildcard {
+            map.serialize_entry(WILDCARD_KEY, &EmptyObject)?;
+        }
+        map.end()
+    }
+}
+
+/// The FieldsV1 key of a wildcard. Its `w` type is unknown to readers that
+/// don't support wildcards, which skip it like other future element types.
+const WILDCARD_KEY: &str = "w:*";
+
+/// The empty JSON object marking a leaf member.
+struct EmptyObject;
+
+impl Serialize for EmptyObject {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_map(Some(0))?.end()
+    }
+}
 
-            i += 1;
+impl Serialize for Set {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        SetLevel {
+            set: self,
+            include_self: false,
+            wildcards: false,
         }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Set {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (set, _) = deserializer.deserialize_map(LevelVisitor)?;
+        Ok(set)
+    }
+}
+
+/// Reads one level of FieldsV1, returning the set below it and whether the
+/// path leading to it is itself a member.
+struct LevelVisitor;
 
-        Ok(result)
+impl<'de> Visitor<'de> for LevelVisitor {
+    type Value = (Set, bool);
+
+    fn expecting(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "a FieldsV1 object")
     }
 
-    fn from_json_object(obj: serde_json::Map<String, serde_json::Value>) -> Result<Set, SerializeError> {
+    fn visit_map<A: MapAccess<'de>>(self, mut access: A) -> Result<Self::Value, A::Error> {
         let mut set = Set::new();
+        let mut is_empty = true;
+        let mut has_self = false;
 
-        for (key, value) in obj {
+        while let Some(key) = access.next_key::<Cow<'de, str>>()? {
+            is_empty = false;
             if key == "." {
-                // Mark current path as in set (handled at parent level)
+                has_self = true;
+                access.next_value::<IgnoredAny>()?;
+                continue;
+            }
+            if key == WILDCARD_KEY {
+                set.insert_wildcard(&Path::new());
+                access.next_value::<IgnoredAny>()?;
                 continue;
             }
 
-            // Try to parse the path element
             let pe = match deserialize_path_element(&key) {
                 Ok(pe) => pe,
-                Err(e) => {
-                    // Skip unknown path element types (for forward compatibility)
-                    if e.message.starts_with("unknown path element type") {
-                        continue;
-                    }
-                    return Err(e);
+                // Skip unknown path element types (for forward compatibility)
+                Err(e) if e.message.starts_with("unknown path element type") => {
+                    access.next_value::<IgnoredAny>()?;
+                    continue;
                 }
+                Err(e) => return Err(A::Error::custom(e)),
             };
 
-            // Parse the child object
-            match value {
-                serde_json::Value::Object(child_obj) => {
-                    if child_obj.is_empty() {
-                        // Empty object means this is just a member
-                        set.members.insert(pe);
-                    } else {
-                        // Check if "." is present (means this path is also a member)
-                        let is_member = child_obj.contains_key(".");
-
-                        // Parse children
-                        let child_set = Self::from_json_object(child_obj)?;
-
-                        if is_member {
-                            set.members.insert(pe.clone());
-                        }
-
-                        if !child_set.is_empty() {
-                            set.children.insert(pe, child_set);
-                        }
-                    }
-                }
-                _ => {
-                    return Err(SerializeError::new(format!(
-                        "expected object value for key: {}",
-                        key
-                    )));
-                }
+            let (child, child_is_member) = access.next_value_seed(LevelSeed)?;
+            if child_is_member {
+                set.members.insert(pe.clone());
+            }
+            if !child.is_empty() {
+                set.children.insert(pe, child);
             }
         }
 
-        Ok(set)
+        // An empty object marks a leaf member; otherwise "." marks the path
+        // leading here as a member too.
+        Ok((set, is_empty || has_self))
+    }
+}
+
+struct LevelSeed;
+
+impl<'de> DeserializeSeed<'de> for LevelSeed {
+    type Value = (Set, bool);
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_map(LevelVisitor)
     }
 }
 
@@ -364,6 +452,14 @@ mod tests {
         assert_eq!(pe, pe2);
     }
 
+    #[test]
+    fn test_deserialize_malformed_path_element() {
+        for key in ["", "f", "€x", "é", "x:1", "i:one", "k:[]", "v:{"] {
+            assert!(deserialize_path_element(key).is_err(), "{:?}", key);
+        }
+        assert!(Set::from_json(r#"{"€":{}}"#.as_bytes()).is_err());
+    }
+
     #[test]
     fn test_set_json_roundtrip() {
         use super::super::path::Path;
@@ -473,6 +569,26 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_set_streaming_roundtrip() {
+        let examples = vec![
+            r#"{"f:a":{".":{},"f:b":{}},"f:c":{}}"#,
+            r#"{"f:aaa":{"k:{\"name\":\"first\"}":{"i:0":{},"v:\"x\"":{}}}}"#,
+        ];
+
+        for example in examples {
+            let set = Set::from_reader(example.as_bytes()).unwrap();
+            assert_eq!(set, Set::from_json(example.as_bytes()).unwrap());
+
+            let mut out = Vec::new();
+            set.to_writer(&mut out).unwrap();
+            assert_eq!(String::from_utf8(out.clone()).unwrap(), example);
+            assert_eq!(out, set.to_json().unwrap());
+        }
+
+        assert!(Set::from_reader(r#"{"f:a":1}"#.as_bytes()).is_err());
+    }
+
     #[test]
     fn test_serialize_nested_set() {
         // Test a set with nested paths
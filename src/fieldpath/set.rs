@@ -2,6 +2,7 @@
 
 use super::path::{Path, PathElement};
 use std::collections::BTreeMap;
+use std::fmt;
 
 /// PathElementSet is a sorted set of PathElements for efficient membership testing.
 #[derive(Debug, Clone, Default, PartialEq, Eq)]
@@ -141,17 +142,28 @@ impl PathElementSet {
 }
 
 /// SetNodeMap maps PathElements to child Sets.
-pub type SetNodeMap = BTreeMap<PathElement, Set>;
+pub(crate) type SetNodeMap = BTreeMap<PathElement, Set>;
 
 /// Set is a tree structure for tracking field ownership.
-#[derive(Debug, Clone, Default, PartialEq, Eq)]
+///
+/// A level can also be a wildcard, which contains every path beneath the
+/// path leading to it, including ones that don't exist yet, e.g. to own
+/// all current and future labels. Wildcards can't exclude paths: removing
+/// a path covered by a wildcard leaves the wildcard in place.
+///
+/// Sets format as their paths in order, on one line with `{}` and `{:?}`
+/// and one per line with `{:#}` and `{:#?}`. Wildcards format as `.*`
+/// after their path.
+#[derive(Clone, Default, PartialEq, Eq)]
 pub struct Set {
     /// Direct children at this level.
-    pub members: PathElementSet,
+    pub(crate) members: PathElementSet,
     /// Nested children for deeper paths.
-    pub children: SetNodeMap,
+    pub(crate) children: SetNodeMap,
     /// True if the empty path (root itself) is in this set.
     root_in_set: bool,
+    /// True if every path beneath this level is in this set.
+    wildcard: bool,
 }
 
 impl Set {
@@ -161,12 +173,13 @@ impl Set {
             members: PathElementSet::new(),
             children: BTreeMap::new(),
             root_in_set: false,
+            wildcard: false,
         }
     }
 
     /// Returns true if the set is empty.
     pub fn is_empty(&self) -> bool {
-        !self.root_in_set && self.members.is_empty() && self.children.is_empty()
+        !self.root_in_set && !self.wildcard && self.members.is_empty() && self.children.is_empty()
     }
 
     /// Returns true if the set is empty (alias for Go compatibility).
@@ -199,6 +212,10 @@ impl Set {
             return true;
         }
 
+        if self.wildcard {
+            return true;
+        }
+
         let first = &elements[0];
         let rest = &elements[1..];
 
@@ -241,6 +258,113 @@ impl Set {
         child.insert_path_elements(rest);
     }
 
+    /// Adds `elements` as members beneath `parent` in one descent, instead
+    /// of walking the parent path once per element.
+    pub(crate) fn insert_members(&mut self, parent: &Path, elements: Vec<PathElement>) {
+        if elements.is_empty() {
+            return;
+        }
+        let mut level = self;
+        for pe in parent.iter() {
+            level = level.children.entry(pe.clone()).or_default();
+        }
+        level.members = level.members.union(&PathElementSet::from_vec(elements));
+    }
+
+    /// Adds a wildcard beneath the path, so the set contains every path
+    /// that starts with it, present or future.
+    pub fn insert_wildcard(&mut self, path: &Path) {
+        let mut level = self;
+        for pe in path.iter() {
+            level = level.children.entry(pe.clone()).or_default();
+        }
+        level.wildcard = true;
+    }
+
+    /// Returns true if the set has a wildcard beneath exactly this path.
+    pub fn has_wildcard(&self, path: &Path) -> bool {
+        let mut level = self;
+        for pe in path.iter() {
+            match level.children.get(pe) {
+                Some(child) => level = child,
+                None => return false,
+            }
+        }
+        level.wildcard
+    }
+
+    /// Iterates over the paths that have a wildcard beneath them.
+    pub fn wildcards_iterate<F>(&self, mut f: F)
+    where
+        F: FnMut(&Path),
+    {
+        self.wildcards_iterate_with_path(&mut Path::new(), &mut f);
+    }
+
+    fn wildcards_iterate_with_path<F>(&self, current_path: &mut Path, f: &mut F)
+    where
+        F: FnMut(&Path),
+    {
+        if self.wildcard {
+            f(current_path);
+        }
+        for (key, child) in &self.children {
+            current_path.push(key.clone());
+            child.wildcards_iterate_with_path(current_path, f);
+            current_path.pop();
+        }
+    }
+
+    /// Returns only the wildcards of the set.
+    pub fn wildcards(&self) -> Set {
+        let mut result = Set::new();
+        self.wildcards_iterate(|path| result.insert_wildcard(path));
+        result
+    }
+
+    /// Lowers the set to one without wildcards, for readers that don't
+    /// understand them: each wildcard is replaced by the paths of `fields`
+    /// beneath it, usually the field set of the current object.
+    pub fn lower_wildcards(&self, fields: &Set) -> Set {
+        let mut result = self.clone();
+        result.lower_wildcards_into(fields);
+        result
+    }
+
+    fn lower_wildcards_into(&mut self, fields: &Set) {
+        if self.wildcard {
+            self.wildcard = false;
+            let mut covered = fields.clone();
+            covered.root_in_set = false;
+            self.union_into(&covered);
+        }
+        let empty = Set::new();
+        for (key, child) in self.children.iter_mut() {
+            child.lower_wildcards_into(fields.children.get(key).unwrap_or(&empty));
+        }
+    }
+
+    /// Lowers the wildcards with paths of `paths` beneath them against
+    /// `fields`, as lower_wildcards does, and keeps the others.
+    pub(crate) fn lower_wildcards_over(&self, paths: &Set, fields: &Set) -> Set {
+        let mut result = self.clone();
+        result.lower_wildcards_over_into(paths, fields);
+        result
+    }
+
+    fn lower_wildcards_over_into(&mut self, paths: &Set, fields: &Set) {
+        if self.wildcard && (paths.wildcard || !paths.members.is_empty() || !paths.children.is_empty()) {
+            self.lower_wildcards_into(fields);
+            return;
+        }
+        let empty = Set::new();
+        for (key, child) in self.children.iter_mut() {
+            if let Some(paths) = paths.children.get(key) {
+                child.lower_wildcards_over_into(paths, fields.children.get(key).unwrap_or(&empty));
+            }
+        }
+    }
+
     /// Returns the union of two sets.
     pub fn union(&self, other: &Set) -> Set {
         let mut result = self.clone();
@@ -250,6 +374,7 @@ impl Set {
 
     fn union_into(&mut self, other: &Set) {
         self.root_in_set = self.root_in_set || other.root_in_set;
+        self.wildcard = self.wildcard || other.wildcard;
         self.members = self.members.union(&other.members);
 
         for (key, other_child) in &other.children {
@@ -264,6 +389,19 @@ impl Set {
     /// Returns the intersection of two sets.
     pub fn intersection(&self, other: &Set) -> Set {
         let root_in_set = self.root_in_set && other.root_in_set;
+
+        // A wildcard keeps whatever the other side has beneath it
+        if self.wildcard || other.wildcard {
+            let mut result = Set::new();
+            for (side, wildcard) in [(other, self.wildcard), (self, other.wildcard)] {
+                if wildcard {
+                    result.union_into(side);
+                }
+            }
+            result.root_in_set = root_in_set;
+            return result;
+        }
+
         let members = self.members.intersection(&other.members);
 
         let mut children = BTreeMap::new();
@@ -276,12 +414,25 @@ impl Set {
             }
         }
 
-        Set { members, children, root_in_set }
+        Set { members, children, root_in_set, wildcard: false }
     }
 
     /// Returns the difference of two sets (self - other).
+    ///
+    /// A wildcard of self is kept whatever other takes from beneath it, as
+    /// the set alone doesn't know the keys it stands for. Lower it against
+    /// the object first to give single keys up.
     pub fn difference(&self, other: &Set) -> Set {
         let root_in_set = self.root_in_set && !other.root_in_set;
+
+        // Everything beneath a wildcard of other is removed
+        if other.wildcard {
+            return Set {
+                root_in_set,
+                ..Set::new()
+            };
+        }
+
         let members = self.members.difference(&other.members);
 
         let mut children = BTreeMap::new();
@@ -296,7 +447,12 @@ impl Set {
             }
         }
 
-        Set { members, children, root_in_set }
+        Set {
+            members,
+            children,
+            root_in_set,
+            wildcard: self.wildcard,
+        }
     }
 
     /// Iterates over all paths in the set.
@@ -331,6 +487,20 @@ impl Set {
         }
     }
 
+    /// Returns the subset of paths for which the predicate returns true.
+    pub fn filter<F>(&self, mut pred: F) -> Set
+    where
+        F: FnMut(&Path) -> bool,
+    {
+        let mut result = Set::new();
+        self.iterate(|path| {
+            if pred(path) {
+                result.insert(path);
+            }
+        });
+        result
+    }
+
     /// Recursively removes a path and all its descendants from the set.
     /// This is different from regular difference - it removes entire subtrees.
     pub fn recursive_difference(&self, other: &Set) -> Set {
@@ -343,6 +513,15 @@ impl Set {
         // If other has root, remove everything
         if other.root_in_set {
             self.root_in_set = false;
+            self.wildcard = false;
+            self.members = PathElementSet::new();
+            self.children.clear();
+            return;
+        }
+
+        // A wildcard in other removes everything beneath this level
+        if other.wildcard {
+            self.wildcard = false;
             self.members = PathElementSet::new();
             self.children.clear();
             return;
@@ -399,12 +578,138 @@ impl Set {
     pub fn members_has(&self, pe: &PathElement) -> bool {
         self.members.contains(pe)
     }
+
+    /// Returns true if the set has the path or paths beneath it.
+    pub(crate) fn touches(&self, path: &Path) -> bool {
+        let Some((last, parents)) = path.as_slice().split_last() else {
+            return !self.is_empty();
+        };
+        let mut set = self;
+        for pe in parents {
+            if set.wildcard {
+                return true;
+            }
+            match set.children_get(pe) {
+                Some(child) => set = child,
+                None => return false,
+            }
+        }
+        set.wildcard || set.members_has(last) || set.children_get(last).is_some()
+    }
+}
+
+impl fmt::Display for Set {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut paths = Vec::new();
+        self.iterate(|path| paths.push((path.clone(), false)));
+        self.wildcards_iterate(|path| paths.push((path.clone(), true)));
+        paths.sort_by(|a, b| (a.0.as_slice(), a.1).cmp(&(b.0.as_slice(), b.1)));
+        let mut set = f.debug_set();
+        for (path, wildcard) in &paths {
+            match wildcard {
+                true => set.entry(&format_args!("{}.*", path)),
+                false => set.entry(&format_args!("{}", path)),
+            };
+        }
+        set.finish()
+    }
+}
+
+impl fmt::Debug for Set {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_set_format() {
+        let mut set = Set::new();
+        set.insert(&Path::from_elements(vec![PathElement::field_name("b")]));
+        set.insert(&Path::from_elements(vec![PathElement::field_name("a"), PathElement::field_name("c")]));
+        assert_eq!(format!("{}", set), "{.a.c, .b}");
+        assert_eq!(format!("{:?}", set), "{.a.c, .b}");
+        assert_eq!(format!("{:#}", set), "{\n    .a.c,\n    .b,\n}");
+        assert_eq!(format!("{}", Set::new()), "{}");
+    }
+
+    #[test]
+    fn test_set_wildcards() {
+        let path = |names: &[&str]| Path::from_elements(names.iter().map(|n| PathElement::field_name(*n)).collect());
+        let mut owned = Set::new();
+        owned.insert(&path(&["metadata"]));
+        owned.insert(&path(&["metadata", "labels"]));
+        owned.insert_wildcard(&path(&["metadata", "labels"]));
+        assert_eq!(owned.to_string(), "{.metadata, .metadata.labels, .metadata.labels.*}");
+        assert!(owned.has(&path(&["metadata", "labels", "future"])));
+        assert!(owned.has(&path(&["metadata", "labels", "a", "b"])));
+        assert!(!owned.has(&path(&["metadata", "annotations"])));
+        assert!(owned.has_wildcard(&path(&["metadata", "labels"])));
+        assert!(!owned.has_wildcard(&path(&["metadata"])));
+
+        let mut changed = Set::new();
+        changed.insert(&path(&["metadata", "labels", "a"]));
+        changed.insert(&path(&["spec"]));
+        assert_eq!(owned.intersection(&changed).to_string(), "{.metadata.labels.a}");
+        assert_eq!(changed.intersection(&owned), owned.intersection(&changed));
+        assert_eq!(changed.difference(&owned).to_string(), "{.spec}");
+        assert_eq!(changed.recursive_difference(&owned).to_string(), "{.spec}");
+        // Removing covered paths leaves the wildcard, unless it's lowered
+        // against the object first
+        assert_eq!(owned.difference(&changed), owned);
+        let mut object = changed.clone();
+        object.insert(&path(&["metadata", "labels", "b"]));
+        assert_eq!(
+            owned.lower_wildcards_over(&changed, &object).difference(&changed).to_string(),
+            "{.metadata, .metadata.labels, .metadata.labels.b}"
+        );
+        let mut elsewhere = Set::new();
+        elsewhere.insert(&path(&["spec"]));
+        assert_eq!(owned.lower_wildcards_over(&elsewhere, &object), owned);
+        assert_eq!(changed.union(&owned.wildcards()).to_string(), "{.metadata.labels.*, .metadata.labels.a, .spec}");
+
+        assert_eq!(
+            owned.lower_wildcards(&object).to_string(),
+            "{.metadata, .metadata.labels, .metadata.labels.a, .metadata.labels.b}"
+        );
+
+        // FieldsV1 proper has no wildcards
+        assert!(owned.to_json().is_err());
+        assert!(owned.lower_wildcards(&object).to_json().is_ok());
+        let json = String::from_utf8(owned.to_json_with_wildcards().unwrap()).unwrap();
+        assert_eq!(json, r#"{"f:metadata":{".":{},"f:labels":{".":{},"w:*":{}}}}"#);
+        assert_eq!(Set::from_json(json.as_bytes()).unwrap(), owned);
+        assert_eq!(Set::from_bytes(&owned.to_bytes()).unwrap(), owned);
+        let mut root = Set::new();
+        root.insert_wildcard(&Path::new());
+        assert!(root.to_json().is_err());
+        assert_eq!(Set::from_json(&root.to_json_with_wildcards().unwrap()).unwrap(), root);
+        assert_eq!(Set::from_bytes(&root.to_bytes()).unwrap(), root);
+    }
+
+    #[test]
+    fn test_set_touches() {
+        let path = |names: &[&str]| Path::from_elements(names.iter().map(|n| PathElement::field_name(*n)).collect());
+        let mut set = Set::new();
+        assert!(!set.touches(&Path::new()));
+        set.insert(&path(&["a", "b"]));
+        set.insert(&path(&["c"]));
+        assert!(set.touches(&Path::new()));
+        assert!(set.touches(&path(&["a"])));
+        assert!(set.touches(&path(&["a", "b"])));
+        assert!(set.touches(&path(&["c"])));
+        assert!(!set.touches(&path(&["a", "c"])));
+        assert!(!set.touches(&path(&["c", "d"])));
+        assert!(!set.touches(&path(&["d"])));
+        set.insert_wildcard(&path(&["e"]));
+        assert!(set.touches(&path(&["e"])));
+        assert!(set.touches(&path(&["e", "f"])));
+        assert!(set.touches(&path(&["e", "f", "g"])));
+    }
+
     #[test]
     fn test_path_element_set_operations() {
         let mut set1 = PathElementSet::new();
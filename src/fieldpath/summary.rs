@@ -0,0 +1,166 @@
+//! Short renderings of paths and sets, for log lines and messages.
+
+use super::{Path, PathElement, Set};
+use std::collections::HashSet;
+
+/// Renders an element with the list items of any kind as `[*]`.
+fn generalized(pe: &PathElement) -> String {
+    match pe {
+        PathElement::FieldName(_) | PathElement::Extension(..) => pe.to_string(),
+        PathElement::Key(_) | PathElement::Value(_) | PathElement::Index(_) => "[*]".to_string(),
+    }
+}
+
+impl Path {
+    /// Renders the path with its list items as `[*]`, so that the paths of
+    /// all the items of a list render the same.
+    #[cfg(feature = "profiling")]
+    pub(crate) fn generalized(&self) -> String {
+        self.iter().map(generalized).collect()
+    }
+
+    /// Renders the path in at most `max_len` characters where it can.
+    ///
+    /// Paths that fit are rendered as Display does. Longer ones have their
+    /// list items rendered as `[*]`, then their middle elements replaced
+    /// with `…`, keeping the first element and as many of the last as fit.
+    /// The last element is always kept, so the result may still be longer.
+    pub fn abbreviate(&self, max_len: usize) -> String {
+        let full = self.to_string();
+        if full.chars().count() <= max_len {
+            return full;
+        }
+        let parts: Vec<String> = self.iter().map(generalized).collect();
+        let generalized = parts.concat();
+        if generalized.chars().count() <= max_len || parts.len() < 2 {
+            return generalized;
+        }
+        let first_len = parts[0].chars().count() + 1;
+        let mut start = parts.len() - 1;
+        let mut len = first_len + parts[start].chars().count();
+        while start > 1 && len + parts[start - 1].chars().count() <= max_len {
+            start -= 1;
+            len += parts[start].chars().count();
+        }
+        if len > max_len {
+            return format!("…{}", parts[start]);
+        }
+        format!("{}…{}", parts[0], parts[start..].concat())
+    }
+}
+
+impl Set {
+    /// Summarizes the set in at most `n` groups of paths, such as
+    /// `.spec.containers[*].{image,resources}, .spec.replicas… +42 more`.
+    ///
+    /// Only the deepest paths are listed, with list items rendered as
+    /// `[*]`, and paths with the same parent are grouped. Groups past the
+    /// first `n` are counted.
+    pub fn summary(&self, n: usize) -> String {
+        let mut paths = Vec::new();
+        self.iterate(|path| paths.push((path.clone(), false)));
+        self.wildcards_iterate(|path| paths.push((path.clone(), true)));
+        paths.sort_by(|a, b| (a.0.as_slice(), a.1).cmp(&(b.0.as_slice(), b.1)));
+
+        // Paths other paths extend are left out
+        let mut parents = HashSet::new();
+        for (path, wildcard) in &paths {
+            // Wildcards extend their own path
+            let len = if *wildcard { path.len() + 1 } else { path.len() };
+            for i in 0..len {
+                parents.insert(&path.as_slice()[..i]);
+            }
+        }
+
+        let mut groups: Vec<(String, Vec<String>)> = Vec::new();
+        for (path, wildcard) in &paths {
+            let (parent, name) = if *wildcard {
+                (path.as_slice(), ".*".to_string())
+            } else if parents.contains(path.as_slice()) {
+                continue;
+            } else {
+                match path.as_slice().split_last() {
+                    Some((last, parent)) => (parent, generalized(last)),
+                    None => continue,
+                }
+            };
+            let parent: String = parent.iter().map(generalized).collect();
+            match groups.iter_mut().find(|(p, _)| *p == parent) {
+                Some((_, names)) if names.contains(&name) => {}
+                Some((_, names)) => names.push(name),
+                None => groups.push((parent, vec![name])),
+            }
+        }
+
+        let mut summary = groups
+            .iter()
+            .take(n)
+            .map(|(parent, names)| match names.as_slice() {
+                [name] => format!("{}{}", parent, name),
+                _ if names.iter().all(|name| name.starts_with('.')) => {
+                    let names: Vec<&str> = names.iter().map(|name| &name[1..]).collect();
+                    format!("{}.{{{}}}", parent, names.join(","))
+                }
+                _ => format!("{}{{{}}}", parent, names.join(",")),
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        if groups.len() > n {
+            summary.push_str(&format!("… +{} more", groups.len() - n));
+        }
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::value::{Field, FieldList, Value};
+
+    #[test]
+    fn test_abbreviate_and_summary() {
+        let container = |name: &str| {
+            PathElement::Key(FieldList::with_fields(vec![Field {
+                name: "name".to_string(),
+                value: Value::String(name.to_string()),
+            }]))
+        };
+        let path = |elements: Vec<PathElement>| Path::from_elements(elements);
+        let field = PathElement::field_name;
+        let image = |name: &str| {
+            path(vec![
+                field("spec"),
+                field("template"),
+                field("spec"),
+                field("containers"),
+                container(name),
+                field("image"),
+            ])
+        };
+
+        assert_eq!(
+            image("app").abbreviate(80),
+            r#".spec.template.spec.containers[name="app"].image"#
+        );
+        assert_eq!(image("app").abbreviate(40), ".spec.template.spec.containers[*].image");
+        assert_eq!(image("app").abbreviate(30), ".spec….containers[*].image");
+        assert_eq!(image("app").abbreviate(5), "….image");
+
+        let mut set = Set::new();
+        for name in ["app", "sidecar"] {
+            set.insert(&image(name));
+            let mut resources = image(name);
+            resources.pop();
+            set.insert(&resources.with(field("resources")));
+            set.insert(&resources);
+        }
+        set.insert(&path(vec![field("spec"), field("replicas")]));
+        set.insert_wildcard(&path(vec![field("metadata"), field("labels")]));
+
+        assert_eq!(
+            set.summary(3),
+            ".metadata.labels.*, .spec.replicas, .spec.template.spec.containers[*].{image,resources}"
+        );
+        assert_eq!(set.summary(1), ".metadata.labels.*… +2 more");
+    }
+}
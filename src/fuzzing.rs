@@ -0,0 +1,292 @@
+//! Fuzzing helpers, enabled by the `fuzzing` feature.
+//!
+//! The generators turn raw fuzzer input into values that fit a schema, so
+//! mutations reach merging and set algebra instead of failing validation.
+//! The checks panic when an invariant doesn't hold; the targets under
+//! `fuzz/` call them.
+
+#![allow(clippy::expect_used, clippy::panic)]
+
+use crate::fieldpath::{Path, PathElement, Set};
+use crate::schema::{Scalar, Schema, TypeRef};
+use crate::typed::{as_typed, ParseableType, Parser, TypedValue};
+use crate::value::{self, Field, FieldList, Map, Value};
+
+/// FUZZ_SCHEMA covers every kind of type the merge code distinguishes.
+/// Its root type is `root`.
+pub const FUZZ_SCHEMA: &str = r#"types:
+- name: root
+  map:
+    fields:
+    - name: name
+      type:
+        scalar: string
+    - name: count
+      type:
+        scalar: numeric
+    - name: enabled
+      type:
+        scalar: boolean
+    - name: labels
+      type:
+        map:
+          elementType:
+            scalar: string
+    - name: items
+      type:
+        list:
+          elementType:
+            namedType: item
+          elementRelationship: associative
+          keys: [name]
+    - name: tags
+      type:
+        list:
+          elementType:
+            scalar: string
+          elementRelationship: associative
+    - name: args
+      type:
+        list:
+          elementType:
+            scalar: string
+          elementRelationship: atomic
+    - name: nested
+      type:
+        namedType: root
+    - name: extra
+      type:
+        namedType: __untyped_atomic_
+- name: item
+  map:
+    fields:
+    - name: name
+      type:
+        scalar: string
+    - name: value
+      type:
+        scalar: untyped
+    - name: sub
+      type:
+        namedType: item
+    elementRelationship: separable
+"#;
+
+const NAMES: [&str; 4] = ["a", "b", "name", "value"];
+const MAX_DEPTH: usize = 4;
+
+/// ByteSource hands out fuzzer input as choices. Once the input is used
+/// up every choice is zero, so generation always terminates.
+#[derive(Debug, Clone)]
+pub struct ByteSource<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ByteSource<'a> {
+    /// Creates a source over the input.
+    pub fn new(data: &'a [u8]) -> Self {
+        ByteSource { data, pos: 0 }
+    }
+
+    /// Returns true once all input has been used.
+    pub fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    /// Returns the next byte.
+    pub fn byte(&mut self) -> u8 {
+        let b = self.data.get(self.pos).copied().unwrap_or(0);
+        self.pos += 1;
+        b
+    }
+
+    /// Returns a number below `n`, which must not be zero.
+    pub fn below(&mut self, n: usize) -> usize {
+        self.byte() as usize % n
+    }
+
+    /// Returns a boolean.
+    pub fn bool(&mut self) -> bool {
+        self.byte() & 1 == 1
+    }
+}
+
+/// Returns the root type of FUZZ_SCHEMA.
+pub fn fuzz_type() -> ParseableType {
+    Parser::new(FUZZ_SCHEMA).expect("fuzz schema is valid").type_by_name("root")
+}
+
+/// Generates a value of the given type. Values usually validate; associative
+/// lists may get duplicate keys, which callers should skip.
+pub fn arbitrary_value(pt: &ParseableType, src: &mut ByteSource<'_>) -> Value {
+    generate(&pt.schema, &pt.type_ref, src, MAX_DEPTH)
+}
+
+fn generate(schema: &Schema, type_ref: &TypeRef, src: &mut ByteSource<'_>, depth: usize) -> Value {
+    let Some(atom) = schema.resolve(type_ref) else {
+        return Value::Null;
+    };
+    if depth == 0 {
+        return Value::Null;
+    }
+    if let Some(map) = atom.map {
+        let mut fields = Map::new();
+        if map.fields.is_empty() {
+            for _ in 0..src.below(4) {
+                let name = NAMES[src.below(NAMES.len())];
+                fields.set(name.to_string(), generate(schema, &map.element_type, src, depth - 1));
+            }
+        } else {
+            for field in &map.fields {
+                if src.bool() {
+                    fields.set(field.name.clone(), generate(schema, &field.field_type, src, depth - 1));
+                }
+            }
+        }
+        return Value::Map(fields);
+    }
+    if let Some(list) = atom.list {
+        let items = (0..src.below(4))
+            .map(|_| generate(schema, &list.element_type, src, depth - 1))
+            .collect();
+        return Value::List(items);
+    }
+    match atom.scalar {
+        Some(Scalar::Numeric) if src.bool() => Value::Float(src.byte() as f64 / 4.0),
+        Some(Scalar::Numeric) => Value::Int(src.byte() as i64 - 128),
+        Some(Scalar::Boolean) => Value::Bool(src.bool()),
+        Some(Scalar::String) | Some(Scalar::Time) => Value::String(NAMES[src.below(NAMES.len())].to_string()),
+        Some(_) => match src.below(3) {
+            0 => Value::Int(src.byte() as i64),
+            1 => Value::Bool(src.bool()),
+            _ => Value::String(NAMES[src.below(NAMES.len())].to_string()),
+        },
+        None => Value::Null,
+    }
+}
+
+/// Generates a set of up to eight paths of up to three elements.
+pub fn arbitrary_set(src: &mut ByteSource<'_>) -> Set {
+    let mut set = Set::new();
+    for _ in 0..src.below(9) {
+        let len = 1 + src.below(3);
+        let elements = (0..len).map(|_| arbitrary_path_element(src)).collect();
+        set.insert(&Path::from_elements(elements));
+    }
+    set
+}
+
+fn arbitrary_path_element(src: &mut ByteSource<'_>) -> PathElement {
+    let name = NAMES[src.below(NAMES.len())];
+    match src.below(4) {
+        0 => PathElement::field_name(name),
+        1 => PathElement::index(src.below(4) as i32),
+        2 => PathElement::value(Value::String(name.to_string())),
+        _ => PathElement::key(FieldList::with_fields(vec![Field {
+            name: "name".to_string(),
+            value: Value::String(name.to_string()),
+        }])),
+    }
+}
+
+/// Mutates a typed value the way a manager would: some of its fields are
+/// removed and a generated value is merged in. Returns the value unchanged
+/// if the result doesn't validate.
+pub fn mutate_value(tv: &TypedValue, src: &mut ByteSource<'_>) -> Value {
+    let Ok(fields) = tv.to_field_set() else {
+        return tv.value().clone();
+    };
+    let mut removed = Set::new();
+    fields.iterate(|path| {
+        if src.below(4) == 0 {
+            removed.insert(path);
+        }
+    });
+    let pt = ParseableType {
+        schema: tv.schema().clone(),
+        type_ref: tv.type_ref().clone(),
+    };
+    let patch = arbitrary_value(&pt, src);
+    let Ok(patch) = as_typed(patch, tv.schema(), tv.type_ref().clone(), &[]) else {
+        return tv.value().clone();
+    };
+    match tv.remove_items(&removed).merge(&patch) {
+        Ok(merged) => merged.value().clone(),
+        Err(_) => tv.value().clone(),
+    }
+}
+
+/// Checks that a set survives the JSON (FieldsV1) and binary encodings.
+pub fn check_set_round_trip(set: &Set) {
+    let json = set.to_json_with_wildcards().expect("sets encode as JSON");
+    assert_eq!(&Set::from_json(&json).expect("encoded sets decode"), set, "JSON round trip");
+    assert_eq!(&Set::from_bytes(&set.to_bytes()).expect("encoded sets decode"), set, "binary round trip");
+}
+
+/// Checks the identities of union, intersection and difference.
+pub fn check_set_algebra(a: &Set, b: &Set) {
+    let union = a.union(b);
+    assert_eq!(union, b.union(a), "union is commutative");
+    assert_eq!(a.intersection(b), b.intersection(a), "intersection is commutative");
+    assert_eq!(union.intersection(a), *a, "union contains its operands");
+    assert!(a.difference(b).intersection(b).is_empty(), "difference excludes the subtrahend");
+    assert_eq!(a.difference(b).union(&a.intersection(b)), *a, "difference and intersection partition");
+}
+
+/// Checks that merging is idempotent and that the field sets of the
+/// operands and the result encode cleanly.
+pub fn check_merge(lhs: &TypedValue, rhs: &TypedValue) {
+    let Ok(merged) = lhs.merge(rhs) else {
+        return;
+    };
+    let again = merged.merge(rhs).expect("merging into a merge result succeeds");
+    assert_eq!(again.value(), merged.value(), "merge is idempotent");
+    for tv in [lhs, rhs, &merged] {
+        check_set_round_trip(&tv.to_field_set().expect("valid values have field sets"));
+    }
+}
+
+/// Parses fuzzer input as YAML of the fuzz type and checks what parses:
+/// the value must revalidate, and its JSON must parse back to it.
+pub fn check_parse(data: &[u8]) {
+    let Ok(yaml) = std::str::from_utf8(data) else {
+        return;
+    };
+    let pt = fuzz_type();
+    let Ok(tv) = pt.from_yaml(yaml) else {
+        return;
+    };
+    assert!(tv.validate(&[]).is_ok(), "parsed values validate");
+    let json = value::to_json(tv.value()).expect("parsed values encode as JSON");
+    let reparsed = pt.from_yaml(&json).expect("encoded values parse");
+    assert_eq!(reparsed.value(), tv.value(), "JSON round trip");
+    check_set_round_trip(&tv.to_field_set().expect("valid values have field sets"));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fuzz_checks() {
+        let pt = fuzz_type();
+        for seed in 0..64u8 {
+            let data: Vec<u8> = (0..256u32).map(|i| (i as u8).wrapping_mul(seed).wrapping_add(seed)).collect();
+            let mut src = ByteSource::new(&data);
+
+            let (a, b) = (arbitrary_set(&mut src), arbitrary_set(&mut src));
+            check_set_round_trip(&a);
+            check_set_algebra(&a, &b);
+
+            let Ok(lhs) = pt.from_value(arbitrary_value(&pt, &mut src)) else {
+                continue;
+            };
+            let Ok(rhs) = pt.from_value(mutate_value(&lhs, &mut src)) else {
+                continue;
+            };
+            check_merge(&lhs, &rhs);
+            check_parse(value::to_json(lhs.value()).unwrap().as_bytes());
+        }
+    }
+}
@@ -13,12 +13,50 @@
 //! - [`fieldpath`] - Field path representation and management for tracking field ownership
 //! - [`typed`] - Operations on Values with specific schemas (validation, comparison, merging)
 //! - [`merge`] - High-level multi-manager merge and apply operations
+//! - [`messages`] - Catalogs of the messages of validation errors and conflicts
 //! - [`openapi`] - OpenAPI v2/v3 to SMD schema conversion
+//! - [`conformance`] - Checks that a schema gives apply sane behavior
+//! - [`scenario`] - Merge tests expressed as data
+//! - [`snapshot`] - Stable renderings of merge results for snapshot tests
+//! - [`status`] - Readiness fields of objects and the managers owning them
+//! - `fuzzing` - Input generators and invariant checks for fuzzing (with the
+//!   `fuzzing` feature)
+//! - `fault` - Failure injection into conversions and schema resolution (with
+//!   the `fault-injection` feature)
+//! - `profile` - Time spent validating and merging per type and path (with
+//!   the `profiling` feature)
+//!
+//! ## Panics
+//!
+//! Malformed input, such as objects, schemas, managed fields or paths that
+//! fail to parse or validate, is reported with errors rather than panics,
+//! so that servers can handle requests from untrusted clients. Library code
+//! can't use `unwrap`, `expect` or `panic!`, except to parse constant
+//! schemas that tests cover, and the parsers of paths, patterns, queries,
+//! scalars and binary sets can't index or slice without bounds checks.
+
+#![cfg_attr(not(test), deny(clippy::unwrap_used, clippy::expect_used, clippy::panic, clippy::unreachable))]
+// The tests ported from Go keep their upstream shape and fixtures
+#![cfg_attr(
+    test,
+    allow(clippy::approx_constant, clippy::expect_fun_call, clippy::redundant_closure, clippy::unnecessary_unwrap)
+)]
 
+pub mod conformance;
+#[cfg(feature = "fault-injection")]
+pub mod fault;
 pub mod fieldpath;
+#[cfg(feature = "fuzzing")]
+pub mod fuzzing;
 pub mod merge;
+pub mod messages;
 pub mod openapi;
+#[cfg(feature = "profiling")]
+pub mod profile;
+pub mod scenario;
 pub mod schema;
+pub mod snapshot;
+pub mod status;
 pub mod typed;
 pub mod value;
 
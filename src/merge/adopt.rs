@@ -0,0 +1,83 @@
+//! Adoption of objects created without field ownership tracking.
+
+use crate::fieldpath::{APIVersion, ManagedFields, PathElement, Set, VersionedSet};
+use crate::typed::{TypedValue, ValidationErrors};
+
+/// AdoptionStrategy selects the fields a manager takes over when adopting
+/// an object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum AdoptionStrategy {
+    /// Own every field of the object.
+    Everything,
+    /// Own the fields under `.spec`, leaving status and metadata unowned.
+    #[default]
+    Spec,
+    /// Own no fields; the first apply takes over what it sets.
+    Nothing,
+}
+
+/// Fabricates managed fields for an object that has none, as if `manager`
+/// had applied the fields `strategy` selects at `version`. A later apply by
+/// the same manager then removes adopted fields it no longer sets.
+pub fn adopt(
+    live: &TypedValue,
+    manager: &str,
+    version: &APIVersion,
+    strategy: AdoptionStrategy,
+) -> Result<ManagedFields, ValidationErrors> {
+    let set = match strategy {
+        AdoptionStrategy::Everything => live.to_field_set()?,
+        AdoptionStrategy::Spec => {
+            let spec = PathElement::field_name("spec");
+            live.to_field_set()?.filter(|path| path.as_slice().first() == Some(&spec))
+        }
+        AdoptionStrategy::Nothing => Set::new(),
+    };
+    let mut managers = ManagedFields::new();
+    managers.insert(manager, VersionedSet::new(set, version.clone(), true));
+    Ok(managers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::managed_fields;
+    use crate::merge::Updater;
+    use crate::typed::deduced_parseable_type;
+
+    #[test]
+    fn test_adopt() {
+        let pt = deduced_parseable_type();
+        let live = pt
+            .from_yaml(r#"{"spec": {"replicas": 1, "paused": true}, "status": {"ready": 1}}"#)
+            .unwrap();
+        let v1 = APIVersion::new("v1");
+
+        let adopted = adopt(&live, "ctrl", &v1, AdoptionStrategy::Everything).unwrap();
+        assert_eq!(adopted.get("ctrl").unwrap().set(), &live.to_field_set().unwrap());
+        let adopted = adopt(&live, "ctrl", &v1, AdoptionStrategy::Nothing).unwrap();
+        assert_eq!(adopted, managed_fields! { "ctrl" => { version: "v1", applied: true, paths: [] } });
+
+        let mut managers = adopt(&live, "ctrl", &v1, AdoptionStrategy::Spec).unwrap();
+        let expected = managed_fields! {
+            "ctrl" => {
+                version: "v1",
+                applied: true,
+                paths: [["f:spec"], ["f:spec", "f:replicas"], ["f:spec", "f:paused"]],
+            },
+        };
+        assert_eq!(managers, expected);
+
+        // The adopting manager's first apply drops the spec fields it omits
+        let config = pt.from_yaml(r#"{"spec": {"replicas": 2}}"#).unwrap();
+        let result = Updater::builder()
+            .build()
+            .apply(&live, &config, &v1, &mut managers, "ctrl", false)
+            .unwrap();
+        assert_eq!(
+            crate::value::to_json(result.value()).unwrap(),
+            r#"{"spec":{"replicas":2},"status":{"ready":1}}"#
+        );
+    }
+}
@@ -0,0 +1,358 @@
+//! One-call apply of JSON objects carrying their own managedFields.
+
+use super::updater::{ApplyError, Updater};
+use crate::fieldpath::{APIVersion, ManagedFields, ManagerIdentifier, Operation, Set, VersionedSet};
+use crate::typed::{ParseError, ParseableType};
+use crate::value::{self, Map, Value};
+use std::collections::HashMap;
+use std::fmt;
+
+/// SchemaRegistry maps apiVersion and kind to the type of objects.
+#[derive(Debug, Clone, Default)]
+pub struct SchemaRegistry {
+    types: HashMap<(String, String), ParseableType>,
+    fallback: Option<ParseableType>,
+}
+
+impl SchemaRegistry {
+    /// Creates an empty registry.
+    pub fn new() -> Self {
+        SchemaRegistry::default()
+    }
+
+    /// Registers the type of objects with the given apiVersion and kind.
+    pub fn register(&mut self, api_version: impl Into<String>, kind: impl Into<String>, pt: ParseableType) {
+        self.types.insert((api_version.into(), kind.into()), pt);
+    }
+
+    /// Sets the type used for objects that aren't registered, e.g. the
+    /// deduced type.
+    pub fn set_fallback(&mut self, pt: ParseableType) {
+        self.fallback = Some(pt);
+    }
+
+    /// Returns the type of objects with the given apiVersion and kind.
+    pub fn get(&self, api_version: &str, kind: &str) -> Option<&ParseableType> {
+        self.types
+            .get(&(api_version.to_string(), kind.to_string()))
+            .or(self.fallback.as_ref())
+    }
+}
+
+/// Options for apply_object.
+#[derive(Debug, Clone, Default)]
+pub struct ApplyObjectOptions {
+    /// Take ownership of conflicting fields instead of failing.
+    pub force: bool,
+    /// Timestamp recorded in the applier's managedFields entry.
+    pub time: Option<String>,
+}
+
+/// ApplyObjectError represents an error during apply_object.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum ApplyObjectError {
+    /// An input isn't a valid JSON object.
+    InvalidObject(String),
+    /// The live object's managedFields can't be decoded.
+    InvalidManagedFields(String),
+    /// No type is registered for the object.
+    UnknownType { api_version: String, kind: String },
+    /// An object doesn't match its type.
+    Parse(ParseError),
+    /// The apply failed, e.g. because of conflicts.
+    Apply(ApplyError),
+}
+
+impl fmt::Display for ApplyObjectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApplyObjectError::InvalidObject(msg) => write!(f, "invalid object: {}", msg),
+            ApplyObjectError::InvalidManagedFields(msg) => write!(f, "invalid managedFields: {}", msg),
+            ApplyObjectError::UnknownType { api_version, kind } => {
+                write!(f, "no schema registered for {}, Kind={}", api_version, kind)
+            }
+            ApplyObjectError::Parse(e) => write!(f, "{}", e),
+            ApplyObjectError::Apply(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ApplyObjectError {}
+
+/// Applies `applied_json` to `live_json` on behalf of `field_manager`.
+///
+/// Ownership is read from the live object's `metadata.managedFields`, and the
+/// returned object JSON carries the updated managedFields. An empty live
+/// object creates the object.
+pub fn apply_object(
+    registry: &SchemaRegistry,
+    live_json: &str,
+    applied_json: &str,
+    field_manager: &str,
+    options: &ApplyObjectOptions,
+) -> Result<String, ApplyObjectError> {
+    let mut live = parse_object(live_json)?;
+    let mut applied = parse_object(applied_json)?;
+
+    let api_version = string_field(&applied, "apiVersion");
+    let kind = string_field(&applied, "kind");
+    let pt = registry
+        .get(&api_version, &kind)
+        .ok_or_else(|| ApplyObjectError::UnknownType {
+            api_version: api_version.clone(),
+            kind: kind.clone(),
+        })?;
+
+    let entries = take_managed_fields(&mut live);
+    take_managed_fields(&mut applied);
+    let (mut managers, times) = decode_managed_fields(&entries)?;
+
+    let live = pt.from_value(Value::Map(live)).map_err(ApplyObjectError::Parse)?;
+    let applied = pt.from_value(Value::Map(applied)).map_err(ApplyObjectError::Parse)?;
+
+    let manager = ManagerIdentifier::apply(field_manager).encode();
+    let version = APIVersion::new(api_version);
+    let result = Updater::builder()
+        .build()
+        .apply(&live, &applied, &version, &mut managers, &manager, options.force)
+        .map_err(ApplyObjectError::Apply)?;
+
+    let fields = result.to_field_set().map_err(|e| ApplyObjectError::InvalidObject(e.to_string()))?;
+    let mut object = match result.into_value() {
+        Value::Map(m) => m,
+        _ => Map::new(),
+    };
+    let managed_fields = encode_managed_fields(&managers, &fields, &times, &manager, options.time.as_deref());
+    set_managed_fields(&mut object, managed_fields);
+
+    value::to_json(&Value::Map(object)).map_err(|e| ApplyObjectError::InvalidObject(e.to_string()))
+}
+
+fn parse_object(json: &str) -> Result<Map, ApplyObjectError> {
+    if json.trim().is_empty() {
+        return Ok(Map::new());
+    }
+    match value::from_json(json) {
+        Ok(Value::Map(m)) => Ok(m),
+        Ok(Value::Null) => Ok(Map::new()),
+        Ok(_) => Err(ApplyObjectError::InvalidObject("expected a JSON object".to_string())),
+        Err(e) => Err(ApplyObjectError::InvalidObject(e.to_string())),
+    }
+}
+
+fn string_field(object: &Map, name: &str) -> String {
+    object.get(name).and_then(Value::as_str).unwrap_or_default().to_string()
+}
+
+fn take_managed_fields(object: &mut Map) -> Vec<Value> {
+    let Some(Value::Map(metadata)) = object.fields.get_mut("metadata") else {
+        return Vec::new();
+    };
+    match metadata.delete("managedFields") {
+        Some(Value::List(entries)) => entries,
+        _ => Vec::new(),
+    }
+}
+
+fn set_managed_fields(object: &mut Map, entries: Vec<Value>) {
+    let metadata = object
+        .fields
+        .entry("metadata".to_string())
+        .or_insert_with(|| Value::Map(Map::new()));
+    if !metadata.is_map() {
+        *metadata = Value::Map(Map::new());
+    }
+    if let Value::Map(metadata) = metadata {
+        if entries.is_empty() {
+            metadata.delete("managedFields");
+        } else {
+            metadata.set("managedFields".to_string(), Value::List(entries));
+        }
+    }
+}
+
+fn decode_managed_fields(entries: &[Value]) -> Result<(ManagedFields, HashMap<String, Value>), ApplyObjectError> {
+    let invalid = |msg: String| ApplyObjectError::InvalidManagedFields(msg);
+    let mut managers = ManagedFields::new();
+    let mut times = HashMap::new();
+
+    for entry in entries {
+        let entry = entry.as_map().ok_or_else(|| invalid("expected an object entry".to_string()))?;
+        let field = |name| entry.get(name).and_then(Value::as_str).unwrap_or_default();
+        let (manager, api_version) = (field("manager"), field("apiVersion"));
+        let id = match field("operation") {
+            "Apply" => ManagerIdentifier::apply(manager),
+            "Update" => ManagerIdentifier::update(manager, api_version),
+            other => return Err(invalid(format!("unknown operation {:?}", other))),
+        };
+        let id = id.with_subresource(field("subresource"));
+
+        let set = match entry.get("fieldsV1") {
+            Some(fields) => {
+                let json = value::to_json(fields).map_err(|e| invalid(e.to_string()))?;
+                Set::from_json(json.as_bytes()).map_err(|e| invalid(e.to_string()))?
+            }
+            None => Set::new(),
+        };
+
+        let applied = id.operation == Operation::Apply;
+        let id = id.encode();
+        if let Some(time) = entry.get("time") {
+            times.insert(id.clone(), time.clone());
+        }
+        managers.insert(id, VersionedSet::new(set, APIVersion::new(api_version), applied));
+    }
+
+    Ok((managers, times))
+}
+
+fn encode_managed_fields(
+    managers: &ManagedFields,
+    fields: &Set,
+    times: &HashMap<String, Value>,
+    applier: &str,
+    applier_time: Option<&str>,
+) -> Vec<Value> {
+    let mut ids: Vec<&String> = managers.managers().collect();
+    ids.sort();
+
+    let mut entries = Vec::new();
+    for id in ids {
+        let vs = match managers.get(id) {
+            Some(vs) if !vs.set().is_empty() => vs,
+            _ => continue,
+        };
+        let Ok(ident) = ManagerIdentifier::decode(id) else {
+            continue;
+        };
+        // FieldsV1 has no wildcards, so they become the object's fields.
+        // Serializing the lowered Set to JSON and reading it back can't fail.
+        let fields = vs
+            .set()
+            .lower_wildcards(fields)
+            .to_json()
+            .ok()
+            .and_then(|json| value::from_json(&String::from_utf8_lossy(&json)).ok())
+            .unwrap_or_default();
+
+        let mut entry = Map::new();
+        entry.set("manager".to_string(), Value::String(ident.manager));
+        entry.set("operation".to_string(), Value::String(ident.operation.to_string()));
+        entry.set("apiVersion".to_string(), Value::String(vs.api_version().to_string()));
+        let time = match applier_time {
+            Some(t) if id == applier => Some(Value::String(t.to_string())),
+            _ => times.get(id).cloned(),
+        };
+        if let Some(time) = time {
+            entry.set("time".to_string(), time);
+        }
+        entry.set("fieldsType".to_string(), Value::String("FieldsV1".to_string()));
+        entry.set("fieldsV1".to_string(), fields);
+        if let Some(subresource) = ident.subresource {
+            entry.set("subresource".to_string(), Value::String(subresource));
+        }
+        entries.push(Value::Map(entry));
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::typed::deduced_parseable_type;
+
+    fn registry() -> SchemaRegistry {
+        let mut registry = SchemaRegistry::new();
+        registry.register("v1", "ConfigMap", deduced_parseable_type());
+        registry
+    }
+
+    fn managed_fields(object: &str) -> Vec<Value> {
+        let object = value::from_json(object).unwrap();
+        object.as_map().unwrap().get("metadata").unwrap().as_map().unwrap()
+            .get("managedFields").unwrap().as_list().unwrap().clone()
+    }
+
+    #[test]
+    fn test_apply_object() {
+        let registry = registry();
+        let options = ApplyObjectOptions {
+            time: Some("2024-01-01T00:00:00Z".to_string()),
+            ..Default::default()
+        };
+
+        let created = apply_object(
+            &registry,
+            "",
+            r#"{"apiVersion": "v1", "kind": "ConfigMap", "metadata": {"name": "cm"}, "data": {"a": "1"}}"#,
+            "alice",
+            &options,
+        )
+        .unwrap();
+        let entries = managed_fields(&created);
+        assert_eq!(entries.len(), 1);
+        let entry = entries[0].as_map().unwrap();
+        assert_eq!(entry.get("manager"), Some(&Value::String("alice".into())));
+        assert_eq!(entry.get("operation"), Some(&Value::String("Apply".into())));
+        assert_eq!(entry.get("time"), Some(&Value::String("2024-01-01T00:00:00Z".into())));
+
+        let updated = apply_object(
+            &registry,
+            &created,
+            r#"{"apiVersion": "v1", "kind": "ConfigMap", "metadata": {"name": "cm"}, "data": {"b": "2"}}"#,
+            "bob",
+            &ApplyObjectOptions::default(),
+        )
+        .unwrap();
+        let object = value::from_json(&updated).unwrap();
+        let data = object.as_map().unwrap().get("data").unwrap().as_map().unwrap();
+        assert_eq!(data.len(), 2);
+        assert_eq!(managed_fields(&updated).len(), 2);
+
+        // Alice's entry keeps its timestamp
+        let alice = managed_fields(&updated).into_iter()
+            .find(|e| e.as_map().unwrap().get("manager") == Some(&Value::String("alice".into())))
+            .unwrap();
+        assert!(alice.as_map().unwrap().get("time").is_some());
+
+        let conflict = apply_object(
+            &registry,
+            &updated,
+            r#"{"apiVersion": "v1", "kind": "ConfigMap", "metadata": {"name": "cm"}, "data": {"a": "3"}}"#,
+            "bob",
+            &ApplyObjectOptions::default(),
+        );
+        assert!(matches!(conflict, Err(ApplyObjectError::Apply(ApplyError::Conflicts(_)))));
+    }
+
+    #[test]
+    fn test_apply_object_lowers_wildcards() {
+        let live = r#"{"apiVersion": "v1", "kind": "ConfigMap", "metadata": {"name": "cm", "managedFields": [
+            {"manager": "sync", "operation": "Update", "apiVersion": "v1", "fieldsType": "FieldsV1",
+             "fieldsV1": {"f:data": {".": {}, "w:*": {}}}}]}, "data": {"a": "1"}}"#;
+        let applied = apply_object(
+            &registry(),
+            live,
+            r#"{"apiVersion": "v1", "kind": "ConfigMap", "metadata": {"name": "cm"}, "data": {"a": "1", "b": "2"}}"#,
+            "alice",
+            &ApplyObjectOptions { force: true, ..Default::default() },
+        )
+        .unwrap();
+        let sync = managed_fields(&applied).into_iter()
+            .find(|e| e.as_map().unwrap().get("manager") == Some(&Value::String("sync".into())))
+            .unwrap();
+        let fields = value::to_json(sync.as_map().unwrap().get("fieldsV1").unwrap()).unwrap();
+        assert_eq!(fields, r#"{"f:data":{".":{},"f:a":{}}}"#);
+    }
+
+    #[test]
+    fn test_apply_object_unknown_type() {
+        let err = apply_object(&registry(), "", r#"{"apiVersion": "v1", "kind": "Pod"}"#, "alice", &ApplyObjectOptions::default());
+        assert!(matches!(err, Err(ApplyObjectError::UnknownType { .. })));
+
+        let mut registry = registry();
+        registry.set_fallback(deduced_parseable_type());
+        assert!(apply_object(&registry, "", r#"{"apiVersion": "v1", "kind": "Pod"}"#, "alice", &ApplyObjectOptions::default()).is_ok());
+    }
+}
@@ -0,0 +1,389 @@
+//! Applies in two phases: planning, then committing the plan.
+//!
+//! Review and approval workflows compute an apply, show it, and only write
+//! it once approved. Updater::plan computes the merged object and the
+//! changes to the managed fields without making them; Updater::commit makes
+//! them later, unless the live object or its managed fields changed in
+//! between, in which case the plan is stale and must be computed again.
+
+use super::updater::{ApplyError, ApplyOptions, Updater};
+use super::{Conflict, Conflicts};
+use crate::fieldpath::{serialize_path_element, APIVersion, ManagedFields, Operation, Path, Set, VersionedSet};
+use crate::typed::{ParseError, ParseableType, TypedValue};
+use crate::value::Value;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// OwnershipChange is the entry of a manager before and after an apply,
+/// None where the manager has none.
+#[derive(Debug, Clone)]
+pub struct OwnershipChange {
+    pub before: Option<VersionedSet>,
+    pub after: Option<VersionedSet>,
+}
+
+/// ApplyPlan is an apply computed but not yet made.
+#[derive(Debug, Clone)]
+pub struct ApplyPlan {
+    /// The manager applying.
+    pub manager: String,
+    /// The object the apply results in.
+    pub object: TypedValue,
+    /// The conflicts with other managers that the apply forces.
+    pub forced: Conflicts,
+    /// The entries the apply changes, by manager.
+    pub changes: BTreeMap<String, OwnershipChange>,
+    /// Identifies the live object and managed fields the plan was computed
+    /// from.
+    fingerprint: u64,
+}
+
+/// StalePlanError is returned when committing a plan computed from another
+/// live object or other managed fields than those given.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StalePlanError {
+    pub message: String,
+}
+
+impl fmt::Display for StalePlanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for StalePlanError {}
+
+/// Fnv hashes bytes with 64-bit FNV-1a, whose results don't change across
+/// builds, so fingerprints can be persisted with plans.
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Self {
+        Fnv(0xcbf2_9ce4_8422_2325)
+    }
+
+    /// Hashes `bytes` preceded by their length, so consecutive fields
+    /// can't run into each other.
+    fn write(&mut self, bytes: &[u8]) {
+        for b in (bytes.len() as u64).to_le_bytes().iter().chain(bytes) {
+            self.0 = (self.0 ^ u64::from(*b)).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+}
+
+/// Returns a hash of the live object and the managed fields, from their
+/// JSON forms.
+fn fingerprint(live: &TypedValue, managers: &ManagedFields) -> u64 {
+    let mut hasher = Fnv::new();
+    hasher.write(&serde_json::to_vec(live.value()).unwrap_or_default());
+    for (manager, vs) in managers.iter() {
+        hasher.write(manager.as_bytes());
+        hasher.write(vs.api_version.as_str().as_bytes());
+        hasher.write(&[u8::from(vs.applied)]);
+        // Sets are hashed by their FieldsV1 form, which is canonical
+        hasher.write(&vs.set().to_json_with_wildcards().unwrap_or_default());
+    }
+    hasher.0
+}
+
+fn same_entry(a: Option<&VersionedSet>, b: Option<&VersionedSet>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => a.api_version == b.api_version && a.applied == b.applied && a.set().equals(b.set()),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+impl Updater {
+    /// Computes the apply of `config_obj` by `manager` as
+    /// apply_with_options does, without changing `managers`.
+    ///
+    /// Conflicts the options don't force fail the plan as they fail the
+    /// apply. Those they force are listed in the plan, for review.
+    pub fn plan(
+        &self,
+        live_obj: &TypedValue,
+        config_obj: &TypedValue,
+        version: &APIVersion,
+        managers: &ManagedFields,
+        manager: &str,
+        options: &ApplyOptions,
+    ) -> Result<ApplyPlan, ApplyError> {
+        let mut planned = managers.clone();
+        let (object, forced) = match self.apply_with_options(
+            live_obj,
+            config_obj,
+            version,
+            &mut planned,
+            manager,
+            &ApplyOptions::default(),
+        ) {
+            Ok(object) => (object, Conflicts::new()),
+            Err(ApplyError::Conflicts(conflicts))
+                if options.force || options.force_fields.is_some() || options.force_lower_priority =>
+            {
+                planned = managers.clone();
+                let object = self.apply_with_options(live_obj, config_obj, version, &mut planned, manager, options)?;
+                (object, conflicts)
+            }
+            Err(e) => return Err(e),
+        };
+
+        let mut changes = BTreeMap::new();
+        for name in managers.managers().chain(planned.managers()) {
+            let (before, after) = (managers.get(name), planned.get(name));
+            if !same_entry(before, after) {
+                changes.insert(
+                    name.clone(),
+                    OwnershipChange {
+                        before: before.cloned(),
+                        after: after.cloned(),
+                    },
+                );
+            }
+        }
+        Ok(ApplyPlan {
+            manager: manager.to_string(),
+            object,
+            forced,
+            changes,
+            fingerprint: fingerprint(live_obj, managers),
+        })
+    }
+
+    /// Makes the changes of `plan` to `managers` and returns the object it
+    /// results in, unless `live_obj` or `managers` changed since the plan
+    /// was computed.
+    pub fn commit(
+        &self,
+        plan: &ApplyPlan,
+        live_obj: &TypedValue,
+        managers: &mut ManagedFields,
+    ) -> Result<TypedValue, StalePlanError> {
+        if fingerprint(live_obj, managers) != plan.fingerprint {
+            return Err(StalePlanError {
+                message: format!(
+                    "the plan of {:?} is stale: the object or its managed fields changed since",
+                    plan.manager
+                ),
+            });
+        }
+        for (name, change) in &plan.changes {
+            match &change.after {
+                Some(vs) => managers.insert(name.clone(), vs.clone()),
+                None => {
+                    managers.remove(name);
+                }
+            }
+        }
+        Ok(plan.object.clone())
+    }
+}
+
+/// The persisted form of a VersionedSet.
+#[derive(Serialize, Deserialize)]
+struct PersistedEntry {
+    version: String,
+    applied: bool,
+    set: Set,
+}
+
+impl PersistedEntry {
+    fn new(vs: &VersionedSet) -> Self {
+        PersistedEntry {
+            version: vs.api_version.as_str().to_string(),
+            applied: vs.applied,
+            set: vs.set().clone(),
+        }
+    }
+
+    fn into_versioned_set(self) -> VersionedSet {
+        VersionedSet::new(self.set, APIVersion::new(self.version), self.applied)
+    }
+}
+
+/// The persisted form of an OwnershipChange.
+#[derive(Serialize, Deserialize)]
+struct PersistedChange {
+    before: Option<PersistedEntry>,
+    after: Option<PersistedEntry>,
+}
+
+/// The persisted form of a Conflict. The path is kept as its FieldsV1
+/// elements, which round-trip keys of any value.
+#[derive(Serialize, Deserialize)]
+struct PersistedConflict {
+    manager: String,
+    path: Vec<String>,
+    operation: Operation,
+}
+
+/// The persisted form of ApplyPlan.
+#[derive(Serialize, Deserialize)]
+struct PersistedPlan {
+    manager: String,
+    object: Value,
+    forced: Vec<PersistedConflict>,
+    changes: BTreeMap<String, PersistedChange>,
+    fingerprint: u64,
+}
+
+impl ApplyPlan {
+    /// Encodes the plan as JSON, so it can be stored while it is reviewed.
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        let mut forced = Vec::new();
+        for c in self.forced.iter() {
+            let path = c
+                .path
+                .iter()
+                .map(serialize_path_element)
+                .collect::<Result<_, _>>()
+                .map_err(serde::ser::Error::custom)?;
+            forced.push(PersistedConflict {
+                manager: c.manager.clone(),
+                path,
+                operation: c.operation,
+            });
+        }
+        serde_json::to_string(&PersistedPlan {
+            manager: self.manager.clone(),
+            object: self.object.value().clone(),
+            forced,
+            changes: self
+                .changes
+                .iter()
+                .map(|(name, change)| {
+                    let persisted = PersistedChange {
+                        before: change.before.as_ref().map(PersistedEntry::new),
+                        after: change.after.as_ref().map(PersistedEntry::new),
+                    };
+                    (name.clone(), persisted)
+                })
+                .collect(),
+            fingerprint: self.fingerprint,
+        })
+    }
+
+    /// Decodes a plan encoded by to_json for objects of the given type.
+    pub fn from_json(pt: &ParseableType, json: &str) -> Result<ApplyPlan, ParseError> {
+        let persisted: PersistedPlan =
+            serde_json::from_str(json).map_err(|e| ParseError::new(format!("failed to parse apply plan: {}", e)))?;
+        let mut forced = Conflicts::new();
+        for c in persisted.forced {
+            let elements: Vec<&str> = c.path.iter().map(String::as_str).collect();
+            let path = Path::from_serialized(&elements)
+                .map_err(|e| ParseError::new(format!("failed to parse apply plan: {}", e)))?;
+            forced.add(Conflict::new(c.manager, path).with_operation(c.operation));
+        }
+        Ok(ApplyPlan {
+            manager: persisted.manager,
+            object: pt.from_value(persisted.object)?,
+            forced,
+            changes: persisted
+                .changes
+                .into_iter()
+                .map(|(name, change)| {
+                    let change = OwnershipChange {
+                        before: change.before.map(PersistedEntry::into_versioned_set),
+                        after: change.after.map(PersistedEntry::into_versioned_set),
+                    };
+                    (name, change)
+                })
+                .collect(),
+            fingerprint: persisted.fingerprint,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::typed::Parser;
+
+    #[test]
+    fn test_plan_and_commit() {
+        let pt = Parser::new("types:\n- name: root\n  map:\n    elementType:\n      scalar: string\n")
+            .unwrap()
+            .type_by_name("root");
+        let updater = Updater::builder().build();
+        let version = APIVersion::new("v1");
+        let mut managers = ManagedFields::new();
+        let empty = pt.from_yaml("{}").unwrap();
+        let live = updater
+            .apply(
+                &empty,
+                &pt.from_yaml(r#"{"a": "x"}"#).unwrap(),
+                &version,
+                &mut managers,
+                "kubectl",
+                false,
+            )
+            .unwrap();
+        let config = pt.from_yaml(r#"{"a": "y", "b": "z"}"#).unwrap();
+
+        // Unforced conflicts fail the plan as they fail the apply
+        let err = updater.plan(&live, &config, &version, &managers, "other", &ApplyOptions::default());
+        assert!(matches!(err, Err(ApplyError::Conflicts(_))));
+
+        let plan = updater
+            .plan(&live, &config, &version, &managers, "other", &ApplyOptions::force())
+            .unwrap();
+        assert_eq!(plan.forced.len(), 1);
+        assert_eq!(plan.changes.keys().collect::<Vec<_>>(), vec!["kubectl", "other"]);
+        assert!(plan.changes["other"].before.is_none());
+        let plan = ApplyPlan::from_json(&pt, &plan.to_json().unwrap()).unwrap();
+        assert_eq!(plan.forced.iter().next().unwrap().path.to_string(), ".a");
+
+        let mut expected = managers.clone();
+        let applied = updater
+            .apply(&live, &config, &version, &mut expected, "other", true)
+            .unwrap();
+
+        // Plans of other managed fields are stale
+        let mut changed = managers.clone();
+        changed.remove("kubectl");
+        assert!(updater.commit(&plan, &live, &mut changed).is_err());
+        assert!(updater.commit(&plan, &applied, &mut managers.clone()).is_err());
+
+        let committed = updater.commit(&plan, &live, &mut managers).unwrap();
+        assert_eq!(committed.value(), applied.value());
+        assert_eq!(managers, expected);
+
+        // Fingerprints are the same in every build
+        assert_eq!(fingerprint(&live, &ManagedFields::new()), 0xd0e8_50fb_983c_cc6f);
+    }
+
+    #[test]
+    fn test_plan_json_keys() {
+        use crate::fieldpath::PathElement;
+        use crate::value::{Field, FieldList, Map};
+
+        let pt = Parser::new("types:\n- name: root\n  map:\n    elementType:\n      scalar: string\n")
+            .unwrap()
+            .type_by_name("root");
+        let mut selector = Map::new();
+        selector.set("app".to_string(), Value::String("web".into()));
+        let path = Path::from_elements(vec![
+            PathElement::field_name("rules"),
+            PathElement::key(FieldList::with_fields(vec![Field {
+                name: "selector".to_string(),
+                value: Value::Map(selector),
+            }])),
+        ]);
+        let mut forced = Conflicts::new();
+        forced.add(Conflict::new("kubectl", path.clone()).with_operation(Operation::Update));
+        let plan = ApplyPlan {
+            manager: "other".to_string(),
+            object: pt.from_yaml("{}").unwrap(),
+            forced,
+            changes: BTreeMap::new(),
+            fingerprint: 0,
+        };
+
+        let decoded = ApplyPlan::from_json(&pt, &plan.to_json().unwrap()).unwrap();
+        let conflict = decoded.forced.iter().next().unwrap();
+        assert_eq!(conflict.path, path);
+        assert_eq!(conflict.operation, Operation::Update);
+    }
+}
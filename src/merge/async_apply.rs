@@ -0,0 +1,279 @@
+//! Applies with conversions that are awaited.
+
+use super::{ApplyError, ConversionError, Converter, Updater};
+use crate::fieldpath::{APIVersion, ManagedFields, Set};
+use crate::typed::TypedValue;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::{Hash, Hasher};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::task::Poll;
+
+/// ConversionFuture is the result of an asynchronous conversion.
+pub type ConversionFuture<'a> = Pin<Box<dyn Future<Output = Result<TypedValue, ConversionError>> + Send + 'a>>;
+
+/// AsyncConverter is a Converter whose conversions may need I/O, such as
+/// calls to a CRD conversion webhook.
+pub trait AsyncConverter: Send + Sync {
+    /// Converts a TypedValue to a different API version.
+    /// See Converter::convert for the meaning of the error kinds.
+    fn convert<'a>(&'a self, obj: &'a TypedValue, version: &'a APIVersion) -> ConversionFuture<'a>;
+
+    /// Converts a field set recorded at `from` to the paths of the same
+    /// fields at `to`. See Converter::convert_set.
+    fn convert_set(&self, _set: &Set, _from: &APIVersion, _to: &APIVersion) -> Option<Result<Set, ConversionError>> {
+        None
+    }
+}
+
+/// ConversionKey identifies a conversion by its target version and a hash
+/// of the object: its semantic hash, type and schema.
+type ConversionKey = (APIVersion, u64);
+
+/// ReplayConverter answers conversions from those awaited so far and
+/// records the ones it doesn't know yet.
+struct ReplayConverter {
+    converter: Arc<dyn AsyncConverter>,
+    state: Mutex<ReplayState>,
+}
+
+#[derive(Default)]
+struct ReplayState {
+    done: HashMap<ConversionKey, Result<TypedValue, ConversionError>>,
+    pending: HashMap<ConversionKey, (TypedValue, APIVersion)>,
+}
+
+fn conversion_key(obj: &TypedValue, version: &APIVersion) -> ConversionKey {
+    let mut hasher = DefaultHasher::new();
+    obj.semantic_hash().hash(&mut hasher);
+    obj.type_ref().named_type.hash(&mut hasher);
+    obj.type_ref().element_relationship.hash(&mut hasher);
+    obj.schema().fingerprint().hash(&mut hasher);
+    (version.clone(), hasher.finish())
+}
+
+impl ReplayConverter {
+    fn take_pending(&self) -> Vec<(ConversionKey, (TypedValue, APIVersion))> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner).pending.drain().collect()
+    }
+
+    fn insert(&self, results: impl IntoIterator<Item = (ConversionKey, Result<TypedValue, ConversionError>)>) {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner).done.extend(results);
+    }
+}
+
+impl Converter for ReplayConverter {
+    /// Conversions not awaited yet are recorded, and the object stands in
+    /// for their result so the attempt goes on to ask for the others it
+    /// needs. Attempts with pending conversions are thrown away.
+    fn convert(&self, obj: &TypedValue, version: &APIVersion) -> Result<TypedValue, ConversionError> {
+        let key = conversion_key(obj, version);
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(result) = state.done.get(&key) {
+            return result.clone();
+        }
+        state.pending.entry(key).or_insert_with(|| (obj.clone(), version.clone()));
+        Ok(obj.clone())
+    }
+
+    fn convert_set(&self, set: &Set, from: &APIVersion, to: &APIVersion) -> Option<Result<Set, ConversionError>> {
+        self.converter.convert_set(set, from, to)
+    }
+}
+
+/// Awaits all conversions together, returning their results in order.
+async fn join_all(mut futures: Vec<ConversionFuture<'_>>) -> Vec<Result<TypedValue, ConversionError>> {
+    let mut results: Vec<Option<Result<TypedValue, ConversionError>>> = futures.iter().map(|_| None).collect();
+    std::future::poll_fn(|cx| {
+        let mut ready = true;
+        for (future, result) in futures.iter_mut().zip(results.iter_mut()) {
+            if result.is_none() {
+                match future.as_mut().poll(cx) {
+                    Poll::Ready(output) => *result = Some(output),
+                    Poll::Pending => ready = false,
+                }
+            }
+        }
+        if ready {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    })
+    .await;
+    results.into_iter().flatten().collect()
+}
+
+impl Updater {
+    /// ApplyAsync is like Apply, but converts with the configured
+    /// AsyncConverter, awaiting its conversions. Without an AsyncConverter
+    /// it is the same as Apply.
+    ///
+    /// The apply is replayed until every conversion it needs has been
+    /// awaited, so the result is the same as Apply with an equivalent
+    /// synchronous Converter. The conversions an attempt asks for are
+    /// awaited together.
+    pub async fn apply_async(
+        &self,
+        live_obj: &TypedValue,
+        config_obj: &TypedValue,
+        version: &APIVersion,
+        managers: &mut ManagedFields,
+        manager: &str,
+        force: bool,
+    ) -> Result<TypedValue, ApplyError> {
+        let Some(converter) = self.async_converter().cloned() else {
+            return self.apply(live_obj, config_obj, version, managers, manager, force);
+        };
+        let replay = Arc::new(ReplayConverter {
+            converter: converter.clone(),
+            state: Mutex::default(),
+        });
+        let updater = self.with_converter(replay.clone());
+        loop {
+            let mut attempt = managers.clone();
+            let result = updater.apply(live_obj, config_obj, version, &mut attempt, manager, force);
+            let pending = replay.take_pending();
+            if pending.is_empty() {
+                *managers = attempt;
+                return result;
+            }
+            let results = join_all(pending.iter().map(|(_, (obj, to))| converter.convert(obj, to)).collect()).await;
+            replay.insert(pending.into_iter().map(|(key, _)| key).zip(results));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::typed::{ParseableType, Parser};
+    use std::task::{Context, Wake, Waker};
+    use std::thread::Thread;
+
+    /// Wakes a thread parked in block_on.
+    struct ThreadWaker(Thread);
+
+    impl Wake for ThreadWaker {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    /// Polls the future each time it's woken, so futures that don't wake
+    /// when they can go on never finish.
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let mut future = std::pin::pin!(future);
+        let waker = Waker::from(Arc::new(ThreadWaker(std::thread::current())));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+            std::thread::park();
+        }
+    }
+
+    fn pt(field: &str) -> ParseableType {
+        Parser::new(&format!(
+            "types:\n- name: root\n  map:\n    fields:\n    - name: {}\n      type:\n        scalar: string\n    - name: other\n      type:\n        scalar: string\n",
+            field
+        ))
+        .unwrap()
+        .type_by_name("root")
+    }
+
+    /// Renames field `a` of v1 to `b` of v2, returning Pending once per
+    /// conversion like a webhook call would, and counting the conversions
+    /// in flight.
+    struct RenameConverter {
+        calls: Arc<Mutex<usize>>,
+        in_flight: Arc<Mutex<(usize, usize)>>,
+    }
+
+    impl AsyncConverter for RenameConverter {
+        fn convert<'a>(&'a self, obj: &'a TypedValue, version: &'a APIVersion) -> ConversionFuture<'a> {
+            Box::pin(async move {
+                let mut yielded = false;
+                std::future::poll_fn(|cx| {
+                    if yielded {
+                        Poll::Ready(())
+                    } else {
+                        yielded = true;
+                        let (current, max) = &mut *self.in_flight.lock().unwrap();
+                        *current += 1;
+                        *max = (*max).max(*current);
+                        cx.waker().wake_by_ref();
+                        Poll::Pending
+                    }
+                })
+                .await;
+                self.in_flight.lock().unwrap().0 -= 1;
+                *self.calls.lock().unwrap() += 1;
+                let (from, to, target) = match version.to_string().as_str() {
+                    "v1" => ("b", "a", pt("a")),
+                    "v2" => ("a", "b", pt("b")),
+                    _ => {
+                        return Err(ConversionError::missing_version(format!("unknown version {}", version)))
+                    }
+                };
+                let json = crate::value::to_json(obj.value())
+                    .unwrap()
+                    .replace(&format!("\"{}\"", from), &format!("\"{}\"", to));
+                target.from_yaml(&json).map_err(|e| ConversionError::incompatible(e.to_string()))
+            })
+        }
+    }
+
+    #[test]
+    fn test_apply_async() {
+        let calls = Arc::new(Mutex::new(0));
+        let in_flight = Arc::new(Mutex::new((0, 0)));
+        let updater = Updater::builder()
+            .async_converter(Box::new(RenameConverter {
+                calls: calls.clone(),
+                in_flight: in_flight.clone(),
+            }))
+            .build();
+        let mut managers = ManagedFields::new();
+
+        let v1 = APIVersion::new("v1");
+        let v2 = APIVersion::new("v2");
+        let live = pt("a").from_yaml("{}").unwrap();
+        let config = pt("a").from_yaml(r#"{"a": "x"}"#).unwrap();
+        let live = block_on(updater.apply_async(&live, &config, &v1, &mut managers, "m1", false)).unwrap();
+        assert_eq!(*calls.lock().unwrap(), 0);
+
+        // The v2 applier conflicts with the v1 owner of the renamed field
+        let config = pt("b").from_yaml(r#"{"b": "y"}"#).unwrap();
+        let err = block_on(updater.apply_async(&live, &config, &v2, &mut managers, "m2", false)).unwrap_err();
+        assert!(matches!(err, ApplyError::Conflicts(ref c) if c.len() == 1));
+        assert!(*calls.lock().unwrap() > 0);
+        // The conversions of an attempt are awaited together
+        assert!(in_flight.lock().unwrap().1 > 1, "{:?}", in_flight.lock().unwrap());
+
+        // Conversions already awaited aren't asked for again
+        let calls_before = *calls.lock().unwrap();
+        let replay = ReplayConverter {
+            converter: Arc::new(RenameConverter { calls: calls.clone(), in_flight: in_flight.clone() }),
+            state: Mutex::default(),
+        };
+        let converted = pt("b").from_yaml(r#"{"b": "x"}"#).unwrap();
+        replay.insert([(conversion_key(&live, &v2), Ok(converted.clone()))]);
+        assert_eq!(Converter::convert(&replay, &live, &v2).unwrap().value(), converted.value());
+        assert!(replay.take_pending().is_empty());
+        // Equal objects share the entry
+        let equal = pt("a").from_yaml(r#"{"a": "x"}"#).unwrap();
+        assert_eq!(Converter::convert(&replay, &equal, &v2).unwrap().value(), converted.value());
+        assert_eq!(Converter::convert(&replay, &live, &v1).unwrap().value(), live.value());
+        assert_eq!(replay.take_pending().len(), 1);
+        assert_eq!(*calls.lock().unwrap(), calls_before);
+
+        let result = block_on(updater.apply_async(&live, &config, &v2, &mut managers, "m2", true)).unwrap();
+        assert_eq!(crate::value::to_json(result.value()).unwrap(), r#"{"b":"y"}"#);
+        assert!(managers.get("m1").is_none());
+        assert_eq!(managers.get("m2").unwrap().api_version(), &v2);
+    }
+}
@@ -0,0 +1,98 @@
+//! Checks of the fields a change touches against the fields it may touch.
+
+use crate::fieldpath::{Path, Set, SetPattern};
+use std::fmt;
+
+/// DeniedPaths lists the changed paths that no allowed pattern matches.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeniedPaths {
+    pub paths: Set,
+}
+
+impl fmt::Display for DeniedPaths {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "changes to fields that aren't allowed: {}", self.paths)
+    }
+}
+
+impl std::error::Error for DeniedPaths {}
+
+/// Checks that every path of `requested_changes`, such as the added and
+/// modified fields of a comparison, matches a pattern of `allowed`.
+///
+/// Wildcards of the changes are allowed only when their own path matches,
+/// as they stand for every path beneath it. Ancestors of allowed paths are
+/// not allowed, so a change recorded at a parent, such as replacing an
+/// atomic map holding an allowed field, is denied.
+pub fn authorize(requested_changes: &Set, allowed: &SetPattern) -> Result<(), DeniedPaths> {
+    let mut denied = Set::new();
+    requested_changes.iterate(|path| {
+        if !allowed.matches(path) {
+            denied.insert(path);
+        }
+    });
+    requested_changes.wildcards_iterate(|path: &Path| {
+        if !allowed.matches(path) {
+            denied.insert_wildcard(path);
+        }
+    });
+    if denied.is_empty() {
+        Ok(())
+    } else {
+        Err(DeniedPaths { paths: denied })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::typed::Parser;
+
+    #[test]
+    fn test_authorize() {
+        let pt = Parser::new(
+            r#"types:
+- name: root
+  map:
+    fields:
+    - name: metadata
+      type:
+        map:
+          fields:
+          - name: labels
+            type:
+              map:
+                elementType:
+                  scalar: string
+    - name: spec
+      type:
+        map:
+          elementType:
+            scalar: numeric
+"#,
+        )
+        .unwrap()
+        .type_by_name("root");
+        let live = pt
+            .from_yaml(r#"{"metadata": {"labels": {"app": "x"}}, "spec": {"replicas": 1}}"#)
+            .unwrap();
+        let allowed = SetPattern::parse([".metadata.labels"]).unwrap();
+
+        let relabeled = pt
+            .from_yaml(r#"{"metadata": {"labels": {"app": "y", "tier": "web"}}, "spec": {"replicas": 1}}"#)
+            .unwrap();
+        let compare = live.compare(&relabeled).unwrap();
+        assert_eq!(authorize(&compare.modified.union(&compare.added), &allowed), Ok(()));
+
+        let scaled = pt
+            .from_yaml(r#"{"metadata": {"labels": {"app": "y"}}, "spec": {"replicas": 3}}"#)
+            .unwrap();
+        let compare = live.compare(&scaled).unwrap();
+        let err = authorize(&compare.modified.union(&compare.added), &allowed).unwrap_err();
+        assert_eq!(err.to_string(), "changes to fields that aren't allowed: {.spec.replicas}");
+
+        let mut everything = Set::new();
+        everything.insert_wildcard(&Path::new());
+        assert!(authorize(&everything, &allowed).is_err());
+    }
+}
@@ -1,6 +1,7 @@
 //! Conflict types for merge operations.
 
-use crate::fieldpath::{ManagedFields, Path, Set};
+use crate::fieldpath::{ManagedFields, ManagerIdentifier, ManagerPriority, Operation, Path, Set};
+use crate::messages::{render, English, Message, MessageCatalog};
 use std::collections::BTreeMap;
 use std::fmt;
 
@@ -11,28 +12,71 @@ pub struct Conflict {
     pub manager: String,
     /// The path to the conflicting field.
     pub path: Path,
+    /// The operation that recorded the manager's ownership.
+    pub operation: Operation,
 }
 
 impl Conflict {
-    /// Creates a new conflict.
+    /// Creates a new conflict with a manager that applied the field.
     pub fn new(manager: impl Into<String>, path: Path) -> Self {
         Conflict {
             manager: manager.into(),
             path,
+            operation: Operation::Apply,
         }
     }
+
+    /// Sets the operation that recorded the manager's ownership.
+    pub fn with_operation(mut self, operation: Operation) -> Self {
+        self.operation = operation;
+        self
+    }
+
+    /// Returns the message of the conflict.
+    pub fn message(&self) -> Message<'_> {
+        Message::Conflict {
+            manager: &self.manager,
+            operation: self.operation,
+            path: &self.path,
+        }
+    }
+
+    /// Returns the message of the conflict as `catalog` words it.
+    pub fn localized(&self, catalog: &dyn MessageCatalog) -> String {
+        render(catalog, &self.message())
+    }
 }
 
 impl fmt::Display for Conflict {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "conflict with manager '{}' at {}", self.manager, self.path)
+        write!(f, "{}", self.message().english())
     }
 }
 
+/// Names a manager in conflict messages. Managers keyed by an encoded
+/// ManagerIdentifier are named the way kube-apiserver does, with the
+/// subresource and, for updates, the API version they wrote.
+fn manager_label(manager: &str) -> String {
+    let Ok(id) = ManagerIdentifier::decode(manager) else {
+        return format!("\"{}\"", manager);
+    };
+    let mut label = format!("\"{}\"", id.manager);
+    if let Some(subresource) = &id.subresource {
+        label.push_str(&format!(" with subresource \"{}\"", subresource));
+    }
+    if let (Operation::Update, Some(version)) = (id.operation, &id.api_version) {
+        label.push_str(&format!(" using {}", version));
+    }
+    label
+}
+
 impl std::error::Error for Conflict {}
 
 /// Conflicts is a collection of conflicts.
-#[derive(Debug, Clone, Default, PartialEq, Eq)]
+///
+/// Conflicts debug-format as a list of their messages, one per line with
+/// `{:#?}`.
+#[derive(Clone, Default, PartialEq, Eq)]
 pub struct Conflicts {
     conflicts: Vec<Conflict>,
 }
@@ -74,9 +118,44 @@ impl Conflicts {
         set
     }
 
+    /// Returns the set of conflicting paths to force when retrying the
+    /// apply, with `ApplyOptions::force_only`. Forcing exactly these fields
+    /// takes them over without overriding fields that become conflicting
+    /// in between.
+    pub fn to_force_set(&self) -> Set {
+        self.to_set()
+    }
+
+    /// Returns the conflicts with the managers of highest priority in
+    /// `managers` first, keeping the order of those of the same priority.
+    pub fn prioritized(&self, managers: &ManagedFields) -> Conflicts {
+        let mut conflicts = self.conflicts.clone();
+        conflicts.sort_by_key(|c| std::cmp::Reverse(managers.priority(&c.manager)));
+        Conflicts { conflicts }
+    }
+
+    /// Returns the manager of highest priority in `managers` among those
+    /// conflicting, the first of them if several share it.
+    pub fn blocking_manager(&self, managers: &ManagedFields) -> Option<&str> {
+        let mut blocking: Option<(&str, ManagerPriority)> = None;
+        for conflict in &self.conflicts {
+            let priority = managers.priority(&conflict.manager);
+            if blocking.is_none_or(|(_, p)| priority > p) {
+                blocking = Some((&conflict.manager, priority));
+            }
+        }
+        blocking.map(|(manager, _)| manager)
+    }
+
     /// Returns the error message in Go-compatible format.
     /// Groups conflicts by manager, sorted alphabetically.
     pub fn error(&self) -> String {
+        self.localized(&English)
+    }
+
+    /// Returns the error message as error does, with the heading of each
+    /// manager worded by `catalog`.
+    pub fn localized(&self, catalog: &dyn MessageCatalog) -> String {
         if self.conflicts.is_empty() {
             return String::new();
         }
@@ -101,7 +180,8 @@ impl Conflicts {
             if i > 0 {
                 result.push('\n');
             }
-            result.push_str(&format!("conflicts with \"{}\":", manager));
+            let label = manager_label(manager);
+            result.push_str(&render(catalog, &Message::ConflictsWith { manager: &label }));
             for path in paths {
                 result.push_str(&format!("\n- {}", path));
             }
@@ -110,6 +190,14 @@ impl Conflicts {
     }
 }
 
+impl FromIterator<Conflict> for Conflicts {
+    fn from_iter<I: IntoIterator<Item = Conflict>>(iter: I) -> Self {
+        Conflicts {
+            conflicts: iter.into_iter().collect(),
+        }
+    }
+}
+
 impl IntoIterator for Conflicts {
     type Item = Conflict;
     type IntoIter = std::vec::IntoIter<Conflict>;
@@ -125,6 +213,16 @@ impl fmt::Display for Conflicts {
     }
 }
 
+impl fmt::Debug for Conflicts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut list = f.debug_list();
+        for conflict in &self.conflicts {
+            list.entry(&format_args!("{}", conflict));
+        }
+        list.finish()
+    }
+}
+
 impl std::error::Error for Conflicts {}
 
 /// Extracts conflicts from ManagedFields.
@@ -133,8 +231,9 @@ pub fn conflicts_from_managers(managers: &ManagedFields) -> Conflicts {
     let mut conflicts = Conflicts::new();
 
     for (manager, vs) in managers.iter() {
+        let operation = if vs.applied() { Operation::Apply } else { Operation::Update };
         vs.set().iterate(|path| {
-            conflicts.add(Conflict::new(manager.clone(), path.clone()));
+            conflicts.add(Conflict::new(manager.clone(), path.clone()).with_operation(operation));
         });
     }
 
@@ -188,6 +287,7 @@ mod tests {
         conflicts.add(Conflict::new("m1", Path::new()));
         assert!(!conflicts.is_empty());
         assert_eq!(conflicts.len(), 1);
+        assert_eq!(format!("{:?}", conflicts), "[conflict with manager 'm1' at ]");
     }
 
     // Test from Go: TestNewFromSets
@@ -0,0 +1,224 @@
+//! Consistency checks for managed fields.
+
+use crate::fieldpath::{APIVersion, ManagedFields, Path, PathElement};
+use crate::schema::{ElementRelationship, Schema, TypeRef};
+use crate::typed::{allows_unknown_fields, TypedValue, ValidationErrors};
+use std::fmt;
+
+/// InconsistencyKind is why an owned path doesn't belong in managed fields.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum InconsistencyKind {
+    /// The path doesn't exist in the object.
+    NotInObject,
+    /// The path can't exist under the object's schema.
+    NotInSchema,
+    /// The path is inside an atomic list or map, whose owners own all of it.
+    InsideAtomic,
+}
+
+/// Inconsistency is an owned path that doesn't fit the object.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Inconsistency {
+    pub manager: String,
+    pub path: Path,
+    pub kind: InconsistencyKind,
+}
+
+impl fmt::Display for Inconsistency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self.kind {
+            InconsistencyKind::NotInObject => "which is not in the object",
+            InconsistencyKind::NotInSchema => "which is not in the schema",
+            InconsistencyKind::InsideAtomic => "which is inside an atomic value",
+        };
+        write!(f, "manager '{}' owns {}, {}", self.manager, self.path, reason)
+    }
+}
+
+/// ManagedFieldsReport lists the inconsistencies found in managed fields.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ManagedFieldsReport {
+    inconsistencies: Vec<Inconsistency>,
+}
+
+impl ManagedFieldsReport {
+    /// Returns true if the managed fields are consistent.
+    pub fn is_empty(&self) -> bool {
+        self.inconsistencies.is_empty()
+    }
+
+    /// Returns the number of inconsistencies.
+    pub fn len(&self) -> usize {
+        self.inconsistencies.len()
+    }
+
+    /// Returns an iterator over the inconsistencies.
+    pub fn iter(&self) -> impl Iterator<Item = &Inconsistency> {
+        self.inconsistencies.iter()
+    }
+
+    /// Returns the managed fields without the inconsistent paths. Managers
+    /// left owning nothing are dropped.
+    pub fn repair(&self, managers: &ManagedFields) -> ManagedFields {
+        let mut repaired = managers.clone();
+        for (manager, vs) in managers.iter() {
+            let bad: Vec<&Path> = self
+                .inconsistencies
+                .iter()
+                .filter(|i| &i.manager == manager)
+                .map(|i| &i.path)
+                .collect();
+            if bad.is_empty() {
+                continue;
+            }
+            let set = vs.set().filter(|path| !bad.contains(&path));
+            if set.is_empty() {
+                repaired.remove(manager);
+            } else if let Some(entry) = repaired.get_mut(manager) {
+                entry.set = set;
+            }
+        }
+        repaired
+    }
+}
+
+impl fmt::Display for ManagedFieldsReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, inconsistency) in self.inconsistencies.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", inconsistency)?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks the managed fields of `live` for owned paths that are missing
+/// from it, that its schema doesn't allow, or that lie inside atomic lists
+/// and maps. Only managers recorded at `version`, the version of `live`,
+/// are checked, since the paths of other versions only mean something
+/// after conversion.
+pub fn validate_managed_fields(
+    live: &TypedValue,
+    version: &APIVersion,
+    managers: &ManagedFields,
+) -> Result<ManagedFieldsReport, ValidationErrors> {
+    let present = live.to_field_set()?;
+    let mut report = ManagedFieldsReport::default();
+    for (manager, vs) in managers.iter().filter(|(_, vs)| vs.api_version() == version) {
+        vs.set().iterate(|path| {
+            let kind = match classify(live.schema(), live.type_ref(), path) {
+                Some(kind) => kind,
+                None if !present.has(path) => InconsistencyKind::NotInObject,
+                None => return,
+            };
+            report.inconsistencies.push(Inconsistency {
+                manager: manager.clone(),
+                path: path.clone(),
+                kind,
+            });
+        });
+    }
+    Ok(report)
+}
+
+/// Walks the schema along a path, returning why it can't be owned, if so.
+fn classify(schema: &Schema, type_ref: &TypeRef, path: &Path) -> Option<InconsistencyKind> {
+    let mut type_ref = type_ref.clone();
+    for pe in path.iter() {
+        let Some(atom) = schema.resolve(&type_ref) else {
+            return Some(InconsistencyKind::NotInSchema);
+        };
+        let (element_type, relationship) = match pe {
+            PathElement::FieldName(name) => {
+                let Some(map) = atom.map else {
+                    return Some(InconsistencyKind::NotInSchema);
+                };
+                let element_type = match map.find_field(name) {
+                    Some(field) => field.field_type.clone(),
+                    None if allows_unknown_fields(&map) => map.element_type.clone(),
+                    None => return Some(InconsistencyKind::NotInSchema),
+                };
+                (element_type, map.element_relationship)
+            }
+            PathElement::Extension(..) => return Some(InconsistencyKind::NotInSchema),
+            PathElement::Key(_) | PathElement::Value(_) | PathElement::Index(_) => {
+                let Some(list) = atom.list else {
+                    return Some(InconsistencyKind::NotInSchema);
+                };
+                (list.element_type, list.element_relationship)
+            }
+        };
+        if relationship == ElementRelationship::Atomic {
+            return Some(InconsistencyKind::InsideAtomic);
+        }
+        type_ref = element_type;
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::managed_fields;
+    use crate::typed::Parser;
+
+    #[test]
+    fn test_validate_managed_fields() {
+        let pt = Parser::new(
+            r#"types:
+- name: root
+  map:
+    fields:
+    - name: replicas
+      type:
+        scalar: numeric
+    - name: args
+      type:
+        list:
+          elementType:
+            scalar: string
+          elementRelationship: atomic
+    - name: labels
+      type:
+        map:
+          elementType:
+            scalar: string
+"#,
+        )
+        .unwrap()
+        .type_by_name("root");
+        let live = pt.from_yaml(r#"{"replicas": 1, "args": ["a"], "labels": {"x": "1"}}"#).unwrap();
+        let v1 = APIVersion::new("v1");
+
+        let managers = managed_fields! {
+            "good" => { version: "v1", applied: true, paths: [["f:replicas"], ["f:args"], ["f:labels", "f:x"]] },
+            "bad" => {
+                version: "v1",
+                applied: false,
+                paths: [["f:labels", "f:y"], ["f:image"], ["f:args", "i:0"], ["f:replicas", "f:x"]],
+            },
+            "gone" => { version: "v1", applied: false, paths: [["f:labels", "f:z"]] },
+            "old" => { version: "v0", applied: true, paths: [["f:size"]] },
+        };
+        let report = validate_managed_fields(&live, &v1, &managers).unwrap();
+        assert_eq!(
+            report.to_string(),
+            "manager 'bad' owns .image, which is not in the schema\n\
+             manager 'bad' owns .args[0], which is inside an atomic value\n\
+             manager 'bad' owns .labels.y, which is not in the object\n\
+             manager 'bad' owns .replicas.x, which is not in the schema\n\
+             manager 'gone' owns .labels.z, which is not in the object"
+        );
+
+        let repaired = report.repair(&managers);
+        let expected = managed_fields! {
+            "good" => { version: "v1", applied: true, paths: [["f:replicas"], ["f:args"], ["f:labels", "f:x"]] },
+            "old" => { version: "v0", applied: true, paths: [["f:size"]] },
+        };
+        assert_eq!(repaired, expected);
+        assert!(validate_managed_fields(&live, &v1, &repaired).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,117 @@
+//! Buffers reused across applies, updates and compares.
+
+use crate::fieldpath::{Path, Set};
+use crate::typed::{CompareScratch, Comparison, TypedValue, ValidationErrors};
+use std::collections::HashMap;
+
+/// MergeContext owns the scratch maps and lists that applies, updates and
+/// compares would otherwise allocate and drop on every call.
+///
+/// Long-running services can keep a context per worker and pass it to
+/// Updater::apply_with_ctx and Updater::update_with_ctx, so that the buffers
+/// grow to what their requests need once and are reused from then on. A
+/// context holds no state between calls, only capacity, so any context can
+/// be passed to any Updater.
+#[derive(Debug, Default)]
+pub struct MergeContext {
+    pub(super) compare: CompareScratch,
+    pub(super) conflicts_by_manager: HashMap<String, Set>,
+    pub(super) removed_by_manager: HashMap<String, Set>,
+    pub(super) obsolete_managers: Vec<String>,
+    pub(super) paths: Vec<Path>,
+}
+
+impl MergeContext {
+    /// Creates a context with empty buffers.
+    pub fn new() -> Self {
+        MergeContext::default()
+    }
+
+    /// Compares `lhs` with `rhs` as TypedValue::compare does.
+    pub fn compare(&mut self, lhs: &TypedValue, rhs: &TypedValue) -> Result<Comparison, ValidationErrors> {
+        lhs.compare_with_scratch(rhs, &mut self.compare)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fieldpath::{APIVersion, ManagedFields};
+    use crate::merge::Updater;
+    use crate::typed::Parser;
+
+    #[test]
+    fn test_merge_context_reuse() {
+        let pt = Parser::new(
+            r#"types:
+- name: root
+  map:
+    fields:
+    - name: replicas
+      type:
+        scalar: numeric
+    - name: ports
+      type:
+        list:
+          elementType:
+            map:
+              fields:
+              - name: port
+                type:
+                  scalar: numeric
+              - name: name
+                type:
+                  scalar: string
+          elementRelationship: associative
+          keys: [port]
+"#,
+        )
+        .unwrap()
+        .type_by_name("root");
+        let updater = Updater::builder().build();
+        let version = APIVersion::new("v1");
+        let configs = [
+            r#"{"replicas": 1, "ports": [{"port": 80, "name": "http"}]}"#,
+            r#"{"replicas": 2, "ports": [{"port": 443, "name": "https"}, {"port": 80}]}"#,
+            r#"{"ports": [{"port": 8080}]}"#,
+        ];
+
+        let mut ctx = MergeContext::new();
+        let (mut live, mut live_ctx) = (pt.from_yaml("{}").unwrap(), pt.from_yaml("{}").unwrap());
+        let (mut managers, mut managers_ctx) = (ManagedFields::new(), ManagedFields::new());
+        for (i, config) in configs.iter().enumerate() {
+            let config = pt.from_yaml(config).unwrap();
+            let manager = format!("manager-{}", i % 2);
+            let expected = updater
+                .apply(&live, &config, &version, &mut managers, &manager, true)
+                .unwrap();
+            let got = updater
+                .apply_with_ctx(
+                    &live_ctx,
+                    &config,
+                    &version,
+                    &mut managers_ctx,
+                    &manager,
+                    true,
+                    &mut ctx,
+                )
+                .unwrap();
+            assert_eq!(got.value(), expected.value());
+            assert_eq!(managers_ctx, managers);
+            let (reused, fresh) = (ctx.compare(&live_ctx, &got).unwrap(), live.compare(&expected).unwrap());
+            assert!(reused.added.equals(&fresh.added) && reused.modified.equals(&fresh.modified));
+            assert!(reused.removed.equals(&fresh.removed));
+            (live, live_ctx) = (expected, got);
+
+            let expected = updater
+                .update(&live, &config, &version, &mut managers, "controller")
+                .unwrap();
+            let got = updater
+                .update_with_ctx(&live_ctx, &config, &version, &mut managers_ctx, "controller", &mut ctx)
+                .unwrap();
+            assert_eq!(got.value(), expected.value());
+            assert_eq!(managers_ctx, managers);
+            (live, live_ctx) = (expected, got);
+        }
+    }
+}
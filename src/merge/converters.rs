@@ -0,0 +1,321 @@
+//! Converters built from simpler ones.
+
+use super::{ConversionError, Converter};
+use crate::fieldpath::{APIVersion, Set};
+use crate::typed::TypedValue;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+/// IdentityConverter returns objects as they are, for versions that share
+/// their schema.
+#[derive(Debug, Clone, Default)]
+pub struct IdentityConverter;
+
+impl IdentityConverter {
+    /// Returns a converter to any version.
+    pub fn new() -> Self {
+        IdentityConverter
+    }
+}
+
+impl Converter for IdentityConverter {
+    fn convert(&self, obj: &TypedValue, _version: &APIVersion) -> Result<TypedValue, ConversionError> {
+        Ok(obj.clone())
+    }
+
+    fn convert_set(&self, set: &Set, _from: &APIVersion, _to: &APIVersion) -> Option<Result<Set, ConversionError>> {
+        Some(Ok(set.clone()))
+    }
+}
+
+/// VersionCheckingConverter returns objects as they are for a known set of
+/// versions that share their schema. Other versions are missing, so the
+/// managers recorded at them are dropped as obsolete.
+#[derive(Debug, Clone)]
+pub struct VersionCheckingConverter {
+    versions: HashSet<APIVersion>,
+}
+
+impl VersionCheckingConverter {
+    /// Returns a converter to the given versions only.
+    pub fn new<V: Into<APIVersion>>(versions: impl IntoIterator<Item = V>) -> Self {
+        VersionCheckingConverter {
+            versions: versions.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    fn check(&self, version: &APIVersion) -> Result<(), ConversionError> {
+        match self.versions.contains(version) {
+            true => Ok(()),
+            false => Err(ConversionError::missing_version(format!("unknown version: {}", version))),
+        }
+    }
+}
+
+impl Converter for VersionCheckingConverter {
+    fn convert(&self, obj: &TypedValue, version: &APIVersion) -> Result<TypedValue, ConversionError> {
+        self.check(version).map(|_| obj.clone())
+    }
+
+    fn convert_set(&self, set: &Set, _from: &APIVersion, to: &APIVersion) -> Option<Result<Set, ConversionError>> {
+        Some(self.check(to).map(|_| set.clone()))
+    }
+}
+
+/// FnConverter converts objects with a closure.
+pub struct FnConverter<F> {
+    f: F,
+}
+
+impl<F> FnConverter<F>
+where
+    F: Fn(&TypedValue, &APIVersion) -> Result<TypedValue, ConversionError> + Send + Sync,
+{
+    /// Returns a converter calling `f` with the object and target version.
+    pub fn new(f: F) -> Self {
+        FnConverter { f }
+    }
+}
+
+impl<F> Converter for FnConverter<F>
+where
+    F: Fn(&TypedValue, &APIVersion) -> Result<TypedValue, ConversionError> + Send + Sync,
+{
+    fn convert(&self, obj: &TypedValue, version: &APIVersion) -> Result<TypedValue, ConversionError> {
+        (self.f)(obj, version)
+    }
+}
+
+/// ChainConverter converts through a hub version: `to_hub` converts objects
+/// of any version to the hub, and `from_hub` converts hub objects to any
+/// version. Objects already at the hub are still passed to `to_hub`.
+pub struct ChainConverter<A, B> {
+    hub: APIVersion,
+    to_hub: A,
+    from_hub: B,
+}
+
+impl<A: Converter, B: Converter> ChainConverter<A, B> {
+    /// Returns a converter through `hub`.
+    pub fn new(hub: impl Into<APIVersion>, to_hub: A, from_hub: B) -> Self {
+        ChainConverter {
+            hub: hub.into(),
+            to_hub,
+            from_hub,
+        }
+    }
+}
+
+impl<A: Converter, B: Converter> Converter for ChainConverter<A, B> {
+    fn convert(&self, obj: &TypedValue, version: &APIVersion) -> Result<TypedValue, ConversionError> {
+        let hub_obj = self.to_hub.convert(obj, &self.hub)?;
+        if *version == self.hub {
+            return Ok(hub_obj);
+        }
+        self.from_hub.convert(&hub_obj, version)
+    }
+
+    /// Converts sets through the hub when both converters convert sets.
+    fn convert_set(&self, set: &Set, from: &APIVersion, to: &APIVersion) -> Option<Result<Set, ConversionError>> {
+        let hub_set = if *from == self.hub {
+            set.clone()
+        } else {
+            match self.to_hub.convert_set(set, from, &self.hub)? {
+                Ok(hub_set) => hub_set,
+                Err(err) => return Some(Err(err)),
+            }
+        };
+        if *to == self.hub {
+            return Some(Ok(hub_set));
+        }
+        self.from_hub.convert_set(&hub_set, &self.hub, to)
+    }
+}
+
+type VersionOf = dyn Fn(&TypedValue) -> Option<APIVersion> + Send + Sync;
+
+/// ConverterRegistry converts between versions with converters registered
+/// for pairs of versions. Conversions without a converter of their own go
+/// through the fewest registered steps, e.g. v1 to v3 through v2.
+///
+/// Registered converters are only ever asked to convert objects of their
+/// `from` version to their `to` version. The version of an object is read
+/// from its top-level `apiVersion` field unless set with `with_version_of`.
+pub struct ConverterRegistry {
+    steps: HashMap<(APIVersion, APIVersion), Box<dyn Converter>>,
+    version_of: Box<VersionOf>,
+}
+
+impl Default for ConverterRegistry {
+    fn default() -> Self {
+        ConverterRegistry {
+            steps: HashMap::new(),
+            version_of: Box::new(|obj| {
+                let version = obj.value().as_map()?.get("apiVersion")?.as_str()?;
+                Some(APIVersion::new(version))
+            }),
+        }
+    }
+}
+
+impl ConverterRegistry {
+    /// Returns a registry without converters.
+    pub fn new() -> Self {
+        ConverterRegistry::default()
+    }
+
+    /// Sets how the version of an object is found.
+    pub fn with_version_of(
+        mut self,
+        version_of: impl Fn(&TypedValue) -> Option<APIVersion> + Send + Sync + 'static,
+    ) -> Self {
+        self.version_of = Box::new(version_of);
+        self
+    }
+
+    /// Registers the converter of objects from one version to another.
+    pub fn register(
+        &mut self,
+        from: impl Into<APIVersion>,
+        to: impl Into<APIVersion>,
+        converter: impl Converter + 'static,
+    ) -> &mut Self {
+        self.steps.insert((from.into(), to.into()), Box::new(converter));
+        self
+    }
+
+    /// Returns the versions a conversion goes through, starting with `from`
+    /// and ending with `to`, or None if the registered steps don't lead
+    /// there.
+    fn route(&self, from: &APIVersion, to: &APIVersion) -> Option<Vec<APIVersion>> {
+        let mut previous: HashMap<&APIVersion, &APIVersion> = HashMap::new();
+        let mut queue = VecDeque::from([from]);
+        while let Some(version) = queue.pop_front() {
+            if version == to {
+                let mut route = vec![to.clone()];
+                let mut current = to;
+                while let Some(prev) = previous.get(current) {
+                    route.push((*prev).clone());
+                    current = prev;
+                }
+                route.reverse();
+                return Some(route);
+            }
+            // Sorted so that equally short routes are picked the same way
+            let mut next: Vec<&APIVersion> = self
+                .steps
+                .keys()
+                .filter(|(step_from, _)| step_from == version)
+                .map(|(_, step_to)| step_to)
+                .collect();
+            next.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+            for step_to in next {
+                if step_to != from && !previous.contains_key(step_to) {
+                    previous.insert(step_to, version);
+                    queue.push_back(step_to);
+                }
+            }
+        }
+        None
+    }
+
+    fn missing_route(from: &APIVersion, to: &APIVersion) -> ConversionError {
+        ConversionError::missing_version(format!("no conversion from {} to {}", from, to))
+    }
+}
+
+impl Converter for ConverterRegistry {
+    fn convert(&self, obj: &TypedValue, version: &APIVersion) -> Result<TypedValue, ConversionError> {
+        let from =
+            (self.version_of)(obj).ok_or_else(|| ConversionError::internal("the version of the object is unknown"))?;
+        let route = self
+            .route(&from, version)
+            .ok_or_else(|| Self::missing_route(&from, version))?;
+        let mut obj = obj.clone();
+        for step in route.windows(2) {
+            if let Some(converter) = self.steps.get(&(step[0].clone(), step[1].clone())) {
+                obj = converter.convert(&obj, &step[1])?;
+            }
+        }
+        Ok(obj)
+    }
+
+    /// Converts sets along the same route as objects, when the converters
+    /// of all its steps convert sets.
+    fn convert_set(&self, set: &Set, from: &APIVersion, to: &APIVersion) -> Option<Result<Set, ConversionError>> {
+        let Some(route) = self.route(from, to) else {
+            return Some(Err(Self::missing_route(from, to)));
+        };
+        let mut set = set.clone();
+        for step in route.windows(2) {
+            let converter = self.steps.get(&(step[0].clone(), step[1].clone()))?;
+            set = match converter.convert_set(&set, &step[0], &step[1])? {
+                Ok(set) => set,
+                Err(err) => return Some(Err(err)),
+            };
+        }
+        Some(Ok(set))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::typed::{ParseableType, Parser};
+    use crate::value::Value;
+
+    /// Returns a converter that renames field `from` to `to` and sets the
+    /// apiVersion.
+    fn rename(pt: &ParseableType, from: &'static str, to: &'static str) -> impl Converter {
+        let pt = pt.clone();
+        FnConverter::new(move |obj: &TypedValue, version: &APIVersion| {
+            let mut m = obj.value().as_map().cloned().unwrap_or_default();
+            if let Some(v) = m.delete(from) {
+                m.set(to.to_string(), v);
+            }
+            m.set("apiVersion".to_string(), Value::String(version.to_string()));
+            pt.from_value(Value::Map(m))
+                .map_err(|e| ConversionError::incompatible(e.to_string()))
+        })
+    }
+
+    #[test]
+    fn test_converter_registry() {
+        let pt = Parser::new("types:\n- name: root\n  map:\n    elementType:\n      scalar: string\n")
+            .unwrap()
+            .type_by_name("root");
+        let mut registry = ConverterRegistry::new();
+        registry
+            .register("v1", "v2", rename(&pt, "a", "b"))
+            .register("v2", "v3", rename(&pt, "b", "c"))
+            .register("v3", "v2", rename(&pt, "c", "b"));
+
+        // v1 to v3 goes through v2
+        let obj = pt.from_yaml(r#"{"apiVersion": "v1", "a": "x"}"#).unwrap();
+        let converted = registry.convert(&obj, &APIVersion::new("v3")).unwrap();
+        assert_eq!(
+            converted.value(),
+            pt.from_yaml(r#"{"apiVersion": "v3", "c": "x"}"#).unwrap().value()
+        );
+
+        // There is no way back to v1
+        let err = registry.convert(&converted, &APIVersion::new("v1")).unwrap_err();
+        assert!(err.is_missing_version());
+        assert_eq!(err.message, "no conversion from v3 to v1");
+
+        // Through a hub, objects of any version reach any other
+        let chain = ChainConverter::new("v2", registry, IdentityConverter::new());
+        let converted = chain.convert(&obj, &APIVersion::new("v2")).unwrap();
+        assert_eq!(
+            converted.value().as_map().unwrap().get("b"),
+            Some(&Value::String("x".to_string()))
+        );
+
+        let identity = IdentityConverter::new();
+        assert_eq!(identity.convert(&obj, &APIVersion::new("v9")).unwrap().value(), obj.value());
+
+        let checking = VersionCheckingConverter::new(["v1"]);
+        assert!(checking.convert(&obj, &APIVersion::new("v1")).is_ok());
+        let err = checking.convert(&obj, &APIVersion::new("v2")).unwrap_err();
+        assert!(err.is_missing_version());
+    }
+}
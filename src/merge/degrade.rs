@@ -0,0 +1,125 @@
+//! Applying to live objects that no longer match their schema.
+//!
+//! Objects stored before their schema was tightened, such as a CRD gaining
+//! a type for a field its clients wrote freely, fail validation. With
+//! InvalidLiveObjects::Degrade, the fields holding invalid values are taken
+//! out of the live object before an apply and put back afterwards, as
+//! atomic untyped values the apply only replaces if its config sets them.
+
+use crate::fieldpath::{Path, PathElement};
+use crate::typed::{as_typed_unvalidated, TypedValue, ValidationError, ValidationErrors};
+use crate::value::{Map, Value};
+
+/// DegradedField is a field of the live object kept out of an apply.
+pub(super) struct DegradedField {
+    pub(super) path: Path,
+    pub(super) value: Value,
+    pub(super) error: String,
+}
+
+/// Returns the path of the field holding the value an error is about: the
+/// closest field at or above it, so that invalid list items degrade their
+/// whole list. Returns None for errors about the root or without a path.
+fn degraded_path(error: &ValidationError) -> Option<Path> {
+    let path = match error {
+        ValidationError::TypeMismatch { path, .. }
+        | ValidationError::MissingField { path, .. }
+        | ValidationError::InvalidValue { path, .. }
+        | ValidationError::TooLarge { path, .. } => Path::parse(path).ok()?,
+        ValidationError::UnknownField { path, field } => {
+            Path::parse(path).ok()?.with(PathElement::field_name(field.clone()))
+        }
+        ValidationError::DuplicateKey { .. } | ValidationError::SchemaError { .. } => return None,
+    };
+    let field = path.as_slice().iter().rposition(|pe| pe.as_field_name().is_some())?;
+    Some(Path::from_elements(path.as_slice()[..=field].to_vec()))
+}
+
+/// Returns the map at the index path `path` in `value`.
+fn map_at<'v>(value: &'v mut Value, path: &[PathElement]) -> Option<&'v mut Map> {
+    let mut value = value;
+    for pe in path {
+        value = match (value, pe) {
+            (Value::Map(map), PathElement::FieldName(name)) => map.fields.get_mut(name)?,
+            (Value::List(items), PathElement::Index(i)) => items.get_mut(usize::try_from(*i).ok()?)?,
+            _ => return None,
+        };
+    }
+    match value {
+        Value::Map(map) => Some(map),
+        _ => None,
+    }
+}
+
+/// Takes the fields the errors are about out of the live object. Fails
+/// with the errors if one can't be, such as an error about the root.
+pub(super) fn degrade(
+    live: &TypedValue,
+    errors: ValidationErrors,
+) -> Result<(TypedValue, Vec<DegradedField>), ValidationErrors> {
+    let Some(mut paths) = errors
+        .iter()
+        .map(|error| Some((degraded_path(error)?, error.to_string())))
+        .collect::<Option<Vec<_>>>()
+    else {
+        return Err(errors);
+    };
+    // Fields within degraded fields go with them
+    paths.sort_by(|a, b| a.0.as_slice().cmp(b.0.as_slice()));
+    let mut kept: Vec<(Path, String)> = Vec::new();
+    for (path, error) in paths {
+        if !kept
+            .iter()
+            .any(|(parent, _)| path.as_slice().starts_with(parent.as_slice()))
+        {
+            kept.push((path, error));
+        }
+    }
+
+    let mut value = live.value().clone();
+    let mut degraded = Vec::new();
+    for (path, error) in kept {
+        let taken = live.index_path(&path).and_then(|index_path| {
+            let (last, parent) = index_path.as_slice().split_last()?;
+            map_at(&mut value, parent)?.fields.remove(last.as_field_name()?)
+        });
+        match taken {
+            Some(taken) => degraded.push(DegradedField {
+                path,
+                value: taken,
+                error,
+            }),
+            None => return Err(errors),
+        }
+    }
+    Ok((
+        as_typed_unvalidated(value, live.schema(), live.type_ref().clone()),
+        degraded,
+    ))
+}
+
+/// Puts the degraded fields back into the result of an apply, except those
+/// the apply set or whose parent it removed.
+pub(super) fn restore(result: TypedValue, degraded: &[DegradedField]) -> TypedValue {
+    if degraded.is_empty() {
+        return result;
+    }
+    let mut value = result.value().clone();
+    for field in degraded {
+        let Some((last, parent)) = field.path.as_slice().split_last() else {
+            continue;
+        };
+        let Some(name) = last.as_field_name() else {
+            continue;
+        };
+        let Some(parent) = result.index_path(&Path::from_elements(parent.to_vec())) else {
+            continue;
+        };
+        if let Some(map) = map_at(&mut value, parent.as_slice()) {
+            map.fields
+                .entry(name.to_string())
+                .or_insert_with(|| field.value.clone());
+        }
+    }
+    as_typed_unvalidated(value, result.schema(), result.type_ref().clone())
+}
@@ -0,0 +1,70 @@
+//! Helpers deciding whether a write changes an object's generation.
+
+use crate::fieldpath::{Path, PathElement, Set};
+use crate::typed::Comparison;
+
+/// Returns the fields that don't count as changes to the desired state:
+/// `reset_fields`, usually the status, and all of `.metadata`.
+pub fn generation_ignored_fields(reset_fields: &Set) -> Set {
+    let mut fields = reset_fields.clone();
+    fields.insert(&Path::from_elements(vec![PathElement::field_name("metadata")]));
+    fields
+}
+
+/// Returns the changed fields of the comparison, added, removed or
+/// modified, that are outside of generation_ignored_fields.
+pub fn spec_changes(comparison: &Comparison, reset_fields: &Set) -> Set {
+    let changes = comparison.exclude(&generation_ignored_fields(reset_fields));
+    changes.added.union(&changes.removed).union(&changes.modified)
+}
+
+/// Returns true if anything outside `reset_fields` and the metadata
+/// changed, which is when the API server bumps `metadata.generation` and
+/// controllers must reconcile again before updating `observedGeneration`.
+pub fn spec_changed(comparison: &Comparison, reset_fields: &Set) -> bool {
+    !comparison.exclude(&generation_ignored_fields(reset_fields)).is_same()
+}
+
+/// Returns the generation after a write: `current + 1` if the spec
+/// changed, otherwise `current`.
+pub fn next_generation(current: i64, comparison: &Comparison, reset_fields: &Set) -> i64 {
+    if spec_changed(comparison, reset_fields) {
+        current + 1
+    } else {
+        current
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merge::kubernetes_status_fields;
+    use crate::typed::deduced_parseable_type;
+
+    #[test]
+    fn test_spec_changed() {
+        let pt = deduced_parseable_type();
+        let live = pt
+            .from_yaml(r#"{"metadata": {"labels": {"a": "1"}}, "spec": {"replicas": 1}, "status": {"ready": 0}}"#)
+            .unwrap();
+        let compare = |yaml: &str| live.compare(&pt.from_yaml(yaml).unwrap()).unwrap();
+        let status = kubernetes_status_fields();
+
+        let status_only = compare(r#"{"metadata": {"labels": {"a": "2"}}, "spec": {"replicas": 1}, "status": {"ready": 1}}"#);
+        assert!(!status_only.is_same());
+        assert!(!spec_changed(&status_only, &status));
+        assert!(spec_changes(&status_only, &status).is_empty());
+        assert_eq!(next_generation(3, &status_only, &status), 3);
+
+        let scaled = compare(r#"{"metadata": {}, "spec": {"replicas": 2, "paused": true}}"#);
+        assert!(spec_changed(&scaled, &status));
+        let mut changes = Vec::new();
+        spec_changes(&scaled, &status).iterate(|path| changes.push(path.to_string()));
+        changes.sort();
+        assert_eq!(changes, [".spec.paused", ".spec.replicas"]);
+        assert_eq!(next_generation(3, &scaled, &status), 4);
+
+        // Without reset fields, status writes count as well
+        assert!(spec_changed(&status_only, &Set::new()));
+    }
+}
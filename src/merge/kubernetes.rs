@@ -0,0 +1,97 @@
+//! Updater presets for Kubernetes objects.
+
+use super::UpdaterBuilder;
+use crate::fieldpath::{Path, PathElement, Set};
+
+/// Fields of object metadata the API server sets and keeps out of managed
+/// fields, since no client should own them.
+pub const KUBERNETES_SERVER_METADATA: [&str; 7] = [
+    "creationTimestamp",
+    "generation",
+    "managedFields",
+    "resourceVersion",
+    "selfLink",
+    "uid",
+    "clusterName",
+];
+
+fn field_path(names: &[&str]) -> Path {
+    Path::from_elements(names.iter().map(|name| PathElement::field_name(*name)).collect())
+}
+
+/// Returns the fields the API server leaves out of managed fields: the
+/// type and identity of the object and the metadata it sets itself.
+pub fn kubernetes_ignored_fields() -> Set {
+    let mut set = Set::new();
+    for path in [
+        field_path(&["apiVersion"]),
+        field_path(&["kind"]),
+        field_path(&["metadata", "name"]),
+        field_path(&["metadata", "namespace"]),
+    ] {
+        set.insert(&path);
+    }
+    for name in KUBERNETES_SERVER_METADATA {
+        set.insert(&field_path(&["metadata", name]));
+    }
+    set
+}
+
+/// Returns the fields that writes to the main resource reset, because
+/// they are written through the status subresource.
+pub fn kubernetes_status_fields() -> Set {
+    let mut set = Set::new();
+    set.insert(&field_path(&["status"]));
+    set
+}
+
+impl UpdaterBuilder {
+    /// Ignores the fields of kubernetes_ignored_fields and
+    /// kubernetes_status_fields at every version, as the API server does
+    /// for writes to the main resource. Updaters for the status subresource
+    /// should only ignore kubernetes_ignored_fields.
+    pub fn kubernetes_defaults(self) -> Self {
+        self.ignored_fields_all_versions(kubernetes_ignored_fields().union(&kubernetes_status_fields()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fieldpath::{APIVersion, ManagedFields};
+    use crate::merge::Updater;
+    use crate::typed::deduced_parseable_type;
+
+    #[test]
+    fn test_kubernetes_defaults() {
+        let pt = deduced_parseable_type();
+        let live = pt.from_yaml("{}").unwrap();
+        let config = pt
+            .from_yaml(
+                r#"{
+                    "apiVersion": "apps/v1",
+                    "kind": "Deployment",
+                    "metadata": {"name": "web", "resourceVersion": "7", "labels": {"app": "web"}},
+                    "spec": {"replicas": 2},
+                    "status": {"readyReplicas": 2}
+                }"#,
+            )
+            .unwrap();
+        let version = APIVersion::new("apps/v1");
+        let mut managers = ManagedFields::new();
+        let updater = Updater::builder()
+            .kubernetes_defaults()
+            .ignored_fields(version.clone(), {
+                let mut set = Set::new();
+                set.insert(&field_path(&["spec", "replicas"]));
+                set
+            })
+            .build();
+        updater.apply(&live, &config, &version, &mut managers, "m", false).unwrap();
+
+        let mut owned = Vec::new();
+        managers.get("m").unwrap().set().iterate(|path| owned.push(path.to_string()));
+        owned.sort();
+        assert_eq!(owned, [".metadata", ".metadata.labels", ".metadata.labels.app", ".spec"]);
+    }
+}
@@ -6,7 +6,7 @@
 mod tests {
     use crate::fieldpath::{ManagedFields, Path, PathElement, Set};
     use crate::merge::{Updater, ApplyError};
-    use crate::schema::{Atom, ElementRelationship, List, Map as SchemaMap, Schema, Scalar, TypeDef, TypeRef, StructField};
+    use crate::schema::{NumericKeys, Schema, SchemaBuilder, Scalar, TypeRef};
     use crate::typed::{TypedValue, deduced_parseable_type};
     use crate::value::{Field, FieldList, Map, Value};
 
@@ -35,113 +35,25 @@ mod tests {
 
     fn create_leaf_fields_schema() -> Schema {
         // Schema with numeric, string, and bool fields
-        Schema::with_types(vec![
-            TypeDef {
-                name: "leafFields".to_string(),
-                atom: Atom {
-                    map: Some(SchemaMap::with_fields(vec![
-                        StructField {
-                            name: "numeric".to_string(),
-                            field_type: TypeRef {
-                                inlined: Box::new(Atom {
-                                    scalar: Some(Scalar::Numeric),
-                                    ..Default::default()
-                                }),
-                                ..Default::default()
-                            },
-                            default: None,
-                        },
-                        StructField {
-                            name: "string".to_string(),
-                            field_type: TypeRef {
-                                inlined: Box::new(Atom {
-                                    scalar: Some(Scalar::String),
-                                    ..Default::default()
-                                }),
-                                ..Default::default()
-                            },
-                            default: None,
-                        },
-                        StructField {
-                            name: "bool".to_string(),
-                            field_type: TypeRef {
-                                inlined: Box::new(Atom {
-                                    scalar: Some(Scalar::Boolean),
-                                    ..Default::default()
-                                }),
-                                ..Default::default()
-                            },
-                            default: None,
-                        },
-                    ])),
-                    ..Default::default()
-                },
-            },
-        ])
+        SchemaBuilder::new()
+            .map_type("leafFields", |m| {
+                m.field("numeric", TypeRef::scalar(Scalar::Numeric))
+                    .field("string", TypeRef::scalar(Scalar::String))
+                    .field("bool", TypeRef::scalar(Scalar::Boolean))
+            })
+            .build()
     }
 
     fn create_set_fields_schema() -> Schema {
         // Schema with set fields (associative lists)
-        Schema::with_types(vec![
-            TypeDef {
-                name: "setFields".to_string(),
-                atom: Atom {
-                    map: Some(SchemaMap::with_fields(vec![
-                        StructField {
-                            name: "setStr".to_string(),
-                            field_type: TypeRef {
-                                named_type: Some("setOfStrings".to_string()),
-                                ..Default::default()
-                            },
-                            default: None,
-                        },
-                        StructField {
-                            name: "setNum".to_string(),
-                            field_type: TypeRef {
-                                named_type: Some("setOfNumerics".to_string()),
-                                ..Default::default()
-                            },
-                            default: None,
-                        },
-                    ])),
-                    ..Default::default()
-                },
-            },
-            TypeDef {
-                name: "setOfStrings".to_string(),
-                atom: Atom {
-                    list: Some(List {
-                        element_type: TypeRef {
-                            inlined: Box::new(Atom {
-                                scalar: Some(Scalar::String),
-                                ..Default::default()
-                            }),
-                            ..Default::default()
-                        },
-                        element_relationship: ElementRelationship::Associative,
-                        keys: vec![],
-                    }),
-                    ..Default::default()
-                },
-            },
-            TypeDef {
-                name: "setOfNumerics".to_string(),
-                atom: Atom {
-                    list: Some(List {
-                        element_type: TypeRef {
-                            inlined: Box::new(Atom {
-                                scalar: Some(Scalar::Numeric),
-                                ..Default::default()
-                            }),
-                            ..Default::default()
-                        },
-                        element_relationship: ElementRelationship::Associative,
-                        keys: vec![],
-                    }),
-                    ..Default::default()
-                },
-            },
-        ])
+        SchemaBuilder::new()
+            .map_type("setFields", |m| {
+                m.field("setStr", TypeRef::named("setOfStrings"))
+                    .field("setNum", TypeRef::named("setOfNumerics"))
+            })
+            .list_type("setOfStrings", TypeRef::scalar(Scalar::String), |l| l.set())
+            .list_type("setOfNumerics", TypeRef::scalar(Scalar::Numeric), |l| l.set())
+            .build()
     }
 
     fn create_typed_value(schema: &Schema, type_name: &str, value: Value) -> TypedValue {
@@ -4451,6 +4363,44 @@ mod tests {
         ])));
     }
 
+    #[test]
+    fn test_default_keys_nested_integral_numeric_keys() {
+        // Like apply_integer_key_with_float_default, but with numbers in keys
+        // canonicalized, so the defaulted and the written page are one key
+        let parser = book_parser();
+        let pt = parser.type_by_name("v1");
+        let updater = Updater::builder().numeric_keys(NumericKeys::Integral).build();
+        let version = crate::fieldpath::APIVersion::new("v1");
+        let mut managers = ManagedFields::new();
+
+        let empty = pt.from_yaml("{}").unwrap();
+        let obj1 = pt.from_yaml(r#"
+            book:
+            - sentences:
+              - text: blah
+        "#).unwrap();
+        let live1 = updater.apply(&empty, &obj1, &version, &mut managers, "default", false).unwrap();
+
+        let book_key = key_by_fields(vec![("chapter", Value::Int(1)), ("section", Value::String("A".into()))]);
+        let float_key = key_by_fields(vec![("line", Value::Int(3)), ("page", Value::Float(2.0))]);
+        let sentence_key = key_by_fields(vec![("line", Value::Int(3)), ("page", Value::Int(2))]);
+        assert_eq!(float_key.with_canonical_numbers(), sentence_key);
+        let sentence = path(vec![field("book"), book_key.clone(), field("sentences"), sentence_key.clone()]);
+        assert!(managers.get("default").unwrap().set().has(&sentence.with(field("text"))));
+
+        let obj2 = pt.from_yaml(r#"
+            book:
+            - sentences:
+              - text: blah
+                page: 2
+        "#).unwrap();
+        updater.apply(&live1, &obj2, &version, &mut managers, "default", false).unwrap();
+        let set = managers.get("default").unwrap().set();
+        assert!(set.has(&sentence.with(field("text"))));
+        assert!(set.has(&sentence.with(field("page"))));
+        assert!(!set.has(&path(vec![field("book"), book_key, field("sentences"), float_key])));
+    }
+
     // =========================================================================
     // Ignore filter tests from merge/ignore_test.go
     // =========================================================================
@@ -5489,38 +5439,31 @@ list:
 
     /// A converter that only accepts specific versions.
     struct SpecificVersionConverter {
-        accepted_versions: std::cell::RefCell<Vec<String>>,
+        accepted_versions: std::sync::Mutex<Vec<String>>,
     }
 
     impl SpecificVersionConverter {
         fn new(versions: Vec<&str>) -> Self {
             SpecificVersionConverter {
-                accepted_versions: std::cell::RefCell::new(versions.iter().map(|s| s.to_string()).collect()),
+                accepted_versions: std::sync::Mutex::new(versions.iter().map(|s| s.to_string()).collect()),
             }
         }
 
         #[allow(dead_code)]
         fn set_versions(&self, versions: Vec<&str>) {
-            *self.accepted_versions.borrow_mut() = versions.iter().map(|s| s.to_string()).collect();
+            *self.accepted_versions.lock().unwrap() = versions.iter().map(|s| s.to_string()).collect();
         }
     }
 
     impl Converter for SpecificVersionConverter {
         fn convert(&self, obj: &TypedValue, version: &crate::fieldpath::APIVersion) -> Result<TypedValue, ConversionError> {
-            let versions = self.accepted_versions.borrow();
+            let versions = self.accepted_versions.lock().unwrap();
             for v in versions.iter() {
                 if v == version.as_str() {
                     return Ok(obj.clone());
                 }
             }
-            Err(ConversionError {
-                message: format!("Unknown version: {}", version),
-                is_missing_version: true,
-            })
-        }
-
-        fn is_missing_version_error(&self, err: &ConversionError) -> bool {
-            err.is_missing_version
+            Err(ConversionError::missing_version(format!("Unknown version: {}", version)))
         }
     }
 
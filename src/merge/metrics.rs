@@ -0,0 +1,177 @@
+//! Field ownership metrics in the OpenMetrics text format.
+
+use super::Conflicts;
+use crate::fieldpath::{ManagedFields, ManagerIdentifier};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Name of the gauge counting the fields owned per manager and version.
+pub const OWNED_FIELDS_METRIC: &str = "smd_owned_fields";
+
+/// Name of the counter of conflicts per manager.
+pub const CONFLICTS_METRIC: &str = "smd_conflicts";
+
+/// MetricSample is a single labelled sample.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MetricSample {
+    /// The sample name, including a `_total` suffix for counters.
+    pub name: String,
+    pub labels: Vec<(String, String)>,
+    pub value: u64,
+}
+
+/// MetricsSink receives samples, e.g. to forward them to a metrics library.
+pub trait MetricsSink {
+    fn record(&mut self, sample: &MetricSample);
+}
+
+/// OwnershipMetrics aggregates field ownership and conflicts across objects.
+///
+/// Managers are labelled by name when their keys are encoded
+/// ManagerIdentifiers, and by the raw key otherwise.
+#[derive(Debug, Clone, Default)]
+pub struct OwnershipMetrics {
+    owned: BTreeMap<(String, String), u64>,
+    conflicts: BTreeMap<String, u64>,
+}
+
+impl OwnershipMetrics {
+    /// Creates empty metrics.
+    pub fn new() -> Self {
+        OwnershipMetrics::default()
+    }
+
+    /// Adds the fields owned in an object's managed fields.
+    pub fn observe_managed_fields(&mut self, managers: &ManagedFields) {
+        for (manager, vs) in managers.iter() {
+            let mut count = 0;
+            vs.set().iterate(|_| count += 1);
+            let key = (manager_label(manager), vs.api_version().to_string());
+            *self.owned.entry(key).or_default() += count;
+        }
+    }
+
+    /// Counts the conflicts of a failed apply against their managers.
+    pub fn observe_conflicts(&mut self, conflicts: &Conflicts) {
+        for conflict in conflicts.iter() {
+            *self.conflicts.entry(manager_label(&conflict.manager)).or_default() += 1;
+        }
+    }
+
+    /// Returns the samples, sorted by metric and labels.
+    pub fn samples(&self) -> Vec<MetricSample> {
+        let owned = self.owned.iter().map(|((manager, version), n)| MetricSample {
+            name: OWNED_FIELDS_METRIC.to_string(),
+            labels: vec![
+                ("manager".to_string(), manager.clone()),
+                ("group_version".to_string(), version.clone()),
+            ],
+            value: *n,
+        });
+        let conflicts = self.conflicts.iter().map(|(manager, n)| MetricSample {
+            name: format!("{}_total", CONFLICTS_METRIC),
+            labels: vec![("manager".to_string(), manager.clone())],
+            value: *n,
+        });
+        owned.chain(conflicts).collect()
+    }
+
+    /// Passes every sample to the sink.
+    pub fn export(&self, sink: &mut dyn MetricsSink) {
+        for sample in self.samples() {
+            sink.record(&sample);
+        }
+    }
+
+    /// Writes the metrics in the OpenMetrics text format.
+    pub fn write_openmetrics(&self, w: &mut impl fmt::Write) -> fmt::Result {
+        writeln!(w, "# TYPE {} gauge", OWNED_FIELDS_METRIC)?;
+        writeln!(w, "# HELP {} Number of fields owned by a manager.", OWNED_FIELDS_METRIC)?;
+        let samples = self.samples();
+        for sample in samples.iter().filter(|s| s.name == OWNED_FIELDS_METRIC) {
+            write_sample(w, sample)?;
+        }
+        writeln!(w, "# TYPE {} counter", CONFLICTS_METRIC)?;
+        writeln!(w, "# HELP {} Number of conflicts with a manager.", CONFLICTS_METRIC)?;
+        for sample in samples.iter().filter(|s| s.name != OWNED_FIELDS_METRIC) {
+            write_sample(w, sample)?;
+        }
+        writeln!(w, "# EOF")
+    }
+}
+
+impl fmt::Display for OwnershipMetrics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write_openmetrics(f)
+    }
+}
+
+fn manager_label(manager: &str) -> String {
+    match ManagerIdentifier::decode(manager) {
+        Ok(id) => id.manager,
+        Err(_) => manager.to_string(),
+    }
+}
+
+fn write_sample(w: &mut impl fmt::Write, sample: &MetricSample) -> fmt::Result {
+    write!(w, "{}{{", sample.name)?;
+    for (i, (name, value)) in sample.labels.iter().enumerate() {
+        if i > 0 {
+            write!(w, ",")?;
+        }
+        let escaped = value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n");
+        write!(w, "{}=\"{}\"", name, escaped)?;
+    }
+    writeln!(w, "}} {}", sample.value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fieldpath::{APIVersion, Path, PathElement, Set, VersionedSet};
+    use crate::merge::Conflict;
+
+    #[test]
+    fn test_ownership_metrics() {
+        let path = |name: &str| Path::from_elements(vec![PathElement::field_name(name)]);
+        let mut set = Set::new();
+        set.insert(&path("a"));
+        set.insert(&path("b"));
+
+        let mut managers = ManagedFields::new();
+        managers.insert(
+            ManagerIdentifier::apply("kubectl").encode(),
+            VersionedSet::new(set.clone(), APIVersion::new("apps/v1"), true),
+        );
+        managers.insert("raw\"key", VersionedSet::new(set, APIVersion::new("v1"), false));
+
+        let mut metrics = OwnershipMetrics::new();
+        metrics.observe_managed_fields(&managers);
+        metrics.observe_managed_fields(&managers);
+        let mut conflicts = Conflicts::new();
+        conflicts.add(Conflict::new("raw\"key", path("a")));
+        metrics.observe_conflicts(&conflicts);
+
+        assert_eq!(
+            metrics.to_string(),
+            "# TYPE smd_owned_fields gauge\n\
+             # HELP smd_owned_fields Number of fields owned by a manager.\n\
+             smd_owned_fields{manager=\"kubectl\",group_version=\"apps/v1\"} 4\n\
+             smd_owned_fields{manager=\"raw\\\"key\",group_version=\"v1\"} 4\n\
+             # TYPE smd_conflicts counter\n\
+             # HELP smd_conflicts Number of conflicts with a manager.\n\
+             smd_conflicts_total{manager=\"raw\\\"key\"} 1\n\
+             # EOF\n"
+        );
+
+        struct Collect(Vec<MetricSample>);
+        impl MetricsSink for Collect {
+            fn record(&mut self, sample: &MetricSample) {
+                self.0.push(sample.clone());
+            }
+        }
+        let mut sink = Collect(Vec::new());
+        metrics.export(&mut sink);
+        assert_eq!(sink.0, metrics.samples());
+    }
+}
@@ -2,11 +2,42 @@
 //!
 //! This module provides tracking of field ownership across multiple managers.
 
+mod adopt;
+mod apply_plan;
+mod apply_object;
+mod async_apply;
+mod authorize;
 mod updater;
 mod conflict;
+mod consistency;
+mod context;
+mod converters;
+mod degrade;
+mod generation;
+mod kubernetes;
+mod metrics;
+mod patch_plan;
+mod prune;
+mod rollback;
+mod warnings;
 
 #[cfg(test)]
 mod merge_test;
 
+pub use adopt::*;
+pub use apply_plan::*;
+pub use apply_object::*;
+pub use async_apply::*;
+pub use authorize::*;
 pub use updater::*;
 pub use conflict::*;
+pub use consistency::*;
+pub use context::*;
+pub use converters::*;
+pub use generation::*;
+pub use kubernetes::*;
+pub use metrics::*;
+pub use patch_plan::*;
+pub use prune::*;
+pub use rollback::*;
+pub use warnings::*;
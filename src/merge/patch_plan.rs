@@ -0,0 +1,108 @@
+//! Patch plans describing the changes an apply made.
+
+use crate::fieldpath::{Path, Set};
+use crate::typed::{Comparison, TypedValue};
+use crate::value::Value;
+use std::fmt;
+
+/// PatchOperationKind is the kind of change made to a field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatchOperationKind {
+    Add,
+    Remove,
+    Replace,
+}
+
+impl fmt::Display for PatchOperationKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PatchOperationKind::Add => write!(f, "add"),
+            PatchOperationKind::Remove => write!(f, "remove"),
+            PatchOperationKind::Replace => write!(f, "replace"),
+        }
+    }
+}
+
+/// PatchOperation is a change made to a single field.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PatchOperation {
+    pub kind: PatchOperationKind,
+    pub path: Path,
+    /// The new value of added and replaced fields.
+    pub value: Option<Value>,
+}
+
+impl fmt::Display for PatchOperation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.kind, self.path)?;
+        if let Some(value) = self.value.as_ref().and_then(|v| crate::value::to_json(v).ok()) {
+            write!(f, " {}", value)?;
+        }
+        Ok(())
+    }
+}
+
+/// PatchPlan lists the field changes an apply made to the live object,
+/// removals first, then replacements, then additions.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PatchPlan {
+    /// Fields that were added.
+    pub added: Set,
+    /// Fields that were removed.
+    pub removed: Set,
+    /// Fields whose value was replaced.
+    pub replaced: Set,
+    operations: Vec<PatchOperation>,
+}
+
+impl PatchPlan {
+    /// Builds the plan from the comparison of the live object with `result`.
+    pub fn from_comparison(compare: &Comparison, result: &TypedValue) -> Self {
+        let mut operations = Vec::new();
+        let mut push = |kind, set: &Set| {
+            set.iterate(|path| {
+                let value = match kind {
+                    PatchOperationKind::Remove => None,
+                    _ => result.element_at(path).map(|tv| tv.value().clone()),
+                };
+                operations.push(PatchOperation {
+                    kind,
+                    path: path.clone(),
+                    value,
+                });
+            });
+        };
+        push(PatchOperationKind::Remove, &compare.removed);
+        push(PatchOperationKind::Replace, &compare.modified);
+        push(PatchOperationKind::Add, &compare.added);
+
+        PatchPlan {
+            added: compare.added.clone(),
+            removed: compare.removed.clone(),
+            replaced: compare.modified.clone(),
+            operations,
+        }
+    }
+
+    /// Returns true if the apply changed nothing.
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+
+    /// Returns the operations.
+    pub fn operations(&self) -> &[PatchOperation] {
+        &self.operations
+    }
+}
+
+impl fmt::Display for PatchPlan {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, op) in self.operations.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", op)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,177 @@
+//! Pruning of objects down to the fields some managers own.
+//!
+//! Drift-correction controllers reset objects to their managed state:
+//! whatever no trusted manager set, such as fields added by hand with
+//! kubectl edit, goes. prune_unowned keeps the fields the given managers
+//! own, along with what keeps the result valid: the key fields of the list
+//! items kept, and atomic values whole.
+
+use crate::fieldpath::{ManagedFields, Path, PathElement, Set};
+use crate::schema::{ElementRelationship, TypeRef};
+use crate::typed::{as_typed_unvalidated, TypedValue};
+use crate::value::{Map, Value};
+
+/// Returns `live` without the fields that none of the managers in `keep`
+/// own. Atomic lists and maps are kept whole if the managers own them or
+/// anything in them, and list items kept keep their key fields. Objects
+/// with nothing owned are pruned to an empty map.
+pub fn prune_unowned(live: &TypedValue, managers: &ManagedFields, keep: &[&str]) -> TypedValue {
+    let mut owned = Set::new();
+    for manager in keep {
+        if let Some(vs) = managers.get(manager) {
+            owned = owned.union(vs.set());
+        }
+    }
+    let value =
+        prune(live, live.value(), live.type_ref(), &Path::new(), &owned).unwrap_or_else(|| match live.value() {
+            Value::Map(_) => Value::Map(Map::new()),
+            _ => Value::Null,
+        });
+    as_typed_unvalidated(value, live.schema(), live.type_ref().clone())
+}
+
+/// Returns what is left of `value`, at `path`, once the fields outside
+/// `owned` are removed, or None if nothing is.
+fn prune(live: &TypedValue, value: &Value, type_ref: &TypeRef, path: &Path, owned: &Set) -> Option<Value> {
+    let atom = live.schema().resolve(type_ref)?;
+    match value {
+        Value::Map(fields) => {
+            let map = atom.map.as_ref()?;
+            if map.element_relationship == ElementRelationship::Atomic {
+                return owned.touches(path).then(|| value.clone());
+            }
+            let mut kept = Map::new();
+            for (key, val) in fields.iter() {
+                let field_type = match map.find_field(key) {
+                    Some(field) => &field.field_type,
+                    None => &map.element_type,
+                };
+                let field_path = path.with(PathElement::field_name(key.clone()));
+                if let Some(val) = prune(live, val, field_type, &field_path, owned) {
+                    kept.set(key.clone(), val);
+                }
+            }
+            (!kept.is_empty() || owned.has(path)).then_some(Value::Map(kept))
+        }
+        Value::List(items) => {
+            let list = live.effective_list(atom.list.as_ref()?, &[items]);
+            if list.element_relationship == ElementRelationship::Atomic {
+                return owned.touches(path).then(|| value.clone());
+            }
+            let mut kept = Vec::new();
+            for (pe, item) in live.list_item_elements(items, &list).into_iter().zip(items) {
+                let Some(mut pruned) = prune(live, item, &list.element_type, &path.with(pe), owned) else {
+                    continue;
+                };
+                if let (Value::Map(fields), Value::Map(original)) = (&mut pruned, item) {
+                    for key in &list.keys {
+                        if let (None, Some(val)) = (fields.get(key), original.get(key)) {
+                            fields.set(key.clone(), val.clone());
+                        }
+                    }
+                }
+                kept.push(pruned);
+            }
+            (!kept.is_empty() || owned.has(path)).then_some(Value::List(kept))
+        }
+        _ => owned.has(path).then(|| value.clone()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::managed_fields;
+    use crate::typed::Parser;
+
+    #[test]
+    fn test_prune_unowned() {
+        let pt = Parser::new(
+            r#"types:
+- name: root
+  map:
+    fields:
+    - name: replicas
+      type:
+        scalar: numeric
+    - name: labels
+      type:
+        map:
+          elementType:
+            scalar: string
+    - name: selector
+      type:
+        map:
+          elementType:
+            scalar: string
+          elementRelationship: atomic
+    - name: ports
+      type:
+        list:
+          elementType:
+            map:
+              fields:
+              - name: port
+                type:
+                  scalar: numeric
+              - name: name
+                type:
+                  scalar: string
+              - name: protocol
+                type:
+                  scalar: string
+          elementRelationship: associative
+          keys: [port]
+"#,
+        )
+        .unwrap()
+        .type_by_name("root");
+        let live = pt
+            .from_yaml(
+                r#"{"replicas": 3, "labels": {"app": "web", "tier": "db"}, "selector": {"a": "1", "b": "2"},
+                "ports": [{"port": 80, "name": "http", "protocol": "TCP"}, {"port": 443, "name": "https"}]}"#,
+            )
+            .unwrap();
+        let managers = managed_fields! {
+            "kubectl" => {
+                version: "v1",
+                applied: true,
+                paths: [["f:labels", "f:app"], ["f:selector", "f:a"]],
+            },
+            "ctrl" => {
+                version: "v1",
+                applied: false,
+                paths: [["f:ports", r#"k:{"port":80}"#, "f:name"]],
+            },
+            "edit" => {
+                version: "v1",
+                applied: false,
+                paths: [
+                    ["f:replicas"],
+                    ["f:labels", "f:tier"],
+                    ["f:ports", r#"k:{"port":80}"#, "f:protocol"],
+                    ["f:ports", r#"k:{"port":443}"#],
+                ],
+            },
+        };
+
+        let pruned = prune_unowned(&live, &managers, &["kubectl", "ctrl"]);
+        let expected = pt
+            .from_yaml(r#"{"labels": {"app": "web"}, "selector": {"a": "1", "b": "2"}, "ports": [{"port": 80, "name": "http"}]}"#)
+            .unwrap();
+        assert_eq!(pruned.value(), expected.value());
+        pruned.validate(&[]).unwrap();
+
+        // Items owned as a whole keep their keys only
+        let pruned = prune_unowned(&live, &managers, &["edit"]);
+        let expected = pt
+            .from_yaml(r#"{"replicas": 3, "labels": {"tier": "db"}, "ports": [{"port": 80, "protocol": "TCP"}, {"port": 443}]}"#)
+            .unwrap();
+        assert_eq!(pruned.value(), expected.value());
+
+        assert_eq!(
+            prune_unowned(&live, &managers, &["nobody"]).value(),
+            &Value::Map(Map::new())
+        );
+    }
+}
@@ -0,0 +1,112 @@
+//! Undo data for applies.
+
+use crate::fieldpath::Set;
+use crate::typed::{ParseError, ParseableType, TypedValue, ValidationErrors};
+use crate::value::Value;
+use serde::{Deserialize, Serialize};
+
+/// RollbackData is the minimal state needed to undo the changes an apply
+/// made to an object.
+#[derive(Debug, Clone)]
+pub struct RollbackData {
+    /// The manager whose apply is undone.
+    pub manager: String,
+    /// The previous values of the fields the apply modified or removed.
+    pub restore: TypedValue,
+    /// The fields the apply added.
+    pub remove: Set,
+}
+
+/// The persisted form of RollbackData.
+#[derive(Serialize, Deserialize)]
+struct PersistedRollback {
+    manager: String,
+    restore: Value,
+    remove: Set,
+}
+
+impl RollbackData {
+    /// Encodes the data as JSON, so it can be stored alongside the object.
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(&PersistedRollback {
+            manager: self.manager.clone(),
+            restore: self.restore.value().clone(),
+            remove: self.remove.clone(),
+        })
+    }
+
+    /// Decodes data encoded by to_json for objects of the given type.
+    pub fn from_json(pt: &ParseableType, json: &str) -> Result<RollbackData, ParseError> {
+        let persisted: PersistedRollback = serde_json::from_str(json)
+            .map_err(|e| ParseError::new(format!("failed to parse rollback data: {}", e)))?;
+        Ok(RollbackData {
+            manager: persisted.manager,
+            restore: pt.from_value(persisted.restore)?,
+            remove: persisted.remove,
+        })
+    }
+}
+
+/// Captures what's needed to restore the fields that `manager`'s apply
+/// changed from `before` to `after`. Fields the apply didn't touch aren't
+/// recorded, so later changes to them survive a rollback.
+pub fn rollback_data(before: &TypedValue, after: &TypedValue, manager: &str) -> Result<RollbackData, ValidationErrors> {
+    let compare = before.compare(after)?;
+    Ok(RollbackData {
+        manager: manager.to_string(),
+        restore: before.extract_items(&compare.removed.union(&compare.modified)),
+        remove: compare.added,
+    })
+}
+
+/// Undoes an apply on `live`: fields it added are removed and fields it
+/// modified or removed get their previous values back.
+pub fn rollback(live: &TypedValue, data: &RollbackData) -> Result<TypedValue, ValidationErrors> {
+    live.remove_items(&data.remove).merge(&data.restore)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::typed::Parser;
+
+    #[test]
+    fn test_rollback() {
+        let pt = Parser::new(
+            r#"types:
+- name: root
+  map:
+    fields:
+    - name: replicas
+      type:
+        scalar: numeric
+    - name: image
+      type:
+        scalar: string
+    - name: labels
+      type:
+        map:
+          elementType:
+            scalar: string
+"#,
+        )
+        .unwrap()
+        .type_by_name("root");
+
+        let before = pt.from_yaml(r#"{"replicas": 1, "labels": {"a": "x", "b": "y"}}"#).unwrap();
+        let after = pt.from_yaml(r#"{"replicas": 3, "image": "new", "labels": {"a": "x"}}"#).unwrap();
+        let data = rollback_data(&before, &after, "deployer").unwrap();
+        assert_eq!(data.manager, "deployer");
+
+        assert_eq!(rollback(&after, &data).unwrap().value(), before.value());
+
+        let restored = RollbackData::from_json(&pt, &data.to_json().unwrap()).unwrap();
+        assert_eq!(restored.remove, data.remove);
+        assert_eq!(rollback(&after, &restored).unwrap().value(), before.value());
+
+        // Untouched fields changed since the apply are kept
+        let live = pt.from_yaml(r#"{"replicas": 3, "image": "new", "labels": {"a": "z"}}"#).unwrap();
+        let expected = pt.from_yaml(r#"{"replicas": 1, "labels": {"a": "z", "b": "y"}}"#).unwrap();
+        assert_eq!(rollback(&live, &data).unwrap().value(), expected.value());
+    }
+}
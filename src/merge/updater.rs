@@ -1,24 +1,84 @@
 //! Updater for merge operations.
 
-use crate::fieldpath::{APIVersion, ManagedFields, Set, VersionedSet};
-use crate::typed::{Comparison, TypedValue, ValidationErrors};
-use super::Conflicts;
-use std::collections::HashMap;
-
-/// Converter trait for version conversion.
-pub trait Converter {
+use crate::fieldpath::{APIVersion, ManagedFields, ManagerIdentifier, Operation, Path, Set, VersionedSet};
+use crate::schema::{HeterogeneousLists, ListOrdering, NumericKeys, TypeRef};
+use crate::typed::{CompareScratch, Comparison, TypedValue, ValidationError, ValidationErrors};
+use crate::value::{Map, Value};
+use super::{AsyncConverter, Conflicts, MergeContext, PatchPlan, Warning, Warnings};
+use super::degrade::{degrade, restore};
+use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, PoisonError};
+
+/// Converter trait for version conversion. Converters are shared by the
+/// clones of an Updater, which may be used from several threads.
+pub trait Converter: Send + Sync {
     /// Converts a TypedValue to a different API version.
+    ///
+    /// Errors of kind MissingVersion mean the version is no longer served;
+    /// managers recorded at it are dropped instead of failing the operation.
     fn convert(&self, obj: &TypedValue, version: &APIVersion) -> Result<TypedValue, ConversionError>;
 
-    /// Returns true if the error indicates a missing version.
-    fn is_missing_version_error(&self, err: &ConversionError) -> bool;
+    /// Converts a field set recorded at `from` to the paths of the same
+    /// fields at `to`.
+    ///
+    /// Returns None if the converter can't convert sets, in which case
+    /// objects are converted to the manager's version and compared there.
+    fn convert_set(&self, _set: &Set, _from: &APIVersion, _to: &APIVersion) -> Option<Result<Set, ConversionError>> {
+        None
+    }
+}
+
+/// ConversionErrorKind classifies conversion errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[non_exhaustive]
+pub enum ConversionErrorKind {
+    /// The target version doesn't exist, or no longer does.
+    MissingVersion,
+    /// The object can't be represented in the target version.
+    Incompatible,
+    /// The conversion itself failed, e.g. a conversion webhook was
+    /// unreachable.
+    #[default]
+    Internal,
 }
 
 /// ConversionError represents an error during version conversion.
 #[derive(Debug, Clone)]
 pub struct ConversionError {
     pub message: String,
-    pub is_missing_version: bool,
+    pub kind: ConversionErrorKind,
+}
+
+impl ConversionError {
+    /// Creates an error of the given kind.
+    pub fn new(kind: ConversionErrorKind, message: impl Into<String>) -> Self {
+        ConversionError {
+            message: message.into(),
+            kind,
+        }
+    }
+
+    /// Creates an error for a version that doesn't exist.
+    pub fn missing_version(message: impl Into<String>) -> Self {
+        ConversionError::new(ConversionErrorKind::MissingVersion, message)
+    }
+
+    /// Creates an error for an object the target version can't represent.
+    pub fn incompatible(message: impl Into<String>) -> Self {
+        ConversionError::new(ConversionErrorKind::Incompatible, message)
+    }
+
+    /// Creates an error for a failed conversion.
+    pub fn internal(message: impl Into<String>) -> Self {
+        ConversionError::new(ConversionErrorKind::Internal, message)
+    }
+
+    /// Returns true if the error is of kind MissingVersion.
+    pub fn is_missing_version(&self) -> bool {
+        self.kind == ConversionErrorKind::MissingVersion
+    }
 }
 
 impl std::fmt::Display for ConversionError {
@@ -30,7 +90,7 @@ impl std::fmt::Display for ConversionError {
 impl std::error::Error for ConversionError {}
 
 /// Filter trait for filtering fields.
-pub trait Filter {
+pub trait Filter: Send + Sync {
     /// Filters the given set.
     fn filter(&self, set: &Set) -> Set;
 }
@@ -53,13 +113,97 @@ impl Filter for ExcludeSetFilter {
     }
 }
 
+/// FieldTransformer normalizes field values before they are merged and
+/// compared, so that cosmetic differences (e.g. "1000m" vs "1") don't cause
+/// conflicts or ownership churn.
+pub trait FieldTransformer: Send + Sync {
+    /// Returns the normalized value for the field at path, or None to leave
+    /// it unchanged.
+    fn transform(&self, path: &Path, value: &Value) -> Option<Value>;
+}
+
+/// VersionDowngradePolicy decides what happens when a manager applies at an
+/// older API version than the one its fields are recorded at, as ordered by
+/// APIVersion::release_cmp.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum VersionDowngradePolicy {
+    /// Records the applied fields at the older version, replacing the
+    /// entry at the newer one.
+    #[default]
+    Overwrite,
+    /// Converts the applied fields to the recorded version and keeps the
+    /// entry there.
+    ConvertAndMerge,
+    /// Fails the apply with ApplyError::VersionDowngrade.
+    Reject,
+    /// Records the applied fields in a separate entry keyed
+    /// `<manager>@<version>`, keeping the entry at the newer version. The
+    /// two entries don't conflict with each other.
+    TrackSeparately,
+}
+
+/// EmptyContainers decides what applying an empty list or map, such as
+/// `list: []` or `map: {}`, does to the contents other managers set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum EmptyContainers {
+    /// As the API server does: the applier owns empty maps but not empty
+    /// lists, and contents set by other managers stay.
+    #[default]
+    Compatible,
+    /// The applier owns the container, list or map, and contents set by
+    /// other managers stay. Contents only the applier set are removed, as
+    /// for any field it stops applying.
+    OwnContainerOnly,
+    /// The applier owns the container, which is emptied, removing the
+    /// contents every manager set. Removals don't conflict.
+    DeleteContents,
+    /// Empty containers are applied as if the field were omitted: neither
+    /// the container nor its contents change, and the applier owns none of
+    /// them.
+    NoOp,
+}
+
+/// InvalidLiveObjects decides what applying to a live object that doesn't
+/// validate against the schema does, such as an object stored before its
+/// schema was tightened. Duplicate list items are always merged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum InvalidLiveObjects {
+    /// The live object is merged as it is.
+    #[default]
+    Unchecked,
+    /// Fails the apply with ApplyError::ValidationError.
+    Reject,
+    /// The fields holding invalid values, or the lists holding invalid
+    /// items, are left out of the apply and kept as they are, as atomic
+    /// untyped values, unless the config sets them. Each is reported with
+    /// Warning::DegradedField. Applies still fail if the root is invalid.
+    Degrade,
+}
+
 /// UpdaterBuilder is a builder for creating an Updater.
 #[derive(Default)]
 pub struct UpdaterBuilder {
-    converter: Option<Box<dyn Converter>>,
-    ignore_filter: HashMap<APIVersion, Box<dyn Filter>>,
+    converter: Option<Arc<dyn Converter>>,
+    async_converter: Option<Arc<dyn AsyncConverter>>,
+    ignore_filter: HashMap<APIVersion, Arc<dyn Filter>>,
     ignored_fields: HashMap<APIVersion, Set>,
+    ignored_fields_all_versions: Set,
     return_input_on_noop: bool,
+    deduced_list_keys: Vec<String>,
+    semantic_scalars: bool,
+    list_ordering: Option<ListOrdering>,
+    numeric_keys: Option<NumericKeys>,
+    heterogeneous_lists: Option<HeterogeneousLists>,
+    preserve_unknown_fields: bool,
+    take_over_own_updates: bool,
+    version_downgrade: VersionDowngradePolicy,
+    empty_containers: EmptyContainers,
+    invalid_live_objects: InvalidLiveObjects,
+    transformers: Vec<(Set, Arc<dyn FieldTransformer>)>,
+    applied_config_cache: usize,
 }
 
 impl UpdaterBuilder {
@@ -70,13 +214,20 @@ impl UpdaterBuilder {
 
     /// Sets the converter.
     pub fn converter(mut self, converter: Box<dyn Converter>) -> Self {
-        self.converter = Some(converter);
+        self.converter = Some(Arc::from(converter));
+        self
+    }
+
+    /// Sets the converter used by apply_async, for conversions that need
+    /// I/O such as CRD conversion webhooks.
+    pub fn async_converter(mut self, converter: Box<dyn AsyncConverter>) -> Self {
+        self.async_converter = Some(Arc::from(converter));
         self
     }
 
     /// Adds an ignore filter for a specific version.
     pub fn ignore_filter(mut self, version: APIVersion, filter: Box<dyn Filter>) -> Self {
-        self.ignore_filter.insert(version, filter);
+        self.ignore_filter.insert(version, Arc::from(filter));
         self
     }
 
@@ -86,29 +237,290 @@ impl UpdaterBuilder {
         self
     }
 
+    /// Adds fields ignored at every version, on top of those ignored per
+    /// version. Like those, they take precedence over ignore filters.
+    pub fn ignored_fields_all_versions(mut self, fields: Set) -> Self {
+        self.ignored_fields_all_versions = self.ignored_fields_all_versions.union(&fields);
+        self
+    }
+
     /// Sets whether to return input on no-op.
     pub fn return_input_on_noop(mut self, value: bool) -> Self {
         self.return_input_on_noop = value;
         self
     }
 
+    /// Sets the candidate key fields used to merge lists of objects parsed
+    /// with the deduced schema as associative lists instead of atomic ones.
+    pub fn deduced_list_keys(mut self, keys: Vec<String>) -> Self {
+        self.deduced_list_keys = keys;
+        self
+    }
+
+    /// Sets whether quantity, int-or-string and time scalars are compared by
+    /// the value they denote, so equivalent representations such as "1Gi"
+    /// and "1073741824" don't conflict or change ownership.
+    pub fn semantic_scalars(mut self, enabled: bool) -> Self {
+        self.semantic_scalars = enabled;
+        self
+    }
+
+    /// Sets the order of items in merged associative lists, overriding the
+    /// objects' schema.
+    pub fn list_ordering(mut self, ordering: ListOrdering) -> Self {
+        self.list_ordering = Some(ordering);
+        self
+    }
+
+    /// Sets how numbers in the keys of associative list items are
+    /// represented, overriding the objects' schema.
+    pub fn numeric_keys(mut self, numeric_keys: NumericKeys) -> Self {
+        self.numeric_keys = Some(numeric_keys);
+        self
+    }
+
+    /// Sets how lists of the deduced schema mixing scalars, lists and maps
+    /// are handled, overriding the objects' schema.
+    pub fn heterogeneous_lists(mut self, heterogeneous_lists: HeterogeneousLists) -> Self {
+        self.heterogeneous_lists = Some(heterogeneous_lists);
+        self
+    }
+
+    /// Sets whether fields of the live object that the objects' schema
+    /// doesn't declare are kept instead of failing, so clients with an older
+    /// schema can apply to objects written by a newer one. Such fields are
+    /// owned as untyped atomic values by the managers that set them.
+    pub fn preserve_unknown_fields(mut self, enabled: bool) -> Self {
+        self.preserve_unknown_fields = enabled;
+        self
+    }
+
+    /// Sets whether an applier takes over the fields of the Update entry of
+    /// the same manager without conflicts, like the API server does for
+    /// controllers that both update and apply. Manager keys must be encoded
+    /// ManagerIdentifiers for the entries to be matched.
+    pub fn take_over_own_updates(mut self, enabled: bool) -> Self {
+        self.take_over_own_updates = enabled;
+        self
+    }
+
+    /// Sets what happens when a manager applies at an older API version
+    /// than its fields are recorded at.
+    pub fn version_downgrade(mut self, policy: VersionDowngradePolicy) -> Self {
+        self.version_downgrade = policy;
+        self
+    }
+
+    /// Sets what applying an empty list or map does to the contents other
+    /// managers set.
+    pub fn empty_containers(mut self, policy: EmptyContainers) -> Self {
+        self.empty_containers = policy;
+        self
+    }
+
+    /// Sets what applying to a live object that doesn't validate against
+    /// the schema does.
+    pub fn invalid_live_objects(mut self, policy: InvalidLiveObjects) -> Self {
+        self.invalid_live_objects = policy;
+        self
+    }
+
+    /// Adds a transformer invoked for the given fields of every object
+    /// before it is merged or compared. Transformers run in the order they
+    /// were added.
+    pub fn field_transformer(mut self, fields: Set, transformer: Box<dyn FieldTransformer>) -> Self {
+        self.transformers.push((fields, Arc::from(transformer)));
+        self
+    }
+
+    /// Sets how many managers the Updater remembers the last applied config
+    /// of, so re-applying an identical config skips computing its field
+    /// set. The least recently applied configs are evicted first, and clones
+    /// of the Updater share the cache. 0, the default, disables it.
+    pub fn applied_config_cache(mut self, capacity: usize) -> Self {
+        self.applied_config_cache = capacity;
+        self
+    }
+
     /// Builds the Updater.
     pub fn build(self) -> Updater {
         Updater {
             converter: self.converter,
+            async_converter: self.async_converter,
             ignore_filter: self.ignore_filter,
             ignored_fields: self.ignored_fields,
+            ignored_fields_all_versions: self.ignored_fields_all_versions,
             return_input_on_noop: self.return_input_on_noop,
+            deduced_list_keys: self.deduced_list_keys,
+            semantic_scalars: self.semantic_scalars,
+            list_ordering: self.list_ordering,
+            numeric_keys: self.numeric_keys,
+            heterogeneous_lists: self.heterogeneous_lists,
+            preserve_unknown_fields: self.preserve_unknown_fields,
+            take_over_own_updates: self.take_over_own_updates,
+            version_downgrade: self.version_downgrade,
+            empty_containers: self.empty_containers,
+            invalid_live_objects: self.invalid_live_objects,
+            transformers: self.transformers,
+            applied_configs: (self.applied_config_cache > 0)
+                .then(|| Arc::new(AppliedConfigs::new(self.applied_config_cache))),
+        }
+    }
+}
+
+/// ApplyOptions control which conflicts an apply takes ownership of.
+#[derive(Debug, Clone, Default)]
+pub struct ApplyOptions {
+    /// Take ownership of all conflicting fields instead of failing.
+    pub force: bool,
+    /// Take ownership of the conflicting fields in this set only; other
+    /// conflicts still fail the apply. Ignored when `force` is set.
+    pub force_fields: Option<Set>,
+    /// Take ownership of the conflicting fields of managers with a lower
+    /// priority than the applier, as tagged in its ManagedFields.
+    pub force_lower_priority: bool,
+}
+
+impl ApplyOptions {
+    /// Returns options that take ownership of all conflicting fields.
+    pub fn force() -> Self {
+        ApplyOptions {
+            force: true,
+            force_fields: None,
+            force_lower_priority: false,
+        }
+    }
+
+    /// Returns options that take ownership of the conflicting fields in
+    /// `fields` only, e.g. those of `Conflicts::to_force_set` when retrying
+    /// an apply that failed with conflicts.
+    pub fn force_only(fields: Set) -> Self {
+        ApplyOptions {
+            force: false,
+            force_fields: Some(fields),
+            force_lower_priority: false,
+        }
+    }
+
+    /// Returns the conflicts that aren't forced for `workflow`.
+    fn unforced(&self, conflicts: Conflicts, managers: &ManagedFields, workflow: &str) -> Conflicts {
+        let conflicts = match (self.force, &self.force_fields) {
+            (true, _) => Conflicts::new(),
+            (false, Some(fields)) => conflicts.into_iter().filter(|c| !fields.has(&c.path)).collect(),
+            (false, None) => conflicts,
+        };
+        if !self.force_lower_priority {
+            return conflicts;
         }
+        let priority = managers.priority(workflow);
+        conflicts
+            .into_iter()
+            .filter(|c| managers.priority(&c.manager) >= priority)
+            .collect()
     }
 }
 
 /// Updater is the main merge orchestrator.
+#[derive(Clone)]
 pub struct Updater {
-    converter: Option<Box<dyn Converter>>,
-    ignore_filter: HashMap<APIVersion, Box<dyn Filter>>,
+    converter: Option<Arc<dyn Converter>>,
+    async_converter: Option<Arc<dyn AsyncConverter>>,
+    ignore_filter: HashMap<APIVersion, Arc<dyn Filter>>,
     ignored_fields: HashMap<APIVersion, Set>,
+    ignored_fields_all_versions: Set,
     pub return_input_on_noop: bool,
+    deduced_list_keys: Vec<String>,
+    semantic_scalars: bool,
+    list_ordering: Option<ListOrdering>,
+    numeric_keys: Option<NumericKeys>,
+    heterogeneous_lists: Option<HeterogeneousLists>,
+    preserve_unknown_fields: bool,
+    take_over_own_updates: bool,
+    version_downgrade: VersionDowngradePolicy,
+    empty_containers: EmptyContainers,
+    invalid_live_objects: InvalidLiveObjects,
+    transformers: Vec<(Set, Arc<dyn FieldTransformer>)>,
+    /// The last configs managers applied, shared by clones.
+    applied_configs: Option<Arc<AppliedConfigs>>,
+}
+
+/// A least recently used cache of the last config each manager applied.
+struct AppliedConfigs {
+    capacity: usize,
+    state: Mutex<AppliedConfigsState>,
+}
+
+#[derive(Default)]
+struct AppliedConfigsState {
+    clock: u64,
+    entries: HashMap<String, AppliedConfig>,
+}
+
+/// The field set of the last config a manager applied, reused while the
+/// manager applies the same config again, as controllers do in a steady
+/// state.
+struct AppliedConfig {
+    hash: u64,
+    schema: u64,
+    type_ref: TypeRef,
+    version: APIVersion,
+    // Kept to rule out hash collisions.
+    config: Value,
+    /// The fields of the config, without the ignored ones.
+    set: Set,
+    /// When the entry was last used, for eviction.
+    used: u64,
+}
+
+impl AppliedConfigs {
+    fn new(capacity: usize) -> Self {
+        AppliedConfigs {
+            capacity,
+            state: Mutex::default(),
+        }
+    }
+
+    /// Returns the field set of the config if `manager` applied the same
+    /// config at the same version last.
+    fn get(&self, manager: &str, config: &TypedValue, version: &APIVersion, hash: u64) -> Option<Set> {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.clock += 1;
+        let clock = state.clock;
+        let cached = state.entries.get_mut(manager)?;
+        let same = cached.hash == hash
+            && cached.version == *version
+            && cached.type_ref == *config.type_ref()
+            && cached.schema == config.schema().fingerprint()
+            && cached.config == *config.value();
+        if !same {
+            return None;
+        }
+        cached.used = clock;
+        Some(cached.set.clone())
+    }
+
+    /// Remembers the field set of the config `manager` applied, evicting the
+    /// least recently used entry if the cache is full.
+    fn insert(&self, manager: &str, config: &TypedValue, version: &APIVersion, hash: u64, set: &Set) {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.clock += 1;
+        if !state.entries.contains_key(manager) && state.entries.len() >= self.capacity {
+            let oldest = state.entries.iter().min_by_key(|(_, c)| c.used).map(|(m, _)| m.clone());
+            if let Some(oldest) = oldest {
+                state.entries.remove(&oldest);
+            }
+        }
+        let cached = AppliedConfig {
+            hash,
+            schema: config.schema().fingerprint(),
+            type_ref: config.type_ref().clone(),
+            version: version.clone(),
+            config: config.value().clone(),
+            set: set.clone(),
+            used: state.clock,
+        };
+        state.entries.insert(manager.to_string(), cached);
+    }
 }
 
 impl Updater {
@@ -117,6 +529,140 @@ impl Updater {
         UpdaterBuilder::new()
     }
 
+    /// Returns the async converter, if one is configured.
+    pub(super) fn async_converter(&self) -> Option<&Arc<dyn AsyncConverter>> {
+        self.async_converter.as_ref()
+    }
+
+    /// Returns a copy of the updater that converts with `converter`.
+    pub(super) fn with_converter(&self, converter: Arc<dyn Converter>) -> Updater {
+        Updater {
+            converter: Some(converter),
+            ..self.clone()
+        }
+    }
+
+    /// Returns the fields ignored at `version`, if any.
+    fn ignored_fields(&self, version: &APIVersion) -> Option<Cow<'_, Set>> {
+        let common = &self.ignored_fields_all_versions;
+        match self.ignored_fields.get(version) {
+            Some(fields) if common.is_empty() => Some(Cow::Borrowed(fields)),
+            Some(fields) => Some(Cow::Owned(fields.union(common))),
+            None if common.is_empty() => None,
+            None => Some(Cow::Borrowed(common)),
+        }
+    }
+
+    /// Returns the config of an apply without its empty lists and maps, if
+    /// the policy ignores them.
+    fn without_empty_containers<'a>(&self, config: &'a TypedValue) -> Cow<'a, TypedValue> {
+        if self.empty_containers != EmptyContainers::NoOp {
+            return Cow::Borrowed(config);
+        }
+        let mut config = config.clone();
+        *config.value_mut() = without_empty_fields(config.value());
+        Cow::Owned(config)
+    }
+
+    /// Returns the paths of the empty lists and maps of an applied config,
+    /// if the policy has the applier own them.
+    fn owned_empty_containers(&self, config: &TypedValue) -> HashSet<Path> {
+        let mut paths = HashSet::new();
+        if matches!(self.empty_containers, EmptyContainers::Compatible | EmptyContainers::NoOp) {
+            return paths;
+        }
+        config.transform_values(|path, value| {
+            if !path.is_empty() && is_empty_container(value) {
+                paths.insert(path.clone());
+            }
+            None
+        });
+        paths
+    }
+
+    /// Empties the containers of a merged object that the config applies
+    /// empty, if the policy deletes their contents.
+    fn clear_empty_containers(&self, merged: TypedValue, emptied: &HashSet<Path>) -> TypedValue {
+        if self.empty_containers != EmptyContainers::DeleteContents || emptied.is_empty() {
+            return merged;
+        }
+        merged.transform_values(|path, value| {
+            if !emptied.contains(path) {
+                return None;
+            }
+            Some(match value {
+                Value::List(_) => Value::List(Vec::new()),
+                Value::Map(_) => Value::Map(Map::new()),
+                _ => value.clone(),
+            })
+        })
+    }
+
+    /// Prepares an incoming object with the configured schema options and
+    /// field transformers.
+    fn prepare<'a>(&self, obj: &'a TypedValue) -> Cow<'a, TypedValue> {
+        let obj = self.with_schema_options(obj);
+        if self.transformers.is_empty() {
+            return obj;
+        }
+        Cow::Owned(obj.transform_values(|path, value| {
+            let mut result: Option<Value> = None;
+            for (fields, transformer) in &self.transformers {
+                if fields.has(path) {
+                    let current = result.as_ref().unwrap_or(value);
+                    if let Some(v) = transformer.transform(path, current) {
+                        result = Some(v);
+                    }
+                }
+            }
+            result
+        }))
+    }
+
+    /// Applies the configured schema options: deduced list keys to objects
+    /// of the deduced schema, semantic scalar comparison, list ordering,
+    /// numeric keys, heterogeneous lists and preservation of unknown fields.
+    fn with_schema_options<'a>(&self, obj: &'a TypedValue) -> Cow<'a, TypedValue> {
+        let is_deduced = obj.type_ref().named_type.as_deref() == Some("__untyped_deduced_");
+        let set_keys = !self.deduced_list_keys.is_empty()
+            && is_deduced
+            && obj.schema().deduced_list_keys() != self.deduced_list_keys.as_slice();
+        let set_semantic = self.semantic_scalars && !obj.schema().semantic_scalars();
+        let set_ordering = self.list_ordering.filter(|o| *o != obj.schema().list_ordering());
+        let set_numeric_keys = self.numeric_keys.filter(|n| *n != obj.schema().numeric_keys());
+        let set_heterogeneous = self.heterogeneous_lists.filter(|h| *h != obj.schema().heterogeneous_lists());
+        let set_preserve = self.preserve_unknown_fields && !obj.schema().preserve_unknown_fields();
+        if !set_keys
+            && !set_semantic
+            && set_ordering.is_none()
+            && set_numeric_keys.is_none()
+            && set_heterogeneous.is_none()
+            && !set_preserve
+        {
+            return Cow::Borrowed(obj);
+        }
+        let mut obj = obj.clone();
+        if set_keys {
+            obj.schema_mut().set_deduced_list_keys(self.deduced_list_keys.clone());
+        }
+        if set_semantic {
+            obj.schema_mut().set_semantic_scalars(true);
+        }
+        if let Some(ordering) = set_ordering {
+            obj.schema_mut().set_list_ordering(ordering);
+        }
+        if let Some(numeric_keys) = set_numeric_keys {
+            obj.schema_mut().set_numeric_keys(numeric_keys);
+        }
+        if let Some(heterogeneous_lists) = set_heterogeneous {
+            obj.schema_mut().set_heterogeneous_lists(heterogeneous_lists);
+        }
+        if set_preserve {
+            obj.schema_mut().set_preserve_unknown_fields(true);
+        }
+        Cow::Owned(obj)
+    }
+
     /// Reconciles managed fields with any changes to the object's schema.
     ///
     /// Supports:
@@ -126,6 +672,7 @@ impl Updater {
         &self,
         live_object: &TypedValue,
         managers: &mut ManagedFields,
+        warnings: &mut Warnings,
     ) -> Result<(), ApplyError> {
         use crate::typed::reconcile_field_set_with_schema;
 
@@ -136,7 +683,7 @@ impl Updater {
             let tv = if let Some(ref converter) = self.converter {
                 match converter.convert(live_object, versioned_set.api_version()) {
                     Ok(v) => v,
-                    Err(e) if converter.is_missing_version_error(&e) => {
+                    Err(e) if e.is_missing_version() => {
                         // Okay to skip, obsolete versions will be deleted automatically anyway
                         continue;
                     }
@@ -149,6 +696,13 @@ impl Updater {
             // Reconcile the field set with the schema
             match reconcile_field_set_with_schema(versioned_set.set(), &tv) {
                 Ok(Some(reconciled)) => {
+                    let collapsed = versioned_set.set().difference(&reconciled);
+                    if !collapsed.is_empty() {
+                        warnings.add(Warning::OwnershipCollapsed {
+                            manager: manager.clone(),
+                            fields: collapsed,
+                        });
+                    }
                     updated_entries.push((
                         manager.clone(),
                         VersionedSet::new(reconciled, versioned_set.api_version().clone(), versioned_set.applied()),
@@ -174,6 +728,7 @@ impl Updater {
     }
 
     /// Internal update logic that computes conflicts and field changes.
+    #[allow(clippy::too_many_arguments)]
     fn update_internal(
         &self,
         old_object: &TypedValue,
@@ -181,16 +736,18 @@ impl Updater {
         version: &APIVersion,
         managers: &mut ManagedFields,
         workflow: &str,
-        force: bool,
+        options: &ApplyOptions,
+        warnings: &mut Warnings,
+        ctx: &mut MergeContext,
     ) -> Result<Comparison, ApplyError> {
         // Compare old and new objects
-        let compare = old_object.compare(new_object)
+        let compare = old_object.compare_with_scratch(new_object, &mut ctx.compare)
             .map_err(ApplyError::ValidationError)?;
 
         // Apply ignored fields filter if configured
-        let filtered_compare = if let Some(fields) = self.ignored_fields.get(version) {
+        let filtered_compare = if let Some(fields) = self.ignored_fields(version) {
             let mut c = compare.clone();
-            c.exclude_fields(fields);
+            c.exclude_fields(&fields);
             c
         } else if let Some(filter) = self.ignore_filter.get(version) {
             let mut c = compare.clone();
@@ -202,90 +759,112 @@ impl Updater {
             compare.clone()
         };
 
-        // Track conflicts and removals
+        // Track conflicts and removals, in each manager's version
         let mut conflicts = Conflicts::new();
-        let mut removed_by_manager: HashMap<String, Set> = HashMap::new();
-        let mut obsolete_managers: Vec<String> = Vec::new();
+        let conflicts_by_manager = &mut ctx.conflicts_by_manager;
+        let removed_by_manager = &mut ctx.removed_by_manager;
+        let obsolete_managers = &mut ctx.obsolete_managers;
+        conflicts_by_manager.clear();
+        removed_by_manager.clear();
+        obsolete_managers.clear();
 
         // Check each manager for conflicts
         for (manager, versioned_set) in managers.iter() {
             if manager == workflow {
                 continue;
             }
-
-            // Get the comparison at the manager's version
-            let manager_compare = if versioned_set.api_version() == version {
-                filtered_compare.clone()
-            } else if let Some(ref converter) = self.converter {
-                // Convert objects to manager's version for comparison
-                let versioned_old = match converter.convert(old_object, versioned_set.api_version()) {
-                    Ok(v) => v,
-                    Err(e) if converter.is_missing_version_error(&e) => {
-                        // Mark this manager as having an obsolete version
-                        obsolete_managers.push(manager.clone());
-                        continue;
-                    },
-                    Err(e) => return Err(ApplyError::ConversionError(e)),
-                };
-                let versioned_new = match converter.convert(new_object, versioned_set.api_version()) {
-                    Ok(v) => v,
-                    Err(e) if converter.is_missing_version_error(&e) => {
-                        // Mark this manager as having an obsolete version
-                        obsolete_managers.push(manager.clone());
-                        continue;
-                    },
-                    Err(e) => return Err(ApplyError::ConversionError(e)),
-                };
-
-                versioned_old.compare(&versioned_new)
-                    .map_err(ApplyError::ValidationError)?
-            } else {
-                filtered_compare.clone()
+            let manager_version = versioned_set.api_version();
+
+            let changes = match self.manager_changes(
+                old_object,
+                new_object,
+                &filtered_compare,
+                versioned_set,
+                version,
+                &mut ctx.compare,
+            ) {
+                Ok(changes) => changes,
+                Err(ApplyError::ConversionError(e)) if e.is_missing_version() => {
+                    // Mark this manager as having an obsolete version
+                    obsolete_managers.push(manager.clone());
+                    continue;
+                }
+                Err(e) => return Err(e),
             };
 
-            // Find conflicts: fields the manager owns that were modified or added
-            let conflict_set = versioned_set.set()
-                .intersection(&manager_compare.modified.union(&manager_compare.added));
-
-            if !conflict_set.is_empty() {
-                let mut paths = Vec::new();
-                conflict_set.iterate(|path| paths.push(path.clone()));
-                for path in paths {
-                    conflicts.add(super::Conflict::new(manager.clone(), path));
+            // The applier's entry at a newer version keeps its fields
+            if !changes.conflicts.is_empty() && !self.is_own_newer_entry(workflow, manager) {
+                // The applier's own Update entry hands its fields over silently
+                if !self.is_own_update(workflow, manager) {
+                    let paths = &mut ctx.paths;
+                    paths.clear();
+                    changes.conflicts.iterate(|path| paths.push(path.clone()));
+                    let operation = if versioned_set.applied() { Operation::Apply } else { Operation::Update };
+                    for path in paths.drain(..) {
+                        conflicts.add(super::Conflict::new(manager.clone(), path).with_operation(operation));
+                    }
                 }
+                let owned = match changes.conflicts_version {
+                    Some(ref v) if v != manager_version => self.convert_set(&changes.conflicts, v, manager_version)?,
+                    _ => changes.conflicts,
+                };
+                conflicts_by_manager.insert(manager.clone(), owned);
             }
 
             // Track removed fields
-            if !manager_compare.removed.is_empty() {
-                removed_by_manager.insert(manager.clone(), manager_compare.removed.clone());
+            if !changes.removed.is_empty() {
+                removed_by_manager.insert(manager.clone(), changes.removed);
             }
         }
 
-        // Return conflicts if not forcing
-        if !force && !conflicts.is_empty() {
-            return Err(ApplyError::Conflicts(conflicts));
+        // Return conflicts unless they are all forced
+        let conflicts = options.unforced(conflicts, managers, workflow);
+        if !conflicts.is_empty() {
+            return Err(ApplyError::Conflicts(conflicts.prioritized(managers)));
         }
 
         // Remove managers with obsolete versions
-        for manager in obsolete_managers {
-            managers.remove(&manager);
+        for manager in obsolete_managers.drain(..) {
+            if let Some(vs) = managers.remove(&manager) {
+                warnings.add(Warning::ObsoleteManagerVersion {
+                    manager,
+                    version: vs.api_version().clone(),
+                });
+            }
         }
 
-        // Remove conflicting fields from other managers
-        for conflict in conflicts.iter() {
-            if let Some(vs) = managers.get(&conflict.manager) {
-                let new_set = vs.set().difference(&conflicts.to_set());
+        // Remove conflicting fields from other managers. A wildcard can't
+        // give up single keys, so those with forced fields beneath them are
+        // first lowered to the keys of the live object
+        let mut live_fields: Option<Set> = None;
+        for (manager, conflict_set) in conflicts_by_manager.drain() {
+            if let Some(vs) = managers.get(&manager) {
+                let owned = if vs.set().wildcards().intersection(&conflict_set).is_empty() {
+                    Cow::Borrowed(vs.set())
+                } else {
+                    let fields = match live_fields {
+                        Some(ref fields) => fields,
+                        None => live_fields.insert(old_object.to_field_set().map_err(ApplyError::ValidationError)?),
+                    };
+                    let fields = match vs.api_version() {
+                        v if v != version => Cow::Owned(self.convert_set(fields, version, v)?),
+                        _ => Cow::Borrowed(fields),
+                    };
+                    Cow::Owned(vs.set().lower_wildcards_over(&conflict_set, &fields))
+                };
+                let new_set = owned.difference(&conflict_set);
                 managers.insert(
-                    conflict.manager.clone(),
+                    manager.clone(),
                     VersionedSet::new(new_set, vs.api_version().clone(), vs.applied()),
                 );
             }
         }
 
-        // Remove fields that were removed from the object
-        for (manager, removed_set) in removed_by_manager {
+        // Remove fields that were removed from the object, with those
+        // beneath them
+        for (manager, removed_set) in removed_by_manager.drain() {
             if let Some(vs) = managers.get(&manager) {
-                let new_set = vs.set().difference(&removed_set);
+                let new_set = vs.set().recursive_difference(&removed_set);
                 managers.insert(
                     manager.clone(),
                     VersionedSet::new(new_set, vs.api_version().clone(), vs.applied()),
@@ -299,6 +878,150 @@ impl Updater {
         Ok(compare)
     }
 
+    /// Computes the fields of a manager that an update conflicts with and the
+    /// fields it removes, comparing paths at the same version.
+    ///
+    /// When the converter can convert sets, the manager's set is converted
+    /// to the updated version and conflicts are reported there. Otherwise
+    /// both objects are converted to the manager's version and compared.
+    fn manager_changes(
+        &self,
+        old_object: &TypedValue,
+        new_object: &TypedValue,
+        compare: &Comparison,
+        versioned_set: &VersionedSet,
+        version: &APIVersion,
+        scratch: &mut CompareScratch,
+    ) -> Result<ManagerChanges, ApplyError> {
+        let manager_version = versioned_set.api_version();
+        let changed = |compare: &Comparison, owned: &Set| owned.intersection(&compare.modified.union(&compare.added));
+
+        let converter = match self.converter {
+            Some(ref converter) if manager_version != version => converter,
+            _ => {
+                return Ok(ManagerChanges {
+                    conflicts: changed(compare, versioned_set.set()),
+                    conflicts_version: None,
+                    removed: compare.removed.clone(),
+                })
+            }
+        };
+
+        if let Some(owned) = converter.convert_set(versioned_set.set(), manager_version, version) {
+            let owned = owned.map_err(ApplyError::ConversionError)?;
+            let removed = self.convert_set(&compare.removed, version, manager_version)?;
+            return Ok(ManagerChanges {
+                conflicts: changed(compare, &owned),
+                conflicts_version: Some(version.clone()),
+                removed,
+            });
+        }
+
+        let versioned_old = converter.convert(old_object, manager_version).map_err(ApplyError::ConversionError)?;
+        let versioned_new = converter.convert(new_object, manager_version).map_err(ApplyError::ConversionError)?;
+        let compare = versioned_old
+            .compare_with_scratch(&versioned_new, scratch)
+            .map_err(ApplyError::ValidationError)?;
+        Ok(ManagerChanges {
+            conflicts: changed(&compare, versioned_set.set()),
+            conflicts_version: None,
+            removed: compare.removed,
+        })
+    }
+
+    /// Returns the conflicts with the other managers owning the fields that
+    /// retainKeys fields drop from the live object in `merged`, unless the
+    /// options force them. Forced, the fields go as other removals do.
+    fn check_retained_keys(
+        &self,
+        live_obj: &TypedValue,
+        merged: &TypedValue,
+        managers: &ManagedFields,
+        applier: &str,
+        options: &ApplyOptions,
+    ) -> Result<(), ApplyError> {
+        if !live_obj.schema().has_retain_keys() {
+            return Ok(());
+        }
+        let dropped = live_obj.compare(merged).map_err(ApplyError::ValidationError)?.removed;
+        let mut conflicts = Conflicts::new();
+        for (manager, vs) in managers.iter() {
+            if manager == applier || self.is_own_update(applier, manager) {
+                continue;
+            }
+            let operation = if vs.applied() { Operation::Apply } else { Operation::Update };
+            // The fields dropped include those beneath the dropped keys
+            let under_dropped = |path: &Path| {
+                let mut prefix = Path::new();
+                path.iter().any(|pe| {
+                    prefix.push(pe.clone());
+                    dropped.has(&prefix)
+                })
+            };
+            vs.set().iterate(|path| {
+                if under_dropped(path) {
+                    conflicts.add(super::Conflict::new(manager.clone(), path.clone()).with_operation(operation));
+                }
+            });
+        }
+        let conflicts = options.unforced(conflicts, managers, applier);
+        if !conflicts.is_empty() {
+            return Err(ApplyError::Conflicts(conflicts.prioritized(managers)));
+        }
+        Ok(())
+    }
+
+    /// Returns true if `manager` is the Update entry of the manager applying
+    /// as `applier`, and such entries are taken over.
+    fn is_own_update(&self, applier: &str, manager: &str) -> bool {
+        if !self.take_over_own_updates {
+            return false;
+        }
+        match (ManagerIdentifier::decode(applier), ManagerIdentifier::decode(manager)) {
+            (Ok(applier), Ok(manager)) => {
+                applier.operation == Operation::Apply
+                    && manager.operation == Operation::Update
+                    && applier.manager == manager.manager
+                    && applier.subresource == manager.subresource
+            }
+            _ => false,
+        }
+    }
+
+    /// Returns the key an operation by `manager` is recorded under. Keys that
+    /// are encoded ManagerIdentifiers are re-encoded for the operation and,
+    /// for updates, the version, as Kubernetes records a manager's applies
+    /// and its updates at each version in separate entries. Other keys are
+    /// used as they are, and their entry's applied flag follows the latest
+    /// operation.
+    fn operation_key<'a>(manager: &'a str, operation: Operation, version: &APIVersion) -> Cow<'a, str> {
+        let Ok(mut id) = ManagerIdentifier::decode(manager) else {
+            return Cow::Borrowed(manager);
+        };
+        id.operation = operation;
+        id.api_version = (operation == Operation::Update).then(|| version.to_string());
+        let key = id.encode();
+        if key == manager {
+            Cow::Borrowed(manager)
+        } else {
+            Cow::Owned(key)
+        }
+    }
+
+    /// Returns true if `manager` is the entry at a newer version of the
+    /// manager applying as `applier` under TrackSeparately.
+    fn is_own_newer_entry(&self, applier: &str, manager: &str) -> bool {
+        self.version_downgrade == VersionDowngradePolicy::TrackSeparately
+            && applier.rsplit_once('@').is_some_and(|(base, _)| base == manager)
+    }
+
+    fn convert_set(&self, set: &Set, from: &APIVersion, to: &APIVersion) -> Result<Set, ApplyError> {
+        match self.converter.as_ref().and_then(|c| c.convert_set(set, from, to)) {
+            Some(result) => result.map_err(ApplyError::ConversionError),
+            None => Ok(set.clone()),
+        }
+    }
+
     /// ExtractApply performs an extract-apply operation.
     ///
     /// This is like apply but additive - it doesn't remove fields that the manager
@@ -313,6 +1036,10 @@ impl Updater {
         manager: &str,
         force: bool,
     ) -> Result<TypedValue, ApplyError> {
+        let manager = &*Self::operation_key(manager, Operation::Apply, version);
+        let live_obj = &*self.prepare(live_obj);
+        let config_obj = &*self.prepare(config_obj);
+
         // Merge config into live object
         let new_object = live_obj.merge(config_obj)
             .map_err(ApplyError::ValidationError)?;
@@ -322,8 +1049,8 @@ impl Updater {
             .map_err(ApplyError::ValidationError)?;
 
         // Apply ignored fields filter
-        let filtered_set = if let Some(fields) = self.ignored_fields.get(version) {
-            config_set.recursive_difference(fields)
+        let filtered_set = if let Some(fields) = self.ignored_fields(version) {
+            config_set.recursive_difference(&fields)
         } else if let Some(filter) = self.ignore_filter.get(version) {
             filter.filter(&config_set)
         } else {
@@ -343,18 +1070,71 @@ impl Updater {
         // Update manager's field set
         managers.insert(
             manager.to_string(),
-            VersionedSet::new(new_manager_set, version.clone(), false),
+            VersionedSet::new(new_manager_set, version.clone(), true),
         );
 
         // Run update to check for conflicts with other managers
-        self.update_internal(live_obj, &new_object, version, managers, manager, force)?;
+        let options = ApplyOptions {
+            force,
+            ..Default::default()
+        };
+        self.update_internal(
+            live_obj,
+            &new_object,
+            version,
+            managers,
+            manager,
+            &options,
+            &mut Warnings::new(),
+            &mut MergeContext::new(),
+        )?;
 
         Ok(new_object)
     }
 
+    /// Converts the live object to the applied version if its type or
+    /// schema types differ from the applied config's. Schema options don't
+    /// belong to a version, so objects differing only in them aren't
+    /// converted. Without a converter, a live object of the same type is
+    /// used as it is, read with the config's schema, and a warning added.
+    fn live_in_applied_version<'a>(
+        &self,
+        live_obj: &'a TypedValue,
+        config_obj: &TypedValue,
+        version: &APIVersion,
+        warnings: &mut Warnings,
+    ) -> Result<Cow<'a, TypedValue>, ApplyError> {
+        let same_type = live_obj.type_ref() == config_obj.type_ref();
+        if same_type && live_obj.schema().types_fingerprint() == config_obj.schema().types_fingerprint() {
+            return Ok(Cow::Borrowed(live_obj));
+        }
+        if same_type && self.converter.is_none() {
+            warnings.add(Warning::UnconvertedLiveObject { version: version.clone() });
+            let (schema, type_ref) = (config_obj.schema().clone(), config_obj.type_ref().clone());
+            let live = TypedValue::new(live_obj.value().clone(), schema, type_ref);
+            return Ok(Cow::Owned(self.prepare(&live).into_owned()));
+        }
+        let converted = self.convert(live_obj, version)?;
+        Ok(Cow::Owned(self.prepare(&converted).into_owned()))
+    }
+
+    fn convert(&self, obj: &TypedValue, version: &APIVersion) -> Result<TypedValue, ApplyError> {
+        match self.converter {
+            Some(ref converter) => converter.convert(obj, version).map_err(ApplyError::ConversionError),
+            None => Err(ApplyError::ConversionError(ConversionError::internal(format!(
+                "no converter configured to convert to {}",
+                version
+            )))),
+        }
+    }
+
     /// Apply performs an apply operation.
     ///
     /// This merges the config object into the live object, tracking field ownership.
+    /// If the live object has a different type or schema than the config, it is
+    /// first converted to the applied version with the configured Converter, and
+    /// the result is returned in the applied version. Without a Converter, a live
+    /// object of the same type is used as it is and a warning is returned.
     pub fn apply(
         &self,
         live_obj: &TypedValue,
@@ -364,28 +1144,220 @@ impl Updater {
         manager: &str,
         force: bool,
     ) -> Result<TypedValue, ApplyError> {
-        // Reconcile managed fields with any schema changes
-        self.reconcile_managed_fields_with_schema_changes(live_obj, managers)?;
+        self.apply_with_warnings(live_obj, config_obj, version, managers, manager, force)
+            .map(|(obj, _)| obj)
+    }
 
-        // Merge config into live object
-        let new_object = live_obj.merge(config_obj)
-            .map_err(ApplyError::ValidationError)?;
+    /// ApplyWithWarnings is like Apply, but also returns the non-fatal
+    /// anomalies met along the way.
+    pub fn apply_with_warnings(
+        &self,
+        live_obj: &TypedValue,
+        config_obj: &TypedValue,
+        version: &APIVersion,
+        managers: &mut ManagedFields,
+        manager: &str,
+        force: bool,
+    ) -> Result<(TypedValue, Warnings), ApplyError> {
+        let options = ApplyOptions {
+            force,
+            ..Default::default()
+        };
+        self.apply_internal(live_obj, config_obj, version, managers, manager, &options, &mut MergeContext::new())
+            .map(|(obj, warnings, _)| (obj, warnings))
+    }
 
-        // Get the field set from the config
-        let config_set = config_obj.to_field_set()
-            .map_err(ApplyError::ValidationError)?;
+    /// ApplyWithOptions is like Apply, but forces conflicts as `options`
+    /// say, e.g. only the fields of an earlier attempt's conflicts.
+    pub fn apply_with_options(
+        &self,
+        live_obj: &TypedValue,
+        config_obj: &TypedValue,
+        version: &APIVersion,
+        managers: &mut ManagedFields,
+        manager: &str,
+        options: &ApplyOptions,
+    ) -> Result<TypedValue, ApplyError> {
+        self.apply_internal(live_obj, config_obj, version, managers, manager, options, &mut MergeContext::new())
+            .map(|(obj, _, _)| obj)
+    }
 
-        // Apply ignored fields filter
-        let filtered_set = if let Some(fields) = self.ignored_fields.get(version) {
-            config_set.recursive_difference(fields)
-        } else if let Some(filter) = self.ignore_filter.get(version) {
-            filter.filter(&config_set)
-        } else {
-            config_set
+    /// ApplyWithPlan is like Apply, but also returns the patch plan: the
+    /// field additions, removals and replacements the apply made to the live
+    /// object.
+    pub fn apply_with_plan(
+        &self,
+        live_obj: &TypedValue,
+        config_obj: &TypedValue,
+        version: &APIVersion,
+        managers: &mut ManagedFields,
+        manager: &str,
+        force: bool,
+    ) -> Result<(TypedValue, PatchPlan), ApplyError> {
+        let options = ApplyOptions {
+            force,
+            ..Default::default()
         };
+        let ctx = &mut MergeContext::new();
+        let (obj, _, compare) = self.apply_internal(live_obj, config_obj, version, managers, manager, &options, ctx)?;
+        let plan = PatchPlan::from_comparison(&compare, &obj);
+        Ok((obj, plan))
+    }
 
-        // Store the previous set for this manager (for pruning and rollback)
-        let last_set = managers.get(manager).cloned();
+    /// ApplyWithCtx is like Apply, but reuses the buffers of `ctx` instead
+    /// of allocating its own.
+    #[allow(clippy::too_many_arguments)]
+    pub fn apply_with_ctx(
+        &self,
+        live_obj: &TypedValue,
+        config_obj: &TypedValue,
+        version: &APIVersion,
+        managers: &mut ManagedFields,
+        manager: &str,
+        force: bool,
+        ctx: &mut MergeContext,
+    ) -> Result<TypedValue, ApplyError> {
+        let options = ApplyOptions {
+            force,
+            ..Default::default()
+        };
+        self.apply_internal(live_obj, config_obj, version, managers, manager, &options, ctx)
+            .map(|(obj, _, _)| obj)
+    }
+
+    /// Applies the config, returning the result, the warnings and the
+    /// comparison of the live object with the result.
+    #[allow(clippy::too_many_arguments)]
+    fn apply_internal(
+        &self,
+        live_obj: &TypedValue,
+        config_obj: &TypedValue,
+        version: &APIVersion,
+        managers: &mut ManagedFields,
+        manager: &str,
+        options: &ApplyOptions,
+        ctx: &mut MergeContext,
+    ) -> Result<(TypedValue, Warnings, Comparison), ApplyError> {
+        let manager = &*Self::operation_key(manager, Operation::Apply, version);
+
+        // Applies at an older version than the applier's entry follow the
+        // downgrade policy
+        let downgraded_from = managers
+            .get(manager)
+            .map(|vs| vs.api_version())
+            .filter(|recorded| version.release_cmp(recorded) == Some(Ordering::Less))
+            .cloned();
+        let separate_key;
+        let manager = match (&downgraded_from, self.version_downgrade) {
+            (Some(recorded), VersionDowngradePolicy::Reject) => {
+                return Err(ApplyError::VersionDowngrade {
+                    manager: manager.to_string(),
+                    recorded: recorded.clone(),
+                    applied: version.clone(),
+                })
+            }
+            (Some(_), VersionDowngradePolicy::TrackSeparately) => {
+                separate_key = format!("{}@{}", manager, version);
+                separate_key.as_str()
+            }
+            _ => manager,
+        };
+
+        let mut warnings = Warnings::new();
+        let live_obj = self.prepare(live_obj);
+        let config_obj = self.prepare(config_obj);
+        let config_obj = &*self.without_empty_containers(&config_obj);
+        let live_obj = &*self.live_in_applied_version(&live_obj, config_obj, version, &mut warnings)?;
+
+        // Duplicates in the live object are collapsed by the merge, and
+        // other invalid values handled as the policy says
+        let mut invalid = ValidationErrors::new();
+        if let Err(errors) = live_obj.validate(&[]) {
+            for error in errors {
+                match error {
+                    ValidationError::DuplicateKey { path, key, indexes } => {
+                        warnings.add(Warning::DuplicateItems { path, key, indexes });
+                    }
+                    error => invalid.add(error),
+                }
+            }
+        }
+        let mut degraded = Vec::new();
+        let degraded_live;
+        let live_obj = match self.invalid_live_objects {
+            _ if invalid.is_empty() => live_obj,
+            InvalidLiveObjects::Unchecked => live_obj,
+            InvalidLiveObjects::Reject => return Err(ApplyError::ValidationError(invalid)),
+            InvalidLiveObjects::Degrade => {
+                let (live, fields) = degrade(live_obj, invalid).map_err(ApplyError::ValidationError)?;
+                for field in &fields {
+                    warnings.add(Warning::DegradedField {
+                        path: field.path.clone(),
+                        error: field.error.clone(),
+                    });
+                }
+                degraded = fields;
+                degraded_live = live;
+                &degraded_live
+            }
+        };
+
+        // Deprecated fields the manager sets are reported
+        for field in config_obj.deprecated_fields() {
+            warnings.add(Warning::DeprecatedField {
+                manager: manager.to_string(),
+                path: field.path,
+                replacement: field.replacement,
+            });
+        }
+
+        // Reconcile managed fields with any schema changes
+        self.reconcile_managed_fields_with_schema_changes(live_obj, managers, &mut warnings)?;
+
+        // Merge config into live object
+        let new_object = live_obj.merge(config_obj)
+            .map_err(ApplyError::ValidationError)?;
+        let empty_containers = self.owned_empty_containers(config_obj);
+        let new_object = self.clear_empty_containers(new_object, &empty_containers);
+        self.check_retained_keys(live_obj, &new_object, managers, manager, options)?;
+
+        // Get the field set from the config, unless the manager applied the
+        // same config last
+        let cache = self.applied_configs.as_ref().map(|c| (c, config_obj.value().content_hash()));
+        let reapplied = cache.and_then(|(c, hash)| c.get(manager, config_obj, version, hash));
+        let is_reapplied = reapplied.is_some();
+        let filtered_set = match reapplied {
+            Some(set) => set,
+            None => {
+                let mut config_set = config_obj.to_field_set()
+                    .map_err(ApplyError::ValidationError)?;
+                for path in &empty_containers {
+                    config_set.insert(path);
+                }
+
+                // Apply ignored fields filter
+                let filtered_set = if let Some(fields) = self.ignored_fields(version) {
+                    config_set.recursive_difference(&fields)
+                } else if let Some(filter) = self.ignore_filter.get(version) {
+                    filter.filter(&config_set)
+                } else {
+                    config_set
+                };
+                if let Some((c, hash)) = cache {
+                    c.insert(manager, config_obj, version, hash, &filtered_set);
+                }
+                filtered_set
+            }
+        };
+
+        // Wildcards can't come from a config, so the applier keeps its own
+        let filtered_set = match managers.get(manager) {
+            Some(vs) if vs.api_version() == version => filtered_set.union(&vs.set().wildcards()),
+            _ => filtered_set,
+        };
+
+        // Store the previous set for this manager (for pruning and rollback)
+        let last_set = managers.get(manager).cloned();
 
         // Check if the previous version is obsolete (can't be converted)
         let prev_version_obsolete = if let Some(ref ls) = last_set {
@@ -395,7 +1367,7 @@ impl Updater {
                 // Try to convert to the old version to see if it's still valid
                 match converter.convert(live_obj, ls.api_version()) {
                     Ok(_) => false,
-                    Err(e) if converter.is_missing_version_error(&e) => true,
+                    Err(e) if e.is_missing_version() => true,
                     Err(_) => false, // Other errors don't indicate obsolete version
                 }
             } else {
@@ -415,7 +1387,9 @@ impl Updater {
         // Skip pruning if the previous version is obsolete (we can't determine what was previously owned)
         let pruned_object = if !prev_version_obsolete {
             if let Some(ref ls) = last_set {
-                if !ls.set().is_empty() {
+                // A manager re-applying the config it owns has nothing to prune
+                let unchanged = is_reapplied && ls.set().equals(&filtered_set);
+                if !ls.set().is_empty() && !unchanged {
                     let removed_from_config = ls.set().difference(&filtered_set);
                     if !removed_from_config.is_empty() {
                         // Remove fields that this manager owned but no longer does
@@ -448,25 +1422,68 @@ impl Updater {
         };
 
         // Run update to check for conflicts with other managers
-        let result = self.update_internal(live_obj, &pruned_object, version, managers, manager, force);
+        let result =
+            self.update_internal(live_obj, &pruned_object, version, managers, manager, options, &mut warnings, ctx);
 
         // If there's a conflict, roll back the manager entry
-        if result.is_err() {
-            // Restore the previous state
-            if let Some(ls) = last_set {
-                managers.insert(manager.to_string(), ls);
-            } else {
-                managers.remove(manager);
+        let compare = match result {
+            Ok(compare) => compare,
+            Err(e) => {
+                // Restore the previous state
+                if let Some(ls) = last_set {
+                    managers.insert(manager.to_string(), ls);
+                } else {
+                    managers.remove(manager);
+                }
+                return Err(e);
             }
-            return result.map(|_| pruned_object);
-        }
+        };
 
         // Check for no-op
         if !self.return_input_on_noop && live_obj.value() == pruned_object.value() {
             // Return the pruned object anyway since we need to track managers
         }
 
-        Ok(pruned_object)
+        if let (Some(recorded), VersionDowngradePolicy::ConvertAndMerge) = (&downgraded_from, self.version_downgrade) {
+            if let Some(vs) = managers.get(manager) {
+                let set = self.convert_set(vs.set(), version, recorded)?;
+                managers.insert(manager.to_string(), VersionedSet::new(set, recorded.clone(), true));
+            }
+        }
+
+        if prev_version_obsolete {
+            if let Some(ls) = last_set {
+                warnings.add(Warning::ObsoleteManagerVersion {
+                    manager: manager.to_string(),
+                    version: ls.api_version().clone(),
+                });
+            }
+        }
+
+        Ok((restore(pruned_object, &degraded), warnings, compare))
+    }
+
+    /// ApplyStored applies a config in any served version to a live object
+    /// kept in `live_version`, returning the result in `live_version`.
+    ///
+    /// Conversions in both directions go through the configured Converter.
+    #[allow(clippy::too_many_arguments)]
+    pub fn apply_stored(
+        &self,
+        live_obj: &TypedValue,
+        live_version: &APIVersion,
+        config_obj: &TypedValue,
+        version: &APIVersion,
+        managers: &mut ManagedFields,
+        manager: &str,
+        force: bool,
+    ) -> Result<TypedValue, ApplyError> {
+        if live_version == version {
+            return self.apply(live_obj, config_obj, version, managers, manager, force);
+        }
+        let live_obj = self.convert(live_obj, version)?;
+        let result = self.apply(&live_obj, config_obj, version, managers, manager, force)?;
+        self.convert(&result, live_version)
     }
 
     /// Update performs an update operation.
@@ -480,23 +1497,68 @@ impl Updater {
         managers: &mut ManagedFields,
         manager: &str,
     ) -> Result<TypedValue, UpdateError> {
+        self.update_with_warnings(live_obj, new_obj, version, managers, manager)
+            .map(|(obj, _)| obj)
+    }
+
+    /// UpdateWithWarnings is like Update, but also returns the non-fatal
+    /// anomalies met along the way.
+    pub fn update_with_warnings(
+        &self,
+        live_obj: &TypedValue,
+        new_obj: &TypedValue,
+        version: &APIVersion,
+        managers: &mut ManagedFields,
+        manager: &str,
+    ) -> Result<(TypedValue, Warnings), UpdateError> {
+        self.update_internal_with_ctx(live_obj, new_obj, version, managers, manager, &mut MergeContext::new())
+    }
+
+    /// UpdateWithCtx is like Update, but reuses the buffers of `ctx` instead
+    /// of allocating its own.
+    pub fn update_with_ctx(
+        &self,
+        live_obj: &TypedValue,
+        new_obj: &TypedValue,
+        version: &APIVersion,
+        managers: &mut ManagedFields,
+        manager: &str,
+        ctx: &mut MergeContext,
+    ) -> Result<TypedValue, UpdateError> {
+        self.update_internal_with_ctx(live_obj, new_obj, version, managers, manager, ctx)
+            .map(|(obj, _)| obj)
+    }
+
+    fn update_internal_with_ctx(
+        &self,
+        live_obj: &TypedValue,
+        new_obj: &TypedValue,
+        version: &APIVersion,
+        managers: &mut ManagedFields,
+        manager: &str,
+        ctx: &mut MergeContext,
+    ) -> Result<(TypedValue, Warnings), UpdateError> {
+        // A no-op update changes neither the object nor its managed fields
+        let noop = live_obj.type_ref() == new_obj.type_ref()
+            && live_obj.value() == new_obj.value()
+            && live_obj.schema().fingerprint() == new_obj.schema().fingerprint();
+        if noop {
+            return Ok((new_obj.clone(), Warnings::new()));
+        }
+
+        let manager = &*Self::operation_key(manager, Operation::Update, version);
+        let mut warnings = Warnings::new();
+        let live_obj = &*self.prepare(live_obj);
+        let new_obj = &*self.prepare(new_obj);
+
         // Reconcile managed fields with any schema changes
-        self.reconcile_managed_fields_with_schema_changes(live_obj, managers)
-            .map_err(|e| match e {
-                ApplyError::Conflicts(c) => UpdateError::Conflicts(c),
-                ApplyError::ConversionError(e) => UpdateError::ConversionError(e),
-                ApplyError::ValidationError(e) => UpdateError::ValidationError(e),
-                ApplyError::NotImplemented => UpdateError::NotImplemented,
-            })?;
+        self.reconcile_managed_fields_with_schema_changes(live_obj, managers, &mut warnings)
+            .map_err(UpdateError::from)?;
 
         // Run update with force=true (updates don't conflict)
-        let compare = self.update_internal(live_obj, new_obj, version, managers, manager, true)
-            .map_err(|e| match e {
-                ApplyError::Conflicts(c) => UpdateError::Conflicts(c),
-                ApplyError::ConversionError(e) => UpdateError::ConversionError(e),
-                ApplyError::ValidationError(e) => UpdateError::ValidationError(e),
-                ApplyError::NotImplemented => UpdateError::NotImplemented,
-            })?;
+        let options = ApplyOptions::force();
+        let compare = self.update_internal(live_obj, new_obj, version, managers, manager, &options, &mut warnings, ctx)
+            .map_err(UpdateError::from)?;
 
         // Get or create manager entry
         let current_set = managers.get(manager)
@@ -512,8 +1574,8 @@ impl Updater {
             .union(&compare.added);
 
         // Apply ignored fields filter
-        let filtered_set = if let Some(fields) = self.ignored_fields.get(version) {
-            new_set.recursive_difference(fields)
+        let filtered_set = if let Some(fields) = self.ignored_fields(version) {
+            new_set.recursive_difference(&fields)
         } else if let Some(filter) = self.ignore_filter.get(version) {
             filter.filter(&new_set)
         } else {
@@ -530,17 +1592,63 @@ impl Updater {
             );
         }
 
-        Ok(new_obj.clone())
+        Ok((new_obj.clone(), warnings))
+    }
+}
+
+/// Returns true for empty lists and maps.
+fn is_empty_container(value: &Value) -> bool {
+    match value {
+        Value::List(items) => items.is_empty(),
+        Value::Map(fields) => fields.is_empty(),
+        _ => false,
+    }
+}
+
+/// Returns the value without the map fields that hold empty lists or maps,
+/// including those left empty once their own such fields are dropped.
+fn without_empty_fields(value: &Value) -> Value {
+    match value {
+        Value::Map(fields) => {
+            let mut map = Map::new();
+            for (key, val) in fields.iter() {
+                let val = without_empty_fields(val);
+                if !is_empty_container(&val) {
+                    map.set(key.clone(), val);
+                }
+            }
+            Value::Map(map)
+        }
+        Value::List(items) => Value::List(items.iter().map(without_empty_fields).collect()),
+        _ => value.clone(),
     }
 }
 
+/// The fields of one manager affected by an update.
+struct ManagerChanges {
+    /// Owned fields the update modifies or adds.
+    conflicts: Set,
+    /// The version of `conflicts` if it isn't the manager's.
+    conflicts_version: Option<APIVersion>,
+    /// Removed fields, in the manager's version.
+    removed: Set,
+}
+
 /// ApplyError represents an error during apply.
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub enum ApplyError {
     Conflicts(Conflicts),
     ConversionError(ConversionError),
     ValidationError(ValidationErrors),
     NotImplemented,
+    /// The manager applied at an older version than its fields are
+    /// recorded at, and the downgrade policy rejects that.
+    VersionDowngrade {
+        manager: String,
+        recorded: APIVersion,
+        applied: APIVersion,
+    },
 }
 
 impl std::fmt::Display for ApplyError {
@@ -550,6 +1658,11 @@ impl std::fmt::Display for ApplyError {
             ApplyError::ConversionError(e) => write!(f, "conversion error: {}", e),
             ApplyError::ValidationError(e) => write!(f, "validation error: {}", e),
             ApplyError::NotImplemented => write!(f, "not implemented"),
+            ApplyError::VersionDowngrade { manager, recorded, applied } => write!(
+                f,
+                "manager '{}' applied at {}, older than its recorded version {}",
+                manager, applied, recorded
+            ),
         }
     }
 }
@@ -558,11 +1671,19 @@ impl std::error::Error for ApplyError {}
 
 /// UpdateError represents an error during update.
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub enum UpdateError {
     Conflicts(Conflicts),
     ConversionError(ConversionError),
     ValidationError(ValidationErrors),
     NotImplemented,
+    /// The manager applied at an older version than its fields are
+    /// recorded at, and the downgrade policy rejects that.
+    VersionDowngrade {
+        manager: String,
+        recorded: APIVersion,
+        applied: APIVersion,
+    },
 }
 
 impl std::fmt::Display for UpdateError {
@@ -572,15 +1693,35 @@ impl std::fmt::Display for UpdateError {
             UpdateError::ConversionError(e) => write!(f, "conversion error: {}", e),
             UpdateError::ValidationError(e) => write!(f, "validation error: {}", e),
             UpdateError::NotImplemented => write!(f, "not implemented"),
+            UpdateError::VersionDowngrade { manager, recorded, applied } => write!(
+                f,
+                "manager '{}' applied at {}, older than its recorded version {}",
+                manager, applied, recorded
+            ),
         }
     }
 }
 
 impl std::error::Error for UpdateError {}
 
+impl From<ApplyError> for UpdateError {
+    fn from(err: ApplyError) -> Self {
+        match err {
+            ApplyError::Conflicts(c) => UpdateError::Conflicts(c),
+            ApplyError::ConversionError(e) => UpdateError::ConversionError(e),
+            ApplyError::ValidationError(e) => UpdateError::ValidationError(e),
+            ApplyError::NotImplemented => UpdateError::NotImplemented,
+            ApplyError::VersionDowngrade { manager, recorded, applied } => {
+                UpdateError::VersionDowngrade { manager, recorded, applied }
+            }
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::merge::PatchOperationKind;
     use crate::schema::{Atom, Map as SchemaMap, Schema, TypeDef, TypeRef, Scalar};
     use crate::value::{Map, Value};
 
@@ -646,6 +1787,1229 @@ mod tests {
         ])));
     }
 
+    #[test]
+    fn test_apply_deduced_list_keys() {
+        use crate::fieldpath::{Path, PathElement};
+        use crate::typed::deduced_parseable_type;
+        use crate::value::{Field, FieldList};
+
+        let pt = deduced_parseable_type();
+        let live = pt.from_yaml("{}").unwrap();
+        let config1 = pt.from_yaml(r#"{"items": [{"name": "a", "v": 1}]}"#).unwrap();
+        let config2 = pt.from_yaml(r#"{"items": [{"name": "b", "v": 2}]}"#).unwrap();
+        let version = APIVersion::new("v1");
+
+        let updater = Updater::builder()
+            .deduced_list_keys(vec!["name".to_string()])
+            .build();
+        let mut managers = ManagedFields::new();
+        let live = updater.apply(&live, &config1, &version, &mut managers, "one", false).unwrap();
+        let live = updater.apply(&live, &config2, &version, &mut managers, "two", false).unwrap();
+
+        // Both items survive because the list is merged by "name"
+        assert_eq!(live.value().as_map().unwrap().get("items").unwrap().as_list().unwrap().len(), 2);
+        let key = PathElement::Key(FieldList::with_fields(vec![Field {
+            name: "name".to_string(),
+            value: Value::String("b".into()),
+        }]));
+        let owned = Path::from_elements(vec![
+            PathElement::field_name("items"),
+            key,
+            PathElement::field_name("v"),
+        ]);
+        assert!(managers.get("two").unwrap().set().has(&owned));
+
+        // Without keys the default atomic behavior replaces the list
+        let updater = Updater::builder().build();
+        let mut managers = ManagedFields::new();
+        let live = pt.from_yaml("{}").unwrap();
+        let live = updater.apply(&live, &config1, &version, &mut managers, "one", false).unwrap();
+        assert!(updater.apply(&live, &config2, &version, &mut managers, "two", false).is_err());
+    }
+
+    #[test]
+    fn test_apply_retain_keys() {
+        use crate::typed::Parser;
+
+        let pt = Parser::new(
+            r#"types:
+- name: root
+  map:
+    fields:
+    - name: strategy
+      retainKeys: true
+      type:
+        map:
+          fields:
+          - name: type
+            type:
+              scalar: string
+          - name: rollingUpdate
+            type:
+              map:
+                fields:
+                - name: maxSurge
+                  type:
+                    scalar: numeric
+"#,
+        )
+        .unwrap()
+        .type_by_name("root");
+        let updater = Updater::builder().build();
+        let version = APIVersion::new("v1");
+        let empty = pt.from_yaml("{}").unwrap();
+        let recreate = pt.from_yaml(r#"{"strategy": {"type": "Recreate"}}"#).unwrap();
+
+        // The applier's own keys are replaced
+        let mut managers = ManagedFields::new();
+        let rolling = pt
+            .from_yaml(r#"{"strategy": {"type": "RollingUpdate", "rollingUpdate": {"maxSurge": 1}}}"#)
+            .unwrap();
+        let live = updater.apply(&empty, &rolling, &version, &mut managers, "applier", false).unwrap();
+        let live = updater.apply(&live, &recreate, &version, &mut managers, "applier", false).unwrap();
+        assert_eq!(live.value(), recreate.value());
+
+        // Keys of other managers conflict, and are dropped with their
+        // ownership once forced
+        let mut managers = ManagedFields::new();
+        let surge = pt.from_yaml(r#"{"strategy": {"rollingUpdate": {"maxSurge": 1}}}"#).unwrap();
+        let live = updater.update(&empty, &surge, &version, &mut managers, "other").unwrap();
+        let err = updater.apply(&live, &recreate, &version, &mut managers, "applier", false);
+        let Err(ApplyError::Conflicts(conflicts)) = err else {
+            panic!("expected conflicts, got {:?}", err);
+        };
+        assert_eq!(
+            conflicts.to_string(),
+            "conflicts with \"other\":\n- .strategy.rollingUpdate\n- .strategy.rollingUpdate.maxSurge"
+        );
+        let live = updater.apply(&live, &recreate, &version, &mut managers, "applier", true).unwrap();
+        assert_eq!(live.value(), recreate.value());
+        let mut owned = Set::new();
+        owned.insert(&Path::parse(".strategy").unwrap());
+        assert!(managers.get("other").unwrap().set().equals(&owned), "{}", managers);
+    }
+
+    #[test]
+    fn test_apply_heterogeneous_lists() {
+        use crate::fieldpath::{Path, PathElement};
+        use crate::schema::HeterogeneousLists;
+        use crate::typed::deduced_parseable_type;
+
+        // An env list with a NAME=value string among name/value maps
+        let pt = deduced_parseable_type();
+        let config1 = pt
+            .from_yaml(r#"{"env": ["FOO=bar", {"name": "BAR", "value": "1"}]}"#)
+            .unwrap();
+        let config2 = pt.from_yaml(r#"{"env": ["BAZ=2", {"name": "QUX"}]}"#).unwrap();
+        let version = APIVersion::new("v1");
+
+        let updater = Updater::builder()
+            .heterogeneous_lists(HeterogeneousLists::PerElement)
+            .build();
+        let mut managers = ManagedFields::new();
+        let live = pt.from_yaml("{}").unwrap();
+        let live = updater.apply(&live, &config1, &version, &mut managers, "one", false).unwrap();
+        let live = updater.apply(&live, &config2, &version, &mut managers, "two", false).unwrap();
+        assert_eq!(
+            live.value(),
+            &crate::value::from_json(r#"{"env": ["FOO=bar", {"name": "BAR", "value": "1"}, "BAZ=2", {"name": "QUX"}]}"#)
+                .unwrap()
+        );
+        let item = |v: Value| Path::from_elements(vec![PathElement::field_name("env"), PathElement::value(v)]);
+        assert!(managers.get("one").unwrap().set().has(&item(Value::String("FOO=bar".into()))));
+        assert!(managers.get("two").unwrap().set().has(&item(Value::String("BAZ=2".into()))));
+
+        // By default the list is atomic and the second apply conflicts
+        let updater = Updater::builder().build();
+        let mut managers = ManagedFields::new();
+        let live = pt.from_yaml("{}").unwrap();
+        let live = updater.apply(&live, &config1, &version, &mut managers, "one", false).unwrap();
+        assert!(updater.apply(&live, &config2, &version, &mut managers, "two", false).is_err());
+
+        let mut schema = pt.schema.clone();
+        schema.set_heterogeneous_lists(HeterogeneousLists::Reject);
+        let strict = crate::typed::ParseableType {
+            schema,
+            type_ref: pt.type_ref.clone(),
+        };
+        assert!(strict.from_yaml(r#"{"env": ["BAZ=2"]}"#).is_ok());
+        let err = strict.from_yaml(r#"{"env": ["FOO=bar", {"name": "BAR"}]}"#).unwrap_err();
+        assert!(err.message.contains(".env: list mixes scalars, lists and maps"), "{}", err);
+    }
+
+    struct MilliCores;
+
+    impl FieldTransformer for MilliCores {
+        fn transform(&self, _path: &Path, value: &Value) -> Option<Value> {
+            let millis: i64 = value.as_str()?.strip_suffix('m')?.parse().ok()?;
+            (millis % 1000 == 0).then(|| Value::String((millis / 1000).to_string()))
+        }
+    }
+
+    #[test]
+    fn test_apply_field_transformer() {
+        use crate::fieldpath::PathElement;
+
+        let schema = create_test_schema();
+        let type_ref = TypeRef {
+            named_type: Some("object".to_string()),
+            ..Default::default()
+        };
+        let obj = |cpu: &str| {
+            let mut m = Map::new();
+            m.set("cpu".to_string(), Value::String(cpu.into()));
+            TypedValue::new(Value::Map(m), schema.clone(), type_ref.clone())
+        };
+        let cpu = Path::from_elements(vec![PathElement::field_name("cpu")]);
+        let mut fields = Set::new();
+        fields.insert(&cpu);
+
+        let version = APIVersion::new("v1");
+        let live = TypedValue::new(Value::Map(Map::new()), schema.clone(), type_ref.clone());
+
+        let updater = Updater::builder()
+            .field_transformer(fields, Box::new(MilliCores))
+            .build();
+        let mut managers = ManagedFields::new();
+        let live = updater.apply(&live, &obj("1"), &version, &mut managers, "one", false).unwrap();
+        let live = updater.apply(&live, &obj("1000m"), &version, &mut managers, "two", false).unwrap();
+
+        // The equivalent value is shared instead of conflicting
+        assert_eq!(live.value(), obj("1").value());
+        assert!(managers.get("one").unwrap().set().has(&cpu));
+        assert!(managers.get("two").unwrap().set().has(&cpu));
+
+        // Without the transformer the values conflict
+        let updater = Updater::builder().build();
+        let mut managers = ManagedFields::new();
+        let live = updater.apply(&live, &obj("1"), &version, &mut managers, "one", false).unwrap();
+        assert!(updater.apply(&live, &obj("1000m"), &version, &mut managers, "two", false).is_err());
+    }
+
+    #[test]
+    fn test_apply_semantic_scalars() {
+        use crate::fieldpath::PathElement;
+        use crate::typed::Parser;
+
+        let parser = Parser::new(
+            r#"types:
+- name: root
+  map:
+    fields:
+    - name: memory
+      type:
+        scalar: quantity
+    - name: port
+      type:
+        scalar: int-or-string
+"#,
+        )
+        .unwrap();
+        let pt = parser.type_by_name("root");
+        let version = APIVersion::new("v1");
+        let memory = Path::from_elements(vec![PathElement::field_name("memory")]);
+        let config1 = pt.from_yaml(r#"{"memory": "1Gi", "port": 80}"#).unwrap();
+        let config2 = pt.from_yaml(r#"{"memory": "1073741824", "port": "80"}"#).unwrap();
+
+        let updater = Updater::builder().semantic_scalars(true).build();
+        let mut managers = ManagedFields::new();
+        let live = pt.from_yaml("{}").unwrap();
+        let live = updater.apply(&live, &config1, &version, &mut managers, "one", false).unwrap();
+        updater.apply(&live, &config2, &version, &mut managers, "two", false).unwrap();
+        assert!(managers.get("one").unwrap().set().has(&memory));
+        assert!(managers.get("two").unwrap().set().has(&memory));
+
+        let updater = Updater::builder().build();
+        let mut managers = ManagedFields::new();
+        let live = pt.from_yaml("{}").unwrap();
+        let live = updater.apply(&live, &config1, &version, &mut managers, "one", false).unwrap();
+        match updater.apply(&live, &config2, &version, &mut managers, "two", false) {
+            Err(ApplyError::Conflicts(c)) => assert_eq!(c.len(), 2),
+            other => panic!("expected conflicts, got {:?}", other.map(|v| v.into_value())),
+        }
+    }
+
+    #[test]
+    fn test_update_noop() {
+        use crate::fieldpath::{Path, PathElement};
+
+        let schema = create_test_schema();
+        let type_ref = TypeRef {
+            named_type: Some("object".to_string()),
+            ..Default::default()
+        };
+        let mut m = Map::new();
+        m.set("a".to_string(), Value::String("1".into()));
+        let live = TypedValue::new(Value::Map(m.clone()), schema.clone(), type_ref.clone());
+        let same = TypedValue::new(Value::Map(m), schema.clone(), type_ref.clone());
+
+        let version = APIVersion::new("v1");
+        let mut managers = ManagedFields::new();
+        let mut owned = Set::new();
+        owned.insert(&Path::from_elements(vec![PathElement::field_name("a")]));
+        managers.insert("other", VersionedSet::new(owned, version.clone(), true));
+        let before = managers.clone();
+
+        let result = Updater::builder().build().update(&live, &same, &version, &mut managers, "m").unwrap();
+        assert_eq!(result.value(), live.value());
+        assert_eq!(managers, before);
+        assert!(managers.get("m").is_none());
+
+        // Neither ignored fields nor another version rewrite the entries
+        let updater = Updater::builder()
+            .ignored_fields_all_versions(before.get("other").unwrap().set().clone())
+            .build();
+        let v2 = APIVersion::new("v2");
+        let result = updater.update(&live, &same, &v2, &mut managers, "other").unwrap();
+        assert_eq!(result.value(), same.value());
+        assert_eq!(managers, before);
+    }
+
+    #[test]
+    fn test_apply_list_ordering() {
+        use crate::typed::Parser;
+
+        let pt = Parser::new(
+            r#"types:
+- name: root
+  map:
+    fields:
+    - name: items
+      type:
+        list:
+          elementType:
+            namedType: item
+          elementRelationship: associative
+          keys: [name]
+- name: item
+  map:
+    fields:
+    - name: name
+      type:
+        scalar: string
+"#,
+        )
+        .unwrap()
+        .type_by_name("root");
+        let live = pt.from_yaml(r#"{"items": [{"name": "a"}, {"name": "b"}, {"name": "c"}]}"#).unwrap();
+        let config = pt.from_yaml(r#"{"items": [{"name": "d"}, {"name": "b"}]}"#).unwrap();
+        let version = APIVersion::new("v1");
+        let names = |tv: &TypedValue| -> Vec<String> {
+            let items = tv.value().as_map().unwrap().get("items").unwrap().as_list().unwrap();
+            items.iter().map(|i| i.as_map().unwrap().get("name").unwrap().as_str().unwrap().to_string()).collect()
+        };
+
+        let result = Updater::builder()
+            .build()
+            .apply(&live, &config, &version, &mut ManagedFields::new(), "m", false)
+            .unwrap();
+        assert_eq!(names(&result), ["a", "c", "d", "b"]);
+
+        let result = Updater::builder()
+            .list_ordering(ListOrdering::AppliedFirst)
+            .build()
+            .apply(&live, &config, &version, &mut ManagedFields::new(), "m", false)
+            .unwrap();
+        assert_eq!(names(&result), ["d", "b", "a", "c"]);
+    }
+
+    struct OnlyV1Converter;
+
+    impl Converter for OnlyV1Converter {
+        fn convert(&self, obj: &TypedValue, version: &APIVersion) -> Result<TypedValue, ConversionError> {
+            if version.as_str() == "v1" {
+                return Ok(obj.clone());
+            }
+            Err(ConversionError::missing_version(format!("unknown version {}", version)))
+        }
+    }
+
+    #[test]
+    fn test_preserve_unknown_fields() {
+        use crate::typed::Parser;
+
+        let pt = Parser::new(
+            "types:\n- name: root\n  map:\n    fields:\n    - name: spec\n      type:\n        namedType: spec\n- name: spec\n  map:\n    fields:\n    - name: replicas\n      type:\n        scalar: numeric\n",
+        )
+        .unwrap()
+        .type_by_name("root");
+        let live_yaml = r#"{"spec": {"replicas": 1, "paused": true}, "extra": {"x": [1]}}"#;
+        assert!(pt.from_yaml(live_yaml).is_err());
+
+        // The live object was written with a newer schema
+        let mut tolerant = pt.clone();
+        tolerant.schema.set_preserve_unknown_fields(true);
+        let live = tolerant.from_yaml(live_yaml).unwrap();
+        let version = APIVersion::new("v1");
+        let mut managers = crate::managed_fields! {
+            "new" => { version: "v1", applied: true, paths: [["f:spec", "f:paused"], ["f:extra"]] },
+        };
+
+        let config = pt.from_yaml(r#"{"spec": {"replicas": 2}}"#).unwrap();
+        let updater = Updater::builder().preserve_unknown_fields(true).build();
+        let result = updater.apply(&live, &config, &version, &mut managers, "old", false).unwrap();
+        assert_eq!(
+            crate::value::to_json(result.value()).unwrap(),
+            r#"{"extra":{"x":[1]},"spec":{"paused":true,"replicas":2}}"#
+        );
+        let expected = crate::managed_fields! {
+            "new" => { version: "v1", applied: true, paths: [["f:spec", "f:paused"], ["f:extra"]] },
+            "old" => { version: "v1", applied: true, paths: [["f:spec", "f:replicas"]] },
+        };
+        assert_eq!(managers, expected);
+    }
+
+    #[test]
+    fn test_wildcard_ownership() {
+        use crate::fieldpath::{Path, PathElement};
+        use crate::typed::deduced_parseable_type;
+
+        let pt = deduced_parseable_type();
+        let version = APIVersion::new("v1");
+        let updater = Updater::builder().build();
+        let labels = Path::from_elements(vec![PathElement::field_name("metadata"), PathElement::field_name("labels")]);
+        let live = pt.from_yaml(r#"{"metadata": {"labels": {"a": "1"}}}"#).unwrap();
+        let mut managers = crate::managed_fields! {
+            "sync" => { version: "v1", applied: true, paths: [["f:metadata"], ["f:metadata", "f:labels"], ["f:metadata", "f:labels", "f:a"]] },
+        };
+        managers.get_mut("sync").unwrap().set_mut().insert_wildcard(&labels);
+
+        // The wildcard survives the owner's applies
+        let config = pt.from_yaml(r#"{"metadata": {"labels": {"a": "1"}}}"#).unwrap();
+        let live = updater.apply(&live, &config, &version, &mut managers, "sync", false).unwrap();
+        assert!(managers.get("sync").unwrap().set().has_wildcard(&labels));
+
+        // Labels nobody set yet are owned by the wildcard too
+        let config = pt.from_yaml(r#"{"metadata": {"labels": {"b": "2"}}}"#).unwrap();
+        let err = updater.apply(&live, &config, &version, &mut managers, "user", false).unwrap_err();
+        assert!(err.to_string().contains(".metadata.labels.b"), "{}", err);
+
+        // Forcing lowers the wildcard to the live labels, so the forced label
+        // is taken over and doesn't conflict again
+        let live = updater.apply(&live, &config, &version, &mut managers, "user", true).unwrap();
+        let sync = managers.get("sync").unwrap().set();
+        assert!(!sync.has_wildcard(&labels));
+        assert!(sync.has(&labels.with(PathElement::field_name("a"))));
+        assert!(!sync.has(&labels.with(PathElement::field_name("b"))));
+        assert!(managers.get("user").unwrap().set().has(&labels.with(PathElement::field_name("b"))));
+        let config = pt.from_yaml(r#"{"metadata": {"labels": {"b": "3"}}}"#).unwrap();
+        let live = updater.apply(&live, &config, &version, &mut managers, "user", false).unwrap();
+        assert_eq!(crate::value::to_json(live.value()).unwrap(), r#"{"metadata":{"labels":{"a":"1","b":"3"}}}"#);
+    }
+
+    #[test]
+    fn test_version_downgrade() {
+        use crate::fieldpath::{Path, PathElement};
+        use crate::typed::deduced_parseable_type;
+
+        let pt = deduced_parseable_type();
+        let live = pt.from_yaml(r#"{"a": "1"}"#).unwrap();
+        let config = pt.from_yaml(r#"{"a": "3", "b": "2"}"#).unwrap();
+        let recorded = crate::managed_fields! {
+            "m" => { version: "v2", applied: true, paths: [["f:a"]] },
+        };
+        let v1 = APIVersion::new("v1");
+        let field = |name: &str| Path::from_elements(vec![PathElement::field_name(name)]);
+        let apply = |policy: VersionDowngradePolicy| {
+            let mut managers = recorded.clone();
+            let updater = Updater::builder().version_downgrade(policy).build();
+            updater
+                .apply(&live, &config, &v1, &mut managers, "m", false)
+                .map(|_| managers)
+        };
+
+        let managers = apply(VersionDowngradePolicy::Overwrite).unwrap();
+        assert_eq!(managers.get("m").unwrap().api_version().as_str(), "v1");
+
+        let managers = apply(VersionDowngradePolicy::ConvertAndMerge).unwrap();
+        let vs = managers.get("m").unwrap();
+        assert_eq!(vs.api_version().as_str(), "v2");
+        assert!(vs.set().has(&field("b")));
+
+        let err = apply(VersionDowngradePolicy::Reject).unwrap_err();
+        assert!(matches!(err, ApplyError::VersionDowngrade { .. }));
+        assert_eq!(err.to_string(), "manager 'm' applied at v1, older than its recorded version v2");
+        let err = UpdateError::from(err);
+        assert!(matches!(err, UpdateError::VersionDowngrade { .. }));
+        assert_eq!(err.to_string(), "manager 'm' applied at v1, older than its recorded version v2");
+
+        // The entry at v2 neither conflicts with nor loses fields to the v1 one
+        let managers = apply(VersionDowngradePolicy::TrackSeparately).unwrap();
+        assert_eq!(managers.get("m"), recorded.get("m"));
+        let vs = managers.get("m@v1").unwrap();
+        assert_eq!(vs.api_version().as_str(), "v1");
+        assert!(vs.set().has(&field("a")) && vs.set().has(&field("b")));
+
+        // Upgrades are never downgrades
+        let mut managers = recorded.clone();
+        Updater::builder()
+            .version_downgrade(VersionDowngradePolicy::Reject)
+            .build()
+            .apply(&live, &config, &APIVersion::new("v3"), &mut managers, "m", false)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_operation_keys() {
+        use crate::typed::deduced_parseable_type;
+
+        let pt = deduced_parseable_type();
+        let v1 = APIVersion::new("v1");
+        let updater = Updater::builder().build();
+        let apply_key = ManagerIdentifier::apply("ctl").encode();
+        let update_key = ManagerIdentifier::update("ctl", "v1").encode();
+
+        let mut managers = ManagedFields::new();
+        let live = pt.from_yaml("{}").unwrap();
+        let config = pt.from_yaml(r#"{"a": "1", "b": "1"}"#).unwrap();
+        let live = updater.apply(&live, &config, &v1, &mut managers, &apply_key, false).unwrap();
+
+        // An update through the Apply key is recorded in the Update entry,
+        // which takes the fields it changes from the Apply one
+        let changed = pt.from_yaml(r#"{"a": "2", "b": "1"}"#).unwrap();
+        let live = updater.update(&live, &changed, &v1, &mut managers, &apply_key).unwrap();
+        let a = Path::from_elements(vec![crate::fieldpath::PathElement::field_name("a")]);
+        let applied = managers.get(&apply_key).unwrap();
+        assert!(applied.applied() && !applied.set().has(&a));
+        let updated = managers.get(&update_key).unwrap();
+        assert!(!updated.applied() && updated.set().has(&a));
+
+        let config = pt.from_yaml(r#"{"a": "3"}"#).unwrap();
+        let err = updater.apply(&live, &config, &v1, &mut managers.clone(), "other", false).unwrap_err();
+        let ApplyError::Conflicts(conflicts) = err else {
+            panic!("unexpected error {}", err);
+        };
+        assert_eq!(conflicts.error(), "conflicts with \"ctl\" using v1:\n- .a");
+        assert_eq!(conflicts.iter().next().unwrap().operation, Operation::Update);
+
+        // Plain keys have one entry, whose flag follows the latest operation
+        let mut managers = ManagedFields::new();
+        let changed = pt.from_yaml(r#"{"a": "2", "b": "2"}"#).unwrap();
+        let live = updater.update(&live, &changed, &v1, &mut managers, "plain").unwrap();
+        assert!(!managers.get("plain").unwrap().applied());
+        updater.extract_apply(&live, &config, &v1, &mut managers, "plain", true).unwrap();
+        assert!(managers.get("plain").unwrap().applied());
+        let err = updater.apply(&live, &config, &v1, &mut crate::managed_fields! {
+            "plain" => { version: "v1", applied: false, paths: [["f:a"]] },
+        }, "other", false).unwrap_err();
+        assert_eq!(format!("{:?}", err), "Conflicts([conflict with manager 'plain' (Update) at .a])");
+    }
+
+    #[test]
+    fn test_send_sync() {
+        use crate::fieldpath::PathElement;
+        use crate::typed::{ParseableType, Parser};
+
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<crate::schema::Schema>();
+        assert_send_sync::<ParseableType>();
+        assert_send_sync::<TypedValue>();
+        assert_send_sync::<Value>();
+        assert_send_sync::<Set>();
+        assert_send_sync::<ManagedFields>();
+        assert_send_sync::<Updater>();
+
+        // One updater serves applies from several threads
+        let pt = Parser::new("types:\n- name: root\n  map:\n    elementType:\n      scalar: string\n")
+            .unwrap()
+            .type_by_name("root");
+        let updater = Arc::new(Updater::builder().converter(Box::new(OnlyV1Converter)).build());
+        let live = pt.from_yaml("{}").unwrap();
+        let handles: Vec<_> = (0..4)
+            .map(|i| {
+                let (updater, pt, live) = (updater.clone(), pt.clone(), live.clone());
+                std::thread::spawn(move || {
+                    let config = pt.from_yaml(&format!(r#"{{"f{}": "x"}}"#, i)).unwrap();
+                    let mut managers = ManagedFields::new();
+                    let version = APIVersion::new("v1");
+                    updater.apply(&live, &config, &version, &mut managers, "m", false).unwrap();
+                    managers
+                })
+            })
+            .collect();
+        for (i, handle) in handles.into_iter().enumerate() {
+            let managers = handle.join().unwrap();
+            let owned = managers.get("m").unwrap().set();
+            assert!(owned.has(&Path::from_elements(vec![PathElement::field_name(format!("f{}", i))])));
+        }
+    }
+
+    #[test]
+    fn test_warnings() {
+        use crate::fieldpath::{Path, PathElement};
+        use crate::typed::{Parser, ValidationOption};
+
+        let pt = Parser::new(
+            r#"types:
+- name: root
+  map:
+    fields:
+    - name: a
+      type:
+        scalar: string
+    - name: items
+      type:
+        list:
+          elementType:
+            scalar: string
+          elementRelationship: associative
+"#,
+        )
+        .unwrap()
+        .type_by_name("root");
+        let version = APIVersion::new("v1");
+        let updater = Updater::builder().converter(Box::new(OnlyV1Converter)).build();
+
+        // Managers at versions that no longer exist are dropped
+        let live = pt.from_yaml(r#"{"a": "1"}"#).unwrap();
+        let new = pt.from_yaml(r#"{"a": "2"}"#).unwrap();
+        let mut managers = ManagedFields::new();
+        let mut owned = Set::new();
+        owned.insert(&Path::from_elements(vec![PathElement::field_name("a")]));
+        managers.insert("old", VersionedSet::new(owned, APIVersion::new("v0"), false));
+        let (_, warnings) = updater.update_with_warnings(&live, &new, &version, &mut managers, "m").unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert!(matches!(
+            warnings.iter().next(),
+            Some(Warning::ObsoleteManagerVersion { manager, .. }) if manager == "old"
+        ));
+        assert!(managers.get("old").is_none());
+
+        // So are they on apply, where the dropped managers are listed
+        let mut owned = Set::new();
+        owned.insert(&Path::from_elements(vec![PathElement::field_name("a")]));
+        managers.insert("old", VersionedSet::new(owned, APIVersion::new("v0"), false));
+        let (_, warnings) = updater
+            .apply_with_warnings(&live, &new, &version, &mut managers, "m", false)
+            .unwrap();
+        let dropped: Vec<_> = warnings.dropped_managers().collect();
+        assert_eq!(dropped, vec![("old", &APIVersion::new("v0"))]);
+
+        // Duplicate items in the live object are reported
+        let live = pt
+            .from_yaml_with_opts(r#"{"items": ["x", "x"]}"#, &[ValidationOption::AllowDuplicates])
+            .unwrap();
+        let config = pt.from_yaml(r#"{"a": "1"}"#).unwrap();
+        let mut managers = ManagedFields::new();
+        let (_, warnings) = updater
+            .apply_with_warnings(&live, &config, &version, &mut managers, "m", false)
+            .unwrap();
+        assert_eq!(
+            warnings.iter().next().unwrap().to_string(),
+            r#".items: duplicate items with key [="x"] at indexes [0, 1] were merged"#
+        );
+
+        // Clean applies have no warnings
+        let (_, warnings) = updater
+            .apply_with_warnings(&config, &config, &version, &mut managers, "m", false)
+            .unwrap();
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn test_apply_deprecated_field_warning() {
+        use crate::fieldpath::{Path, PathElement};
+        use crate::typed::Parser;
+
+        let pt = Parser::new(
+            r#"types:
+- name: root
+  map:
+    fields:
+    - name: serviceAccount
+      type:
+        scalar: string
+      deprecated: true
+      replacement: serviceAccountName
+    - name: serviceAccountName
+      type:
+        scalar: string
+"#,
+        )
+        .unwrap()
+        .type_by_name("root");
+        let version = APIVersion::new("v1");
+        let updater = Updater::builder().build();
+
+        let live = pt.from_yaml("{}").unwrap();
+        let config = pt.from_yaml(r#"{"serviceAccount": "a"}"#).unwrap();
+        let mut managers = ManagedFields::new();
+        let (_, warnings) = updater
+            .apply_with_warnings(&live, &config, &version, &mut managers, "m", false)
+            .unwrap();
+        assert_eq!(
+            warnings.iter().collect::<Vec<_>>(),
+            vec![&Warning::DeprecatedField {
+                manager: "m".to_string(),
+                path: Path::from_elements(vec![PathElement::field_name("serviceAccount")]),
+                replacement: Some("serviceAccountName".to_string()),
+            }]
+        );
+        assert_eq!(
+            warnings.to_string(),
+            "manager 'm' applied deprecated field .serviceAccount, use serviceAccountName instead"
+        );
+
+        let config = pt.from_yaml(r#"{"serviceAccountName": "a"}"#).unwrap();
+        let (_, warnings) = updater
+            .apply_with_warnings(&live, &config, &version, &mut managers, "m", false)
+            .unwrap();
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn test_apply_with_plan() {
+        use crate::fieldpath::{Path, PathElement};
+        use crate::typed::Parser;
+
+        let pt = Parser::new(
+            "types:\n- name: root\n  map:\n    fields:\n    - name: a\n      type:\n        scalar: string\n    - name: b\n      type:\n        scalar: string\n    - name: c\n      type:\n        scalar: string\n",
+        )
+        .unwrap()
+        .type_by_name("root");
+        let path = |name: &str| Path::from_elements(vec![PathElement::field_name(name)]);
+        let version = APIVersion::new("v1");
+        let updater = Updater::builder().build();
+        let mut managers = ManagedFields::new();
+
+        let live = pt.from_yaml(r#"{"a": "1", "b": "2"}"#).unwrap();
+        let config = pt.from_yaml(r#"{"a": "1", "b": "2"}"#).unwrap();
+        let live = updater.apply(&live, &config, &version, &mut managers, "m", false).unwrap();
+
+        let config = pt.from_yaml(r#"{"a": "x", "c": "3"}"#).unwrap();
+        let (_, plan) = updater.apply_with_plan(&live, &config, &version, &mut managers, "m", false).unwrap();
+        let ops: Vec<(PatchOperationKind, Path)> = plan.operations().iter().map(|op| (op.kind, op.path.clone())).collect();
+        assert_eq!(
+            ops,
+            vec![
+                (PatchOperationKind::Remove, path("b")),
+                (PatchOperationKind::Replace, path("a")),
+                (PatchOperationKind::Add, path("c")),
+            ]
+        );
+        assert_eq!(plan.to_string(), "remove .b\nreplace .a \"x\"\nadd .c \"3\"");
+        assert!(plan.replaced.has(&path("a")));
+    }
+
+    #[test]
+    fn test_take_over_own_updates() {
+        use crate::fieldpath::{Path, PathElement};
+        use crate::typed::Parser;
+
+        let pt = Parser::new("types:\n- name: root\n  map:\n    fields:\n    - name: a\n      type:\n        scalar: string\n")
+            .unwrap()
+            .type_by_name("root");
+        let version = APIVersion::new("v1");
+        let live = pt.from_yaml(r#"{"a": "x"}"#).unwrap();
+        let config = pt.from_yaml(r#"{"a": "y"}"#).unwrap();
+
+        let applier = ManagerIdentifier::apply("ctrl").encode();
+        let twin = ManagerIdentifier::update("ctrl", "v1").encode();
+        let stranger = ManagerIdentifier::update("other", "v1").encode();
+        let mut owned = Set::new();
+        owned.insert(&Path::from_elements(vec![PathElement::field_name("a")]));
+        let managers_owned_by = |manager: &str| {
+            let mut managers = ManagedFields::new();
+            managers.insert(manager, VersionedSet::new(owned.clone(), version.clone(), false));
+            managers
+        };
+
+        // Disabled by default
+        let mut managers = managers_owned_by(&twin);
+        let result = Updater::builder().build().apply(&live, &config, &version, &mut managers, &applier, false);
+        assert!(matches!(result, Err(ApplyError::Conflicts(_))));
+
+        let updater = Updater::builder().take_over_own_updates(true).build();
+        updater.apply(&live, &config, &version, &mut managers, &applier, false).unwrap();
+        assert!(managers.get(&twin).is_none());
+        assert!(managers.get(&applier).unwrap().set().has(&Path::from_elements(vec![PathElement::field_name("a")])));
+
+        // Other managers still conflict
+        let mut managers = managers_owned_by(&stranger);
+        let result = updater.apply(&live, &config, &version, &mut managers, &applier, false);
+        assert!(matches!(result, Err(ApplyError::Conflicts(_))));
+    }
+
+    #[test]
+    fn test_apply_force_only() {
+        use crate::fieldpath::{Path, PathElement};
+        use crate::typed::Parser;
+
+        let pt = Parser::new(
+            "types:\n- name: root\n  map:\n    fields:\n    - name: a\n      type:\n        scalar: string\n    - name: b\n      type:\n        scalar: string\n",
+        )
+        .unwrap()
+        .type_by_name("root");
+        let version = APIVersion::new("v1");
+        let field = |name: &str| Path::from_elements(vec![PathElement::field_name(name)]);
+        let live = pt.from_yaml(r#"{"a": "x", "b": "x"}"#).unwrap();
+        let mut owned = Set::new();
+        owned.insert(&field("a"));
+        owned.insert(&field("b"));
+        let mut managers = ManagedFields::new();
+        managers.insert("other", VersionedSet::new(owned, version.clone(), false));
+        let updater = Updater::builder().build();
+
+        // Fields outside the forced set still conflict
+        let config = pt.from_yaml(r#"{"a": "y", "b": "y"}"#).unwrap();
+        let mut forced = Set::new();
+        forced.insert(&field("a"));
+        let result = updater.apply_with_options(
+            &live,
+            &config,
+            &version,
+            &mut managers.clone(),
+            "ctrl",
+            &ApplyOptions::force_only(forced),
+        );
+        match result {
+            Err(ApplyError::Conflicts(conflicts)) => assert_eq!(conflicts.to_force_set(), {
+                let mut set = Set::new();
+                set.insert(&field("b"));
+                set
+            }),
+            other => panic!("expected conflicts, got {:?}", other),
+        }
+
+        // Retrying with the conflicts forced takes them over, and only them
+        let config = pt.from_yaml(r#"{"a": "y"}"#).unwrap();
+        let conflicts = match updater.apply(&live, &config, &version, &mut managers, "ctrl", false) {
+            Err(ApplyError::Conflicts(conflicts)) => conflicts,
+            other => panic!("expected conflicts, got {:?}", other),
+        };
+        let options = ApplyOptions::force_only(conflicts.to_force_set());
+        updater
+            .apply_with_options(&live, &config, &version, &mut managers, "ctrl", &options)
+            .unwrap();
+        assert!(managers.get("ctrl").unwrap().set().has(&field("a")));
+        assert!(!managers.get("other").unwrap().set().has(&field("a")));
+        assert!(managers.get("other").unwrap().set().has(&field("b")));
+    }
+
+    #[test]
+    fn test_apply_manager_priorities() {
+        use crate::fieldpath::{ManagerPriority, Path, PathElement};
+        use crate::typed::Parser;
+
+        let pt = Parser::new("types:\n- name: root\n  map:\n    elementType:\n      scalar: string\n")
+            .unwrap()
+            .type_by_name("root");
+        let version = APIVersion::new("v1");
+        let field = |name: &str| Path::from_elements(vec![PathElement::field_name(name)]);
+        let live = pt.from_yaml(r#"{"a": "x", "b": "x", "c": "x"}"#).unwrap();
+        let mut managers = ManagedFields::new();
+        for (manager, name, priority) in [
+            ("kubectl", "a", ManagerPriority::User),
+            ("admission", "b", ManagerPriority::System),
+            ("hpa", "c", ManagerPriority::Controller),
+        ] {
+            let mut owned = Set::new();
+            owned.insert(&field(name));
+            managers.insert(manager, VersionedSet::new(owned, version.clone(), true));
+            managers.set_priority(manager, priority);
+        }
+        managers.set_priority("ctrl", ManagerPriority::Controller);
+        let updater = Updater::builder().build();
+        let config = pt.from_yaml(r#"{"a": "y", "b": "y", "c": "y"}"#).unwrap();
+
+        // The system manager blocks first
+        let conflicts = match updater.apply(&live, &config, &version, &mut managers.clone(), "ctrl", false) {
+            Err(ApplyError::Conflicts(conflicts)) => conflicts,
+            other => panic!("expected conflicts, got {:?}", other),
+        };
+        let order: Vec<&str> = conflicts.iter().map(|c| c.manager.as_str()).collect();
+        assert_eq!(order, vec!["admission", "hpa", "kubectl"]);
+        assert_eq!(conflicts.blocking_manager(&managers), Some("admission"));
+
+        // Only the user's field is forced
+        let options = ApplyOptions {
+            force_lower_priority: true,
+            ..Default::default()
+        };
+        let result = updater.apply_with_options(&live, &config, &version, &mut managers.clone(), "ctrl", &options);
+        let conflicts = match result {
+            Err(ApplyError::Conflicts(conflicts)) => conflicts,
+            other => panic!("expected conflicts, got {:?}", other),
+        };
+        let order: Vec<&str> = conflicts.iter().map(|c| c.manager.as_str()).collect();
+        assert_eq!(order, vec!["admission", "hpa"]);
+
+        let config = pt.from_yaml(r#"{"a": "y"}"#).unwrap();
+        updater
+            .apply_with_options(&live, &config, &version, &mut managers, "ctrl", &options)
+            .unwrap();
+        assert!(managers.get("ctrl").unwrap().set().has(&field("a")));
+        assert!(managers.get("kubectl").is_none());
+        assert_eq!(managers.priority("kubectl"), ManagerPriority::User);
+    }
+
+    #[test]
+    fn test_apply_empty_containers() {
+        use crate::fieldpath::{Path, PathElement};
+        use crate::typed::Parser;
+
+        let pt = Parser::new(
+            r#"types:
+- name: root
+  map:
+    fields:
+    - name: items
+      type:
+        list:
+          elementType:
+            map:
+              fields:
+              - name: name
+                type:
+                  scalar: string
+          elementRelationship: associative
+          keys: [name]
+"#,
+        )
+        .unwrap()
+        .type_by_name("root");
+        let version = APIVersion::new("v1");
+        let items = Path::from_elements(vec![PathElement::field_name("items")]);
+        let empty = pt.from_yaml("{}").unwrap();
+        let controlled = pt.from_yaml(r#"{"items": [{"name": "a"}]}"#).unwrap();
+        let config = pt.from_yaml(r#"{"items": []}"#).unwrap();
+
+        let apply = |policy: EmptyContainers| {
+            let updater = Updater::builder().empty_containers(policy).build();
+            let mut managers = ManagedFields::new();
+            let live = updater.update(&empty, &controlled, &version, &mut managers, "controller").unwrap();
+            let result = updater.apply(&live, &config, &version, &mut managers, "applier", false).unwrap();
+            (result, managers)
+        };
+
+        // Empty lists aren't owned by default
+        let (result, managers) = apply(EmptyContainers::Compatible);
+        assert_eq!(result.value(), controlled.value());
+        assert!(managers.get("applier").is_none());
+
+        let (result, managers) = apply(EmptyContainers::OwnContainerOnly);
+        assert_eq!(result.value(), controlled.value());
+        assert!(managers.get("applier").unwrap().set().has(&items));
+        assert!(managers.get("controller").is_some());
+
+        let (result, managers) = apply(EmptyContainers::DeleteContents);
+        assert_eq!(result.value(), config.value());
+        assert!(managers.get("applier").unwrap().set().has(&items));
+
+        let (result, managers) = apply(EmptyContainers::NoOp);
+        assert_eq!(result.value(), controlled.value());
+        assert!(managers.get("applier").is_none());
+        assert!(managers.get("controller").is_some());
+    }
+
+    /// Filter counting the field sets it filters.
+    struct CountingFilter(Arc<std::sync::atomic::AtomicUsize>);
+
+    impl Filter for CountingFilter {
+        fn filter(&self, set: &Set) -> Set {
+            self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            set.clone()
+        }
+    }
+
+    #[test]
+    fn test_reapply_reuses_field_set() {
+        use crate::fieldpath::{Path, PathElement};
+        use crate::typed::Parser;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let pt = Parser::new("types:\n- name: root\n  map:\n    elementType:\n      scalar: string\n")
+            .unwrap()
+            .type_by_name("root");
+        let field = |name: &str| Path::from_elements(vec![PathElement::field_name(name)]);
+        let version = APIVersion::new("v1");
+        let a = pt.from_yaml(r#"{"a": "x"}"#).unwrap();
+        let b = pt.from_yaml(r#"{"b": "y"}"#).unwrap();
+        let c = pt.from_yaml(r#"{"c": "z"}"#).unwrap();
+        let steps = [("applier", &a), ("applier", &a), ("applier", &b), ("other", &c), ("applier", &b)];
+
+        // Counts the sets filtered by each apply of the steps
+        let run = |capacity: usize| {
+            let computed = Arc::new(AtomicUsize::new(0));
+            let updater = Updater::builder()
+                .ignore_filter(version.clone(), Box::new(CountingFilter(computed.clone())))
+                .applied_config_cache(capacity)
+                .build();
+            let mut managers = ManagedFields::new();
+            let mut live = pt.from_yaml("{}").unwrap();
+            let mut counts = Vec::new();
+            for (manager, config) in steps {
+                let before = computed.load(Ordering::SeqCst);
+                live = updater.apply(&live, config, &version, &mut managers, manager, false).unwrap();
+                counts.push(computed.load(Ordering::SeqCst) - before);
+            }
+            (counts, live, managers)
+        };
+        let (uncached, uncached_live, uncached_managers) = run(0);
+
+        // Re-applying the same config reuses its field set, and another
+        // manager evicts the applier from a full cache
+        let (cached, live, managers) = run(1);
+        let hits: Vec<usize> = uncached.iter().zip(&cached).map(|(u, c)| u - c).collect();
+        assert_eq!(hits, vec![0, 1, 0, 0, 0]);
+        assert_eq!(live.value(), uncached_live.value());
+        assert_eq!(managers, uncached_managers);
+        assert!(!managers.get("applier").unwrap().set().has(&field("a")));
+        assert!(managers.get("applier").unwrap().set().has(&field("b")));
+
+        // With room for both managers, the applier's config stays cached
+        let (cached, _, managers) = run(2);
+        let hits: Vec<usize> = uncached.iter().zip(&cached).map(|(u, c)| u - c).collect();
+        assert_eq!(hits, vec![0, 1, 0, 0, 1]);
+        assert_eq!(managers, uncached_managers);
+    }
+
+    /// RenameConverter that also converts field sets.
+    struct RenameSetConverter(RenameConverter);
+
+    impl Converter for RenameSetConverter {
+        fn convert(&self, obj: &TypedValue, version: &APIVersion) -> Result<TypedValue, ConversionError> {
+            self.0.convert(obj, version)
+        }
+
+        fn convert_set(&self, set: &Set, _from: &APIVersion, to: &APIVersion) -> Option<Result<Set, ConversionError>> {
+            use crate::fieldpath::{Path, PathElement};
+
+            let (from, to) = if to.as_str() == "v1" { ("b", "a") } else { ("a", "b") };
+            let mut out = Set::new();
+            set.iterate(|path| {
+                let renamed = match path.as_slice() {
+                    [PathElement::FieldName(name)] if name == from => {
+                        Path::from_elements(vec![PathElement::field_name(to)])
+                    }
+                    _ => path.clone(),
+                };
+                out.insert(&renamed);
+            });
+            Some(Ok(out))
+        }
+    }
+
+    #[test]
+    fn test_apply_invalid_live_object() {
+        use crate::typed::{as_typed_unvalidated, Parser};
+
+        let pt = Parser::new(
+            r#"types:
+- name: root
+  map:
+    fields:
+    - name: replicas
+      type:
+        scalar: numeric
+    - name: image
+      type:
+        scalar: string
+    - name: spec
+      type:
+        map:
+          fields:
+          - name: paused
+            type:
+              scalar: boolean
+"#,
+        )
+        .unwrap()
+        .type_by_name("root");
+        let version = APIVersion::new("v1");
+        let legacy = crate::value::from_yaml(r#"{"replicas": "two", "image": "a", "spec": {"paused": "no"}}"#).unwrap();
+        let live = as_typed_unvalidated(legacy, &pt.schema, pt.type_ref.clone());
+        let config = pt.from_yaml(r#"{"image": "b", "replicas": 2}"#).unwrap();
+        let apply = |policy: InvalidLiveObjects| {
+            let updater = Updater::builder().invalid_live_objects(policy).build();
+            let mut managers = ManagedFields::new();
+            updater
+                .apply_with_warnings(&live, &config, &version, &mut managers, "applier", false)
+                .map(|(result, warnings)| (result, warnings, managers))
+        };
+
+        assert!(matches!(
+            apply(InvalidLiveObjects::Reject),
+            Err(ApplyError::ValidationError(_))
+        ));
+
+        // Invalid fields the config doesn't set are kept as they are
+        let (result, warnings, managers) = apply(InvalidLiveObjects::Degrade).unwrap();
+        assert_eq!(
+            crate::value::to_json(result.value()).unwrap(),
+            r#"{"image":"b","replicas":2,"spec":{"paused":"no"}}"#
+        );
+        let degraded: Vec<String> = warnings
+            .iter()
+            .filter_map(|w| match w {
+                Warning::DegradedField { path, .. } => Some(path.to_string()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(degraded, vec![".replicas", ".spec.paused"]);
+        let owned = managers.get("applier").unwrap().set();
+        assert!(owned.has(&Path::parse(".replicas").unwrap()));
+        assert!(!owned.has(&Path::parse(".spec.paused").unwrap()));
+
+        // The root can't be degraded
+        let broken = as_typed_unvalidated(crate::value::Value::String("x".into()), &pt.schema, pt.type_ref.clone());
+        let updater = Updater::builder().invalid_live_objects(InvalidLiveObjects::Degrade).build();
+        let mut managers = ManagedFields::new();
+        assert!(updater.apply(&broken, &config, &version, &mut managers, "applier", false).is_err());
+    }
+
+    #[test]
+    fn test_conflicts_across_versions() {
+        use crate::fieldpath::{Path, PathElement};
+        use crate::typed::Parser;
+
+        let pt = |field: &str| {
+            Parser::new(&format!(
+                "types:\n- name: root\n  map:\n    fields:\n    - name: {}\n      type:\n        scalar: string\n",
+                field
+            ))
+            .unwrap()
+            .type_by_name("root")
+        };
+        let (v1, v2) = (APIVersion::new("v1"), APIVersion::new("v2"));
+        let path = |name: &str| Path::from_elements(vec![PathElement::field_name(name)]);
+        let live = pt("b").from_yaml(r#"{"b": "x"}"#).unwrap();
+        let config = pt("b").from_yaml(r#"{"b": "y"}"#).unwrap();
+        let mut owned = Set::new();
+        owned.insert(&path("a"));
+        let before = {
+            let mut managers = ManagedFields::new();
+            managers.insert("old", VersionedSet::new(owned, v1.clone(), false));
+            managers
+        };
+
+        // Conflicts are reported at the applied version, and removed from
+        // the other manager at its own version
+        let updater = Updater::builder()
+            .converter(Box::new(RenameSetConverter(RenameConverter { v1: pt("a"), v2: pt("b") })))
+            .build();
+        let mut managers = before.clone();
+        match updater.apply(&live, &config, &v2, &mut managers, "m", false) {
+            Err(ApplyError::Conflicts(c)) => {
+                assert_eq!(c.len(), 1);
+                assert_eq!(c.iter().next().unwrap().path, path("b"));
+            }
+            other => panic!("expected conflicts, got {:?}", other.map(|_| ())),
+        }
+        updater.apply(&live, &config, &v2, &mut managers, "m", true).unwrap();
+        assert!(managers.get("old").is_none());
+    }
+
+    /// Converts between a v1 schema with field "a" and a v2 schema that
+    /// renamed it to "b".
+    struct RenameConverter {
+        v1: crate::typed::ParseableType,
+        v2: crate::typed::ParseableType,
+    }
+
+    impl Converter for RenameConverter {
+        fn convert(&self, obj: &TypedValue, version: &APIVersion) -> Result<TypedValue, ConversionError> {
+            let (from, to, pt) = match version.as_str() {
+                "v1" => ("b", "a", &self.v1),
+                _ => ("a", "b", &self.v2),
+            };
+            let mut m = obj.value().as_map().cloned().unwrap_or_default();
+            if let Some(v) = m.delete(from) {
+                m.set(to.to_string(), v);
+            }
+            pt.from_value(Value::Map(m)).map_err(|e| ConversionError::incompatible(e.to_string()))
+        }
+    }
+
+    #[test]
+    fn test_apply_across_versions() {
+        use crate::typed::Parser;
+
+        let pt = |field: &str| {
+            Parser::new(&format!(
+                "types:\n- name: root\n  map:\n    fields:\n    - name: {}\n      type:\n        scalar: string\n",
+                field
+            ))
+            .unwrap()
+            .type_by_name("root")
+        };
+        let (v1, v2) = (APIVersion::new("v1"), APIVersion::new("v2"));
+        let live = pt("a").from_yaml(r#"{"a": "x"}"#).unwrap();
+        let config = pt("b").from_yaml(r#"{"b": "y"}"#).unwrap();
+
+        // Without a converter the versions can't be reconciled
+        let mut managers = ManagedFields::new();
+        let other = Parser::new("types:\n- name: other\n  scalar: string\n").unwrap().type_by_name("other");
+        let other = other.from_yaml("z").unwrap();
+        let err = Updater::builder().build().apply(&live, &other, &v2, &mut managers, "m", false);
+        assert!(matches!(err, Err(ApplyError::ConversionError(_))));
+
+        let updater = Updater::builder()
+            .converter(Box::new(RenameConverter { v1: pt("a"), v2: pt("b") }))
+            .build();
+        let result = updater.apply(&live, &config, &v2, &mut managers, "m", false).unwrap();
+        assert_eq!(result.value(), config.value());
+
+        let mut managers = ManagedFields::new();
+        let stored = updater.apply_stored(&live, &v1, &config, &v2, &mut managers, "m", false).unwrap();
+        assert_eq!(stored.value(), pt("a").from_yaml(r#"{"a": "y"}"#).unwrap().value());
+        assert_eq!(managers.get("m").unwrap().api_version(), &v2);
+    }
+
+    #[test]
+    fn test_apply_same_type_without_converter() {
+        use crate::typed::Parser;
+
+        // Schemas of the same type but different versions, without a
+        // converter to go between them
+        let pt = |field: &str| {
+            Parser::new(&format!(
+                "types:\n- name: root\n  map:\n    fields:\n    - name: {}\n      type:\n        scalar: string\n\
+                 \x20   - name: c\n      type:\n        scalar: string\n",
+                field
+            ))
+            .unwrap()
+            .type_by_name("root")
+        };
+        let live = pt("a").from_yaml(r#"{"c": "x"}"#).unwrap();
+        let config = pt("b").from_yaml(r#"{"b": "y"}"#).unwrap();
+        let version = APIVersion::new("v2");
+        let mut managers = ManagedFields::new();
+        let (result, warnings) = Updater::builder()
+            .build()
+            .apply_with_warnings(&live, &config, &version, &mut managers, "m", false)
+            .unwrap();
+        assert_eq!(result.value(), pt("b").from_yaml(r#"{"b": "y", "c": "x"}"#).unwrap().value());
+        assert_eq!(result.schema().types_fingerprint(), config.schema().types_fingerprint());
+        assert!(warnings.iter().any(|w| matches!(w, Warning::UnconvertedLiveObject { version: v } if v == &version)));
+    }
+
+    #[test]
+    fn test_apply_same_version_without_converter() {
+        // Objects of equal schemas aren't converted, so need no converter
+        let type_ref = TypeRef {
+            named_type: Some("object".to_string()),
+            ..Default::default()
+        };
+        let object = |yaml: &str| {
+            // Each object gets a schema of its own, equal to the other's
+            TypedValue::new(crate::value::from_yaml(yaml).unwrap(), create_test_schema(), type_ref.clone())
+        };
+        let (live, config) = (object(r#"{"a": "x"}"#), object(r#"{"b": "y"}"#));
+        let updater = Updater::builder().build();
+        let version = APIVersion::new("v1");
+        let mut managers = ManagedFields::new();
+        let result = updater.apply(&live, &config, &version, &mut managers, "m", false).unwrap();
+        assert_eq!(result.value(), object(r#"{"a": "x", "b": "y"}"#).value());
+
+        // Nor do schemas differing only in options
+        let mut schema = create_test_schema();
+        schema.set_semantic_scalars(true);
+        let config = TypedValue::new(crate::value::from_yaml(r#"{"c": "z"}"#).unwrap(), schema, type_ref.clone());
+        let result = updater.apply(&result, &config, &version, &mut managers, "n", false).unwrap();
+        assert_eq!(result.value(), object(r#"{"a": "x", "b": "y", "c": "z"}"#).value());
+    }
+
     #[test]
     fn test_apply_simple() {
         let updater = Updater::builder().build();
@@ -0,0 +1,135 @@
+//! Warning types for merge operations.
+
+use crate::fieldpath::{APIVersion, Path, Set};
+use std::fmt;
+
+/// Warning describes a suspicious but non-fatal condition met while merging.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum Warning {
+    /// The live object had duplicate items in a list, at the given
+    /// indexes, which the merge collapsed.
+    DuplicateItems {
+        path: String,
+        key: String,
+        indexes: Vec<usize>,
+    },
+    /// A manager's entry was dropped because its version can no longer be
+    /// converted to.
+    ObsoleteManagerVersion { manager: String, version: APIVersion },
+    /// Fields a manager owned were folded into atomic parents after a
+    /// schema change.
+    OwnershipCollapsed { manager: String, fields: Set },
+    /// A manager applied a field the schema marks as deprecated.
+    DeprecatedField {
+        manager: String,
+        path: Path,
+        replacement: Option<String>,
+    },
+    /// A field of the live object held a value the schema doesn't allow,
+    /// and was kept out of the apply as it is.
+    DegradedField { path: Path, error: String },
+    /// The live object's schema differed from the applied config's and no
+    /// converter was configured, so it was used as it is in the version.
+    UnconvertedLiveObject { version: APIVersion },
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Warning::DuplicateItems { path, key, indexes } => {
+                write!(f, "{}: duplicate items with key {} at indexes {:?} were merged", path, key, indexes)
+            }
+            Warning::ObsoleteManagerVersion { manager, version } => {
+                write!(f, "dropped manager '{}' with obsolete version {}", manager, version)
+            }
+            Warning::OwnershipCollapsed { manager, fields } => {
+                write!(f, "ownership of manager '{}' collapsed into atomic fields:", manager)?;
+                fields.iterate(|path| {
+                    let _ = write!(f, " {}", path);
+                });
+                Ok(())
+            }
+            Warning::DeprecatedField {
+                manager,
+                path,
+                replacement,
+            } => {
+                write!(f, "manager '{}' applied deprecated field {}", manager, path)?;
+                match replacement {
+                    Some(replacement) => write!(f, ", use {} instead", replacement),
+                    None => Ok(()),
+                }
+            }
+            Warning::DegradedField { path, error } => {
+                write!(f, "kept invalid field {} as it is: {}", path, error)
+            }
+            Warning::UnconvertedLiveObject { version } => {
+                write!(f, "no converter configured, so the live object was used as it is in {}", version)
+            }
+        }
+    }
+}
+
+/// Warnings is a collection of warnings.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Warnings {
+    warnings: Vec<Warning>,
+}
+
+impl Warnings {
+    /// Creates a new empty Warnings collection.
+    pub fn new() -> Self {
+        Warnings { warnings: Vec::new() }
+    }
+
+    /// Adds a warning.
+    pub fn add(&mut self, warning: Warning) {
+        self.warnings.push(warning);
+    }
+
+    /// Returns true if there are no warnings.
+    pub fn is_empty(&self) -> bool {
+        self.warnings.is_empty()
+    }
+
+    /// Returns the number of warnings.
+    pub fn len(&self) -> usize {
+        self.warnings.len()
+    }
+
+    /// Returns an iterator over the warnings.
+    pub fn iter(&self) -> impl Iterator<Item = &Warning> {
+        self.warnings.iter()
+    }
+
+    /// Returns the managers that were dropped because their versions are
+    /// missing, with the versions they were recorded at.
+    pub fn dropped_managers(&self) -> impl Iterator<Item = (&str, &APIVersion)> {
+        self.warnings.iter().filter_map(|warning| match warning {
+            Warning::ObsoleteManagerVersion { manager, version } => Some((manager.as_str(), version)),
+            _ => None,
+        })
+    }
+}
+
+impl IntoIterator for Warnings {
+    type Item = Warning;
+    type IntoIter = std::vec::IntoIter<Warning>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.warnings.into_iter()
+    }
+}
+
+impl fmt::Display for Warnings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, warning) in self.warnings.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", warning)?;
+        }
+        Ok(())
+    }
+}
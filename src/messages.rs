@@ -0,0 +1,166 @@
+//! Catalogs of the messages of validation errors and conflicts.
+//!
+//! Errors display their messages in English. Embedders showing errors to
+//! users in another language, or with their own phrasing, render them with
+//! a MessageCatalog instead of matching on the English text: the catalog
+//! gets each message with its arguments, and returns its text or leaves it
+//! to the English default.
+//!
+//! ```
+//! use structured_merge_diff::messages::{Message, MessageCatalog};
+//! use structured_merge_diff::typed::ValidationError;
+//!
+//! struct French;
+//!
+//! impl MessageCatalog for French {
+//!     fn text(&self, message: &Message<'_>) -> Option<String> {
+//!         match message {
+//!             Message::UnknownField { path, field } => Some(format!("{}: champ inconnu : {}", path, field)),
+//!             _ => None,
+//!         }
+//!     }
+//! }
+//!
+//! let err = ValidationError::unknown_field(".spec", "replica");
+//! assert_eq!(err.localized(&French), ".spec: champ inconnu : replica");
+//! assert_eq!(err.to_string(), ".spec: unknown field: replica");
+//! ```
+
+use crate::fieldpath::{Operation, Path};
+
+/// Message is a user-facing message with its arguments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Message<'a> {
+    /// A value of the wrong type.
+    TypeMismatch {
+        path: &'a str,
+        expected: &'a str,
+        actual: &'a str,
+    },
+    /// A field the schema doesn't declare.
+    UnknownField { path: &'a str, field: &'a str },
+    /// A required field that is missing.
+    MissingField { path: &'a str, field: &'a str },
+    /// Items of an associative list sharing a key.
+    DuplicateKey {
+        path: &'a str,
+        key: &'a str,
+        indexes: &'a [usize],
+    },
+    /// Another invalid value, described in English by `message`.
+    InvalidValue { path: &'a str, message: &'a str },
+    /// A value with more units than its schema allows.
+    TooLarge {
+        path: &'a str,
+        unit: &'a str,
+        max: usize,
+        actual: usize,
+    },
+    /// A problem with the schema, described in English by `message`.
+    SchemaError { message: &'a str },
+    /// A field an apply changes that `manager` owns.
+    Conflict {
+        manager: &'a str,
+        operation: Operation,
+        path: &'a Path,
+    },
+    /// The heading of the fields conflicting with a manager, in
+    /// Conflicts::error. `manager` is named as kube-apiserver names it.
+    ConflictsWith { manager: &'a str },
+}
+
+impl Message<'_> {
+    /// Returns the English text of the message.
+    pub fn english(&self) -> String {
+        match self {
+            Message::TypeMismatch { path, expected, actual } => {
+                format!("{}: type mismatch: expected {}, got {}", path, expected, actual)
+            }
+            Message::UnknownField { path, field } => format!("{}: unknown field: {}", path, field),
+            Message::MissingField { path, field } => format!("{}: missing required field: {}", path, field),
+            Message::DuplicateKey { path, key, indexes } => {
+                format!("{}: duplicate key in list: {} at indexes {:?}", path, key, indexes)
+            }
+            Message::InvalidValue { path, message } => format!("{}: {}", path, message),
+            Message::TooLarge {
+                path,
+                unit,
+                max,
+                actual,
+            } => {
+                format!("{}: has {} {}, more than the maximum of {}", path, actual, unit, max)
+            }
+            Message::SchemaError { message } => message.to_string(),
+            Message::Conflict {
+                manager,
+                operation,
+                path,
+            } => match operation {
+                Operation::Update => format!("conflict with manager '{}' (Update) at {}", manager, path),
+                Operation::Apply => format!("conflict with manager '{}' at {}", manager, path),
+            },
+            Message::ConflictsWith { manager } => format!("conflicts with {}:", manager),
+        }
+    }
+}
+
+/// MessageCatalog supplies the text of messages.
+pub trait MessageCatalog: Send + Sync {
+    /// Returns the text of `message`, or None to use the English text.
+    fn text(&self, message: &Message<'_>) -> Option<String>;
+}
+
+/// English is the catalog of the messages errors display.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct English;
+
+impl MessageCatalog for English {
+    fn text(&self, message: &Message<'_>) -> Option<String> {
+        Some(message.english())
+    }
+}
+
+/// Returns the text of `message` in `catalog`, falling back to English.
+pub(crate) fn render(catalog: &dyn MessageCatalog, message: &Message<'_>) -> String {
+    catalog.text(message).unwrap_or_else(|| message.english())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merge::{Conflict, Conflicts};
+
+    struct Terse;
+
+    impl MessageCatalog for Terse {
+        fn text(&self, message: &Message<'_>) -> Option<String> {
+            match message {
+                Message::ConflictsWith { manager } => Some(format!("owned by {}:", manager)),
+                _ => None,
+            }
+        }
+    }
+
+    #[test]
+    fn test_localized_conflicts() {
+        let conflicts: Conflicts = [
+            Conflict::new("kubectl", Path::parse(".a").unwrap()),
+            Conflict::new("hpa", Path::parse(".b").unwrap()).with_operation(Operation::Update),
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(conflicts.localized(&English), conflicts.error());
+        assert_eq!(
+            conflicts.localized(&Terse),
+            "owned by \"hpa\":\n- .b\nowned by \"kubectl\":\n- .a"
+        );
+        for conflict in conflicts.iter() {
+            assert_eq!(conflict.localized(&Terse), conflict.to_string());
+        }
+        assert_eq!(
+            conflicts.iter().nth(1).unwrap().to_string(),
+            "conflict with manager 'hpa' (Update) at .b"
+        );
+    }
+}
@@ -5,17 +5,23 @@
 
 use crate::schema::{
     Atom, ElementRelationship, List, Map as SchemaMap, Scalar, Schema, StructField, TypeDef,
-    TypeRef, Union, UnionField,
+    TypeRef, Union, UnionField, EMBEDDED_RESOURCE_TYPE,
 };
 use super::schema::{
     AdditionalProperties, OpenAPIDocument, OpenAPIv2, OpenAPIv3, SchemaV2, SchemaV3,
 };
+use crate::typed::validate_defaults;
 use std::collections::BTreeMap;
 
+/// The definition name of Kubernetes resource quantities.
+const QUANTITY_TYPE_NAME: &str = "io.k8s.apimachinery.pkg.api.resource.Quantity";
+
 /// Converter from OpenAPI to SMD schema.
 pub struct OpenAPIConverter {
     /// Errors encountered during conversion.
     errors: Vec<ConversionError>,
+    /// Whether a converted type refers to an embedded resource.
+    uses_embedded_resource: bool,
 }
 
 /// Error during OpenAPI to SMD conversion.
@@ -46,18 +52,29 @@ pub struct ConversionResult {
 impl OpenAPIConverter {
     /// Create a new converter.
     pub fn new() -> Self {
-        OpenAPIConverter { errors: Vec::new() }
+        OpenAPIConverter {
+            errors: Vec::new(),
+            uses_embedded_resource: false,
+        }
     }
 
     /// Convert an OpenAPI document to SMD schema.
     pub fn convert(&mut self, doc: &OpenAPIDocument) -> ConversionResult {
         self.errors.clear();
+        self.uses_embedded_resource = false;
 
         let schema = match doc {
             OpenAPIDocument::V2(v2) => self.convert_v2(v2),
             OpenAPIDocument::V3(v3) => self.convert_v3(v3),
         };
 
+        // Defaults are kept as declared, but ones that don't fit are reported
+        if let Err(errs) = validate_defaults(&schema) {
+            for err in errs {
+                self.add_error(&format!("{}.{}", err.type_name, err.field), &err.message);
+            }
+        }
+
         ConversionResult {
             schema,
             errors: std::mem::take(&mut self.errors),
@@ -74,7 +91,7 @@ impl OpenAPIConverter {
             }
         }
 
-        Schema::with_types(types)
+        self.finish_schema(types)
     }
 
     /// Convert OpenAPI v3 document to SMD schema.
@@ -87,7 +104,26 @@ impl OpenAPIConverter {
             }
         }
 
-        Schema::with_types(types)
+        self.finish_schema(types)
+    }
+
+    /// Builds the schema, adding the built-in types if any type refers to them.
+    fn finish_schema(&self, types: Vec<TypeDef>) -> Schema {
+        let schema = Schema::with_types(types);
+        if self.uses_embedded_resource {
+            schema.with_builtin_types()
+        } else {
+            schema
+        }
+    }
+
+    /// Returns a reference to the built-in embedded resource type.
+    fn embedded_resource_type_ref(&mut self) -> TypeRef {
+        self.uses_embedded_resource = true;
+        TypeRef {
+            named_type: Some(EMBEDDED_RESOURCE_TYPE.to_string()),
+            ..Default::default()
+        }
     }
 
     /// Convert a v2 schema to SMD TypeDef.
@@ -97,7 +133,14 @@ impl OpenAPIConverter {
         schema: &SchemaV2,
         definitions: &BTreeMap<String, SchemaV2>,
     ) -> Option<TypeDef> {
-        let atom = self.schema_v2_to_atom(schema, definitions, name);
+        let atom = if name == QUANTITY_TYPE_NAME {
+            Atom {
+                scalar: Some(Scalar::Quantity),
+                ..Default::default()
+            }
+        } else {
+            self.schema_v2_to_atom(schema, definitions, name)
+        };
         Some(TypeDef {
             name: name.to_string(),
             atom,
@@ -111,7 +154,14 @@ impl OpenAPIConverter {
         schema: &SchemaV3,
         definitions: &BTreeMap<String, SchemaV3>,
     ) -> Option<TypeDef> {
-        let atom = self.schema_v3_to_atom(schema, definitions, name);
+        let atom = if name == QUANTITY_TYPE_NAME {
+            Atom {
+                scalar: Some(Scalar::Quantity),
+                ..Default::default()
+            }
+        } else {
+            self.schema_v3_to_atom(schema, definitions, name)
+        };
         Some(TypeDef {
             name: name.to_string(),
             atom,
@@ -133,7 +183,7 @@ impl OpenAPIConverter {
         // Handle x-kubernetes-int-or-string
         if schema.x_kubernetes_int_or_string == Some(true) {
             return Atom {
-                scalar: Some(Scalar::String), // treated as string in SMD
+                scalar: Some(Scalar::IntOrString),
                 ..Default::default()
             };
         }
@@ -212,7 +262,7 @@ impl OpenAPIConverter {
         // Handle x-kubernetes-int-or-string
         if schema.x_kubernetes_int_or_string == Some(true) {
             return Atom {
-                scalar: Some(Scalar::String),
+                scalar: Some(Scalar::IntOrString),
                 ..Default::default()
             };
         }
@@ -255,10 +305,14 @@ impl OpenAPIConverter {
     }
 
     /// Convert string type with format.
-    fn convert_string_type(&self, _format: &Option<String>) -> Atom {
-        // All string formats map to Scalar::String in SMD
+    fn convert_string_type(&self, format: &Option<String>) -> Atom {
+        // Timestamps get their own scalar; other string formats map to Scalar::String
+        let scalar = match format.as_deref() {
+            Some("date-time") => Scalar::Time,
+            _ => Scalar::String,
+        };
         Atom {
-            scalar: Some(Scalar::String),
+            scalar: Some(scalar),
             ..Default::default()
         }
     }
@@ -342,6 +396,13 @@ impl OpenAPIConverter {
                         name: name.clone(),
                         field_type,
                         default: prop_schema.default.clone(),
+                        retain_keys: retains_keys(prop_schema.x_kubernetes_patch_strategy.as_deref()),
+                        aliases: Vec::new(),
+                        deprecated: false,
+                        replacement: None,
+                        max_length: None,
+                        max_items: None,
+                        max_properties: None,
                     }
                 })
                 .collect();
@@ -414,6 +475,13 @@ impl OpenAPIConverter {
                         name: name.clone(),
                         field_type,
                         default: prop_schema.default.clone(),
+                        retain_keys: retains_keys(prop_schema.x_kubernetes_patch_strategy.as_deref()),
+                        aliases: Vec::new(),
+                        deprecated: false,
+                        replacement: None,
+                        max_length: None,
+                        max_items: None,
+                        max_properties: None,
                     }
                 })
                 .collect();
@@ -490,6 +558,10 @@ impl OpenAPIConverter {
             };
         }
 
+        if schema.x_kubernetes_embedded_resource == Some(true) {
+            return self.embedded_resource_type_ref();
+        }
+
         // For inline schemas, we create an inline type
         let atom = self.schema_v2_to_atom(schema, definitions, path);
         TypeRef {
@@ -513,6 +585,10 @@ impl OpenAPIConverter {
             };
         }
 
+        if schema.x_kubernetes_embedded_resource == Some(true) {
+            return self.embedded_resource_type_ref();
+        }
+
         // For inline schemas, we create an inline type
         let atom = self.schema_v3_to_atom(schema, definitions, path);
         TypeRef {
@@ -736,6 +812,12 @@ impl Default for OpenAPIConverter {
     }
 }
 
+/// Returns true if a patch strategy such as "merge,retainKeys" includes
+/// retainKeys.
+fn retains_keys(strategy: Option<&str>) -> bool {
+    strategy.is_some_and(|s| s.split(',').any(|s| s.trim() == "retainKeys"))
+}
+
 /// Convert an OpenAPI document to SMD schema.
 pub fn convert_openapi_to_schema(doc: &OpenAPIDocument) -> ConversionResult {
     let mut converter = OpenAPIConverter::new();
@@ -766,9 +848,9 @@ mod tests {
         let result = convert_openapi_to_schema(&doc);
 
         assert!(result.errors.is_empty());
-        assert_eq!(result.schema.types.len(), 1);
+        assert_eq!(result.schema.types().len(), 1);
 
-        let pet_type = &result.schema.types[0];
+        let pet_type = &result.schema.types()[0];
         assert_eq!(pet_type.name, "Pet");
         assert!(pet_type.atom.map.is_some());
 
@@ -802,7 +884,7 @@ mod tests {
 
         assert!(result.errors.is_empty());
 
-        let container_list = result.schema.types.iter().find(|t| t.name == "ContainerList").unwrap();
+        let container_list = result.schema.types().iter().find(|t| t.name == "ContainerList").unwrap();
         assert!(container_list.atom.list.is_some());
 
         let list = container_list.atom.list.as_ref().unwrap();
@@ -829,7 +911,7 @@ mod tests {
 
         assert!(result.errors.is_empty());
 
-        let labels = result.schema.types.iter().find(|t| t.name == "Labels").unwrap();
+        let labels = result.schema.types().iter().find(|t| t.name == "Labels").unwrap();
         assert!(labels.atom.map.is_some());
 
         let map = labels.atom.map.as_ref().unwrap();
@@ -862,9 +944,9 @@ mod tests {
         let result = convert_openapi_to_schema(&doc);
 
         assert!(result.errors.is_empty());
-        assert_eq!(result.schema.types.len(), 1);
+        assert_eq!(result.schema.types().len(), 1);
 
-        let pet = &result.schema.types[0];
+        let pet = &result.schema.types()[0];
         assert_eq!(pet.name, "Pet");
     }
 
@@ -886,10 +968,130 @@ mod tests {
 
         assert!(result.errors.is_empty());
 
-        let raw_ext = result.schema.types.iter().find(|t| t.name == "RawExtension").unwrap();
+        let raw_ext = result.schema.types().iter().find(|t| t.name == "RawExtension").unwrap();
         assert!(raw_ext.atom.map.is_some());
 
         let map = raw_ext.atom.map.as_ref().unwrap();
         assert!(map.element_type.named_type.as_ref().unwrap().contains("untyped"));
     }
+
+    #[test]
+    fn test_convert_scalar_subtypes() {
+        let json = r#"{
+            "swagger": "2.0",
+            "info": {"title": "Test", "version": "1.0"},
+            "definitions": {
+                "io.k8s.apimachinery.pkg.api.resource.Quantity": {
+                    "type": "string"
+                },
+                "Probe": {
+                    "type": "object",
+                    "properties": {
+                        "port": {"x-kubernetes-int-or-string": true},
+                        "since": {"type": "string", "format": "date-time"}
+                    }
+                }
+            }
+        }"#;
+
+        let doc = OpenAPIDocument::from_json(json).unwrap();
+        let result = convert_openapi_to_schema(&doc);
+        assert!(result.errors.is_empty());
+
+        let quantity = result.schema.types().iter().find(|t| t.name == QUANTITY_TYPE_NAME).unwrap();
+        assert_eq!(quantity.atom.scalar, Some(Scalar::Quantity));
+
+        let probe = result.schema.types().iter().find(|t| t.name == "Probe").unwrap();
+        let map = probe.atom.map.as_ref().unwrap();
+        let scalar_of = |name: &str| {
+            map.fields.iter().find(|f| f.name == name).unwrap().field_type.inlined.scalar
+        };
+        assert_eq!(scalar_of("port"), Some(Scalar::IntOrString));
+        assert_eq!(scalar_of("since"), Some(Scalar::Time));
+    }
+
+    #[test]
+    fn test_convert_embedded_resource() {
+        use crate::typed::ParseableType;
+
+        let json = r#"{
+            "swagger": "2.0",
+            "info": {"title": "Test", "version": "1.0"},
+            "definitions": {
+                "Revision": {
+                    "type": "object",
+                    "properties": {
+                        "data": {"type": "object", "x-kubernetes-embedded-resource": true}
+                    }
+                }
+            }
+        }"#;
+
+        let doc = OpenAPIDocument::from_json(json).unwrap();
+        let result = convert_openapi_to_schema(&doc);
+        assert!(result.errors.is_empty());
+        assert!(result.schema.find_named_type(EMBEDDED_RESOURCE_TYPE).is_some());
+
+        let pt = ParseableType {
+            schema: result.schema,
+            type_ref: TypeRef {
+                named_type: Some("Revision".to_string()),
+                ..Default::default()
+            },
+        };
+        assert!(pt
+            .from_yaml(r#"{"data": {"apiVersion": "v1", "kind": "ConfigMap", "data": {"a": "b"}}}"#)
+            .is_ok());
+        assert!(pt.from_yaml(r#"{"data": {"kind": "ConfigMap"}}"#).is_err());
+        assert!(pt.from_yaml(r#"{"data": {"apiVersion": "v1", "kind": "ConfigMap", "metadata": {"name": 1}}}"#).is_err());
+    }
+
+    #[test]
+    fn test_convert_retain_keys() {
+        use crate::typed::ParseableType;
+
+        let json = r##"{
+            "swagger": "2.0",
+            "info": {"title": "Test", "version": "1.0"},
+            "definitions": {
+                "DeploymentSpec": {
+                    "type": "object",
+                    "properties": {
+                        "strategy": {
+                            "$ref": "#/definitions/DeploymentStrategy",
+                            "x-kubernetes-patch-strategy": "retainKeys"
+                        }
+                    }
+                },
+                "DeploymentStrategy": {
+                    "type": "object",
+                    "properties": {
+                        "type": {"type": "string"},
+                        "rollingUpdate": {
+                            "type": "object",
+                            "properties": {"maxSurge": {"type": "integer"}}
+                        }
+                    }
+                }
+            }
+        }"##;
+
+        let doc = OpenAPIDocument::from_json(json).unwrap();
+        let result = convert_openapi_to_schema(&doc);
+        assert!(result.errors.is_empty());
+
+        let pt = ParseableType {
+            schema: result.schema,
+            type_ref: TypeRef {
+                named_type: Some("DeploymentSpec".to_string()),
+                ..Default::default()
+            },
+        };
+        let live = pt
+            .from_yaml(r#"{"strategy": {"type": "RollingUpdate", "rollingUpdate": {"maxSurge": 1}}}"#)
+            .unwrap();
+        let applied = pt.from_yaml(r#"{"strategy": {"type": "Recreate"}}"#).unwrap();
+        let merged = live.merge(&applied).unwrap();
+        assert_eq!(merged.value(), applied.value());
+    }
 }
@@ -355,6 +355,7 @@ impl OpenAPIDocument {
 
 /// Error type for OpenAPI parsing.
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub enum OpenAPIParseError {
     /// Invalid JSON.
     InvalidJson(String),
@@ -0,0 +1,229 @@
+//! Time spent validating and merging, enabled by the `profiling` feature.
+//!
+//! A profiling session records, on the current thread, how long validation
+//! and merges spend in each named type and, for validation, each path, with
+//! the items of lists counted together as `[*]`. The hottest entries point
+//! at the subtrees worth marking atomic.
+//!
+//! Times are inclusive of nested values, so a parent counts the time of its
+//! children too, and include the cost of profiling itself.
+
+use crate::fieldpath::Path;
+use crate::schema::TypeRef;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Phase is the operation a timing was recorded in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[non_exhaustive]
+pub enum Phase {
+    Validate,
+    Merge,
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Phase::Validate => write!(f, "validate"),
+            Phase::Merge => write!(f, "merge"),
+        }
+    }
+}
+
+/// Timing is the number of times a type or path was visited and the time
+/// spent there.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Timing {
+    pub calls: u64,
+    pub total: Duration,
+}
+
+/// HotSpot is a type or path with its timing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HotSpot {
+    pub phase: Phase,
+    /// The name of the type, or the path with list items as `[*]`.
+    pub name: String,
+    pub timing: Timing,
+}
+
+impl fmt::Display for HotSpot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}: {:?} in {} calls",
+            self.phase, self.name, self.timing.total, self.timing.calls
+        )
+    }
+}
+
+/// Profile holds the timings recorded by a profiling session.
+#[derive(Debug, Clone, Default)]
+pub struct Profile {
+    types: HashMap<(Phase, String), Timing>,
+    paths: HashMap<(Phase, String), Timing>,
+}
+
+impl Profile {
+    /// Returns the `n` named types that took the longest.
+    pub fn top_types(&self, n: usize) -> Vec<HotSpot> {
+        top(&self.types, n)
+    }
+
+    /// Returns the `n` paths that took the longest. The root is left out,
+    /// as it takes all the time.
+    pub fn top_paths(&self, n: usize) -> Vec<HotSpot> {
+        top(&self.paths, n)
+    }
+}
+
+fn top(timings: &HashMap<(Phase, String), Timing>, n: usize) -> Vec<HotSpot> {
+    let mut spots: Vec<HotSpot> = timings
+        .iter()
+        .map(|((phase, name), timing)| HotSpot {
+            phase: *phase,
+            name: name.clone(),
+            timing: *timing,
+        })
+        .collect();
+    spots.sort_by(|a, b| (b.timing.total, a.phase, &a.name).cmp(&(a.timing.total, b.phase, &b.name)));
+    spots.truncate(n);
+    spots
+}
+
+thread_local! {
+    static SESSION: RefCell<Option<Profile>> = const { RefCell::new(None) };
+}
+
+/// Profiling is a profiling session on the current thread, which ends when
+/// it's finished or dropped.
+#[must_use = "profiling stops when the session is dropped"]
+pub struct Profiling {
+    _private: (),
+}
+
+impl Profiling {
+    /// Ends the session and returns what it recorded.
+    pub fn finish(self) -> Profile {
+        SESSION.with(|session| session.borrow_mut().take()).unwrap_or_default()
+    }
+}
+
+impl Drop for Profiling {
+    fn drop(&mut self) {
+        SESSION.with(|session| session.borrow_mut().take());
+    }
+}
+
+/// Starts a profiling session on the current thread, replacing any running
+/// one.
+pub fn start() -> Profiling {
+    SESSION.with(|session| *session.borrow_mut() = Some(Profile::default()));
+    Profiling { _private: () }
+}
+
+/// Timer records the time until it's dropped against a type and path.
+pub(crate) struct Timer {
+    phase: Phase,
+    type_name: Option<String>,
+    path: Option<String>,
+    start: Instant,
+}
+
+/// Starts timing a value of type `type_ref` at `path`, if a session is
+/// running on the current thread.
+pub(crate) fn timer(phase: Phase, type_ref: &TypeRef, path: Option<&Path>) -> Option<Timer> {
+    if !SESSION.with(|session| session.borrow().is_some()) {
+        return None;
+    }
+    Some(Timer {
+        phase,
+        type_name: type_ref.named_type.clone(),
+        path: path.filter(|path| !path.is_empty()).map(Path::generalized),
+        start: Instant::now(),
+    })
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        let elapsed = self.start.elapsed();
+        SESSION.with(|session| {
+            let mut session = session.borrow_mut();
+            let Some(profile) = session.as_mut() else {
+                return;
+            };
+            let entries = [
+                (&mut profile.types, self.type_name.take()),
+                (&mut profile.paths, self.path.take()),
+            ];
+            for (timings, name) in entries {
+                if let Some(name) = name {
+                    let timing = timings.entry((self.phase, name)).or_default();
+                    timing.calls += 1;
+                    timing.total += elapsed;
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::typed::Parser;
+
+    #[test]
+    fn test_profile() {
+        let pt = Parser::new(
+            r#"types:
+- name: root
+  map:
+    fields:
+    - name: containers
+      type:
+        list:
+          elementType:
+            namedType: container
+          elementRelationship: associative
+          keys: [name]
+- name: container
+  map:
+    fields:
+    - name: name
+      type:
+        scalar: string
+    - name: image
+      type:
+        scalar: string
+"#,
+        )
+        .unwrap()
+        .type_by_name("root");
+        let yaml = r#"{"containers": [{"name": "a", "image": "x"}, {"name": "b", "image": "y"}]}"#;
+        let unprofiled = pt.from_yaml(yaml).unwrap();
+
+        let session = start();
+        let obj = pt.from_yaml(yaml).unwrap();
+        obj.merge(&unprofiled).unwrap();
+        let profile = session.finish();
+
+        let types = profile.top_types(10);
+        let count = |spots: &[HotSpot], phase: Phase, name: &str| {
+            spots
+                .iter()
+                .find(|spot| spot.phase == phase && spot.name == name)
+                .map(|spot| spot.timing.calls)
+        };
+        assert_eq!(count(&types, Phase::Validate, "container"), Some(2));
+        assert_eq!(count(&types, Phase::Merge, "container"), Some(2));
+        let paths = profile.top_paths(10);
+        assert_eq!(count(&paths, Phase::Validate, ".containers[*]"), Some(2));
+        assert_eq!(count(&paths, Phase::Validate, ".containers[*].image"), Some(2));
+        assert_eq!(profile.top_paths(1).len(), 1);
+
+        // Nothing is timed outside of a session
+        assert!(timer(Phase::Merge, obj.type_ref(), None).is_none());
+    }
+}
@@ -0,0 +1,318 @@
+//! Merge tests as data, after the fixture package of the Go implementation.
+//!
+//! A TestCase lists operations by managers and the object and managed
+//! fields expected once they ran. Operations that succeed are run a second
+//! time on their own result, which must change nothing.
+//!
+//! ```
+//! use structured_merge_diff::managed_fields;
+//! use structured_merge_diff::scenario::{Op, TestCase};
+//! use structured_merge_diff::typed::Parser;
+//!
+//! let pt = Parser::new("types:\n- name: root\n  map:\n    elementType:\n      scalar: string\n")
+//!     .unwrap()
+//!     .type_by_name("root");
+//! TestCase {
+//!     ops: vec![
+//!         Op::apply("kubectl", "v1", r#"{"a": "x"}"#),
+//!         Op::update("controller", "v1", r#"{"a": "x", "b": "y"}"#),
+//!     ],
+//!     object: Some(r#"{"a": "x", "b": "y"}"#.to_string()),
+//!     managed: Some(managed_fields! {
+//!         "kubectl" => { version: "v1", applied: true, paths: [["f:a"]] },
+//!         "controller" => { version: "v1", applied: false, paths: [["f:b"]] },
+//!     }),
+//!     ..Default::default()
+//! }
+//! .test(&pt)
+//! .unwrap();
+//! ```
+
+use crate::fieldpath::{APIVersion, ManagedFields};
+use crate::merge::{Conflicts, UpdateError, Updater};
+use crate::typed::{ParseableType, TypedValue};
+use std::fmt;
+
+/// OpKind is what an operation does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpKind {
+    Apply,
+    ForceApply,
+    ExtractApply,
+    Update,
+}
+
+impl fmt::Display for OpKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OpKind::Apply => write!(f, "apply"),
+            OpKind::ForceApply => write!(f, "force apply"),
+            OpKind::ExtractApply => write!(f, "extract apply"),
+            OpKind::Update => write!(f, "update"),
+        }
+    }
+}
+
+/// Op is one operation of a TestCase: a manager applying or updating an
+/// object given in YAML.
+#[derive(Debug, Clone)]
+pub struct Op {
+    pub kind: OpKind,
+    pub manager: String,
+    pub api_version: APIVersion,
+    pub object: String,
+    /// The conflicts the operation fails with, if it should fail. Failed
+    /// operations change nothing.
+    pub conflicts: Option<Conflicts>,
+}
+
+impl Op {
+    /// Returns an operation of the given kind.
+    pub fn new(
+        kind: OpKind,
+        manager: impl Into<String>,
+        api_version: impl Into<APIVersion>,
+        object: impl Into<String>,
+    ) -> Self {
+        Op {
+            kind,
+            manager: manager.into(),
+            api_version: api_version.into(),
+            object: object.into(),
+            conflicts: None,
+        }
+    }
+
+    /// Returns an apply.
+    pub fn apply(manager: impl Into<String>, api_version: impl Into<APIVersion>, object: impl Into<String>) -> Self {
+        Op::new(OpKind::Apply, manager, api_version, object)
+    }
+
+    /// Returns an apply that takes over conflicting fields.
+    pub fn force_apply(
+        manager: impl Into<String>,
+        api_version: impl Into<APIVersion>,
+        object: impl Into<String>,
+    ) -> Self {
+        Op::new(OpKind::ForceApply, manager, api_version, object)
+    }
+
+    /// Returns an extract apply, which keeps the fields the manager owned.
+    pub fn extract_apply(
+        manager: impl Into<String>,
+        api_version: impl Into<APIVersion>,
+        object: impl Into<String>,
+    ) -> Self {
+        Op::new(OpKind::ExtractApply, manager, api_version, object)
+    }
+
+    /// Returns an update.
+    pub fn update(manager: impl Into<String>, api_version: impl Into<APIVersion>, object: impl Into<String>) -> Self {
+        Op::new(OpKind::Update, manager, api_version, object)
+    }
+
+    /// Expects the operation to fail with these conflicts, in any order.
+    pub fn with_conflicts(mut self, conflicts: Conflicts) -> Self {
+        self.conflicts = Some(conflicts);
+        self
+    }
+
+    pub(crate) fn run(
+        &self,
+        updater: &Updater,
+        live: &TypedValue,
+        config: &TypedValue,
+        managers: &mut ManagedFields,
+    ) -> Result<TypedValue, UpdateError> {
+        let (manager, version) = (self.manager.as_str(), &self.api_version);
+        let applied = match self.kind {
+            OpKind::Apply => updater.apply(live, config, version, managers, manager, false),
+            OpKind::ForceApply => updater.apply(live, config, version, managers, manager, true),
+            OpKind::ExtractApply => updater.extract_apply(live, config, version, managers, manager, false),
+            OpKind::Update => return updater.update(live, config, version, managers, manager),
+        };
+        applied.map_err(UpdateError::from)
+    }
+}
+
+/// TestCase is a list of operations and the state expected after them.
+#[derive(Debug, Clone, Default)]
+pub struct TestCase {
+    pub ops: Vec<Op>,
+    /// The live object before the first operation, in YAML. Empty if None.
+    pub live: Option<String>,
+    /// The managed fields before the first operation.
+    pub managers: ManagedFields,
+    /// The expected object after the last operation, in YAML.
+    pub object: Option<String>,
+    /// The expected managed fields after the last operation.
+    pub managed: Option<ManagedFields>,
+    /// Skips running successful operations a second time.
+    pub skip_idempotency_check: bool,
+}
+
+/// ScenarioError reports the first operation or expectation that failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScenarioError {
+    pub message: String,
+}
+
+impl ScenarioError {
+    fn new(message: impl Into<String>) -> Self {
+        ScenarioError {
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ScenarioError {}
+
+impl TestCase {
+    /// Runs the test case on objects of type `pt` with a default Updater.
+    pub fn test(&self, pt: &ParseableType) -> Result<(), ScenarioError> {
+        self.test_with_updater(pt, &Updater::builder().build())
+    }
+
+    /// Runs the test case on objects of type `pt` with `updater`.
+    pub fn test_with_updater(&self, pt: &ParseableType, updater: &Updater) -> Result<(), ScenarioError> {
+        let parse = |what: &str, yaml: &str| {
+            pt.from_yaml(yaml)
+                .map_err(|e| ScenarioError::new(format!("{}: {}", what, e)))
+        };
+        let mut live = match &self.live {
+            Some(yaml) => parse("live object", yaml)?,
+            None => parse("live object", "{}")?.empty(),
+        };
+        let mut managers = self.managers.clone();
+
+        for (i, op) in self.ops.iter().enumerate() {
+            let context = format!("op {} ({} by {:?})", i, op.kind, op.manager);
+            let config = parse(&context, &op.object)?;
+            let result = op.run(updater, &live, &config, &mut managers);
+            match (result, &op.conflicts) {
+                (Err(UpdateError::Conflicts(got)), Some(expected)) => {
+                    if conflict_messages(&got) != conflict_messages(expected) {
+                        return Err(ScenarioError::new(format!(
+                            "{}: expected conflicts {:?}, got {:?}",
+                            context, expected, got
+                        )));
+                    }
+                }
+                (Err(e), _) => return Err(ScenarioError::new(format!("{}: {}", context, e))),
+                (Ok(_), Some(expected)) => {
+                    return Err(ScenarioError::new(format!(
+                        "{}: expected conflicts {:?}, got none",
+                        context, expected
+                    )));
+                }
+                (Ok(result), None) => {
+                    if !self.skip_idempotency_check {
+                        let mut again = managers.clone();
+                        let rerun = op
+                            .run(updater, &result, &config, &mut again)
+                            .map_err(|e| ScenarioError::new(format!("{} run again: {}", context, e)))?;
+                        if !same(&rerun, &result) || again != managers {
+                            return Err(ScenarioError::new(format!(
+                                "{} run again changed the object to {:?} and managers to {}",
+                                context,
+                                rerun.value(),
+                                again
+                            )));
+                        }
+                    }
+                    live = result;
+                }
+            }
+        }
+
+        if let Some(yaml) = &self.object {
+            let expected = parse("expected object", yaml)?;
+            if !same(&live, &expected) {
+                return Err(ScenarioError::new(format!(
+                    "expected object {:?}, got {:?}",
+                    expected.value(),
+                    live.value()
+                )));
+            }
+        }
+        if let Some(expected) = &self.managed {
+            if managers != *expected {
+                return Err(ScenarioError::new(format!(
+                    "expected managers {}, got {}",
+                    expected, managers
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the conflict messages, sorted so that order doesn't matter.
+fn conflict_messages(conflicts: &Conflicts) -> Vec<String> {
+    let mut messages: Vec<String> = conflicts.iter().map(|c| c.to_string()).collect();
+    messages.sort();
+    messages
+}
+
+/// Returns true if the objects are the same, as compare sees them.
+pub(crate) fn same(lhs: &TypedValue, rhs: &TypedValue) -> bool {
+    lhs.value() == rhs.value() || lhs.compare(rhs).is_ok_and(|c| c.is_same())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fieldpath::Path;
+    use crate::managed_fields;
+    use crate::merge::Conflict;
+    use crate::typed::Parser;
+
+    #[test]
+    fn test_scenario() {
+        let pt = Parser::new("types:\n- name: root\n  map:\n    elementType:\n      scalar: string\n")
+            .unwrap()
+            .type_by_name("root");
+        let mut conflicts = Conflicts::new();
+        conflicts.add(Conflict::new("kubectl", Path::parse(".a").unwrap()));
+
+        let tc = TestCase {
+            ops: vec![
+                Op::apply("kubectl", "v1", r#"{"a": "x"}"#),
+                Op::apply("other", "v1", r#"{"a": "y"}"#).with_conflicts(conflicts),
+                Op::force_apply("other", "v1", r#"{"a": "y", "b": "z"}"#),
+                Op::extract_apply("kubectl", "v1", r#"{"c": "w"}"#),
+            ],
+            object: Some(r#"{"a": "y", "b": "z", "c": "w"}"#.to_string()),
+            managed: Some(managed_fields! {
+                "kubectl" => { version: "v1", applied: true, paths: [["f:c"]] },
+                "other" => { version: "v1", applied: true, paths: [["f:a"], ["f:b"]] },
+            }),
+            ..Default::default()
+        };
+        assert_eq!(tc.test(&pt), Ok(()));
+
+        let wrong = TestCase {
+            object: Some(r#"{"a": "y"}"#.to_string()),
+            ..tc.clone()
+        };
+        assert!(wrong.test(&pt).unwrap_err().message.starts_with("expected object"));
+
+        let unexpected = TestCase {
+            ops: vec![
+                Op::apply("kubectl", "v1", r#"{"a": "x"}"#),
+                Op::apply("other", "v1", r#"{"a": "y"}"#),
+            ],
+            managed: None,
+            object: None,
+            ..Default::default()
+        };
+        let err = unexpected.test(&pt).unwrap_err();
+        assert!(err.message.starts_with("op 1 (apply by \"other\"): conflict"));
+    }
+}
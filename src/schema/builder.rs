@@ -0,0 +1,323 @@
+//! Fluent construction of schemas in code.
+
+use super::elements::{Atom, ElementRelationship, List, Map, Scalar, Schema, StructField, TypeDef, TypeRef, Union};
+
+impl TypeRef {
+    /// Returns a reference to the named type.
+    pub fn named(name: impl Into<String>) -> Self {
+        TypeRef {
+            named_type: Some(name.into()),
+            ..Default::default()
+        }
+    }
+
+    /// Returns an inlined scalar type.
+    pub fn scalar(scalar: Scalar) -> Self {
+        TypeRef::inline(Atom {
+            scalar: Some(scalar),
+            ..Default::default()
+        })
+    }
+
+    /// Returns an inlined type.
+    pub fn inline(atom: Atom) -> Self {
+        TypeRef {
+            inlined: Box::new(atom),
+            ..Default::default()
+        }
+    }
+
+    /// Overrides the element relationship of the referred type.
+    pub fn with_element_relationship(mut self, element_relationship: ElementRelationship) -> Self {
+        self.element_relationship = Some(element_relationship);
+        self
+    }
+}
+
+/// SchemaBuilder builds a schema type by type.
+///
+/// ```
+/// use structured_merge_diff::schema::{Scalar, SchemaBuilder, TypeRef};
+///
+/// let schema = SchemaBuilder::new()
+///     .map_type("pod", |m| m.field("spec", TypeRef::named("podSpec")))
+///     .map_type("podSpec", |m| {
+///         m.field("nodeName", TypeRef::scalar(Scalar::String))
+///             .field("containers", TypeRef::named("containers"))
+///     })
+///     .list_type("containers", TypeRef::named("container"), |l| l.keys(["name"]))
+///     .map_type("container", |m| m.field("name", TypeRef::scalar(Scalar::String)))
+///     .build();
+/// assert!(schema.find_named_type("podSpec").is_some());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SchemaBuilder {
+    types: Vec<TypeDef>,
+}
+
+impl SchemaBuilder {
+    /// Creates a builder of an empty schema.
+    pub fn new() -> Self {
+        SchemaBuilder::default()
+    }
+
+    /// Adds a named map type, defined by `f`.
+    pub fn map_type(self, name: impl Into<String>, f: impl FnOnce(TypeDefBuilder) -> TypeDefBuilder) -> Self {
+        self.type_def(f(TypeDefBuilder::map(name)).build())
+    }
+
+    /// Adds a named list type with elements of `element_type`, defined by
+    /// `f`. Lists are atomic unless `f` says otherwise.
+    pub fn list_type(
+        self,
+        name: impl Into<String>,
+        element_type: TypeRef,
+        f: impl FnOnce(TypeDefBuilder) -> TypeDefBuilder,
+    ) -> Self {
+        self.type_def(f(TypeDefBuilder::list(name, element_type)).build())
+    }
+
+    /// Adds a named scalar type.
+    pub fn scalar_type(self, name: impl Into<String>, scalar: Scalar) -> Self {
+        self.type_def(TypeDefBuilder::scalar(name, scalar).build())
+    }
+
+    /// Adds a type definition.
+    pub fn type_def(mut self, def: TypeDef) -> Self {
+        self.types.push(def);
+        self
+    }
+
+    /// Returns the schema of the added types.
+    pub fn build(self) -> Schema {
+        Schema::with_types(self.types)
+    }
+}
+
+/// TypeDefBuilder builds a type definition. The methods of map and list
+/// types apply to whichever the type is, and do nothing for other types.
+#[derive(Debug, Clone, Default)]
+pub struct TypeDefBuilder {
+    def: TypeDef,
+}
+
+impl TypeDefBuilder {
+    /// Starts a map type without fields. Unknown fields are rejected unless
+    /// an element type is set.
+    pub fn map(name: impl Into<String>) -> Self {
+        TypeDefBuilder::new(
+            name,
+            Atom {
+                map: Some(Map::new()),
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Starts an atomic list type with elements of `element_type`.
+    pub fn list(name: impl Into<String>, element_type: TypeRef) -> Self {
+        TypeDefBuilder::new(
+            name,
+            Atom {
+                list: Some(List {
+                    element_type,
+                    element_relationship: ElementRelationship::Atomic,
+                    keys: Vec::new(),
+                }),
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Starts a scalar type.
+    pub fn scalar(name: impl Into<String>, scalar: Scalar) -> Self {
+        TypeDefBuilder::new(
+            name,
+            Atom {
+                scalar: Some(scalar),
+                ..Default::default()
+            },
+        )
+    }
+
+    fn new(name: impl Into<String>, atom: Atom) -> Self {
+        TypeDefBuilder {
+            def: TypeDef {
+                name: name.into(),
+                atom,
+            },
+        }
+    }
+
+    /// Adds a field of the given type to a map.
+    pub fn field(self, name: impl Into<String>, field_type: TypeRef) -> Self {
+        self.field_with(name, field_type, |_| {})
+    }
+
+    /// Adds a field of the given type to a map, letting `f` set its other
+    /// attributes, such as its default.
+    pub fn field_with(
+        mut self,
+        name: impl Into<String>,
+        field_type: TypeRef,
+        f: impl FnOnce(&mut StructField),
+    ) -> Self {
+        if let Some(map) = &mut self.def.atom.map {
+            let mut field = StructField {
+                name: name.into(),
+                field_type,
+                ..Default::default()
+            };
+            f(&mut field);
+            let mut fields = std::mem::take(&mut map.fields);
+            fields.push(field);
+            // Rebuilt so the field lookup cache sees the new field
+            *map = Map::with_all(
+                fields,
+                map.element_type.clone(),
+                map.element_relationship,
+                map.unions.clone(),
+            );
+        }
+        self
+    }
+
+    /// Sets the type of the unknown fields of a map or the elements of a
+    /// list.
+    pub fn element_type(mut self, element_type: TypeRef) -> Self {
+        if let Some(map) = &mut self.def.atom.map {
+            map.element_type = element_type;
+        } else if let Some(list) = &mut self.def.atom.list {
+            list.element_type = element_type;
+        }
+        self
+    }
+
+    /// Sets the relationship between the items of a map or list.
+    pub fn element_relationship(mut self, element_relationship: ElementRelationship) -> Self {
+        if let Some(map) = &mut self.def.atom.map {
+            map.element_relationship = element_relationship;
+        } else if let Some(list) = &mut self.def.atom.list {
+            list.element_relationship = element_relationship;
+        }
+        self
+    }
+
+    /// Makes a map or list atomic.
+    pub fn atomic(self) -> Self {
+        self.element_relationship(ElementRelationship::Atomic)
+    }
+
+    /// Makes a list associative, keyed by the given fields of its items.
+    /// Without keys, the list is a set of scalars.
+    pub fn keys<S: Into<String>>(mut self, keys: impl IntoIterator<Item = S>) -> Self {
+        if let Some(list) = &mut self.def.atom.list {
+            list.element_relationship = ElementRelationship::Associative;
+            list.keys = keys.into_iter().map(Into::into).collect();
+        }
+        self
+    }
+
+    /// Makes a list a set of scalars.
+    pub fn set(self) -> Self {
+        self.keys(Vec::<String>::new())
+    }
+
+    /// Adds a union of fields to a map.
+    pub fn union(mut self, union: Union) -> Self {
+        if let Some(map) = &mut self.def.atom.map {
+            map.unions.push(union);
+        }
+        self
+    }
+
+    /// Returns the type definition.
+    pub fn build(self) -> TypeDef {
+        self.def
+    }
+
+    /// Returns the type inlined into a reference, ignoring its name.
+    pub fn into_type_ref(self) -> TypeRef {
+        TypeRef::inline(self.def.atom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schema_builder() {
+        let built = SchemaBuilder::new()
+            .map_type("root", |m| {
+                m.field("name", TypeRef::scalar(Scalar::String))
+                    .field_with("replicas", TypeRef::scalar(Scalar::Numeric), |f| {
+                        f.default = Some(serde_json::json!(1));
+                    })
+                    .field(
+                        "tags",
+                        TypeDefBuilder::list("", TypeRef::scalar(Scalar::String))
+                            .set()
+                            .into_type_ref(),
+                    )
+                    .field("items", TypeRef::named("items"))
+                    .field(
+                        "labels",
+                        TypeRef::named("labels").with_element_relationship(ElementRelationship::Atomic),
+                    )
+            })
+            .list_type("items", TypeRef::named("item"), |l| l.keys(["name"]))
+            .map_type("item", |m| m.field("name", TypeRef::scalar(Scalar::String)))
+            .map_type("labels", |m| m.element_type(TypeRef::scalar(Scalar::String)))
+            .build();
+
+        let parsed: Schema = serde_yaml::from_str(
+            r#"types:
+- name: root
+  map:
+    fields:
+    - name: name
+      type:
+        scalar: string
+    - name: replicas
+      type:
+        scalar: numeric
+      default: 1
+    - name: tags
+      type:
+        list:
+          elementType:
+            scalar: string
+          elementRelationship: associative
+    - name: items
+      type:
+        namedType: items
+    - name: labels
+      type:
+        namedType: labels
+        elementRelationship: atomic
+- name: items
+  list:
+    elementType:
+      namedType: item
+    elementRelationship: associative
+    keys: [name]
+- name: item
+  map:
+    fields:
+    - name: name
+      type:
+        scalar: string
+- name: labels
+  map:
+    elementType:
+      scalar: string
+"#,
+        )
+        .unwrap();
+        assert_eq!(built, parsed);
+
+        let root = built.find_named_type("root").unwrap().atom.map.as_ref().unwrap();
+        assert!(root.find_field("labels").is_some());
+    }
+}
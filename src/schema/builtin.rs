@@ -0,0 +1,121 @@
+//! Built-in types that generated schemas can refer to.
+
+use super::elements::{Schema, TypeDef};
+use once_cell::sync::Lazy;
+
+/// The untyped type whose containers are atomic.
+pub const UNTYPED_ATOMIC_TYPE: &str = "__untyped_atomic_";
+
+/// The untyped type whose maps are merged granularly.
+pub const UNTYPED_DEDUCED_TYPE: &str = "__untyped_deduced_";
+
+/// An embedded Kubernetes object (`x-kubernetes-embedded-resource`).
+///
+/// Its apiVersion, kind and metadata are typed and a non-empty object must
+/// set apiVersion and kind; all other fields are preserved as deduced values.
+pub const EMBEDDED_RESOURCE_TYPE: &str = "__embedded_resource_";
+
+const BUILTIN_TYPES_YAML: &str = r#"types:
+- name: __untyped_atomic_
+  scalar: untyped
+  list:
+    elementType:
+      namedType: __untyped_atomic_
+    elementRelationship: atomic
+  map:
+    elementType:
+      namedType: __untyped_atomic_
+    elementRelationship: atomic
+- name: __untyped_deduced_
+  scalar: untyped
+  list:
+    elementType:
+      namedType: __untyped_atomic_
+    elementRelationship: atomic
+  map:
+    elementType:
+      namedType: __untyped_deduced_
+    elementRelationship: separable
+- name: __embedded_resource_
+  map:
+    fields:
+    - name: apiVersion
+      type:
+        scalar: string
+    - name: kind
+      type:
+        scalar: string
+    - name: metadata
+      type:
+        namedType: __embedded_object_meta_
+    elementType:
+      namedType: __untyped_deduced_
+- name: __embedded_object_meta_
+  map:
+    fields:
+    - name: name
+      type:
+        scalar: string
+    - name: generateName
+      type:
+        scalar: string
+    - name: namespace
+      type:
+        scalar: string
+    - name: labels
+      type:
+        map:
+          elementType:
+            scalar: string
+    - name: annotations
+      type:
+        map:
+          elementType:
+            scalar: string
+    elementType:
+      namedType: __untyped_deduced_
+"#;
+
+// The definitions are a constant that test_builtin_types parses, so parsing
+// them can't fail at runtime.
+#[allow(clippy::expect_used)]
+static BUILTIN_TYPES: Lazy<Vec<TypeDef>> = Lazy::new(|| {
+    serde_yaml::from_str::<Schema>(BUILTIN_TYPES_YAML)
+        .map(|schema| schema.types().to_vec())
+        .expect("builtin types parse")
+});
+
+/// Returns the definitions of all built-in types.
+pub fn builtin_types() -> &'static [TypeDef] {
+    &BUILTIN_TYPES
+}
+
+impl Schema {
+    /// Returns this schema with the built-in types added, except those it
+    /// already defines.
+    pub fn with_builtin_types(self) -> Self {
+        let mut types = self.types().to_vec();
+        for def in builtin_types() {
+            if self.find_named_type(&def.name).is_none() {
+                types.push(def.clone());
+            }
+        }
+        let mut schema = Schema::with_types(types);
+        schema.set_options(self.options().clone());
+        schema
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_types() {
+        let names: Vec<&str> = builtin_types().iter().map(|def| def.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [UNTYPED_ATOMIC_TYPE, UNTYPED_DEDUCED_TYPE, EMBEDDED_RESOURCE_TYPE, "__embedded_object_meta_"]
+        );
+    }
+}
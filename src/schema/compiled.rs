@@ -0,0 +1,286 @@
+//! Compiled schemas: a flattened, pre-resolved form of a schema that can be
+//! stored and loaded without resolving any types again.
+
+use super::elements::{Atom, ElementRelationship, Schema, SchemaOptions, TypeDef, TypeRef};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// The format version of serialized compiled schemas. Loading a compiled
+/// schema of another version fails, so callers can recompile.
+pub const COMPILED_SCHEMA_VERSION: u32 = 1;
+
+/// TypeId identifies a type in the table of a CompiledSchema.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct TypeId(u32);
+
+impl TypeId {
+    /// Returns the position of the type in the table.
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+/// CompiledType is a resolved type whose nested references point into the
+/// table of its CompiledSchema.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompiledType {
+    /// The named type this entry resolves, or None for an inlined type.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// The `elementRelationship` override of the reference.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub element_relationship: Option<ElementRelationship>,
+    /// The atom the reference resolves to, with overrides applied.
+    pub atom: Atom,
+    /// The types of the map's fields, in declaration order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub field_types: Vec<Option<TypeId>>,
+    /// The type of the map's other fields.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub map_element_type: Option<TypeId>,
+    /// The type of the list's items.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub list_element_type: Option<TypeId>,
+    /// The key fields of an associative list.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub list_keys: Vec<String>,
+}
+
+/// CompiledSchemaError is returned when serializing a compiled schema fails,
+/// or when loading a malformed one or one of another format version.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompiledSchemaError {
+    pub message: String,
+}
+
+impl std::fmt::Display for CompiledSchemaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid compiled schema: {}", self.message)
+    }
+}
+
+impl std::error::Error for CompiledSchemaError {}
+
+/// CompiledSchema is an immutable schema whose every named reference, with
+/// each override it appears with, is resolved ahead of time.
+///
+/// Schemas built from it with into_schema resolve named references by
+/// lookup, and it serializes with to_bytes so that processes can load it
+/// instead of parsing and resolving the schema at startup.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CompiledSchema {
+    version: u32,
+    types: Vec<TypeDef>,
+    options: SchemaOptions,
+    table: Vec<CompiledType>,
+    #[serde(skip)]
+    index: HashMap<String, [Option<TypeId>; 4]>,
+}
+
+/// The slot of an override in the index.
+fn override_slot(er: Option<ElementRelationship>) -> usize {
+    match er {
+        None => 0,
+        Some(ElementRelationship::Associative) => 1,
+        Some(ElementRelationship::Atomic) => 2,
+        Some(ElementRelationship::Separable) => 3,
+    }
+}
+
+impl Schema {
+    /// Compiles the schema and its current options.
+    pub fn compile(&self) -> CompiledSchema {
+        let mut compiled = CompiledSchema {
+            version: COMPILED_SCHEMA_VERSION,
+            types: self.types().to_vec(),
+            options: self.options().clone(),
+            table: Vec::new(),
+            index: HashMap::new(),
+        };
+        for def in self.types() {
+            let tr = TypeRef {
+                named_type: Some(def.name.clone()),
+                ..Default::default()
+            };
+            compiled.intern(self, &tr);
+        }
+        compiled
+    }
+}
+
+impl CompiledSchema {
+    /// Adds the resolution of `tr` and everything it references to the
+    /// table, returning its id, or None if it doesn't resolve.
+    fn intern(&mut self, schema: &Schema, tr: &TypeRef) -> Option<TypeId> {
+        let slot = override_slot(tr.element_relationship);
+        if let Some(name) = &tr.named_type {
+            if let Some(id) = self.index.get(name).and_then(|ids| ids[slot]) {
+                return Some(id);
+            }
+        }
+        let atom = schema.resolve(tr)?;
+        let id = TypeId(self.table.len() as u32);
+        if let Some(name) = &tr.named_type {
+            // Registered before recursing so recursive types end
+            self.index.entry(name.clone()).or_default()[slot] = Some(id);
+        }
+        self.table.push(CompiledType {
+            name: tr.named_type.clone(),
+            element_relationship: tr.element_relationship,
+            atom: atom.clone(),
+            field_types: Vec::new(),
+            map_element_type: None,
+            list_element_type: None,
+            list_keys: Vec::new(),
+        });
+
+        let mut field_types = Vec::new();
+        let mut map_element_type = None;
+        if let Some(map) = &atom.map {
+            field_types = map.fields.iter().map(|f| self.intern(schema, &f.field_type)).collect();
+            map_element_type = self.intern(schema, &map.element_type);
+        }
+        let list_element_type = atom.list.as_ref().and_then(|list| self.intern(schema, &list.element_type));
+
+        let entry = &mut self.table[id.index()];
+        entry.field_types = field_types;
+        entry.map_element_type = map_element_type;
+        entry.list_element_type = list_element_type;
+        entry.list_keys = atom.list.as_ref().map(|list| list.keys.clone()).unwrap_or_default();
+        Some(id)
+    }
+
+    /// Returns the id of the named type without overrides.
+    pub fn type_id(&self, name: &str) -> Option<TypeId> {
+        self.index.get(name).and_then(|ids| ids[0])
+    }
+
+    /// Returns the type with the given id.
+    ///
+    /// Panics if the id is not from this schema.
+    pub fn get(&self, id: TypeId) -> &CompiledType {
+        &self.table[id.index()]
+    }
+
+    /// Returns the number of types in the table.
+    pub fn len(&self) -> usize {
+        self.table.len()
+    }
+
+    /// Returns true if the table has no types.
+    pub fn is_empty(&self) -> bool {
+        self.table.is_empty()
+    }
+
+    /// Returns the resolved atom of a named reference, if it was compiled.
+    pub(crate) fn lookup(&self, tr: &TypeRef) -> Option<&Atom> {
+        let ids = self.index.get(tr.named_type.as_ref()?)?;
+        ids[override_slot(tr.element_relationship)].map(|id| &self.table[id.index()].atom)
+    }
+
+    /// Serializes the compiled schema.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, CompiledSchemaError> {
+        serde_json::to_vec(self).map_err(|e| CompiledSchemaError { message: e.to_string() })
+    }
+
+    /// Loads a compiled schema serialized with to_bytes.
+    pub fn from_bytes(data: &[u8]) -> Result<CompiledSchema, CompiledSchemaError> {
+        let error = |message: String| CompiledSchemaError { message };
+        let mut compiled: CompiledSchema = serde_json::from_slice(data).map_err(|e| error(e.to_string()))?;
+        if compiled.version != COMPILED_SCHEMA_VERSION {
+            return Err(error(format!(
+                "format version {} is not {}",
+                compiled.version, COMPILED_SCHEMA_VERSION
+            )));
+        }
+        let len = compiled.table.len();
+        for (i, entry) in compiled.table.iter().enumerate() {
+            let refs = entry.field_types.iter().chain([&entry.map_element_type, &entry.list_element_type]);
+            if refs.flatten().any(|id| id.index() >= len) {
+                return Err(error(format!("type {} references a type outside the table", i)));
+            }
+            if let Some(name) = &entry.name {
+                let slot = override_slot(entry.element_relationship);
+                compiled.index.entry(name.clone()).or_default()[slot] = Some(TypeId(i as u32));
+            }
+        }
+        Ok(compiled)
+    }
+
+    /// Returns a schema with the compiled types and options that resolves
+    /// named references from the table.
+    pub fn into_schema(self) -> Schema {
+        let mut schema = Schema::with_types(self.types.clone());
+        schema.set_options(self.options.clone());
+        schema.set_compiled(Arc::new(self));
+        schema
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCHEMA: &str = r#"types:
+- name: node
+  map:
+    fields:
+    - name: name
+      type:
+        scalar: string
+    - name: children
+      type:
+        list:
+          elementType:
+            namedType: node
+          elementRelationship: associative
+          keys: [name]
+    - name: labels
+      type:
+        namedType: labels
+        elementRelationship: atomic
+- name: labels
+  map:
+    elementType:
+      scalar: string
+"#;
+
+    #[test]
+    fn test_compile() {
+        let schema: Schema = serde_yaml::from_str(SCHEMA).unwrap();
+        let compiled = schema.compile();
+
+        let node = compiled.get(compiled.type_id("node").unwrap());
+        assert_eq!(node.field_types.len(), 3);
+        let children = compiled.get(node.field_types[1].unwrap());
+        assert_eq!(children.list_keys, ["name"]);
+        // The recursive reference points back at the node entry
+        assert_eq!(children.list_element_type, compiled.type_id("node"));
+        let labels = compiled.get(node.field_types[2].unwrap());
+        assert_eq!(labels.element_relationship, Some(ElementRelationship::Atomic));
+        assert_eq!(labels.atom.map.as_ref().unwrap().element_relationship, ElementRelationship::Atomic);
+        assert_ne!(node.field_types[2], compiled.type_id("labels"));
+
+        let loaded = CompiledSchema::from_bytes(&compiled.to_bytes().unwrap()).unwrap();
+        assert_eq!(loaded.len(), compiled.len());
+        assert_eq!(loaded.type_id("labels"), compiled.type_id("labels"));
+
+        let loaded = loaded.into_schema();
+        assert_eq!(loaded.fingerprint(), schema.fingerprint());
+        let tr = TypeRef {
+            named_type: Some("labels".to_string()),
+            element_relationship: Some(ElementRelationship::Atomic),
+            ..Default::default()
+        };
+        let atom = loaded.resolve(&tr).unwrap();
+        assert_eq!(atom.map.unwrap().element_relationship, ElementRelationship::Atomic);
+
+        let mut stale: serde_json::Value = serde_json::from_slice(&compiled.to_bytes().unwrap()).unwrap();
+        stale["version"] = serde_json::json!(0);
+        let err = CompiledSchema::from_bytes(&serde_json::to_vec(&stale).unwrap()).unwrap_err();
+        assert_eq!(err.to_string(), "invalid compiled schema: format version 0 is not 1");
+        assert!(CompiledSchema::from_bytes(b"{").is_err());
+    }
+}
@@ -1,24 +1,37 @@
 //! Core schema elements and type definitions.
 
+use super::builtin::{builtin_types, UNTYPED_ATOMIC_TYPE};
+use super::compiled::CompiledSchema;
 use once_cell::sync::OnceCell;
 use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
-use std::sync::Mutex;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex, PoisonError};
 
 /// Schema is a list of named types.
 ///
-/// Schema types are indexed in a map before the first search so this type
-/// should be considered immutable.
+/// Schema types are indexed in a map before the first search, so they are
+/// changed through types_mut, which clears the index and other caches.
 #[derive(Debug, Default, Serialize, Deserialize)]
 pub struct Schema {
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
-    pub types: Vec<TypeDef>,
+    types: Vec<TypeDef>,
 
     #[serde(skip)]
     type_map: OnceCell<HashMap<String, TypeDef>>,
 
     #[serde(skip)]
     resolved_types: Mutex<HashMap<TypeRefKey, Atom>>,
+
+    #[serde(skip)]
+    options: SchemaOptions,
+
+    #[serde(skip)]
+    types_hash: OnceCell<u64>,
+
+    #[serde(skip)]
+    compiled: Option<Arc<CompiledSchema>>,
 }
 
 impl Clone for Schema {
@@ -27,10 +40,132 @@ impl Clone for Schema {
             types: self.types.clone(),
             type_map: OnceCell::new(),
             resolved_types: Mutex::new(HashMap::new()),
+            options: self.options.clone(),
+            types_hash: self.types_hash.clone(),
+            compiled: self.compiled.clone(),
         }
     }
 }
 
+/// SchemaOptions are the options of a schema, which aren't part of its
+/// serialized form. Kept together, copies of the schema can't leave one out.
+#[derive(Debug, Clone, Default, Hash, Serialize, Deserialize)]
+pub(super) struct SchemaOptions {
+    pub(super) override_scope: OverrideScope,
+    pub(super) deduced_list_keys: Vec<String>,
+    pub(super) semantic_scalars: bool,
+    pub(super) list_ordering: ListOrdering,
+    pub(super) preserve_unknown_fields: bool,
+    // Compiled before the option existed, schemas have the default
+    #[serde(default)]
+    pub(super) numeric_keys: NumericKeys,
+    #[serde(default)]
+    pub(super) heterogeneous_lists: HeterogeneousLists,
+}
+
+/// Adapts a Hasher to io::Write.
+struct HashWriter(DefaultHasher);
+
+impl std::io::Write for HashWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.write(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// OverrideScope controls how far an `elementRelationship` override on a
+/// TypeRef reaches when the reference is resolved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub enum OverrideScope {
+    /// The override only applies to the referenced type itself; nested named
+    /// types keep their own element relationships.
+    #[default]
+    Referenced,
+    /// The override is inherited by every nested list or map type reachable
+    /// from the reference, unless a nested reference carries its own override.
+    Transitive,
+}
+
+/// ListOrdering controls the order of items in merged associative lists.
+///
+/// Both orderings are deterministic: the result only depends on the order of
+/// items in the live and applied lists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub enum ListOrdering {
+    /// Items only in the live list come first in live order, followed by the
+    /// applied items in applied order. Applying a proper subset of a set
+    /// keeps the live order.
+    #[default]
+    LiveFirst,
+    /// Applied items come first in applied order, followed by items only in
+    /// the live list in live order.
+    AppliedFirst,
+}
+
+/// NumericKeys controls how numbers in the keys of associative list items,
+/// and so in the paths of their field sets, are represented.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub enum NumericKeys {
+    /// Numbers are kept as written or defaulted, so an item with `page: 2`
+    /// and one whose `page` defaults to `2.0` have different keys.
+    #[default]
+    AsWritten,
+    /// Floats with an integral value are keyed as ints, the canonical form
+    /// of PathElement::with_canonical_numbers, so such items have the same
+    /// key whichever form their writer used. Field sets recorded with the
+    /// other form aren't rewritten.
+    Integral,
+}
+
+/// HeterogeneousLists controls lists of the deduced schema whose items mix
+/// scalars, lists and maps, as malformed objects often have, e.g. a list of
+/// env vars with a `NAME=value` string among `{name, value}` maps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[non_exhaustive]
+pub enum HeterogeneousLists {
+    /// Such lists are atomic, like other deduced lists without a deduced
+    /// key: they are owned and replaced as a whole.
+    #[default]
+    Atomic,
+    /// Such lists fail validation.
+    Reject,
+    /// Such lists are sets of their items, each owned and merged on its
+    /// own and identified by its whole value. Items must be distinct, and
+    /// lists or maps among them are atomic. A list is a set when it or the
+    /// list it is merged with mixes kinds, so configs should too: a config
+    /// of scalars alone is atomic and owns the whole list.
+    PerElement,
+}
+
+/// OverrideError describes an `elementRelationship` override that can't be
+/// honored by the type it refers to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OverrideError {
+    /// Name of the top-level type containing the offending reference.
+    pub type_name: String,
+    /// Description of the contradiction.
+    pub message: String,
+}
+
+impl std::fmt::Display for OverrideError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.type_name, self.message)
+    }
+}
+
+impl std::error::Error for OverrideError {}
+
 /// Key for caching resolved type references.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 struct TypeRefKey {
@@ -111,6 +246,13 @@ pub enum Scalar {
     String,
     Boolean,
     Untyped,
+    /// A resource quantity such as "1Gi" or "500m", as a string or number.
+    Quantity,
+    /// An integer or a string.
+    #[serde(rename = "int-or-string")]
+    IntOrString,
+    /// An RFC 3339 timestamp string.
+    Time,
 }
 
 /// ElementRelationship is an enum of the different possible relationships
@@ -211,6 +353,45 @@ pub struct StructField {
     /// Default value for the field, None if not present.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub default: Option<serde_json::Value>,
+
+    /// RetainKeys makes an applied map replace the keys of the field's live
+    /// map instead of merging into it: live keys absent from the applied
+    /// map are dropped. This mirrors the strategic merge patch `retainKeys`
+    /// strategy, e.g. for a Deployment's `strategy`. Applies dropping keys
+    /// other managers own conflict with them.
+    #[serde(default, skip_serializing_if = "is_false", rename = "retainKeys")]
+    pub retain_keys: bool,
+
+    /// Aliases are former names of the field, e.g. from renames in older
+    /// versions. Parsing can map them to the field when asked to.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+
+    /// Deprecated marks a field that should no longer be set. Applies
+    /// setting it are warned about.
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub deprecated: bool,
+
+    /// Replacement names what to use instead of a deprecated field, e.g.
+    /// the path of its successor.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replacement: Option<String>,
+
+    /// MaxLength bounds the number of characters of a string value.
+    #[serde(default, skip_serializing_if = "Option::is_none", rename = "maxLength")]
+    pub max_length: Option<usize>,
+
+    /// MaxItems bounds the number of items of a list value.
+    #[serde(default, skip_serializing_if = "Option::is_none", rename = "maxItems")]
+    pub max_items: Option<usize>,
+
+    /// MaxProperties bounds the number of entries of a map value.
+    #[serde(default, skip_serializing_if = "Option::is_none", rename = "maxProperties")]
+    pub max_properties: Option<usize>,
+}
+
+fn is_false(b: &bool) -> bool {
+    !*b
 }
 
 /// List represents a type which contains zero or more elements, all of the
@@ -243,7 +424,157 @@ impl Schema {
             types,
             type_map: OnceCell::new(),
             resolved_types: Mutex::new(HashMap::new()),
+            options: SchemaOptions::default(),
+            types_hash: OnceCell::new(),
+            compiled: None,
+        }
+    }
+
+    /// Returns how far `elementRelationship` overrides reach.
+    pub fn override_scope(&self) -> OverrideScope {
+        self.options.override_scope
+    }
+
+    /// Sets how far `elementRelationship` overrides reach.
+    pub fn set_override_scope(&mut self, scope: OverrideScope) {
+        self.options.override_scope = scope;
+        // Resolved atoms depend on the scope
+        self.resolved_types = Mutex::new(HashMap::new());
+        self.compiled = None;
+    }
+
+    /// Returns this schema with the given override scope.
+    pub fn with_override_scope(mut self, scope: OverrideScope) -> Self {
+        self.set_override_scope(scope);
+        self
+    }
+
+    /// Returns the candidate key fields for deduced lists.
+    pub fn deduced_list_keys(&self) -> &[String] {
+        &self.options.deduced_list_keys
+    }
+
+    /// Sets the candidate key fields for deduced lists.
+    ///
+    /// Atomic lists of the deduced schema whose elements are all maps with a
+    /// distinct value for one of these fields are treated as associative lists
+    /// keyed by that field. Candidates are tried in order.
+    pub fn set_deduced_list_keys(&mut self, keys: Vec<String>) {
+        self.options.deduced_list_keys = keys;
+    }
+
+    /// Returns true if quantity, int-or-string and time scalars are compared
+    /// by the value they denote rather than by representation.
+    pub fn semantic_scalars(&self) -> bool {
+        self.options.semantic_scalars
+    }
+
+    /// Sets whether quantity, int-or-string and time scalars are compared by
+    /// the value they denote, so that e.g. "1Gi" and "1073741824" are equal.
+    pub fn set_semantic_scalars(&mut self, enabled: bool) {
+        self.options.semantic_scalars = enabled;
+    }
+
+    /// Returns the order of items in merged associative lists.
+    pub fn list_ordering(&self) -> ListOrdering {
+        self.options.list_ordering
+    }
+
+    /// Sets the order of items in merged associative lists.
+    pub fn set_list_ordering(&mut self, ordering: ListOrdering) {
+        self.options.list_ordering = ordering;
+    }
+
+    /// Returns how numbers in the keys of associative list items are
+    /// represented.
+    pub fn numeric_keys(&self) -> NumericKeys {
+        self.options.numeric_keys
+    }
+
+    /// Sets how numbers in the keys of associative list items are
+    /// represented.
+    pub fn set_numeric_keys(&mut self, numeric_keys: NumericKeys) {
+        self.options.numeric_keys = numeric_keys;
+    }
+
+    /// Returns how untyped lists mixing scalars, lists and maps are handled.
+    pub fn heterogeneous_lists(&self) -> HeterogeneousLists {
+        self.options.heterogeneous_lists
+    }
+
+    /// Sets how untyped lists mixing scalars, lists and maps are handled.
+    pub fn set_heterogeneous_lists(&mut self, heterogeneous_lists: HeterogeneousLists) {
+        self.options.heterogeneous_lists = heterogeneous_lists;
+    }
+
+    /// Returns true if maps accept fields the schema doesn't declare.
+    pub fn preserve_unknown_fields(&self) -> bool {
+        self.options.preserve_unknown_fields
+    }
+
+    /// Sets whether maps accept fields the schema doesn't declare, as when
+    /// objects written by a newer version of the schema are merged. Such
+    /// fields are typed as untyped atomic values, so they are kept by merges
+    /// and owned as a whole. Enabling this adds the untyped atomic type to
+    /// the schema if it lacks it.
+    pub fn set_preserve_unknown_fields(&mut self, enabled: bool) {
+        self.options.preserve_unknown_fields = enabled;
+        if enabled && self.find_named_type(UNTYPED_ATOMIC_TYPE).is_none() {
+            let untyped = builtin_types().iter().filter(|def| def.name == UNTYPED_ATOMIC_TYPE);
+            self.types_mut().extend(untyped.cloned());
+        }
+        // Resolved atoms depend on the option
+        self.resolved_types = Mutex::new(HashMap::new());
+        self.compiled = None;
+    }
+
+    /// Returns the type definitions.
+    pub fn types(&self) -> &[TypeDef] {
+        &self.types
+    }
+
+    /// Returns the type definitions for changing them, clearing the index
+    /// and caches built from them.
+    pub fn types_mut(&mut self) -> &mut Vec<TypeDef> {
+        self.type_map = OnceCell::new();
+        self.types_hash = OnceCell::new();
+        self.resolved_types = Mutex::new(HashMap::new());
+        self.compiled = None;
+        &mut self.types
+    }
+
+    /// Returns a hash identifying the schema's content and options.
+    ///
+    /// Schemas with equal types and options have the same fingerprint,
+    /// whether or not they are the same instance.
+    pub fn fingerprint(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.types_fingerprint().hash(&mut hasher);
+        self.options.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Returns a hash identifying the schema's types, without its options.
+    pub(crate) fn types_fingerprint(&self) -> u64 {
+        *self.types_hash.get_or_init(|| {
+            let mut hasher = HashWriter(DefaultHasher::new());
+            // Serializing into a hasher can't fail.
+            let _ = serde_json::to_writer(&mut hasher, &self.types);
+            hasher.0.finish()
+        })
+    }
+
+    /// Returns true if a field of the schema has retainKeys set.
+    pub(crate) fn has_retain_keys(&self) -> bool {
+        fn atom_has(atom: &Atom) -> bool {
+            let list_has = atom.list.as_ref().is_some_and(|l| atom_has(&l.element_type.inlined));
+            let map_has = atom.map.as_ref().is_some_and(|m| {
+                atom_has(&m.element_type.inlined)
+                    || m.fields.iter().any(|f| f.retain_keys || atom_has(&f.field_type.inlined))
+            });
+            list_has || map_has
         }
+        self.types.iter().any(|t| atom_has(&t.atom))
     }
 
     /// FindNamedType returns the referenced TypeDef, if it exists.
@@ -258,11 +589,27 @@ impl Schema {
     }
 
     fn resolve_no_overrides(&self, tr: &TypeRef) -> Option<Atom> {
-        if let Some(ref named) = tr.named_type {
+        let atom = if let Some(ref named) = tr.named_type {
             self.find_named_type(named).map(|t| t.atom.clone())
         } else {
             Some((*tr.inlined).clone())
+        };
+        if !self.options.preserve_unknown_fields {
+            return atom;
         }
+        atom.map(|mut atom| {
+            let declares_only_fields = |m: &&mut Map| {
+                let et = &m.element_type;
+                et.named_type.is_none() && et.inlined.scalar.is_none() && et.inlined.list.is_none() && et.inlined.map.is_none()
+            };
+            if let Some(map) = atom.map.as_mut().filter(declares_only_fields) {
+                map.element_type = TypeRef {
+                    named_type: Some(UNTYPED_ATOMIC_TYPE.to_string()),
+                    ..Default::default()
+                };
+            }
+            atom
+        })
     }
 
     /// Resolve returns the atom referenced, whether it is inline or named.
@@ -271,31 +618,51 @@ impl Schema {
     /// This allows callers to not care about the difference between a (possibly
     /// inlined) reference and a definition.
     pub fn resolve(&self, tr: &TypeRef) -> Option<Atom> {
+        #[cfg(feature = "fault-injection")]
+        if crate::fault::resolution_fails(tr) {
+            return None;
+        }
+
+        if let Some(atom) = self.compiled.as_ref().and_then(|c| c.lookup(tr)) {
+            return Some(atom.clone());
+        }
+
         // If this is a plain reference with no overrides, just return the type
-        if tr.element_relationship.is_none() {
+        let Some(element_relationship) = tr.element_relationship else {
             return self.resolve_no_overrides(tr);
-        }
+        };
 
-        let key = TypeRefKey::from(tr);
+        // Inlined references can't be told apart by key, so only named ones are cached
+        let key = tr.named_type.as_ref().map(|_| TypeRefKey::from(tr));
 
         // Check cache first
-        {
-            let cache = self.resolved_types.lock().unwrap();
-            if let Some(atom) = cache.get(&key) {
+        if let Some(ref key) = key {
+            let cache = self.resolved_types.lock().unwrap_or_else(PoisonError::into_inner);
+            if let Some(atom) = cache.get(key) {
                 return Some(atom.clone());
             }
         }
 
         // Calculate result
         let result = self.resolve_no_overrides(tr)?;
-        let element_relationship = tr.element_relationship.unwrap();
 
         let result = match (&result.map, &result.list, &result.scalar) {
             (Some(map), _, _) => {
-                let mut map_copy = map.clone();
-                map_copy.element_relationship = element_relationship;
+                let mut fields = map.fields.clone();
+                let mut element_type = map.element_type.clone();
+                if self.options.override_scope == OverrideScope::Transitive {
+                    for field in &mut fields {
+                        self.inherit_override(&mut field.field_type, element_relationship);
+                    }
+                    self.inherit_override(&mut element_type, element_relationship);
+                }
                 Atom {
-                    map: Some(map_copy),
+                    map: Some(Map::with_all(
+                        fields,
+                        element_type,
+                        element_relationship,
+                        map.unions.clone(),
+                    )),
                     list: None,
                     scalar: None,
                 }
@@ -303,6 +670,9 @@ impl Schema {
             (_, Some(list), _) => {
                 let mut list_copy = list.clone();
                 list_copy.element_relationship = element_relationship;
+                if self.options.override_scope == OverrideScope::Transitive {
+                    self.inherit_override(&mut list_copy.element_type, element_relationship);
+                }
                 Atom {
                     map: None,
                     list: Some(list_copy),
@@ -314,20 +684,140 @@ impl Schema {
         };
 
         // Cache and return
-        {
-            let mut cache = self.resolved_types.lock().unwrap();
+        if let Some(key) = key {
+            let mut cache = self.resolved_types.lock().unwrap_or_else(PoisonError::into_inner);
             cache.insert(key, result.clone());
         }
 
         Some(result)
     }
 
+    /// Passes an override down to a nested reference that has none of its own.
+    /// References to pure scalars are left alone since they can't carry one.
+    fn inherit_override(&self, tr: &mut TypeRef, element_relationship: ElementRelationship) {
+        if tr.element_relationship.is_some() {
+            return;
+        }
+        if let Some(atom) = self.resolve_no_overrides(tr) {
+            if atom.is_list() || atom.is_map() {
+                tr.element_relationship = Some(element_relationship);
+            }
+        }
+    }
+
+    /// Reports every `elementRelationship` override that contradicts the type
+    /// it refers to, taking the schema's override scope into account.
+    ///
+    /// Contradictions are overrides on pure scalar types, `associative` on
+    /// maps, and transitive `associative` overrides that would reach nested maps.
+    pub fn validate_overrides(&self) -> Result<(), Vec<OverrideError>> {
+        let mut errors = Vec::new();
+        for td in &self.types {
+            self.validate_atom_overrides(&td.name, &td.atom, &mut errors);
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    fn validate_atom_overrides(&self, type_name: &str, atom: &Atom, errors: &mut Vec<OverrideError>) {
+        if let Some(ref map) = atom.map {
+            for field in &map.fields {
+                self.validate_ref_override(type_name, &field.field_type, errors);
+            }
+            self.validate_ref_override(type_name, &map.element_type, errors);
+        }
+        if let Some(ref list) = atom.list {
+            self.validate_ref_override(type_name, &list.element_type, errors);
+        }
+    }
+
+    fn validate_ref_override(&self, type_name: &str, tr: &TypeRef, errors: &mut Vec<OverrideError>) {
+        // Inlined atoms may contain references of their own
+        if tr.named_type.is_none() {
+            self.validate_atom_overrides(type_name, &tr.inlined, errors);
+        }
+
+        let er = match tr.element_relationship {
+            Some(er) => er,
+            None => return,
+        };
+        let target = tr.named_type.as_deref().unwrap_or("<inlined>");
+        let atom = match self.resolve_no_overrides(tr) {
+            Some(atom) => atom,
+            None => return,
+        };
+
+        let error = |message: String| OverrideError {
+            type_name: type_name.to_string(),
+            message,
+        };
+
+        if !atom.is_list() && !atom.is_map() {
+            errors.push(error(format!(
+                "elementRelationship override {:?} on scalar type {}",
+                er, target
+            )));
+        } else if er == ElementRelationship::Associative && !atom.is_list() {
+            errors.push(error(format!(
+                "associative override on map type {}",
+                target
+            )));
+        } else if er == ElementRelationship::Associative
+            && self.options.override_scope == OverrideScope::Transitive
+            && self.reaches_map(&atom, &mut Vec::new())
+        {
+            errors.push(error(format!(
+                "transitive associative override on {} would apply to nested maps",
+                target
+            )));
+        }
+    }
+
+    /// Returns true if a map type is reachable below the given list atom.
+    fn reaches_map(&self, atom: &Atom, visited: &mut Vec<String>) -> bool {
+        let list = match atom.list {
+            Some(ref list) => list,
+            None => return false,
+        };
+        if let Some(ref name) = list.element_type.named_type {
+            if visited.contains(name) {
+                return false;
+            }
+            visited.push(name.clone());
+        }
+        match self.resolve_no_overrides(&list.element_type) {
+            Some(element) => element.is_map() || self.reaches_map(&element, visited),
+            None => false,
+        }
+    }
+
     /// Copies this schema into the destination.
     pub fn copy_into(&self, dst: &mut Schema) {
-        dst.types = self.types.clone();
-        // Reset the cache in destination
-        dst.type_map = OnceCell::new();
-        dst.resolved_types = Mutex::new(HashMap::new());
+        // Resets the caches in destination
+        *dst.types_mut() = self.types.clone();
+        dst.options = self.options.clone();
+        dst.compiled = self.compiled.clone();
+    }
+
+    /// Returns the options of the schema.
+    pub(super) fn options(&self) -> &SchemaOptions {
+        &self.options
+    }
+
+    /// Sets all the options of the schema.
+    pub(super) fn set_options(&mut self, options: SchemaOptions) {
+        let preserve_unknown_fields = options.preserve_unknown_fields;
+        self.options = options;
+        // Adds the untyped atomic type if needed and resets the caches
+        self.set_preserve_unknown_fields(preserve_unknown_fields);
+    }
+
+    /// Resolves named references from a compiled form of this schema.
+    pub(super) fn set_compiled(&mut self, compiled: Arc<CompiledSchema>) {
+        self.compiled = Some(compiled);
     }
 }
 
@@ -481,6 +971,54 @@ mod tests {
         assert!(schema.find_named_type("nonexistent").is_none());
     }
 
+    #[test]
+    fn test_schema_types_mut() {
+        let mut schema = Schema::with_types(vec![TypeDef {
+            name: "string".to_string(),
+            atom: Atom {
+                scalar: Some(Scalar::String),
+                ..Default::default()
+            },
+        }]);
+        let fingerprint = schema.fingerprint();
+        assert!(schema.find_named_type("int").is_none());
+
+        schema.types_mut().push(TypeDef {
+            name: "int".to_string(),
+            atom: Atom {
+                scalar: Some(Scalar::Numeric),
+                ..Default::default()
+            },
+        });
+        assert!(schema.find_named_type("int").is_some());
+        assert_ne!(schema.fingerprint(), fingerprint);
+    }
+
+    #[test]
+    fn test_schema_copy_into() {
+        let mut schema = Schema::with_types(vec![TypeDef {
+            name: "string".to_string(),
+            atom: Atom {
+                scalar: Some(Scalar::String),
+                ..Default::default()
+            },
+        }]);
+        schema.set_override_scope(OverrideScope::Transitive);
+        schema.set_deduced_list_keys(vec!["name".to_string()]);
+        schema.set_semantic_scalars(true);
+        schema.set_list_ordering(ListOrdering::AppliedFirst);
+        schema.set_numeric_keys(NumericKeys::Integral);
+        schema.set_heterogeneous_lists(HeterogeneousLists::PerElement);
+        schema.set_preserve_unknown_fields(true);
+
+        let mut copy = Schema::new();
+        schema.copy_into(&mut copy);
+        assert_eq!(copy.heterogeneous_lists(), HeterogeneousLists::PerElement);
+        assert_eq!(copy.fingerprint(), schema.fingerprint());
+        assert_eq!(schema.clone().fingerprint(), schema.fingerprint());
+        assert_eq!(schema.clone().with_builtin_types().heterogeneous_lists(), HeterogeneousLists::PerElement);
+    }
+
     #[test]
     fn test_map_find_field() {
         let map = Map {
@@ -548,4 +1086,54 @@ mod tests {
             ElementRelationship::Atomic
         );
     }
+
+    #[test]
+    fn test_validate_overrides() {
+        let list_ref = |er| TypeRef {
+            named_type: Some("items".to_string()),
+            element_relationship: Some(er),
+            ..Default::default()
+        };
+        let schema = Schema::with_types(vec![
+            TypeDef {
+                name: "root".to_string(),
+                atom: Atom {
+                    map: Some(Map::with_fields(vec![StructField {
+                        name: "items".to_string(),
+                        field_type: list_ref(ElementRelationship::Associative),
+                        ..Default::default()
+                    }])),
+                    ..Default::default()
+                },
+            },
+            TypeDef {
+                name: "items".to_string(),
+                atom: Atom {
+                    list: Some(List {
+                        element_type: TypeRef {
+                            named_type: Some("item".to_string()),
+                            ..Default::default()
+                        },
+                        element_relationship: ElementRelationship::Atomic,
+                        keys: vec!["name".to_string()],
+                    }),
+                    ..Default::default()
+                },
+            },
+            TypeDef {
+                name: "item".to_string(),
+                atom: Atom {
+                    map: Some(Map::default()),
+                    ..Default::default()
+                },
+            },
+        ]);
+
+        assert!(schema.validate_overrides().is_ok());
+
+        let schema = schema.with_override_scope(OverrideScope::Transitive);
+        let errs = schema.validate_overrides().unwrap_err();
+        assert_eq!(errs.len(), 1);
+        assert_eq!(errs[0].type_name, "root");
+    }
 }
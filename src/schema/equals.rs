@@ -4,12 +4,12 @@ use super::elements::*;
 
 impl PartialEq for Schema {
     fn eq(&self, other: &Self) -> bool {
-        if self.types.len() != other.types.len() {
+        if self.types().len() != other.types().len() {
             return false;
         }
-        self.types
+        self.types()
             .iter()
-            .zip(other.types.iter())
+            .zip(other.types().iter())
             .all(|(a, b)| a == b)
     }
 }
@@ -149,6 +149,21 @@ impl PartialEq for StructField {
         if self.default != other.default {
             return false;
         }
+        if self.retain_keys != other.retain_keys {
+            return false;
+        }
+        if self.aliases != other.aliases {
+            return false;
+        }
+        if self.deprecated != other.deprecated || self.replacement != other.replacement {
+            return false;
+        }
+        if self.max_length != other.max_length
+            || self.max_items != other.max_items
+            || self.max_properties != other.max_properties
+        {
+            return false;
+        }
         self.field_type == other.field_type
     }
 }
@@ -4,9 +4,15 @@
 //! formalizing a model which allows certain operations ("apply") to be more
 //! well defined.
 
+mod builder;
+mod builtin;
+mod compiled;
 mod elements;
 mod equals;
 mod schemaschema;
 
+pub use builder::*;
+pub use builtin::*;
+pub use compiled::*;
 pub use elements::*;
 pub use schemaschema::SCHEMA_SCHEMA_YAML;
@@ -115,6 +115,30 @@ pub const SCHEMA_SCHEMA_YAML: &str = r#"types:
     - name: default
       type:
         namedType: __untyped_atomic_
+    - name: retainKeys
+      type:
+        scalar: boolean
+    - name: aliases
+      type:
+        list:
+          elementType:
+            scalar: string
+          elementRelationship: atomic
+    - name: deprecated
+      type:
+        scalar: boolean
+    - name: replacement
+      type:
+        scalar: string
+    - name: maxLength
+      type:
+        scalar: numeric
+    - name: maxItems
+      type:
+        scalar: numeric
+    - name: maxProperties
+      type:
+        scalar: numeric
 - name: list
   map:
     fields:
@@ -159,7 +183,7 @@ mod tests {
         assert!(schema.is_ok(), "Failed to parse schema schema: {:?}", schema.err());
 
         let schema = schema.unwrap();
-        assert!(!schema.types.is_empty());
+        assert!(!schema.types().is_empty());
 
         // Verify some key types exist
         assert!(schema.find_named_type("schema").is_some());
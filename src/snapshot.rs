@@ -0,0 +1,290 @@
+//! Stable renderings of merge results, for snapshot tests.
+//!
+//! Debug and Display output follows the order things were inserted or
+//! merged in and changes along with the types. The renderings here are
+//! meant to be stored and compared instead: one line per path, sorted by
+//! path, under a header naming what was rendered and the version of the
+//! format, which changes whenever the rendering of anything does.
+//!
+//! Values and fields matched by the redaction patterns of the options are
+//! rendered as `<redacted>`, for secrets or values that differ between
+//! runs.
+//!
+//! ```
+//! use structured_merge_diff::snapshot::SnapshotOptions;
+//! use structured_merge_diff::typed::Parser;
+//!
+//! let pt = Parser::new("types:\n- name: root\n  map:\n    elementType:\n      scalar: string\n")
+//!     .unwrap()
+//!     .type_by_name("root");
+//! let obj = pt.from_yaml(r#"{"b": "x", "a": "y"}"#).unwrap();
+//! assert_eq!(
+//!     obj.snapshot(&SnapshotOptions::default()),
+//!     "# structured-merge-diff snapshot v1: object\n.a: \"y\"\n.b: \"x\"\n"
+//! );
+//! ```
+
+use crate::fieldpath::{ManagedFields, Path, PathElement, Set, SetPattern};
+use crate::merge::Conflicts;
+use crate::schema::TypeRef;
+use crate::typed::{Comparison, TypedValue};
+use crate::value::Value;
+
+/// The version of the snapshot format, in the header of every rendering.
+pub const FORMAT_VERSION: u32 = 1;
+
+const REDACTED: &str = "<redacted>";
+
+/// SnapshotOptions controls what snapshots leave out.
+#[derive(Debug, Clone, Default)]
+pub struct SnapshotOptions {
+    /// The paths whose values, and the fields beneath them, are redacted.
+    pub redact: SetPattern,
+}
+
+impl SnapshotOptions {
+    /// Returns the path cut after the elements a redaction pattern matched,
+    /// if one did.
+    fn redacted(&self, path: &Path) -> Option<Path> {
+        (0..=path.len())
+            .map(|len| Path::from_elements(path.as_slice()[..len].to_vec()))
+            .find(|prefix| self.redact.matches(prefix))
+    }
+
+    /// Renders a path, cut with `<redacted>` after the elements a
+    /// redaction pattern matched.
+    fn path(&self, path: &Path) -> String {
+        match self.redacted(path) {
+            Some(prefix) => format!("{} {}", prefix, REDACTED),
+            None => path.to_string(),
+        }
+    }
+
+    /// Renders the paths of the set, one per line, sorted and indented.
+    fn set(&self, set: &Set, out: &mut String) {
+        let mut paths = Vec::new();
+        set.iterate(|path| paths.push((path.as_slice().to_vec(), self.path(path))));
+        set.wildcards_iterate(|path| {
+            let mut elements = path.as_slice().to_vec();
+            elements.push(PathElement::field_name("*"));
+            let line = match self.redacted(path) {
+                Some(_) => self.path(path),
+                None => format!("{}.*", path),
+            };
+            paths.push((elements, line));
+        });
+        lines(paths, "  ", out);
+    }
+}
+
+/// Sorts the lines by the path they render, drops duplicates, which
+/// redaction makes, and appends them to `out`.
+fn lines(mut lines: Vec<(Vec<PathElement>, String)>, indent: &str, out: &mut String) {
+    lines.sort();
+    lines.dedup_by(|a, b| a.1 == b.1);
+    for (_, line) in lines {
+        out.push_str(indent);
+        out.push_str(&line);
+        out.push('\n');
+    }
+}
+
+fn header(kind: &str) -> String {
+    format!("# structured-merge-diff snapshot v{}: {}\n", FORMAT_VERSION, kind)
+}
+
+impl TypedValue {
+    /// Renders the value for a snapshot: a line per scalar, empty map or
+    /// empty list, with its path and value in JSON, sorted by path.
+    ///
+    /// Items of associative lists and sets render under their key, so
+    /// their order doesn't show; items of other lists under their index.
+    pub fn snapshot(&self, options: &SnapshotOptions) -> String {
+        let mut leaves = Vec::new();
+        self.snapshot_leaves(self.value(), self.type_ref(), Path::new(), options, &mut leaves);
+        let mut out = header("object");
+        lines(leaves, "", &mut out);
+        out
+    }
+
+    fn snapshot_leaves(
+        &self,
+        value: &Value,
+        type_ref: &TypeRef,
+        path: Path,
+        options: &SnapshotOptions,
+        leaves: &mut Vec<(Vec<PathElement>, String)>,
+    ) {
+        if let Some(prefix) = options.redacted(&path) {
+            leaves.push((prefix.as_slice().to_vec(), format!("{}: {}", prefix, REDACTED)));
+            return;
+        }
+        let children = self.children(value, type_ref);
+        if children.is_empty() {
+            leaves.push((path.as_slice().to_vec(), format!("{}: {}", path, value)));
+            return;
+        }
+        for (pe, child, child_type) in children {
+            self.snapshot_leaves(child, &child_type, path.with(pe), options, leaves);
+        }
+    }
+}
+
+impl ManagedFields {
+    /// Renders the managed fields for a snapshot: each manager, sorted by
+    /// name, with its operation and API version, followed by the paths it
+    /// owns.
+    pub fn snapshot(&self, options: &SnapshotOptions) -> String {
+        let mut out = header("managed fields");
+        for (manager, vs) in self.iter() {
+            let operation = if vs.applied { "Apply" } else { "Update" };
+            out.push_str(&format!("{}: {} {}\n", manager, operation, vs.api_version));
+            options.set(vs.set(), &mut out);
+        }
+        out
+    }
+}
+
+impl Conflicts {
+    /// Renders the conflicts for a snapshot: a line per conflict, sorted by
+    /// path and manager.
+    pub fn snapshot(&self, options: &SnapshotOptions) -> String {
+        let conflicts = self
+            .iter()
+            .map(|c| {
+                let line = format!("{} owned by {} ({})", options.path(&c.path), c.manager, c.operation);
+                (c.path.as_slice().to_vec(), line)
+            })
+            .collect();
+        let mut out = header("conflicts");
+        lines(conflicts, "", &mut out);
+        out
+    }
+}
+
+impl Comparison {
+    /// Renders the comparison for a snapshot: the removed, modified and
+    /// added fields and renamed items, leaving out the empty sections.
+    pub fn snapshot(&self, options: &SnapshotOptions) -> String {
+        let mut out = header("comparison");
+        for (section, set) in [
+            ("removed", &self.removed),
+            ("modified", &self.modified),
+            ("added", &self.added),
+        ] {
+            if !set.is_empty() {
+                out.push_str(&format!("{}:\n", section));
+                options.set(set, &mut out);
+            }
+        }
+        if !self.renamed.is_empty() {
+            out.push_str("renamed:\n");
+            let renames = self
+                .renamed
+                .iter()
+                .map(|r| {
+                    let line = format!("{} -> {}", options.path(&r.from), options.path(&r.to));
+                    (r.from.as_slice().to_vec(), line)
+                })
+                .collect();
+            lines(renames, "  ", &mut out);
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merge::Conflict;
+    use crate::typed::Parser;
+
+    #[test]
+    fn test_snapshot() {
+        let pt = Parser::new(
+            r#"types:
+- name: root
+  map:
+    fields:
+    - name: finalizers
+      type:
+        list:
+          elementType:
+            scalar: string
+          elementRelationship: associative
+    - name: data
+      type:
+        map:
+          elementType:
+            scalar: string
+    - name: replicas
+      type:
+        scalar: numeric
+"#,
+        )
+        .unwrap()
+        .type_by_name("root");
+        let lhs = pt
+            .from_yaml(r#"{"replicas": 1, "finalizers": ["b", "a"], "data": {"password": "hunter2"}}"#)
+            .unwrap();
+        let rhs = pt
+            .from_yaml(r#"{"finalizers": ["a", "b"], "replicas": 2, "data": {}}"#)
+            .unwrap();
+        let options = SnapshotOptions {
+            redact: SetPattern::parse([".data.password"]).unwrap(),
+        };
+
+        assert_eq!(
+            lhs.snapshot(&options),
+            r#"# structured-merge-diff snapshot v1: object
+.data.password: <redacted>
+.finalizers[="a"]: "a"
+.finalizers[="b"]: "b"
+.replicas: 1
+"#
+        );
+        // Sets render the same in any order
+        assert_eq!(
+            rhs.snapshot(&options),
+            "# structured-merge-diff snapshot v1: object\n.data: {}\n.finalizers[=\"a\"]: \"a\"\n\
+             .finalizers[=\"b\"]: \"b\"\n.replicas: 2\n"
+        );
+
+        let managers = ManagedFields::builder()
+            .applied(
+                "kubectl",
+                "v1",
+                [".replicas", ".data.password"].map(|p| Path::parse(p).unwrap()),
+            )
+            .build();
+        assert_eq!(
+            managers.snapshot(&options),
+            r#"# structured-merge-diff snapshot v1: managed fields
+kubectl: Apply v1
+  .data.password <redacted>
+  .replicas
+"#
+        );
+
+        let mut conflicts = Conflicts::new();
+        conflicts.add(Conflict::new("kubectl", Path::parse(".replicas").unwrap()));
+        conflicts.add(Conflict::new("kubectl", Path::parse(".data.password").unwrap()));
+        assert_eq!(
+            conflicts.snapshot(&options),
+            r#"# structured-merge-diff snapshot v1: conflicts
+.data.password <redacted> owned by kubectl (Apply)
+.replicas owned by kubectl (Apply)
+"#
+        );
+
+        assert_eq!(
+            lhs.compare(&rhs).unwrap().snapshot(&options),
+            r#"# structured-merge-diff snapshot v1: comparison
+removed:
+  .data.password <redacted>
+modified:
+  .replicas
+"#
+        );
+    }
+}
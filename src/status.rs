@@ -0,0 +1,225 @@
+//! The readiness fields of objects, and who owns them.
+//!
+//! Health and status tools, after kstatus, decide whether an object is
+//! ready from a few well-known fields: the conditions under
+//! `.status.conditions` and the generations in `.metadata.generation` and
+//! `.status.observedGeneration`. Readiness extracts them from a typed
+//! object together with the managers owning them, which tells which
+//! controller reported a condition.
+
+use crate::fieldpath::{ManagedFields, Path, PathElement, Set};
+use crate::typed::TypedValue;
+use crate::value::Value;
+
+/// Condition is an item of `.status.conditions`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Condition {
+    /// The type of the condition, such as Ready or Available.
+    pub condition_type: String,
+    /// True, False or Unknown.
+    pub status: String,
+    pub reason: Option<String>,
+    pub message: Option<String>,
+    /// The generation of the object the condition was set for.
+    pub observed_generation: Option<i64>,
+    pub last_transition_time: Option<String>,
+    /// The path of the condition, by its key if the schema makes the list
+    /// associative, by its index otherwise.
+    pub path: Path,
+    /// The managers owning fields of the condition, sorted.
+    pub managers: Vec<String>,
+}
+
+/// Readiness is what status tools read from an object.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Readiness {
+    /// The generation of the spec, in `.metadata.generation`.
+    pub generation: Option<i64>,
+    /// The generation the status was reported for, in
+    /// `.status.observedGeneration`.
+    pub observed_generation: Option<i64>,
+    /// The managers owning `.status.observedGeneration`, sorted.
+    pub observed_generation_managers: Vec<String>,
+    /// The conditions with a type, in the order of the list.
+    pub conditions: Vec<Condition>,
+}
+
+impl Readiness {
+    /// Returns the condition of the given type.
+    pub fn condition(&self, condition_type: &str) -> Option<&Condition> {
+        self.conditions.iter().find(|c| c.condition_type == condition_type)
+    }
+
+    /// Returns true if the status was reported for the current generation,
+    /// or the object doesn't track generations.
+    pub fn is_current(&self) -> bool {
+        match (self.generation, self.observed_generation) {
+            (Some(generation), Some(observed)) => observed >= generation,
+            (Some(_), None) => false,
+            (None, _) => true,
+        }
+    }
+}
+
+/// Returns true if the set has the path or a path beneath it.
+fn owns(set: &Set, path: &Path) -> bool {
+    let Some((last, parent)) = path.as_slice().split_last() else {
+        return !set.is_empty();
+    };
+    let mut set = set;
+    for pe in parent {
+        match set.children_get(pe) {
+            Some(children) => set = children,
+            None => return false,
+        }
+    }
+    set.members_has(last) || set.children_get(last).is_some_and(|children| !children.is_empty())
+}
+
+/// Returns the managers owning the path or a path beneath it, sorted.
+fn owners(managers: &ManagedFields, path: &Path) -> Vec<String> {
+    let mut owners: Vec<String> = managers
+        .iter()
+        .filter(|(_, vs)| owns(vs.set(), path))
+        .map(|(manager, _)| manager.clone())
+        .collect();
+    owners.sort();
+    owners
+}
+
+/// Returns the path of the fields named.
+fn field_path(names: &[&str]) -> Path {
+    Path::from_elements(names.iter().map(|name| PathElement::field_name(*name)).collect())
+}
+
+fn field_str(fields: &Value, name: &str) -> Option<String> {
+    fields.as_map()?.get(name)?.as_str().map(String::from)
+}
+
+fn field_int(fields: &Value, name: &str) -> Option<i64> {
+    fields.as_map()?.get(name)?.as_int()
+}
+
+impl TypedValue {
+    /// Extracts the readiness fields of the object, with the managers in
+    /// `managers` owning them.
+    pub fn readiness(&self, managers: &ManagedFields) -> Readiness {
+        let field = |names: &[&str]| self.element_at(&field_path(names));
+        let mut readiness = Readiness {
+            generation: field(&["metadata"]).and_then(|m| field_int(m.value(), "generation")),
+            observed_generation: field(&["status"]).and_then(|s| field_int(s.value(), "observedGeneration")),
+            observed_generation_managers: owners(managers, &field_path(&["status", "observedGeneration"])),
+            conditions: Vec::new(),
+        };
+
+        let Some(conditions) = field(&["status", "conditions"]) else {
+            return readiness;
+        };
+        let conditions_path = field_path(&["status", "conditions"]);
+        for (pe, item, _) in self.children(conditions.value(), conditions.type_ref()) {
+            let Some(condition_type) = field_str(item, "type") else {
+                continue;
+            };
+            let path = conditions_path.with(pe);
+            readiness.conditions.push(Condition {
+                condition_type,
+                status: field_str(item, "status").unwrap_or_default(),
+                reason: field_str(item, "reason"),
+                message: field_str(item, "message"),
+                observed_generation: field_int(item, "observedGeneration"),
+                last_transition_time: field_str(item, "lastTransitionTime"),
+                managers: owners(managers, &path),
+                path,
+            });
+        }
+        readiness
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::managed_fields;
+    use crate::typed::Parser;
+
+    #[test]
+    fn test_readiness() {
+        let pt = Parser::new(
+            r#"types:
+- name: root
+  map:
+    fields:
+    - name: metadata
+      type:
+        map:
+          fields:
+          - name: generation
+            type:
+              scalar: numeric
+    - name: status
+      type:
+        map:
+          fields:
+          - name: observedGeneration
+            type:
+              scalar: numeric
+          - name: conditions
+            type:
+              list:
+                elementType:
+                  map:
+                    elementType:
+                      scalar: untyped
+                elementRelationship: associative
+                keys: [type]
+"#,
+        )
+        .unwrap()
+        .type_by_name("root");
+        let obj = pt
+            .from_yaml(
+                r#"{"metadata": {"generation": 3}, "status": {"observedGeneration": 2, "conditions": [
+                {"type": "Ready", "status": "False", "reason": "Scaling", "observedGeneration": 2},
+                {"type": "Available", "status": "True"}]}}"#,
+            )
+            .unwrap();
+        let managers = managed_fields! {
+            "controller" => {
+                version: "v1",
+                applied: false,
+                paths: [
+                    ["f:status", "f:observedGeneration"],
+                    ["f:status", "f:conditions", r#"k:{"type":"Ready"}"#, "f:status"],
+                ],
+            },
+            "monitor" => {
+                version: "v1",
+                applied: false,
+                paths: [["f:status", "f:conditions", r#"k:{"type":"Available"}"#]],
+            },
+        };
+
+        let readiness = obj.readiness(&managers);
+        assert_eq!(readiness.generation, Some(3));
+        assert_eq!(readiness.observed_generation, Some(2));
+        assert_eq!(readiness.observed_generation_managers, vec!["controller"]);
+        assert!(!readiness.is_current());
+
+        let ready = readiness.condition("Ready").unwrap();
+        assert_eq!(ready.status, "False");
+        assert_eq!(ready.reason.as_deref(), Some("Scaling"));
+        assert_eq!(ready.observed_generation, Some(2));
+        assert_eq!(ready.path.to_string(), r#".status.conditions[type="Ready"]"#);
+        assert_eq!(ready.managers, vec!["controller"]);
+        let available = readiness.condition("Available").unwrap();
+        assert_eq!(
+            (available.status.as_str(), available.message.as_deref()),
+            ("True", None)
+        );
+        assert_eq!(available.managers, vec!["monitor"]);
+
+        let empty = pt.from_yaml("{}").unwrap().readiness(&ManagedFields::new());
+        assert_eq!(empty, Readiness::default());
+        assert!(empty.is_current());
+    }
+}
@@ -1,6 +1,6 @@
 //! Comparison result types.
 
-use crate::fieldpath::Set;
+use crate::fieldpath::{Path, Set};
 use std::fmt;
 
 /// Comparison holds the result of comparing two TypedValues.
@@ -15,6 +15,39 @@ pub struct Comparison {
     pub modified: Set,
     /// Fields that were in the right-hand side but not the left-hand side.
     pub added: Set,
+    /// Removed and added associative list items that are the same item
+    /// under a new key. Their fields stay in `removed` and `added`.
+    pub renamed: Vec<ItemRename>,
+}
+
+/// ItemRename pairs a removed associative list item with the added item
+/// it was renamed to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ItemRename {
+    /// The path of the item on the left-hand side.
+    pub from: Path,
+    /// The path of the item on the right-hand side.
+    pub to: Path,
+}
+
+impl ItemRename {
+    /// Returns the set with the paths at or beneath `from` moved beneath
+    /// `to`, e.g. to carry the ownership of an item over to its new key.
+    pub fn rebase(&self, set: &Set) -> Set {
+        let from = self.from.as_slice();
+        let mut result = Set::new();
+        set.iterate(|path| match path.as_slice().strip_prefix(from) {
+            Some(rest) => {
+                let mut moved = self.to.clone();
+                for pe in rest {
+                    moved.push(pe.clone());
+                }
+                result.insert(&moved);
+            }
+            None => result.insert(path),
+        });
+        result
+    }
 }
 
 impl Comparison {
@@ -24,6 +57,7 @@ impl Comparison {
             removed: Set::new(),
             modified: Set::new(),
             added: Set::new(),
+            renamed: Vec::new(),
         }
     }
 
@@ -39,6 +73,20 @@ impl Comparison {
         self.added = self.added.difference(fields);
     }
 
+    /// Returns a copy of the comparison without the given fields or anything
+    /// beneath them.
+    ///
+    /// This makes "did anything change outside status?" a single expression:
+    /// `lhs.compare(&rhs)?.exclude(&status).is_same()`.
+    pub fn exclude(&self, fields: &Set) -> Comparison {
+        Comparison {
+            removed: self.removed.recursive_difference(fields),
+            modified: self.modified.recursive_difference(fields),
+            added: self.added.recursive_difference(fields),
+            renamed: self.renamed.clone(),
+        }
+    }
+
     /// Filters the comparison to only include the given fields.
     pub fn filter_fields(&mut self, fields: &Set) {
         self.removed = self.removed.intersection(fields);
@@ -46,6 +94,16 @@ impl Comparison {
         self.added = self.added.intersection(fields);
     }
 
+    /// Keeps only the paths for which the predicate returns true.
+    pub fn retain_fields<F>(&mut self, mut pred: F)
+    where
+        F: FnMut(&Path) -> bool,
+    {
+        self.removed = self.removed.filter(&mut pred);
+        self.modified = self.modified.filter(&mut pred);
+        self.added = self.added.filter(&mut pred);
+    }
+
     /// Returns true if any fields were removed.
     pub fn has_removed(&self) -> bool {
         !self.removed.is_empty()
@@ -92,12 +150,23 @@ impl fmt::Display for Comparison {
             if !first {
                 writeln!(f)?;
             }
+            first = false;
             write!(f, "- Removed Fields:")?;
             self.removed.iterate(|path| {
                 let _ = write!(f, "\n  {}", path);
             });
         }
 
+        if !self.renamed.is_empty() {
+            if !first {
+                writeln!(f)?;
+            }
+            write!(f, "- Renamed Items:")?;
+            for rename in &self.renamed {
+                write!(f, "\n  {} -> {}", rename.from, rename.to)?;
+            }
+        }
+
         Ok(())
     }
 }
@@ -139,6 +208,50 @@ mod tests {
         assert!(comp.added.has(&Path::from_elements(vec![PathElement::field_name("b")])));
     }
 
+    #[test]
+    fn test_comparison_exclude_subtree() {
+        let status = Path::from_elements(vec![PathElement::field_name("status")]);
+        let mut comp = Comparison::new();
+        comp.modified.insert(&status.with(PathElement::field_name("phase")));
+
+        let mut exclude = Set::new();
+        exclude.insert(&status);
+        assert!(comp.exclude(&exclude).is_same());
+
+        comp.added.insert(&Path::from_elements(vec![PathElement::field_name("spec")]));
+        assert!(!comp.exclude(&exclude).is_same());
+    }
+
+    #[test]
+    fn test_comparison_filter_fields() {
+        let a = Path::from_elements(vec![PathElement::field_name("a")]);
+        let mut comp = Comparison::new();
+        comp.added.insert(&a);
+        comp.modified.insert(&a.with(PathElement::field_name("b")));
+
+        let mut fields = Set::new();
+        fields.insert(&a);
+        comp.filter_fields(&fields);
+
+        assert!(comp.has_added());
+        assert!(!comp.has_modified());
+    }
+
+    #[test]
+    fn test_comparison_retain_fields() {
+        let mut comp = Comparison::new();
+        comp.added.insert(&Path::from_elements(vec![PathElement::field_name("a")]));
+        comp.removed.insert(&Path::from_elements(vec![
+            PathElement::field_name("b"),
+            PathElement::field_name("c"),
+        ]));
+
+        comp.retain_fields(|path| path.len() == 1);
+
+        assert!(comp.has_added());
+        assert!(!comp.has_removed());
+    }
+
     #[test]
     fn test_comparison_display() {
         let mut comp = Comparison::new();
@@ -0,0 +1,179 @@
+//! Removal of duplicate items from associative lists and sets.
+
+use super::typed_value::TypedValue;
+use crate::fieldpath::{Path, PathElement};
+use crate::schema::{ElementRelationship, TypeRef};
+use crate::value::Value;
+
+/// KeepDuplicate selects which of several items with the same key stays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum KeepDuplicate {
+    #[default]
+    First,
+    Last,
+}
+
+/// RemovedDuplicates records the items removed from one list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemovedDuplicates {
+    /// The path of the list.
+    pub path: Path,
+    /// The indexes the removed items had in the list, ascending.
+    pub indexes: Vec<usize>,
+}
+
+impl TypedValue {
+    /// Removes the items of associative lists and sets whose key, or value
+    /// for sets, repeats an earlier or later item, as `keep` says. Lists
+    /// inside removed items aren't reported. Returns the removed indexes of
+    /// every list that had duplicates, parents before children.
+    pub fn dedup_list_items(&mut self, keep: KeepDuplicate) -> Vec<RemovedDuplicates> {
+        let mut value = std::mem::replace(self.value_mut(), Value::Null);
+        let type_ref = self.type_ref().clone();
+        let mut removed = Vec::new();
+        self.dedup_value(&mut value, &type_ref, &Path::new(), keep, &mut removed);
+        *self.value_mut() = value;
+        removed
+    }
+
+    fn dedup_value(
+        &self,
+        value: &mut Value,
+        type_ref: &TypeRef,
+        path: &Path,
+        keep: KeepDuplicate,
+        removed: &mut Vec<RemovedDuplicates>,
+    ) {
+        let Some(atom) = self.schema().resolve(type_ref) else {
+            return;
+        };
+        let relationship = match value {
+            Value::Map(_) => atom.map.as_ref().map(|m| m.element_relationship),
+            Value::List(_) => atom.list.as_ref().map(|l| l.element_relationship),
+            _ => None,
+        };
+        if relationship.is_none_or(|r| r == ElementRelationship::Atomic) {
+            return;
+        }
+        let mut children: Vec<(PathElement, TypeRef)> = self
+            .children(value, type_ref)
+            .into_iter()
+            .map(|(pe, _, child_type)| (pe, child_type))
+            .collect();
+        match value {
+            Value::Map(fields) => {
+                for (val, (pe, child_type)) in fields.fields.values_mut().zip(&children) {
+                    self.dedup_value(val, child_type, &path.with(pe.clone()), keep, removed);
+                }
+            }
+            Value::List(items) => {
+                let duplicates: Vec<usize> = (0..children.len())
+                    .filter(|&i| {
+                        let mut others = match keep {
+                            KeepDuplicate::First => 0..i,
+                            KeepDuplicate::Last => i + 1..children.len(),
+                        };
+                        others.any(|j| children[j].0 == children[i].0)
+                    })
+                    .collect();
+                if !duplicates.is_empty() {
+                    for &i in duplicates.iter().rev() {
+                        items.remove(i);
+                        children.remove(i);
+                    }
+                    removed.push(RemovedDuplicates {
+                        path: path.clone(),
+                        indexes: duplicates,
+                    });
+                }
+                for (item, (pe, child_type)) in items.iter_mut().zip(&children) {
+                    self.dedup_value(item, child_type, &path.with(pe.clone()), keep, removed);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::typed::{Parser, ValidationOption};
+
+    #[test]
+    fn test_dedup_list_items() {
+        let pt = Parser::new(
+            r#"types:
+- name: root
+  map:
+    fields:
+    - name: containers
+      type:
+        list:
+          elementType:
+            namedType: container
+          elementRelationship: associative
+          keys: [name]
+    - name: args
+      type:
+        list:
+          elementType:
+            scalar: string
+          elementRelationship: atomic
+- name: container
+  map:
+    fields:
+    - name: name
+      type:
+        scalar: string
+    - name: image
+      type:
+        scalar: string
+    - name: finalizers
+      type:
+        list:
+          elementType:
+            scalar: string
+          elementRelationship: associative
+"#,
+        )
+        .unwrap()
+        .type_by_name("root");
+        let yaml = r#"{
+            "containers": [
+                {"name": "a", "image": "1", "finalizers": ["x", "y", "x"]},
+                {"name": "b"},
+                {"name": "a", "image": "2"}
+            ],
+            "args": ["-v", "-v"]
+        }"#;
+        let tv = pt.from_yaml_with_opts(yaml, &[ValidationOption::AllowDuplicates]).unwrap();
+        assert!(tv.validate(&[]).is_err());
+        let json = |tv: &TypedValue| crate::value::to_json(tv.value()).unwrap();
+
+        let mut first = tv.clone();
+        let removed = first.dedup_list_items(KeepDuplicate::First);
+        assert_eq!(
+            json(&first),
+            r#"{"args":["-v","-v"],"containers":[{"finalizers":["x","y"],"image":"1","name":"a"},{"name":"b"}]}"#
+        );
+        let removed: Vec<(String, Vec<usize>)> = removed.into_iter().map(|r| (r.path.to_string(), r.indexes)).collect();
+        assert_eq!(
+            removed,
+            vec![
+                (".containers".to_string(), vec![2]),
+                (".containers[name=\"a\"].finalizers".to_string(), vec![2]),
+            ]
+        );
+        assert!(first.validate(&[]).is_ok());
+
+        let mut last = tv.clone();
+        let removed = last.dedup_list_items(KeepDuplicate::Last);
+        assert_eq!(json(&last), r#"{"args":["-v","-v"],"containers":[{"name":"b"},{"image":"2","name":"a"}]}"#);
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].indexes, vec![0]);
+
+        assert!(first.dedup_list_items(KeepDuplicate::First).is_empty());
+    }
+}
@@ -0,0 +1,156 @@
+//! Validation of the default values schemas declare for fields.
+
+use super::typed_value::{as_typed, json_value_to_value};
+use crate::schema::{Atom, Schema, TypeRef};
+use crate::value::Value;
+
+/// DefaultError reports a field default that doesn't fit the schema.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DefaultError {
+    /// The name of the type declaring the field.
+    pub type_name: String,
+    /// The name of the field.
+    pub field: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for DefaultError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "type {}: default of field {}: {}",
+            self.type_name, self.field, self.message
+        )
+    }
+}
+
+impl std::error::Error for DefaultError {}
+
+/// Checks that the default of every field conforms to the field's type, and
+/// that fields used as keys of associative lists default to scalars, as
+/// keys must be. Defaults of fields whose types don't resolve are skipped.
+///
+/// Parser::new rejects schemas that fail the check, so that a bad default
+/// is reported when the schema is loaded rather than as items with
+/// unexpected keys during merges.
+pub fn validate_defaults(schema: &Schema) -> Result<(), Vec<DefaultError>> {
+    let mut errors = Vec::new();
+    for td in schema.types() {
+        validate_atom_defaults(schema, &td.name, &td.atom, &mut errors);
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+fn validate_atom_defaults(schema: &Schema, type_name: &str, atom: &Atom, errors: &mut Vec<DefaultError>) {
+    let error = |field: &str, message: String| DefaultError {
+        type_name: type_name.to_string(),
+        field: field.to_string(),
+        message,
+    };
+
+    if let Some(map) = &atom.map {
+        for field in &map.fields {
+            validate_ref_defaults(schema, type_name, &field.field_type, errors);
+            let Some(default) = &field.default else {
+                continue;
+            };
+            if schema.resolve(&field.field_type).is_none() {
+                continue;
+            }
+            if let Err(errs) = as_typed(json_value_to_value(default), schema, field.field_type.clone(), &[]) {
+                errors.push(error(&field.name, errs.to_string()));
+            }
+        }
+        validate_ref_defaults(schema, type_name, &map.element_type, errors);
+    }
+
+    if let Some(list) = &atom.list {
+        validate_ref_defaults(schema, type_name, &list.element_type, errors);
+        let element = schema.resolve(&list.element_type);
+        let element_map = element.as_ref().and_then(|atom| atom.map.as_ref());
+        for key in &list.keys {
+            let default = element_map
+                .and_then(|map| map.find_field(key))
+                .and_then(|f| f.default.as_ref());
+            if let Some(Value::List(_) | Value::Map(_) | Value::Null) = default.map(json_value_to_value) {
+                errors.push(error(key, "list key fields must default to a scalar".to_string()));
+            }
+        }
+    }
+}
+
+/// Checks the defaults of an inlined type. Named types are checked on
+/// their own.
+fn validate_ref_defaults(schema: &Schema, type_name: &str, tr: &TypeRef, errors: &mut Vec<DefaultError>) {
+    if tr.named_type.is_none() {
+        validate_atom_defaults(schema, type_name, &tr.inlined, errors);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::typed::Parser;
+
+    #[test]
+    fn test_validate_defaults() {
+        let schema = |replicas: &str, port: &str| {
+            format!(
+                r#"types:
+- name: root
+  map:
+    fields:
+    - name: replicas
+      type:
+        scalar: numeric
+      default: {}
+    - name: ports
+      type:
+        list:
+          elementType:
+            map:
+              fields:
+              - name: port
+                type:
+                  list:
+                    elementType:
+                      scalar: numeric
+                    elementRelationship: atomic
+                default: {}
+          elementRelationship: associative
+          keys: [port]
+"#,
+                replicas, port
+            )
+        };
+
+        let parser = Parser::new(&schema("1", "null")).unwrap();
+        assert_eq!(validate_defaults(parser.schema()), Ok(()));
+
+        // The list of ports is a fine value, but not a key
+        let parsed: Schema = serde_yaml::from_str(&schema("one", "[80]")).unwrap();
+        let errors = validate_defaults(&parsed).unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(
+            (errors[0].type_name.as_str(), errors[0].field.as_str()),
+            ("root", "replicas")
+        );
+        assert!(errors[0].message.contains("expected numeric"), "{}", errors[0]);
+        assert_eq!(
+            errors[1].to_string(),
+            "type root: default of field port: list key fields must default to a scalar"
+        );
+
+        let err = Parser::new(&schema("one", "null")).unwrap_err();
+        assert!(
+            err.message
+                .starts_with("invalid defaults: type root: default of field replicas"),
+            "{}",
+            err.message
+        );
+    }
+}
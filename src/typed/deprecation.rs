@@ -0,0 +1,102 @@
+//! Detection of deprecated fields set in typed values.
+
+use super::typed_value::TypedValue;
+use crate::fieldpath::Path;
+use crate::schema::TypeRef;
+use crate::value::Value;
+
+/// DeprecatedField is a set field that the schema marks as deprecated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeprecatedField {
+    pub path: Path,
+    /// What to use instead, if the schema says.
+    pub replacement: Option<String>,
+}
+
+impl TypedValue {
+    /// Returns the deprecated fields set in the value, in document order.
+    pub fn deprecated_fields(&self) -> Vec<DeprecatedField> {
+        let mut found = Vec::new();
+        self.collect_deprecated(self.value(), self.type_ref(), &Path::new(), &mut found);
+        found
+    }
+
+    fn collect_deprecated(&self, value: &Value, type_ref: &TypeRef, path: &Path, found: &mut Vec<DeprecatedField>) {
+        let map = self.schema().resolve(type_ref).and_then(|atom| atom.map);
+        for (pe, child, child_type) in self.children(value, type_ref) {
+            let child_path = path.with(pe);
+            if let (Some(map), Some(name)) = (&map, child_path.last().and_then(|pe| pe.as_field_name())) {
+                if let Some(field) = map.find_field(name).filter(|f| f.deprecated) {
+                    found.push(DeprecatedField {
+                        path: child_path.clone(),
+                        replacement: field.replacement.clone(),
+                    });
+                }
+            }
+            self.collect_deprecated(child, &child_type, &child_path, found);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fieldpath::PathElement;
+    use crate::typed::Parser;
+
+    #[test]
+    fn test_deprecated_fields() {
+        let pt = Parser::new(
+            r#"types:
+- name: root
+  map:
+    fields:
+    - name: replicas
+      type:
+        scalar: numeric
+    - name: serviceAccount
+      type:
+        scalar: string
+      deprecated: true
+      replacement: serviceAccountName
+    - name: items
+      type:
+        list:
+          elementType:
+            namedType: item
+          elementRelationship: atomic
+- name: item
+  map:
+    fields:
+    - name: old
+      type:
+        scalar: string
+      deprecated: true
+"#,
+        )
+        .unwrap()
+        .type_by_name("root");
+
+        let tv = pt
+            .from_yaml(r#"{"replicas": 1, "serviceAccount": "a", "items": [{"old": "x"}]}"#)
+            .unwrap();
+        assert_eq!(
+            tv.deprecated_fields(),
+            vec![
+                DeprecatedField {
+                    path: Path::from_elements(vec![
+                        PathElement::field_name("items"),
+                        PathElement::index(0),
+                        PathElement::field_name("old"),
+                    ]),
+                    replacement: None,
+                },
+                DeprecatedField {
+                    path: Path::from_elements(vec![PathElement::field_name("serviceAccount")]),
+                    replacement: Some("serviceAccountName".to_string()),
+                },
+            ]
+        );
+        assert!(pt.from_yaml(r#"{"replicas": 1}"#).unwrap().deprecated_fields().is_empty());
+    }
+}
@@ -0,0 +1,175 @@
+//! Comparisons that carry the values of the fields they found modified.
+//!
+//! A Comparison only tells which fields changed. Change logs also want the
+//! values before and after, which compare_detailed records during the same
+//! traversal instead of looking every modified path up in both objects.
+
+use super::comparison::Comparison;
+use super::scratch::{CompareOutput, CompareScratch};
+use super::typed_value::TypedValue;
+use super::validation::ValidationErrors;
+use crate::fieldpath::Path;
+use crate::value::Value;
+use std::fmt;
+
+/// DetailedCompareOptions controls how many values compare_detailed keeps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DetailedCompareOptions {
+    /// The most modified fields whose values are kept, or None to keep them
+    /// all. Fields past the limit are still in the comparison and counted.
+    pub max_values: Option<usize>,
+}
+
+/// ValueChange is a modified field with its values on both sides.
+///
+/// The values are those of the leaf that changed: a scalar, an atomic list
+/// or map, or a value that changed kind, such as a map that became null.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValueChange {
+    pub path: Path,
+    pub old: Value,
+    pub new: Value,
+}
+
+/// DetailedComparison is a Comparison with the values of the modified
+/// fields.
+#[derive(Debug, Clone, Default)]
+pub struct DetailedComparison {
+    pub comparison: Comparison,
+    /// The values of the modified fields, in the order they were found.
+    pub changes: Vec<ValueChange>,
+    /// The number of modified fields whose values were left out, past
+    /// `max_values`.
+    pub omitted: usize,
+}
+
+/// Renders a change log, a line per modified field with its old and new
+/// values in JSON.
+impl fmt::Display for DetailedComparison {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, change) in self.changes.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}: {} -> {}", change.path, change.old, change.new)?;
+        }
+        if self.omitted > 0 {
+            if !self.changes.is_empty() {
+                writeln!(f)?;
+            }
+            write!(f, "… and {} more", self.omitted)?;
+        }
+        Ok(())
+    }
+}
+
+/// ValueChanges collects the values of modified fields during a compare.
+pub(crate) struct ValueChanges {
+    max_values: Option<usize>,
+    changes: Vec<ValueChange>,
+    omitted: usize,
+}
+
+impl ValueChanges {
+    /// Records the values of the modified field at `path`.
+    pub(crate) fn record(&mut self, path: &Path, lhs: &Value, rhs: &Value) {
+        if self.max_values.is_some_and(|max| self.changes.len() >= max) {
+            self.omitted += 1;
+            return;
+        }
+        self.changes.push(ValueChange {
+            path: path.clone(),
+            old: lhs.clone(),
+            new: rhs.clone(),
+        });
+    }
+}
+
+impl TypedValue {
+    /// Compares this TypedValue with another as compare does, and keeps the
+    /// values of the modified fields on both sides.
+    pub fn compare_detailed(
+        &self,
+        rhs: &TypedValue,
+        options: DetailedCompareOptions,
+    ) -> Result<DetailedComparison, ValidationErrors> {
+        let mut scratch = CompareScratch::default();
+        let mut out = CompareOutput::new(&mut scratch);
+        out.changes = Some(ValueChanges {
+            max_values: options.max_values,
+            changes: Vec::new(),
+            omitted: 0,
+        });
+        self.compare_into(rhs, &mut out)?;
+        let (changes, omitted) = match out.changes {
+            Some(changes) => (changes.changes, changes.omitted),
+            None => (Vec::new(), 0),
+        };
+        Ok(DetailedComparison {
+            comparison: out.comparison,
+            changes,
+            omitted,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::typed::Parser;
+
+    #[test]
+    fn test_compare_detailed() {
+        let pt = Parser::new(
+            r#"types:
+- name: root
+  map:
+    fields:
+    - name: replicas
+      type:
+        scalar: numeric
+    - name: image
+      type:
+        scalar: string
+    - name: args
+      type:
+        list:
+          elementType:
+            scalar: string
+          elementRelationship: atomic
+    - name: labels
+      type:
+        map:
+          elementType:
+            scalar: string
+"#,
+        )
+        .unwrap()
+        .type_by_name("root");
+        let lhs = pt
+            .from_yaml(r#"{"replicas": 1, "image": "a", "args": ["x"], "labels": {"app": "web"}}"#)
+            .unwrap();
+        let rhs = pt
+            .from_yaml(r#"{"replicas": 2, "image": "a", "args": ["x", "y"], "labels": {"tier": "db"}}"#)
+            .unwrap();
+
+        let detailed = lhs.compare_detailed(&rhs, DetailedCompareOptions::default()).unwrap();
+        let comparison = lhs.compare(&rhs).unwrap();
+        assert!(detailed.comparison.modified.equals(&comparison.modified));
+        assert!(detailed.comparison.added.equals(&comparison.added));
+        assert!(detailed.comparison.removed.equals(&comparison.removed));
+
+        let mut lines: Vec<String> = detailed.to_string().lines().map(String::from).collect();
+        lines.sort();
+        assert_eq!(lines, vec![r#".args: ["x"] -> ["x","y"]"#, ".replicas: 1 -> 2"]);
+        assert_eq!(detailed.omitted, 0);
+
+        let bounded = lhs
+            .compare_detailed(&rhs, DetailedCompareOptions { max_values: Some(1) })
+            .unwrap();
+        assert_eq!(bounded.changes.len(), 1);
+        assert_eq!(bounded.omitted, 1);
+        assert!(bounded.comparison.modified.equals(&comparison.modified));
+        assert!(bounded.to_string().ends_with("… and 1 more"));
+    }
+}
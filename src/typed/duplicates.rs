@@ -0,0 +1,156 @@
+//! Field sets that tell apart the items of lists with duplicate keys.
+//!
+//! Items of associative lists and sets are identified by their key, or
+//! value for sets, so items that share one also share their paths, and
+//! whoever owns one owns them all. When duplicates are retained, such items
+//! can be recorded under their index instead, `i:<index>` in FieldsV1, as
+//! kube-apiserver does. Removing and extracting items accepts either form.
+
+use super::typed_value::TypedValue;
+use super::validation::ValidationErrors;
+use crate::fieldpath::{Path, PathElement, Set};
+use crate::schema::{ElementRelationship, List};
+use crate::value::Value;
+use std::collections::HashMap;
+
+impl TypedValue {
+    /// Converts the typed value to a field set as to_field_set does, except
+    /// that items of associative lists and sets that share their key with
+    /// another item are recorded under their index in the list.
+    pub fn to_field_set_with_duplicate_indexes(&self) -> Result<Set, ValidationErrors> {
+        let mut set = Set::new();
+        let mut errors = ValidationErrors::new();
+        self.collect_field_set(self.value(), self.type_ref(), Path::new(), true, &mut set, &mut errors);
+        if errors.is_empty() {
+            Ok(set)
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Returns the path elements of the items of a list: their key for
+    /// associative lists, their value for sets and their index otherwise.
+    /// Items whose key can't be read are given their index.
+    pub(crate) fn list_item_elements(&self, items: &[Value], list: &List) -> Vec<PathElement> {
+        items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| {
+                if list.element_relationship != ElementRelationship::Associative {
+                    PathElement::index(i as i32)
+                } else if list.keys.is_empty() {
+                    PathElement::value(item.clone())
+                } else {
+                    match self.list_item_to_key(item, list) {
+                        Ok(key) => PathElement::Key(key),
+                        Err(_) => PathElement::index(i as i32),
+                    }
+                }
+            })
+            .collect()
+    }
+}
+
+/// Returns, for the path element of each item of a list, whether another
+/// item has the same one.
+pub(crate) fn duplicated(elements: &[PathElement]) -> Vec<bool> {
+    let mut counts: HashMap<&PathElement, usize> = HashMap::new();
+    for pe in elements {
+        *counts.entry(pe).or_default() += 1;
+    }
+    elements
+        .iter()
+        .map(|pe| !matches!(pe, PathElement::Index(_)) && counts.get(pe) > Some(&1))
+        .collect()
+}
+
+/// Returns the path of the item at index `i` of the list at `path`, as
+/// `items` records it: under its index if it is a duplicate that `items`
+/// has paths under, and under its key otherwise.
+pub(crate) fn item_path(path: &Path, pe: PathElement, i: usize, duplicated: bool, items: &Set) -> Path {
+    if duplicated {
+        let index_path = path.with(PathElement::index(i as i32));
+        if items.touches(&index_path) {
+            return index_path;
+        }
+    }
+    path.with(pe)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fieldpath::Set;
+    use crate::typed::{Parser, ValidationOption};
+
+    #[test]
+    fn test_duplicate_indexes() {
+        let pt = Parser::new(
+            r#"types:
+- name: root
+  map:
+    fields:
+    - name: env
+      type:
+        list:
+          elementType:
+            map:
+              fields:
+              - name: name
+                type:
+                  scalar: string
+              - name: value
+                type:
+                  scalar: string
+          elementRelationship: associative
+          keys: [name]
+"#,
+        )
+        .unwrap()
+        .type_by_name("root");
+        let tv = pt
+            .from_yaml_with_opts(
+                r#"{"env": [{"name": "a", "value": "1"}, {"name": "b", "value": "2"}, {"name": "a", "value": "3"}]}"#,
+                &[ValidationOption::AllowDuplicates],
+            )
+            .unwrap();
+
+        let set = tv.to_field_set_with_duplicate_indexes().unwrap();
+        let json = String::from_utf8(set.to_json().unwrap()).unwrap();
+        assert_eq!(
+            json,
+            r#"{"f:env":{"i:0":{".":{},"f:name":{},"f:value":{}},"i:2":{".":{},"f:name":{},"f:value":{}},"k:{\"name\":\"b\"}":{".":{},"f:name":{},"f:value":{}}}}"#
+        );
+        let set = Set::from_json(json.as_bytes()).unwrap();
+
+        // Only the second duplicate is owned, so only it goes
+        let mut second = Set::new();
+        set.iterate(|path| {
+            if path.to_string().starts_with(".env[2]") {
+                second.insert(path);
+            }
+        });
+        let removed = tv.remove_items(&second);
+        let expected = pt
+            .from_yaml_with_opts(
+                r#"{"env": [{"name": "a", "value": "1"}, {"name": "b", "value": "2"}]}"#,
+                &[ValidationOption::AllowDuplicates],
+            )
+            .unwrap();
+        assert_eq!(removed.value(), expected.value());
+
+        let extracted = tv.extract_items(&second);
+        let expected = pt
+            .from_yaml_with_opts(
+                r#"{"env": [{"name": "a", "value": "3"}]}"#,
+                &[ValidationOption::AllowDuplicates],
+            )
+            .unwrap();
+        assert_eq!(extracted.value(), expected.value());
+
+        // Without duplicates, the field set is the usual one
+        assert_eq!(
+            expected.to_field_set_with_duplicate_indexes().unwrap(),
+            expected.to_field_set().unwrap()
+        );
+    }
+}
@@ -0,0 +1,317 @@
+//! Editing typed values in place, validating only what an edit touches.
+//!
+//! Programs that build or patch objects field by field would otherwise
+//! validate the whole object after every edit. set_path_validated checks
+//! the new value against the type at its path, and the few things around
+//! it that the edit can break: the field in its map, and the keys of the
+//! associative list the edited item belongs to.
+
+use super::typed_value::{allows_unknown_fields, check_size_limits, TypedValue};
+use super::validation::{ValidationError, ValidationErrors};
+use crate::fieldpath::{Path, PathElement};
+use crate::schema::{ElementRelationship, TypeRef, EMBEDDED_RESOURCE_TYPE};
+use crate::value::Value;
+
+/// Target is where an edit goes.
+struct Target {
+    /// The path with list keys and set values replaced by indexes.
+    index_path: Vec<PathElement>,
+    /// The types along the path, from the root to the target.
+    types: Vec<TypeRef>,
+}
+
+/// Returns the value at the index path `path` in `value`.
+fn value_at<'v>(value: &'v Value, path: &[PathElement]) -> Option<&'v Value> {
+    let mut value = value;
+    for pe in path {
+        value = match (value, pe) {
+            (Value::Map(map), PathElement::FieldName(name)) => map.fields.get(name)?,
+            (Value::List(items), PathElement::Index(i)) => items.get(usize::try_from(*i).ok()?)?,
+            _ => return None,
+        };
+    }
+    Some(value)
+}
+
+/// Returns the value at the index path `path` in `value`, to change it.
+fn value_at_mut<'v>(value: &'v mut Value, path: &[PathElement]) -> Option<&'v mut Value> {
+    let mut value = value;
+    for pe in path {
+        value = match (value, pe) {
+            (Value::Map(map), PathElement::FieldName(name)) => map.fields.get_mut(name)?,
+            (Value::List(items), PathElement::Index(i)) => items.get_mut(usize::try_from(*i).ok()?)?,
+            _ => return None,
+        };
+    }
+    Some(value)
+}
+
+/// Puts `value` at the index path `path`, returning what was there. The
+/// outer None means the path's parent doesn't exist.
+fn put(root: &mut Value, path: &[PathElement], value: Value) -> Option<Option<Value>> {
+    let (last, parent) = path.split_last()?;
+    match (value_at_mut(root, parent)?, last) {
+        (Value::Map(map), PathElement::FieldName(name)) => Some(map.fields.insert(name.clone(), value)),
+        (Value::List(items), PathElement::Index(i)) => {
+            let item = items.get_mut(usize::try_from(*i).ok()?)?;
+            Some(Some(std::mem::replace(item, value)))
+        }
+        _ => None,
+    }
+}
+
+/// Undoes put, given what it returned.
+fn restore(root: &mut Value, path: &[PathElement], old: Option<Value>) {
+    match old {
+        Some(old) => {
+            put(root, path, old);
+        }
+        None => {
+            if let Some((PathElement::FieldName(name), parent)) = path.split_last() {
+                if let Some(Value::Map(map)) = value_at_mut(root, parent) {
+                    map.fields.remove(name);
+                }
+            }
+        }
+    }
+}
+
+impl TypedValue {
+    /// Sets the value at `path` and validates the edit against the schema,
+    /// without validating the rest of the object again. On errors, the
+    /// object is left as it was.
+    ///
+    /// The path is in the form of field sets, or uses indexes for list
+    /// items. It may name a field missing from an existing map, which is
+    /// added, but not a missing list item, since where an item goes depends
+    /// on the list.
+    pub fn set_path_validated(&mut self, path: &Path, value: Value) -> Result<(), ValidationErrors> {
+        if path.is_empty() {
+            let old = std::mem::replace(self.value_mut(), value);
+            let result = self.validate(&[]);
+            if result.is_err() {
+                *self.value_mut() = old;
+            }
+            return result;
+        }
+        let Some(target) = self.target(path) else {
+            return Err(ValidationErrors::from_error(ValidationError::invalid_value(
+                path.to_string(),
+                "nothing exists at the path",
+            )));
+        };
+        let mut errors = ValidationErrors::new();
+        self.validate_edit(path, &target, &value, &mut errors);
+
+        let Some(old) = put(self.value_mut(), &target.index_path, value) else {
+            return Err(errors);
+        };
+        self.validate_list_keys(path, &target, &mut errors);
+        if errors.is_empty() {
+            return Ok(());
+        }
+        restore(self.value_mut(), &target.index_path, old);
+        Err(errors)
+    }
+
+    /// Finds where an edit of `path` goes, or None if its parent, or the
+    /// list item it names, doesn't exist.
+    fn target(&self, path: &Path) -> Option<Target> {
+        let mut value = Some(self.value());
+        let mut types = vec![self.type_ref().clone()];
+        let mut index_path = Vec::new();
+        for pe in path {
+            let current = value?;
+            let atom = self.schema().resolve(types.last()?)?;
+            let (next, next_type, index_pe) = match (pe, current) {
+                (PathElement::FieldName(name), Value::Map(fields)) => {
+                    let map = atom.map.as_ref()?;
+                    let field_type = match map.find_field(name) {
+                        Some(field) => field.field_type.clone(),
+                        None => map.element_type.clone(),
+                    };
+                    (fields.get(name), field_type, pe.clone())
+                }
+                (_, Value::List(items)) => {
+                    let list = self.effective_list(atom.list.as_ref()?, &[items]);
+                    let index = match pe {
+                        PathElement::Index(i) => usize::try_from(*i).ok().filter(|i| *i < items.len())?,
+                        _ => self.list_item_elements(items, &list).iter().position(|e| e == pe)?,
+                    };
+                    (
+                        items.get(index),
+                        list.element_type.clone(),
+                        PathElement::Index(index as i32),
+                    )
+                }
+                _ => return None,
+            };
+            value = next;
+            types.push(next_type);
+            index_path.push(index_pe);
+        }
+        Some(Target { index_path, types })
+    }
+
+    /// Validates `value` as the new value at `path`, and as a field of its
+    /// map if it is one.
+    fn validate_edit(&self, path: &Path, target: &Target, value: &Value, errors: &mut ValidationErrors) {
+        let Some(type_ref) = target.types.last() else {
+            return;
+        };
+        if let (Some(PathElement::FieldName(name)), [.., parent_type, _]) = (path.as_slice().last(), &target.types[..])
+        {
+            let parent_path = Path::from_elements(path.as_slice()[..path.len() - 1].to_vec());
+            let Some(atom) = self.schema().resolve(parent_type) else {
+                return;
+            };
+            let Some(map) = atom.map.as_ref() else {
+                return;
+            };
+            match map.find_field(name) {
+                Some(field) => check_size_limits(field, value, path, errors),
+                None if !allows_unknown_fields(map) => {
+                    errors.add(ValidationError::unknown_field(parent_path.to_string(), name.clone()));
+                    return;
+                }
+                None => {}
+            }
+            if parent_type.named_type.as_deref() == Some(EMBEDDED_RESOURCE_TYPE)
+                && (name == "apiVersion" || name == "kind")
+                && value.as_str().is_none_or(str::is_empty)
+            {
+                errors.add(ValidationError::missing_field(parent_path.to_string(), name.clone()));
+            }
+        }
+        self.validate_value(value, type_ref, path.clone(), false, errors);
+    }
+
+    /// Validates the keys of the associative list holding the edited item,
+    /// or the item whose field was edited, once the edit is made.
+    fn validate_list_keys(&self, path: &Path, target: &Target, errors: &mut ValidationErrors) {
+        let n = target.index_path.len();
+        let is_item = |i: usize| matches!(target.index_path[i], PathElement::Index(_));
+        // The list holding the edited item, or the item holding the edited field
+        let list_len = match n {
+            0 => return,
+            _ if is_item(n - 1) => n - 1,
+            _ if n >= 2 && is_item(n - 2) => n - 2,
+            _ => return,
+        };
+        let Some(Value::List(items)) = value_at(self.value(), &target.index_path[..list_len]) else {
+            return;
+        };
+        let Some(atom) = self.schema().resolve(&target.types[list_len]) else {
+            return;
+        };
+        let Some(list) = atom.list.as_ref() else {
+            return;
+        };
+        let list = self.effective_list(list, &[items]);
+        if list.element_relationship != ElementRelationship::Associative {
+            return;
+        }
+        let PathElement::Index(edited) = target.index_path[list_len] else {
+            return;
+        };
+        let edited = edited as usize;
+        let list_path = Path::from_elements(path.as_slice()[..list_len].to_vec());
+        let key = match items.get(edited).map(|v| self.list_item_to_key(v, &list)) {
+            Some(Ok(key)) => key,
+            Some(Err(e)) => {
+                errors.add(e);
+                return;
+            }
+            None => return,
+        };
+        let indexes: Vec<usize> = items
+            .iter()
+            .enumerate()
+            .filter(|(_, v)| self.list_item_to_key(v, &list).ok().as_ref() == Some(&key))
+            .map(|(i, _)| i)
+            .collect();
+        if indexes.len() > 1 {
+            let rendered = if list.keys.is_empty() {
+                PathElement::value(items[edited].clone()).to_string()
+            } else {
+                PathElement::Key(key).to_string()
+            };
+            errors.add(ValidationError::duplicate_key(list_path.to_string(), rendered, indexes));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::typed::Parser;
+    use crate::value::from_yaml;
+
+    #[test]
+    fn test_set_path_validated() {
+        let pt = Parser::new(
+            r#"types:
+- name: root
+  map:
+    fields:
+    - name: replicas
+      type:
+        scalar: numeric
+    - name: labels
+      type:
+        map:
+          elementType:
+            scalar: string
+    - name: ports
+      type:
+        list:
+          elementType:
+            map:
+              fields:
+              - name: port
+                type:
+                  scalar: numeric
+              - name: name
+                type:
+                  scalar: string
+          elementRelationship: associative
+          keys: [port]
+"#,
+        )
+        .unwrap()
+        .type_by_name("root");
+        let mut obj = pt
+            .from_yaml(r#"{"replicas": 1, "ports": [{"port": 80}, {"port": 443}]}"#)
+            .unwrap();
+        let original = obj.clone();
+        let path = |p: &str| Path::parse(p).unwrap();
+        let value = |yaml: &str| from_yaml(yaml).unwrap();
+
+        // Invalid edits leave the object as it was
+        for (p, v) in [
+            (".replicas", r#""three""#),
+            (".unknown", "1"),
+            (".labels", r#"{"app": 1}"#),
+            (".ports[port=80].port", "443"),
+            (".ports[port=8080].name", r#""alt""#),
+            (".ports", r#"[{"port": 80}, {"port": 80}]"#),
+        ] {
+            assert!(obj.set_path_validated(&path(p), value(v)).is_err(), "{}", p);
+            assert_eq!(obj.value(), original.value(), "{}", p);
+        }
+
+        obj.set_path_validated(&path(".replicas"), value("3")).unwrap();
+        obj.set_path_validated(&path(".labels"), value(r#"{"app": "web"}"#)).unwrap();
+        obj.set_path_validated(&path(".labels.tier"), value(r#""db""#)).unwrap();
+        obj.set_path_validated(&path(".ports[port=80].name"), value(r#""http""#)).unwrap();
+        obj.set_path_validated(&path(".ports[port=443]"), value(r#"{"port": 8443}"#)).unwrap();
+        obj.validate(&[]).unwrap();
+        assert_eq!(
+            obj.value(),
+            &value(
+                r#"{"replicas": 3, "labels": {"app": "web", "tier": "db"},
+                "ports": [{"port": 80, "name": "http"}, {"port": 8443}]}"#
+            )
+        );
+    }
+}
@@ -0,0 +1,166 @@
+//! Hashing of typed values modulo the equivalences of their schema.
+
+use super::semantic::{parse_time, quantity};
+use super::typed_value::{json_value_to_value, TypedValue};
+use crate::fieldpath::PathElement;
+use crate::schema::{Scalar, TypeRef};
+use crate::value::Value;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+impl TypedValue {
+    /// Returns a hash of the value that is equal for values the schema
+    /// considers the same: integral floats hash like ints, items of sets
+    /// and keyed lists hash regardless of their order, and fields set to
+    /// their schema default hash like absent fields. With semantic scalars
+    /// enabled, quantities, int-or-strings and times hash by the value they
+    /// denote.
+    ///
+    /// The hash is stable within a build, so it can cheaply tell whether
+    /// an object changed since it was last seen, but it shouldn't be
+    /// persisted.
+    pub fn semantic_hash(&self) -> u64 {
+        self.hash_value(self.value(), self.type_ref())
+    }
+
+    fn hash_value(&self, value: &Value, type_ref: &TypeRef) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        let atom = self.schema().resolve(type_ref);
+        match value {
+            Value::Map(_) => {
+                let map = atom.as_ref().and_then(|atom| atom.map.as_ref());
+                5u8.hash(&mut hasher);
+                for (pe, child, child_type) in self.children(value, type_ref) {
+                    let PathElement::FieldName(name) = &pe else {
+                        continue;
+                    };
+                    let default = map.and_then(|map| map.find_field(name)).and_then(|f| f.default.as_ref());
+                    if default.is_some_and(|default| json_value_to_value(default) == *child) {
+                        continue;
+                    }
+                    name.hash(&mut hasher);
+                    self.hash_value(child, &child_type).hash(&mut hasher);
+                }
+                if map.is_none() {
+                    // Untyped maps have no children, hash them as they are
+                    value.hash(&mut hasher);
+                }
+            }
+            Value::List(items) => {
+                4u8.hash(&mut hasher);
+                let children = self.children(value, type_ref);
+                if children.is_empty() {
+                    items.hash(&mut hasher);
+                } else {
+                    let unordered = children.iter().any(|(pe, _, _)| !matches!(pe, PathElement::Index(_)));
+                    let mut hashes: Vec<u64> = children.iter().map(|(_, item, tr)| self.hash_value(item, tr)).collect();
+                    if unordered {
+                        hashes.sort_unstable();
+                    }
+                    hashes.hash(&mut hasher);
+                }
+            }
+            _ => {
+                let scalar = atom.and_then(|atom| atom.scalar).filter(|_| self.schema().semantic_scalars());
+                hash_scalar(value, scalar, &mut hasher);
+            }
+        }
+        hasher.finish()
+    }
+}
+
+fn hash_scalar(value: &Value, scalar: Option<Scalar>, hasher: &mut DefaultHasher) {
+    match (scalar, value) {
+        (Some(Scalar::Quantity), _) if quantity(value).is_some() => {
+            6u8.hash(hasher);
+            quantity(value).hash(hasher);
+        }
+        (Some(Scalar::IntOrString), Value::String(s)) if s.parse::<i64>().is_ok() => {
+            hash_int(s.parse().unwrap_or_default(), hasher);
+        }
+        (Some(Scalar::Time), Value::String(s)) if parse_time(s).is_some() => {
+            7u8.hash(hasher);
+            parse_time(s).hash(hasher);
+        }
+        (_, Value::Int(i)) => hash_int(*i, hasher),
+        (_, Value::Float(f)) => {
+            if f.fract() == 0.0 && *f >= i64::MIN as f64 && *f < i64::MAX as f64 {
+                // Also folds -0.0 into 0
+                hash_int(*f as i64, hasher);
+            } else {
+                3u8.hash(hasher);
+                f.to_bits().hash(hasher);
+            }
+        }
+        (_, Value::String(s)) => {
+            2u8.hash(hasher);
+            s.hash(hasher);
+        }
+        (_, Value::Bool(b)) => {
+            1u8.hash(hasher);
+            b.hash(hasher);
+        }
+        _ => 0u8.hash(hasher),
+    }
+}
+
+fn hash_int(i: i64, hasher: &mut DefaultHasher) {
+    8u8.hash(hasher);
+    i.hash(hasher);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::typed::Parser;
+
+    const SCHEMA: &str = r#"types:
+- name: root
+  map:
+    fields:
+    - name: replicas
+      type:
+        scalar: numeric
+    - name: policy
+      type:
+        scalar: string
+      default: Always
+    - name: memory
+      type:
+        scalar: quantity
+    - name: tags
+      type:
+        list:
+          elementType:
+            scalar: string
+          elementRelationship: associative
+    - name: args
+      type:
+        list:
+          elementType:
+            scalar: string
+          elementRelationship: atomic
+"#;
+
+    #[test]
+    fn test_semantic_hash() {
+        let parser = Parser::new(SCHEMA).unwrap();
+        let pt = parser.type_by_name("root");
+        let hash = |yaml: &str| pt.from_yaml(yaml).unwrap().semantic_hash();
+
+        let base = hash(r#"{"replicas": 1, "tags": ["a", "b"], "args": ["x", "y"]}"#);
+        assert_eq!(base, hash(r#"{"replicas": 1.0, "tags": ["b", "a"], "args": ["x", "y"]}"#));
+        assert_eq!(base, hash(r#"{"replicas": 1, "policy": "Always", "tags": ["a", "b"], "args": ["x", "y"]}"#));
+        assert_ne!(base, hash(r#"{"replicas": 1, "policy": "Never", "tags": ["a", "b"], "args": ["x", "y"]}"#));
+        assert_ne!(base, hash(r#"{"replicas": 2, "tags": ["a", "b"], "args": ["x", "y"]}"#));
+        assert_ne!(base, hash(r#"{"replicas": 1, "tags": ["a", "b"], "args": ["y", "x"]}"#));
+        assert_ne!(base, hash(r#"{"replicas": 1, "tags": ["a"], "args": ["x", "y"]}"#));
+
+        assert_ne!(hash(r#"{"memory": "1Gi"}"#), hash(r#"{"memory": 1073741824}"#));
+        let mut schema = parser.schema().clone();
+        schema.set_semantic_scalars(true);
+        let pt = Parser::from_schema(schema).type_by_name("root");
+        let hash = |yaml: &str| pt.from_yaml(yaml).unwrap().semantic_hash();
+        assert_eq!(hash(r#"{"memory": "1Gi"}"#), hash(r#"{"memory": 1073741824}"#));
+        assert_ne!(hash(r#"{"memory": "1Gi"}"#), hash(r#"{"memory": "1G"}"#));
+    }
+}
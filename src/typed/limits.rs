@@ -0,0 +1,334 @@
+//! Resource limits for parsing untrusted input.
+
+#![cfg_attr(not(test), deny(clippy::indexing_slicing, clippy::string_slice))]
+
+use super::parser::{ParseError, ParseableType};
+use super::typed_value::{as_typed, TypedValue};
+use crate::value::{Map, Value};
+use serde::de::{self, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
+use std::cell::Cell;
+use std::fmt;
+
+/// ParseLimits bounds the resources parsing may use. Limits left unset are
+/// not enforced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ParseLimits {
+    /// Maximum length of the input in bytes.
+    pub max_bytes: Option<usize>,
+    /// Maximum number of values in the document, counting every scalar,
+    /// list and map. YAML aliases count once per use, so documents that
+    /// expand exponentially are stopped early.
+    pub max_nodes: Option<usize>,
+    /// Maximum nesting depth of lists and maps.
+    pub max_depth: Option<usize>,
+}
+
+impl ParseLimits {
+    /// Limits suited to untrusted Kubernetes objects: 3 MiB of input, the
+    /// API server's request size limit, a million values and 64 levels.
+    pub fn untrusted() -> Self {
+        ParseLimits {
+            max_bytes: Some(3 * 1024 * 1024),
+            max_nodes: Some(1_000_000),
+            max_depth: Some(64),
+        }
+    }
+}
+
+/// InputChecks are checks of the document itself, made while it is read,
+/// that plain parsing leaves out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(super) struct InputChecks {
+    /// Reject maps with a key more than once, instead of keeping the last.
+    pub duplicate_keys: bool,
+    /// Reject integers that don't fit in 64 bits and non-finite floats,
+    /// instead of reading them as floats.
+    pub numeric_overflow: bool,
+}
+
+/// LimitExceeded identifies the limit an input went over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LimitExceeded {
+    Bytes { limit: usize, actual: usize },
+    Nodes { limit: usize },
+    Depth { limit: usize },
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LimitExceeded::Bytes { limit, actual } => {
+                write!(f, "input of {} bytes exceeds the limit of {} bytes", actual, limit)
+            }
+            LimitExceeded::Nodes { limit } => write!(f, "input exceeds the limit of {} values", limit),
+            LimitExceeded::Depth { limit } => write!(f, "input exceeds the nesting limit of {}", limit),
+        }
+    }
+}
+
+impl ParseableType {
+    /// Parses a YAML string into a TypedValue within the given limits.
+    pub fn from_yaml_with_limits(&self, yaml: &str, limits: &ParseLimits) -> Result<TypedValue, ParseError> {
+        let value = read_yaml(yaml, limits, InputChecks::default())?;
+        as_typed(value, &self.schema, self.type_ref.clone(), &[])
+            .map_err(|e| ParseError::new(format!("validation failed: {}", e)))
+    }
+
+    /// Parses a JSON string into a TypedValue.
+    pub fn from_json(&self, json: &str) -> Result<TypedValue, ParseError> {
+        self.from_json_with_limits(json, &ParseLimits::default())
+    }
+
+    /// Parses a JSON string into a TypedValue within the given limits.
+    pub fn from_json_with_limits(&self, json: &str, limits: &ParseLimits) -> Result<TypedValue, ParseError> {
+        let value = read_json(json, limits, InputChecks::default())?;
+        as_typed(value, &self.schema, self.type_ref.clone(), &[])
+            .map_err(|e| ParseError::new(format!("validation failed: {}", e)))
+    }
+}
+
+/// Reads a YAML document within the given limits.
+pub(super) fn read_yaml(yaml: &str, limits: &ParseLimits, checks: InputChecks) -> Result<Value, ParseError> {
+    read_limited(yaml, limits, checks, "YAML", |seed| {
+        seed.deserialize(serde_yaml::Deserializer::from_str(yaml))
+    })
+}
+
+/// Reads a JSON document within the given limits.
+pub(super) fn read_json(json: &str, limits: &ParseLimits, checks: InputChecks) -> Result<Value, ParseError> {
+    read_limited(json, limits, checks, "JSON", |seed| {
+        let mut de = serde_json::Deserializer::from_str(json);
+        let value = seed.deserialize(&mut de)?;
+        de.end()?;
+        Ok::<_, serde_json::Error>(value)
+    })
+}
+
+/// Fails if the input is longer than the byte limit.
+pub(super) fn check_bytes(input: &str, limits: &ParseLimits) -> Result<(), ParseError> {
+    match limits.max_bytes.filter(|limit| input.len() > *limit) {
+        Some(limit) => Err(ParseError::limit_exceeded(LimitExceeded::Bytes {
+            limit,
+            actual: input.len(),
+        })),
+        None => Ok(()),
+    }
+}
+
+/// Reads a document with `read`, counting values as they are built so
+/// oversized input fails before it is materialized.
+fn read_limited<E: fmt::Display>(
+    input: &str,
+    limits: &ParseLimits,
+    checks: InputChecks,
+    format: &str,
+    read: impl FnOnce(ValueSeed<'_>) -> Result<Value, E>,
+) -> Result<Value, ParseError> {
+    check_bytes(input, limits)?;
+    let budget = Budget {
+        limits: *limits,
+        checks,
+        nodes: Cell::new(0),
+        exceeded: Cell::new(None),
+    };
+    read(ValueSeed {
+        budget: &budget,
+        depth: 0,
+    })
+    .map_err(|e| match budget.exceeded.get() {
+        Some(exceeded) => ParseError::limit_exceeded(exceeded),
+        None => ParseError::new(format!("failed to parse {}: {}", format, e)),
+    })
+}
+
+struct Budget {
+    limits: ParseLimits,
+    checks: InputChecks,
+    nodes: Cell<usize>,
+    exceeded: Cell<Option<LimitExceeded>>,
+}
+
+impl Budget {
+    fn exceed<E: de::Error>(&self, exceeded: LimitExceeded) -> E {
+        self.exceeded.set(Some(exceeded));
+        E::custom(exceeded)
+    }
+}
+
+/// ValueSeed builds a Value like its Deserialize impl does, charging every
+/// value against the budget.
+#[derive(Clone, Copy)]
+struct ValueSeed<'b> {
+    budget: &'b Budget,
+    depth: usize,
+}
+
+impl<'b> ValueSeed<'b> {
+    fn count<E: de::Error>(&self) -> Result<(), E> {
+        let nodes = self.budget.nodes.get() + 1;
+        self.budget.nodes.set(nodes);
+        match self.budget.limits.max_nodes {
+            Some(limit) if nodes > limit => Err(self.budget.exceed(LimitExceeded::Nodes { limit })),
+            _ => Ok(()),
+        }
+    }
+
+    fn nested<E: de::Error>(&self) -> Result<ValueSeed<'b>, E> {
+        self.count()?;
+        let depth = self.depth + 1;
+        match self.budget.limits.max_depth {
+            Some(limit) if depth > limit => Err(self.budget.exceed(LimitExceeded::Depth { limit })),
+            _ => Ok(ValueSeed {
+                budget: self.budget,
+                depth,
+            }),
+        }
+    }
+
+    fn scalar<E: de::Error>(&self, value: Value) -> Result<Value, E> {
+        self.count()?;
+        Ok(value)
+    }
+}
+
+impl<'de> DeserializeSeed<'de> for ValueSeed<'_> {
+    type Value = Value;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+}
+
+impl<'de> Visitor<'de> for ValueSeed<'_> {
+    type Value = Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a JSON or YAML value")
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Value, E> {
+        self.scalar(Value::Null)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<Value, E> {
+        self.scalar(Value::Null)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Value, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Value, E> {
+        self.scalar(Value::Bool(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Value, E> {
+        self.scalar(Value::Int(v))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Value, E> {
+        match i64::try_from(v) {
+            Ok(v) => self.scalar(Value::Int(v)),
+            Err(_) if self.budget.checks.numeric_overflow => {
+                Err(E::custom(format!("integer {} is out of range of 64-bit integers", v)))
+            }
+            Err(_) => self.scalar(Value::Float(v as f64)),
+        }
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Value, E> {
+        if self.budget.checks.numeric_overflow && !v.is_finite() {
+            return Err(E::custom(format!("number {} is out of range of 64-bit floats", v)));
+        }
+        self.scalar(Value::Float(v))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Value, E> {
+        self.scalar(Value::String(v.to_string()))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Value, E> {
+        self.scalar(Value::String(v))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Value, A::Error> {
+        let seed = self.nested()?;
+        let mut items = Vec::new();
+        while let Some(item) = seq.next_element_seed(seed)? {
+            items.push(item);
+        }
+        Ok(Value::List(items))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Value, A::Error> {
+        let seed = self.nested()?;
+        let mut fields = Map::new();
+        while let Some(key) = map.next_key::<String>()? {
+            let value = map.next_value_seed(seed)?;
+            if self.budget.checks.duplicate_keys && fields.get(&key).is_some() {
+                return Err(de::Error::custom(format!("duplicate key {:?}", key)));
+            }
+            fields.set(key, value);
+        }
+        Ok(Value::Map(fields))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::typed::deduced_parseable_type;
+
+    #[test]
+    fn test_parse_limits() {
+        let pt = deduced_parseable_type();
+        let yaml = "a: [1, {b: x}]\nc: 2.5\n";
+        let unlimited = pt.from_yaml(yaml).unwrap();
+        assert_eq!(
+            pt.from_yaml_with_limits(yaml, &ParseLimits::untrusted()).unwrap().value(),
+            unlimited.value()
+        );
+        assert_eq!(
+            pt.from_json_with_limits(r#"{"a": [1, {"b": "x"}], "c": 2.5}"#, &ParseLimits::untrusted())
+                .unwrap()
+                .value(),
+            unlimited.value()
+        );
+
+        let exceeded = |limits: ParseLimits| pt.from_yaml_with_limits(yaml, &limits).unwrap_err().limit_exceeded;
+        assert_eq!(
+            exceeded(ParseLimits {
+                max_bytes: Some(8),
+                ..Default::default()
+            }),
+            Some(LimitExceeded::Bytes { limit: 8, actual: yaml.len() })
+        );
+        assert_eq!(
+            exceeded(ParseLimits {
+                max_nodes: Some(5),
+                ..Default::default()
+            }),
+            Some(LimitExceeded::Nodes { limit: 5 })
+        );
+        assert_eq!(
+            exceeded(ParseLimits {
+                max_depth: Some(2),
+                ..Default::default()
+            }),
+            Some(LimitExceeded::Depth { limit: 2 })
+        );
+        assert!(pt.from_json("{").unwrap_err().limit_exceeded.is_none());
+
+        // Aliases count every time they are expanded
+        let laughs = "a: &a [x, x, x, x]\nb: &b [*a, *a, *a, *a]\nc: [*b, *b, *b, *b]\n";
+        let limits = ParseLimits {
+            max_nodes: Some(50),
+            ..Default::default()
+        };
+        assert_eq!(
+            pt.from_yaml_with_limits(laughs, &limits).unwrap_err().limit_exceeded,
+            Some(LimitExceeded::Nodes { limit: 50 })
+        );
+    }
+}
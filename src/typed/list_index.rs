@@ -0,0 +1,203 @@
+//! Index maps of the atomic lists a merge replaces.
+
+use super::typed_value::TypedValue;
+use super::validation::ValidationErrors;
+use crate::fieldpath::{Path, PathElementMap};
+use crate::schema::{ElementRelationship, TypeRef};
+use crate::value::Value;
+
+/// ListIndexMap describes how an atomic list replaced by a merge relates to
+/// the list it replaced, so that the change can be shown as the items that
+/// were removed and added rather than as a new list.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListIndexMap {
+    /// The path of the list.
+    pub path: Path,
+    /// Pairs of the index of an item in the old list and its index in the
+    /// new one, for the longest run of equal items kept in order. Old items
+    /// that aren't listed were removed and new ones were added. When the
+    /// changed parts of the lists are too large to compare item by item,
+    /// only the equal items before and after them are paired.
+    pub indexes: Vec<(usize, usize)>,
+}
+
+/// The most cells of the table comparing the changed parts of two lists,
+/// which bounds the memory an index map takes to 16 MiB.
+const MAX_TABLE_CELLS: usize = 1 << 22;
+
+impl TypedValue {
+    /// Merges like `merge`, also returning an index map for each atomic
+    /// list whose items the merge replaced, in the order the lists are met.
+    pub fn merge_with_index_maps(&self, rhs: &TypedValue) -> Result<(TypedValue, Vec<ListIndexMap>), ValidationErrors> {
+        let merged = self.merge(rhs)?;
+        let mut maps = Vec::new();
+        self.collect_index_maps(self.value(), rhs.value(), self.type_ref(), Path::new(), &mut maps);
+        Ok((merged, maps))
+    }
+
+    fn collect_index_maps(
+        &self,
+        lhs: &Value,
+        rhs: &Value,
+        type_ref: &TypeRef,
+        path: Path,
+        maps: &mut Vec<ListIndexMap>,
+    ) {
+        if lhs == rhs {
+            return;
+        }
+        if let (Value::List(old), Value::List(new)) = (lhs, rhs) {
+            let list = self.schema().resolve(type_ref).and_then(|atom| atom.list);
+            if let Some(list) = list {
+                if self.effective_list(&list, &[old, new]).element_relationship == ElementRelationship::Atomic {
+                    maps.push(ListIndexMap {
+                        path,
+                        indexes: equal_items(old, new),
+                    });
+                    return;
+                }
+            }
+        }
+
+        let lhs_children: PathElementMap<(&Value, TypeRef)> = self
+            .children(lhs, type_ref)
+            .into_iter()
+            .map(|(pe, child, child_type)| (pe, (child, child_type)))
+            .collect();
+        for (pe, child, child_type) in self.children(rhs, type_ref) {
+            if let Some((lhs_child, _)) = lhs_children.get(&pe) {
+                self.collect_index_maps(lhs_child, child, &child_type, path.with(pe), maps);
+            }
+        }
+    }
+}
+
+/// Returns the index pairs of a longest common subsequence of the lists.
+fn equal_items(old: &[Value], new: &[Value]) -> Vec<(usize, usize)> {
+    // The items before and after the change need no table
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (old_mid, new_mid) = (&old[prefix..old.len() - suffix], &new[prefix..new.len() - suffix]);
+    let mut indexes: Vec<(usize, usize)> = (0..prefix).map(|i| (i, i)).collect();
+    let (old_tail, new_tail) = (old.len() - suffix, new.len() - suffix);
+    let cells = (old_mid.len() + 1).saturating_mul(new_mid.len() + 1);
+    if cells > MAX_TABLE_CELLS {
+        indexes.extend((0..suffix).map(|k| (old_tail + k, new_tail + k)));
+        return indexes;
+    }
+
+    // lengths[i][j] is the length of the longest common subsequence of
+    // old_mid[i..] and new_mid[j..]
+    let mut lengths = vec![vec![0u32; new_mid.len() + 1]; old_mid.len() + 1];
+    for i in (0..old_mid.len()).rev() {
+        for j in (0..new_mid.len()).rev() {
+            lengths[i][j] = if old_mid[i] == new_mid[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let (mut i, mut j) = (0, 0);
+    while i < old_mid.len() && j < new_mid.len() {
+        if old_mid[i] == new_mid[j] {
+            indexes.push((prefix + i, prefix + j));
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    indexes.extend((0..suffix).map(|k| (old_tail + k, new_tail + k)));
+    indexes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fieldpath::PathElement;
+    use crate::typed::Parser;
+
+    #[test]
+    fn test_merge_with_index_maps() {
+        let parser = Parser::new(
+            r#"types:
+- name: root
+  map:
+    fields:
+    - name: args
+      type:
+        list:
+          elementType:
+            scalar: string
+          elementRelationship: atomic
+    - name: items
+      type:
+        list:
+          elementType:
+            map:
+              fields:
+              - name: name
+                type:
+                  scalar: string
+              - name: args
+                type:
+                  list:
+                    elementType:
+                      scalar: string
+                    elementRelationship: atomic
+          elementRelationship: associative
+          keys: [name]
+"#,
+        )
+        .unwrap();
+        let pt = parser.type_by_name("root");
+        let live = pt
+            .from_yaml(r#"{"args": ["a", "b", "c", "d"], "items": [{"name": "x", "args": ["1", "2"]}, {"name": "y", "args": ["3"]}]}"#)
+            .unwrap();
+        let config = pt
+            .from_yaml(r#"{"args": ["a", "c", "e", "d"], "items": [{"name": "x", "args": ["0", "2"]}, {"name": "y", "args": ["3"]}]}"#)
+            .unwrap();
+
+        let (merged, maps) = live.merge_with_index_maps(&config).unwrap();
+        assert_eq!(merged.value(), live.merge(&config).unwrap().value());
+        assert_eq!(
+            maps,
+            vec![
+                ListIndexMap {
+                    path: Path::from_elements(vec![PathElement::field_name("args")]),
+                    indexes: vec![(0, 0), (2, 1), (3, 3)],
+                },
+                ListIndexMap {
+                    path: Path::parse(r#".items[name="x"].args"#).unwrap(),
+                    indexes: vec![(1, 1)],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_large_list_index_map() {
+        // 50k items each way would need a 10 GB table
+        let old: Vec<Value> = (0..50_000).map(Value::Int).collect();
+        let mut new: Vec<Value> = (0..50_000).map(|i| Value::Int(i + 1)).collect();
+        new[0] = Value::Int(0);
+        new[49_999] = Value::Int(49_999);
+        assert_eq!(equal_items(&old, &new), vec![(0, 0), (49_999, 49_999)]);
+
+        // Small changes are still compared item by item
+        let old: Vec<Value> = (0..1_000).map(Value::Int).collect();
+        let new: Vec<Value> = (1..1_001).map(Value::Int).collect();
+        let indexes = equal_items(&old, &new);
+        assert_eq!(indexes.len(), 999);
+        assert_eq!(indexes[0], (1, 0));
+    }
+}
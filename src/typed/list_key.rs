@@ -0,0 +1,160 @@
+//! Keys of associative list items.
+
+use super::typed_value::list_item_key;
+use super::validation::ValidationError;
+use crate::fieldpath::PathElement;
+use crate::schema::{ElementRelationship, Schema, TypeRef};
+use crate::value::{Field, FieldList, Value};
+use std::fmt;
+
+/// ListKey identifies an item of an associative list by the values of the
+/// list's key fields, ordered by field name.
+///
+/// It is derived the same way merge derives keys, including schema defaults
+/// for omitted key fields.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ListKey {
+    fields: FieldList,
+}
+
+impl ListKey {
+    /// Creates a key from field name and value pairs.
+    pub fn new<S: Into<String>>(fields: impl IntoIterator<Item = (S, Value)>) -> Self {
+        ListKey {
+            fields: FieldList::with_fields(
+                fields
+                    .into_iter()
+                    .map(|(name, value)| Field {
+                        name: name.into(),
+                        value,
+                    })
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Derives the key of an item of the keyed associative list `list_type`.
+    pub fn from_item(schema: &Schema, list_type: &TypeRef, item: &Value) -> Result<ListKey, ValidationError> {
+        let atom = schema
+            .resolve(list_type)
+            .ok_or_else(|| ValidationError::schema_error("unable to resolve list type"))?;
+        let list = match atom.list {
+            Some(list) if list.element_relationship == ElementRelationship::Associative && !list.keys.is_empty() => list,
+            _ => return Err(ValidationError::schema_error("expected a keyed associative list")),
+        };
+        Ok(ListKey {
+            fields: list_item_key(schema, item, &list)?,
+        })
+    }
+
+    /// Returns the item of `list` with this key, where `list` is a value of
+    /// the keyed associative list `list_type`.
+    pub fn find_in<'a>(&self, schema: &Schema, list_type: &TypeRef, list: &'a Value) -> Option<&'a Value> {
+        list.as_list()?
+            .iter()
+            .find(|item| ListKey::from_item(schema, list_type, item).ok().as_ref() == Some(self))
+    }
+
+    /// Returns the value of a key field.
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.fields.get(name).map(|f| &f.value)
+    }
+
+    /// Returns the key fields.
+    pub fn fields(&self) -> &FieldList {
+        &self.fields
+    }
+
+    /// Returns the path element addressing the item.
+    pub fn to_path_element(&self) -> PathElement {
+        PathElement::Key(self.fields.clone())
+    }
+}
+
+impl From<FieldList> for ListKey {
+    fn from(mut fields: FieldList) -> Self {
+        fields.sort();
+        ListKey { fields }
+    }
+}
+
+impl From<ListKey> for FieldList {
+    fn from(key: ListKey) -> Self {
+        key.fields
+    }
+}
+
+impl fmt::Display for ListKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_path_element())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::typed::Parser;
+
+    const SCHEMA: &str = r#"types:
+- name: pod
+  map:
+    fields:
+    - name: containers
+      type:
+        namedType: containers
+- name: containers
+  list:
+    elementType:
+      namedType: container
+    elementRelationship: associative
+    keys:
+    - name
+    - protocol
+- name: container
+  map:
+    fields:
+    - name: name
+      type:
+        scalar: string
+    - name: protocol
+      type:
+        scalar: string
+      default: TCP
+    - name: image
+      type:
+        scalar: string
+"#;
+
+    #[test]
+    fn test_list_key() {
+        let parser = Parser::new(SCHEMA).unwrap();
+        let pod = parser
+            .type_by_name("pod")
+            .from_yaml(r#"{"containers": [{"name": "sidecar", "image": "a"}, {"name": "nginx", "image": "b"}]}"#)
+            .unwrap();
+        let containers = pod.value().as_map().unwrap().get("containers").unwrap();
+        let list_type = TypeRef {
+            named_type: Some("containers".to_string()),
+            ..Default::default()
+        };
+
+        // Defaults fill in omitted key fields
+        let key = ListKey::from_item(&parser.schema, &list_type, &containers.as_list().unwrap()[1]).unwrap();
+        assert_eq!(key, ListKey::new([("protocol", Value::String("TCP".into())), ("name", Value::String("nginx".into()))]));
+        assert_eq!(key.get("name"), Some(&Value::String("nginx".into())));
+        assert_eq!(key.to_string(), r#"[name="nginx",protocol="TCP"]"#);
+
+        let found = key.find_in(&parser.schema, &list_type, containers).unwrap();
+        assert_eq!(found.as_map().unwrap().get("image"), Some(&Value::String("b".into())));
+        assert!(containers.find_by_key(key.fields()).is_none());
+        assert!(containers
+            .find_by_key(ListKey::new([("name", Value::String("nginx".into()))]).fields())
+            .is_some());
+
+        let not_keyed = TypeRef {
+            named_type: Some("container".to_string()),
+            ..Default::default()
+        };
+        assert!(ListKey::from_item(&parser.schema, &not_keyed, containers).is_err());
+    }
+}
@@ -3,10 +3,31 @@
 //! This module provides validation, comparison, and merging operations.
 
 mod comparison;
+mod dedup;
+mod defaults;
+mod deprecation;
+mod detailed;
+mod duplicates;
+mod edit;
+mod hash;
+mod limits;
+mod list_index;
+mod list_key;
+mod normalize;
+mod output;
+mod parse_cache;
 mod parser;
+mod profile;
+mod pointer;
+mod query;
 mod reconcile_schema;
+mod render;
+mod rename;
+mod scratch;
+mod semantic;
 mod typed_value;
 mod validation;
+mod yaml_policy;
 
 #[cfg(test)]
 mod toset_test;
@@ -24,7 +45,23 @@ mod deduced_test;
 mod merge_test;
 
 pub use comparison::*;
+pub use dedup::*;
+pub use defaults::*;
+pub use deprecation::*;
+pub use detailed::*;
+pub use limits::*;
+pub use list_index::*;
+pub use list_key::*;
+pub use normalize::*;
+pub use output::*;
+pub use parse_cache::*;
 pub use parser::*;
+pub use profile::*;
+pub use query::*;
 pub use reconcile_schema::*;
+pub use render::*;
 pub use typed_value::*;
 pub use validation::*;
+pub use yaml_policy::*;
+
+pub(crate) use scratch::CompareScratch;
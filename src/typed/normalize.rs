@@ -0,0 +1,218 @@
+//! Mapping of field aliases and differently cased names to canonical fields.
+
+use super::parser::{ParseError, ParseableType};
+use super::typed_value::{as_typed, list_item_key, TypedValue};
+use crate::fieldpath::{Path, PathElement};
+use crate::schema::{ElementRelationship, Map, Schema, TypeRef};
+use crate::value::Value;
+
+/// FieldMatching selects how leniently parsing matches field names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FieldMatching {
+    /// Map the aliases declared in the schema to their fields.
+    pub aliases: bool,
+    /// Map names that only differ in ASCII case from exactly one field.
+    pub case_insensitive: bool,
+}
+
+/// FieldRename records a field name that was mapped to its canonical name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldRename {
+    /// The path of the canonical field.
+    pub path: Path,
+    /// The name used in the input.
+    pub from: String,
+}
+
+impl ParseableType {
+    /// Parses a YAML string into a TypedValue, first mapping field names to
+    /// canonical fields as `matching` asks. Returns the renames made.
+    pub fn from_yaml_with_matching(
+        &self,
+        yaml: &str,
+        matching: FieldMatching,
+    ) -> Result<(TypedValue, Vec<FieldRename>), ParseError> {
+        let value: Value = serde_yaml::from_str(yaml)
+            .map_err(|e| ParseError::new(format!("failed to parse YAML: {}", e)))?;
+        self.from_value_with_matching(value, matching)
+    }
+
+    /// Creates a TypedValue from a Value, first mapping field names to
+    /// canonical fields as `matching` asks. Returns the renames made.
+    pub fn from_value_with_matching(
+        &self,
+        mut value: Value,
+        matching: FieldMatching,
+    ) -> Result<(TypedValue, Vec<FieldRename>), ParseError> {
+        let mut renames = Vec::new();
+        let normalizer = Normalizer {
+            schema: &self.schema,
+            matching,
+        };
+        normalizer.normalize(&mut value, &self.type_ref, &Path::new(), &mut renames);
+        let tv = as_typed(value, &self.schema, self.type_ref.clone(), &[])
+            .map_err(|e| ParseError::new(format!("validation failed: {}", e)))?;
+        Ok((tv, renames))
+    }
+}
+
+struct Normalizer<'a> {
+    schema: &'a Schema,
+    matching: FieldMatching,
+}
+
+impl Normalizer<'_> {
+    fn normalize(&self, value: &mut Value, type_ref: &TypeRef, path: &Path, renames: &mut Vec<FieldRename>) {
+        let Some(atom) = self.schema.resolve(type_ref) else {
+            return;
+        };
+        match value {
+            Value::Map(fields) => {
+                let Some(ref map) = atom.map else {
+                    return;
+                };
+                for (from, to) in self.rename_fields(fields, map) {
+                    renames.push(FieldRename {
+                        path: path.with(PathElement::field_name(to)),
+                        from,
+                    });
+                }
+                for (key, val) in fields.fields.iter_mut() {
+                    let field_type = match map.find_field(key) {
+                        Some(field) => &field.field_type,
+                        None => &map.element_type,
+                    };
+                    self.normalize(val, field_type, &path.with(PathElement::field_name(key.clone())), renames);
+                }
+            }
+            Value::List(items) => {
+                let Some(ref list) = atom.list else {
+                    return;
+                };
+                let item_map = self.schema.resolve(&list.element_type).and_then(|a| a.map);
+                for (i, item) in items.iter_mut().enumerate() {
+                    // Key fields must be canonical before the key is derived
+                    let item_renames = match (&mut *item, &item_map) {
+                        (Value::Map(fields), Some(map)) => self.rename_fields(fields, map),
+                        _ => Vec::new(),
+                    };
+                    let pe = if list.element_relationship != ElementRelationship::Associative {
+                        PathElement::index(i as i32)
+                    } else if list.keys.is_empty() {
+                        PathElement::value(item.clone())
+                    } else {
+                        match list_item_key(self.schema, item, list) {
+                            Ok(key) => PathElement::Key(key),
+                            Err(_) => PathElement::index(i as i32),
+                        }
+                    };
+                    let item_path = path.with(pe);
+                    for (from, to) in item_renames {
+                        renames.push(FieldRename {
+                            path: item_path.with(PathElement::field_name(to)),
+                            from,
+                        });
+                    }
+                    self.normalize(item, &list.element_type, &item_path, renames);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Renames the unknown fields of a map that match a field, unless the
+    /// canonical field is set too. Returns the (from, to) pairs.
+    fn rename_fields(&self, fields: &mut crate::value::Map, map: &Map) -> Vec<(String, String)> {
+        let unknown: Vec<String> = fields.fields.keys().filter(|k| map.find_field(k).is_none()).cloned().collect();
+        let mut renamed = Vec::new();
+        for key in unknown {
+            let Some(canonical) = self.canonical_name(&key, map) else {
+                continue;
+            };
+            if fields.get(&canonical).is_some() {
+                continue;
+            }
+            if let Some(val) = fields.delete(&key) {
+                fields.set(canonical.clone(), val);
+                renamed.push((key, canonical));
+            }
+        }
+        renamed
+    }
+
+    fn canonical_name(&self, key: &str, map: &Map) -> Option<String> {
+        if self.matching.aliases {
+            if let Some(field) = map.fields.iter().find(|f| f.aliases.iter().any(|a| a == key)) {
+                return Some(field.name.clone());
+            }
+        }
+        if self.matching.case_insensitive {
+            let mut matches = map.fields.iter().filter(|f| f.name.eq_ignore_ascii_case(key));
+            if let (Some(field), None) = (matches.next(), matches.next()) {
+                return Some(field.name.clone());
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::typed::Parser;
+
+    const SCHEMA: &str = r#"types:
+- name: root
+  map:
+    fields:
+    - name: replicas
+      type:
+        scalar: numeric
+      aliases: [size]
+    - name: ports
+      type:
+        list:
+          elementType:
+            namedType: port
+          elementRelationship: associative
+          keys: [containerPort]
+- name: port
+  map:
+    fields:
+    - name: containerPort
+      type:
+        scalar: numeric
+      aliases: [port]
+"#;
+
+    #[test]
+    fn test_field_matching() {
+        let pt = Parser::new(SCHEMA).unwrap().type_by_name("root");
+        let yaml = r#"{"size": 2, "Ports": [{"port": 80}]}"#;
+
+        assert!(pt.from_yaml_with_matching(yaml, FieldMatching::default()).is_err());
+        let aliases_only = FieldMatching {
+            aliases: true,
+            ..Default::default()
+        };
+        assert!(pt.from_yaml_with_matching(yaml, aliases_only).is_err());
+
+        let matching = FieldMatching {
+            aliases: true,
+            case_insensitive: true,
+        };
+        let (tv, renames) = pt.from_yaml_with_matching(yaml, matching).unwrap();
+        let expected = pt.from_yaml(r#"{"replicas": 2, "ports": [{"containerPort": 80}]}"#).unwrap();
+        assert_eq!(tv.value(), expected.value());
+
+        let renamed: Vec<(String, String)> = renames.iter().map(|r| (r.from.clone(), r.path.to_string())).collect();
+        assert_eq!(
+            renamed,
+            vec![
+                ("Ports".to_string(), ".ports".to_string()),
+                ("size".to_string(), ".replicas".to_string()),
+                ("port".to_string(), ".ports[containerPort=80].containerPort".to_string()),
+            ]
+        );
+    }
+}
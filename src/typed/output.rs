@@ -0,0 +1,126 @@
+//! Serialization of typed values with schema-aware output options.
+
+use super::typed_value::TypedValue;
+use crate::schema::{ElementRelationship, TypeRef};
+use crate::value::{self, Map, Value};
+
+/// OutputOptions controls how TypedValue::to_json_with and to_yaml_with
+/// emit a value. The value itself is left as it is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct OutputOptions {
+    /// Emit the items of sets, associative lists of scalars, in sorted
+    /// order, so that output doesn't depend on the order the items were
+    /// merged in.
+    pub sort_sets: bool,
+}
+
+impl TypedValue {
+    /// Returns the value as it is emitted with the options.
+    pub fn output_value(&self, options: OutputOptions) -> Value {
+        if !options.sort_sets {
+            return self.value().clone();
+        }
+        self.sorted_sets(self.value(), self.type_ref())
+    }
+
+    /// Serializes the value to JSON with the options.
+    pub fn to_json_with(&self, options: OutputOptions) -> Result<String, serde_json::Error> {
+        value::to_json(&self.output_value(options))
+    }
+
+    /// Serializes the value to YAML with the options.
+    pub fn to_yaml_with(&self, options: OutputOptions) -> Result<String, serde_yaml::Error> {
+        value::to_yaml(&self.output_value(options))
+    }
+
+    fn sorted_sets(&self, value: &Value, type_ref: &TypeRef) -> Value {
+        let children = self.children(value, type_ref);
+        match value {
+            Value::Map(_) if !children.is_empty() => {
+                let mut map = Map::new();
+                for (pe, child, child_type) in children {
+                    if let Some(name) = pe.as_field_name() {
+                        map.set(name.to_string(), self.sorted_sets(child, &child_type));
+                    }
+                }
+                Value::Map(map)
+            }
+            Value::List(_) if !children.is_empty() => {
+                let mut items: Vec<Value> = children
+                    .iter()
+                    .map(|(_, child, child_type)| self.sorted_sets(child, child_type))
+                    .collect();
+                let is_set = self
+                    .schema()
+                    .resolve(type_ref)
+                    .and_then(|atom| atom.list)
+                    .is_some_and(|list| {
+                        list.element_relationship == ElementRelationship::Associative && list.keys.is_empty()
+                    });
+                if is_set && items.iter().all(|item| !matches!(item, Value::List(_) | Value::Map(_))) {
+                    items.sort();
+                }
+                Value::List(items)
+            }
+            _ => value.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::typed::Parser;
+
+    #[test]
+    fn test_sort_sets() {
+        let pt = Parser::new(
+            r#"types:
+- name: root
+  map:
+    fields:
+    - name: finalizers
+      type:
+        list:
+          elementType:
+            scalar: string
+          elementRelationship: associative
+    - name: args
+      type:
+        list:
+          elementType:
+            scalar: string
+          elementRelationship: atomic
+    - name: nested
+      type:
+        map:
+          elementType:
+            namedType: root
+"#,
+        )
+        .unwrap()
+        .type_by_name("root");
+        let obj = pt
+            .from_yaml(r#"{"finalizers": ["b", "a"], "args": ["y", "x"], "nested": {"n": {"finalizers": ["z", "c"]}}}"#)
+            .unwrap();
+
+        let sorted = OutputOptions { sort_sets: true };
+        assert_eq!(
+            obj.to_json_with(sorted).unwrap(),
+            r#"{"args":["y","x"],"finalizers":["a","b"],"nested":{"n":{"finalizers":["c","z"]}}}"#
+        );
+        assert_eq!(
+            obj.to_json_with(OutputOptions::default()).unwrap(),
+            value::to_json(obj.value()).unwrap()
+        );
+        // The value keeps its order
+        assert_eq!(
+            value::to_json(obj.value()).unwrap(),
+            r#"{"args":["y","x"],"finalizers":["b","a"],"nested":{"n":{"finalizers":["z","c"]}}}"#
+        );
+        assert_eq!(
+            obj.to_yaml_with(sorted).unwrap(),
+            "args:\n- y\n- x\nfinalizers:\n- a\n- b\nnested:\n  n:\n    finalizers:\n    - c\n    - z\n"
+        );
+    }
+}
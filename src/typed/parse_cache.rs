@@ -0,0 +1,197 @@
+//! Content-addressable cache of parsed objects.
+
+use super::parser::{ParseError, ParseableType};
+use super::typed_value::TypedValue;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+
+/// ParseCache remembers parsed objects keyed by the schema and the exact
+/// input bytes, so repeated identical inputs are parsed and validated once.
+///
+/// The cache holds at most `capacity` objects and evicts the oldest first.
+/// Failed parses are not cached. It can be shared between threads.
+#[derive(Debug)]
+pub struct ParseCache {
+    capacity: usize,
+    state: Mutex<CacheState>,
+}
+
+#[derive(Debug, Default)]
+struct CacheState {
+    entries: HashMap<CacheKey, Vec<CacheEntry>>,
+    order: VecDeque<(CacheKey, Arc<TypedValue>)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct CacheKey {
+    schema: u64,
+    content: u64,
+}
+
+#[derive(Debug)]
+struct CacheEntry {
+    // Kept to rule out hash collisions.
+    input: Box<str>,
+    value: Arc<TypedValue>,
+}
+
+impl ParseCache {
+    /// Creates a cache holding at most `capacity` objects.
+    pub fn new(capacity: usize) -> Self {
+        ParseCache {
+            capacity,
+            state: Mutex::new(CacheState::default()),
+        }
+    }
+
+    /// Parses a YAML (or JSON) document as the given type, returning the
+    /// cached result if the same document was parsed before.
+    pub fn from_yaml(&self, pt: &ParseableType, yaml: &str) -> Result<Arc<TypedValue>, ParseError> {
+        let key = CacheKey {
+            schema: type_fingerprint(pt),
+            content: content_hash(yaml),
+        };
+
+        if let Some(hit) = self.lookup(key, yaml) {
+            return Ok(hit);
+        }
+
+        let value = Arc::new(pt.from_yaml(yaml)?);
+        self.store(key, yaml, value.clone());
+        Ok(value)
+    }
+
+    /// Returns the number of cached objects.
+    pub fn len(&self) -> usize {
+        self.lock().order.len()
+    }
+
+    /// Returns true if nothing is cached.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Removes all cached objects.
+    pub fn clear(&self) {
+        *self.lock() = CacheState::default();
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, CacheState> {
+        // The state is always consistent between statements, so a panic
+        // elsewhere doesn't invalidate it.
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn lookup(&self, key: CacheKey, input: &str) -> Option<Arc<TypedValue>> {
+        let state = self.lock();
+        state
+            .entries
+            .get(&key)?
+            .iter()
+            .find(|e| &*e.input == input)
+            .map(|e| e.value.clone())
+    }
+
+    fn store(&self, key: CacheKey, input: &str, value: Arc<TypedValue>) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut state = self.lock();
+        let bucket = state.entries.entry(key).or_default();
+        // Another thread may have parsed the same input meanwhile.
+        if bucket.iter().any(|e| &*e.input == input) {
+            return;
+        }
+        bucket.push(CacheEntry {
+            input: input.into(),
+            value: value.clone(),
+        });
+        state.order.push_back((key, value));
+
+        while state.order.len() > self.capacity {
+            let Some((old_key, old_value)) = state.order.pop_front() else {
+                break;
+            };
+            if let Some(bucket) = state.entries.get_mut(&old_key) {
+                bucket.retain(|e| !Arc::ptr_eq(&e.value, &old_value));
+                if bucket.is_empty() {
+                    state.entries.remove(&old_key);
+                }
+            }
+        }
+    }
+}
+
+fn type_fingerprint(pt: &ParseableType) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    pt.schema.fingerprint().hash(&mut hasher);
+    // TypeRef has no Hash impl; its JSON form identifies it.
+    serde_json::to_string(&pt.type_ref)
+        .unwrap_or_default()
+        .hash(&mut hasher);
+    hasher.finish()
+}
+
+fn content_hash(input: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    input.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::typed::Parser;
+
+    const SCHEMA: &str = r#"types:
+- name: root
+  map:
+    fields:
+    - name: a
+      type:
+        scalar: numeric
+- name: other
+  map:
+    fields:
+    - name: a
+      type:
+        scalar: string
+"#;
+
+    #[test]
+    fn test_parse_cache_hits() {
+        let parser = Parser::new(SCHEMA).unwrap();
+        let root = parser.type_by_name("root");
+        let cache = ParseCache::new(2);
+
+        let first = cache.from_yaml(&root, "{a: 1}").unwrap();
+        let second = cache.from_yaml(&root, "{a: 1}").unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(cache.len(), 1);
+
+        // The same schema loaded twice shares entries
+        let reloaded = Parser::new(SCHEMA).unwrap().type_by_name("root");
+        assert!(Arc::ptr_eq(&first, &cache.from_yaml(&reloaded, "{a: 1}").unwrap()));
+
+        // A different type is a different entry, and errors aren't cached
+        assert!(cache.from_yaml(&parser.type_by_name("other"), "{a: 1}").is_err());
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_cache_evicts_oldest() {
+        let root = Parser::new(SCHEMA).unwrap().type_by_name("root");
+        let cache = ParseCache::new(2);
+
+        let one = cache.from_yaml(&root, "{a: 1}").unwrap();
+        cache.from_yaml(&root, "{a: 2}").unwrap();
+        cache.from_yaml(&root, "{a: 3}").unwrap();
+        assert_eq!(cache.len(), 2);
+        assert!(!Arc::ptr_eq(&one, &cache.from_yaml(&root, "{a: 1}").unwrap()));
+
+        cache.clear();
+        assert!(cache.is_empty());
+    }
+}
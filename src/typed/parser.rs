@@ -1,9 +1,14 @@
 //! Parser for creating typed values from YAML schemas and objects.
 
-use crate::schema::{Schema, TypeRef};
+#![cfg_attr(not(test), deny(clippy::indexing_slicing, clippy::string_slice))]
+
+use crate::fieldpath::{Path, PathElement};
+use crate::schema::{CompiledSchema, OverrideScope, Schema, TypeRef, EMBEDDED_RESOURCE_TYPE};
 use crate::value::Value;
-use super::typed_value::{as_typed, TypedValue};
-use super::validation::{ValidationErrors, ValidationOption};
+use super::defaults::validate_defaults;
+use super::limits::LimitExceeded;
+use super::typed_value::{allows_unknown_fields, as_typed, as_typed_with_field_validation, TypedValue};
+use super::validation::{FieldValidation, ValidationError, ValidationErrors, ValidationOption};
 
 /// Parser implements YAML schema parsing and type creation.
 #[derive(Debug, Clone)]
@@ -12,16 +17,51 @@ pub struct Parser {
 }
 
 impl Parser {
-    /// Creates a new parser from a YAML schema string.
+    /// Creates a new parser from a YAML schema string, rejecting defaults
+    /// that don't fit their fields.
     pub fn new(schema_yaml: &str) -> Result<Parser, ParseError> {
         let schema: Schema = serde_yaml::from_str(schema_yaml)
             .map_err(|e| ParseError::new(format!("failed to parse schema: {}", e)))?;
+        validate_defaults(&schema).map_err(|errs| {
+            let messages: Vec<String> = errs.iter().map(|e| e.to_string()).collect();
+            ParseError::new(format!("invalid defaults: {}", messages.join("; ")))
+        })?;
         Ok(Parser { schema })
     }
 
+    /// Creates a parser from an already built schema, e.g. one produced by
+    /// the OpenAPI converter.
+    pub fn from_schema(schema: Schema) -> Parser {
+        Parser { schema }
+    }
+
+    /// Creates a parser from a compiled schema, e.g. one loaded with
+    /// CompiledSchema::from_bytes, resolving types from its table.
+    pub fn from_compiled(compiled: CompiledSchema) -> Parser {
+        Parser {
+            schema: compiled.into_schema(),
+        }
+    }
+
+    /// Returns the schema types are resolved against.
+    pub fn schema(&self) -> &Schema {
+        &self.schema
+    }
+
+    /// Sets how far `elementRelationship` overrides reach in this parser's
+    /// schema, rejecting overrides that contradict their referenced types.
+    pub fn with_override_scope(mut self, scope: OverrideScope) -> Result<Parser, ParseError> {
+        self.schema.set_override_scope(scope);
+        self.schema.validate_overrides().map_err(|errs| {
+            let messages: Vec<String> = errs.iter().map(|e| e.to_string()).collect();
+            ParseError::new(format!("invalid overrides: {}", messages.join("; ")))
+        })?;
+        Ok(self)
+    }
+
     /// Returns the list of type names in this schema.
     pub fn type_names(&self) -> Vec<&str> {
-        self.schema.types.iter().map(|t| t.name.as_str()).collect()
+        self.schema.types().iter().map(|t| t.name.as_str()).collect()
     }
 
     /// Returns a ParseableType helper for the given type name.
@@ -34,6 +74,56 @@ impl Parser {
             },
         }
     }
+
+    /// Returns the types `value` conforms to, with the share of its fields
+    /// and items each type declares, best first. A score of 1.0 means the
+    /// value is fully described by the type; types that don't accept the
+    /// value at all are left out.
+    pub fn infer_type(&self, value: &Value) -> Vec<(String, f64)> {
+        self.infer_type_among(value, &self.type_names())
+    }
+
+    /// Like infer_type, but only tries the named types.
+    pub fn infer_type_among(&self, value: &Value, names: &[&str]) -> Vec<(String, f64)> {
+        let total = count_nodes(value) as f64;
+        let mut scores: Vec<(String, f64)> = names
+            .iter()
+            .filter_map(|name| {
+                let pt = self.type_by_name(name);
+                if !pt.is_valid() {
+                    return None;
+                }
+                let (tv, _) = pt.from_value_with_validation(value.clone(), FieldValidation::Ignore).ok()?;
+                Some((name.to_string(), count_nodes(tv.value()) as f64 / total))
+            })
+            .collect();
+        scores.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        scores
+    }
+
+    /// Returns the type at a dotted path: the longest prefix naming a type,
+    /// such as `io.k8s.api.core.v1.PodSpec`, followed by field names, such
+    /// as `io.k8s.api.core.v1.Pod.spec.containers`. Returns None if no
+    /// prefix names a type or a field doesn't exist.
+    pub fn type_by_path(&self, path: &str) -> Option<ParseableType> {
+        let parts: Vec<&str> = path.split('.').collect();
+        let (name, fields) = (1..=parts.len())
+            .rev()
+            .map(|i| parts.split_at(i))
+            .map(|(name, fields)| (name.join("."), fields))
+            .find(|(name, _)| self.schema.find_named_type(name).is_some())?;
+        let fields = fields.iter().map(|f| PathElement::field_name(*f)).collect();
+        self.type_by_name(&name).sub_type(&Path::from_elements(fields))
+    }
+}
+
+/// Counts the value and every field and item nested in it.
+fn count_nodes(value: &Value) -> usize {
+    1 + match value {
+        Value::Map(map) => map.fields.values().map(count_nodes).sum(),
+        Value::List(items) => items.iter().map(count_nodes).sum(),
+        _ => 0,
+    }
 }
 
 /// ParseableType allows for easy production of typed objects.
@@ -49,6 +139,34 @@ impl ParseableType {
         self.schema.resolve(&self.type_ref).is_some()
     }
 
+    /// Returns the type of the values at `path` within this type, so
+    /// fragments such as a single container can be parsed and merged on
+    /// their own. Field names step into map fields, and keys, values and
+    /// indexes into list items. Returns None if the path leaves the schema.
+    pub fn sub_type(&self, path: &Path) -> Option<ParseableType> {
+        let mut type_ref = self.type_ref.clone();
+        for pe in path.iter() {
+            let atom = self.schema.resolve(&type_ref)?;
+            type_ref = match pe {
+                PathElement::FieldName(name) => {
+                    let map = atom.map?;
+                    match map.find_field(name) {
+                        Some(field) => field.field_type.clone(),
+                        None if allows_unknown_fields(&map) => map.element_type,
+                        None => return None,
+                    }
+                }
+                PathElement::Key(_) | PathElement::Value(_) | PathElement::Index(_) => atom.list?.element_type,
+                PathElement::Extension(..) => return None,
+            };
+        }
+        let sub = ParseableType {
+            schema: self.schema.clone(),
+            type_ref,
+        };
+        sub.is_valid().then_some(sub)
+    }
+
     /// Parses a YAML string into a TypedValue.
     pub fn from_yaml(&self, yaml: &str) -> Result<TypedValue, ParseError> {
         self.from_yaml_with_opts(yaml, &[])
@@ -67,6 +185,19 @@ impl ParseableType {
             .map_err(|e| ParseError::new(format!("validation failed: {}", e)))
     }
 
+    /// Parses a YAML string into a TypedValue, handling unknown fields and
+    /// duplicate keys as `validation` asks. Returns the warnings collected in
+    /// Warn mode.
+    pub fn from_yaml_with_validation(
+        &self,
+        yaml: &str,
+        validation: FieldValidation,
+    ) -> Result<(TypedValue, Vec<ValidationError>), ParseError> {
+        let value: Value = serde_yaml::from_str(yaml)
+            .map_err(|e| ParseError::new(format!("failed to parse YAML: {}", e)))?;
+        self.from_value_with_validation(value, validation)
+    }
+
     /// Creates a TypedValue from a Value.
     pub fn from_value(&self, value: Value) -> Result<TypedValue, ParseError> {
         self.from_value_with_opts(value, &[])
@@ -81,18 +212,39 @@ impl ParseableType {
         as_typed(value, &self.schema, self.type_ref.clone(), opts)
             .map_err(|e| ParseError::new(format!("validation failed: {}", e)))
     }
+
+    /// Creates a TypedValue from a Value with the given field validation.
+    pub fn from_value_with_validation(
+        &self,
+        value: Value,
+        validation: FieldValidation,
+    ) -> Result<(TypedValue, Vec<ValidationError>), ParseError> {
+        as_typed_with_field_validation(value, &self.schema, self.type_ref.clone(), validation)
+            .map_err(|e| ParseError::new(format!("validation failed: {}", e)))
+    }
 }
 
 /// Error type for parsing operations.
 #[derive(Debug, Clone)]
 pub struct ParseError {
     pub message: String,
+    /// The limit the input went over, if parsing stopped at one.
+    pub limit_exceeded: Option<LimitExceeded>,
 }
 
 impl ParseError {
     pub fn new(message: impl Into<String>) -> Self {
         ParseError {
             message: message.into(),
+            limit_exceeded: None,
+        }
+    }
+
+    /// Creates an error for input that went over a parse limit.
+    pub fn limit_exceeded(exceeded: LimitExceeded) -> Self {
+        ParseError {
+            message: exceeded.to_string(),
+            limit_exceeded: Some(exceeded),
         }
     }
 }
@@ -136,10 +288,24 @@ pub fn deduced_parseable_type() -> ParseableType {
     elementRelationship: separable
 "#;
 
-    let parser = Parser::new(schema_yaml).expect("deduced schema should parse");
+    // The schema is a constant that test_deduced_parseable_type parses, so
+    // parsing it can't fail at runtime.
+    #[allow(clippy::expect_used)]
+    let parser = Parser::new(schema_yaml).expect("deduced schema parses");
     parser.type_by_name("__untyped_deduced_")
 }
 
+/// Creates a parseable type for embedded Kubernetes objects.
+pub fn embedded_resource_parseable_type() -> ParseableType {
+    ParseableType {
+        schema: Schema::new().with_builtin_types(),
+        type_ref: TypeRef {
+            named_type: Some(EMBEDDED_RESOURCE_TYPE.to_string()),
+            ..Default::default()
+        },
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -162,6 +328,16 @@ mod tests {
         assert!(parser.type_names().contains(&"stringPair"));
     }
 
+    #[test]
+    fn test_parser_from_schema() {
+        let schema: Schema = serde_yaml::from_str(TEST_SCHEMA).unwrap();
+        let parser = Parser::from_schema(schema.clone());
+        assert_eq!(parser.schema(), &schema);
+
+        let tv = parser.type_by_name("stringPair").from_yaml(r#"{"key": "foo"}"#).unwrap();
+        assert!(tv.value().is_map());
+    }
+
     #[test]
     fn test_parseable_type_from_yaml() {
         let parser = Parser::new(TEST_SCHEMA).unwrap();
@@ -178,6 +354,222 @@ mod tests {
         assert!(!parser.type_by_name("nonexistent").is_valid());
     }
 
+    #[test]
+    fn test_field_validation() {
+        let schema = r#"types:
+- name: pairs
+  map:
+    fields:
+    - name: items
+      type:
+        list:
+          elementType:
+            namedType: stringPair
+          elementRelationship: associative
+          keys: [key]
+- name: stringPair
+  map:
+    fields:
+    - name: key
+      type:
+        scalar: string
+    - name: value
+      type:
+        scalar: string
+"#;
+        let pt = Parser::new(schema).unwrap().type_by_name("pairs");
+        let yaml = r#"{"items": [{"key": "a", "extra": 1}, {"key": "a"}]}"#;
+
+        assert!(pt.from_yaml_with_validation(yaml, FieldValidation::Strict).is_err());
+
+        let (tv, warnings) = pt.from_yaml_with_validation(yaml, FieldValidation::Warn).unwrap();
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings.iter().all(ValidationError::is_field_error));
+        assert!(!crate::value::to_json(tv.value()).unwrap().contains("extra"));
+
+        let (_, warnings) = pt.from_yaml_with_validation(yaml, FieldValidation::Ignore).unwrap();
+        assert!(warnings.is_empty());
+
+        // Other errors still fail
+        let bad = r#"{"items": [{"key": 1}]}"#;
+        assert!(pt.from_yaml_with_validation(bad, FieldValidation::Ignore).is_err());
+    }
+
+    const OVERRIDE_SCHEMA: &str = r#"types:
+- name: outer
+  map:
+    fields:
+    - name: inner
+      type:
+        namedType: inner
+        elementRelationship: separable
+- name: inner
+  map:
+    fields:
+    - name: deep
+      type:
+        namedType: deep
+    elementRelationship: atomic
+- name: deep
+  map:
+    elementType:
+      scalar: numeric
+    elementRelationship: atomic
+"#;
+
+    #[test]
+    fn test_override_scope() {
+        use crate::fieldpath::{Path, PathElement};
+
+        let deep = Path::from_elements(vec![
+            PathElement::field_name("inner"),
+            PathElement::field_name("deep"),
+        ]);
+        let leaf = deep.with(PathElement::field_name("c"));
+        let yaml = r#"{"inner": {"deep": {"c": 1}}}"#;
+
+        let parser = Parser::new(OVERRIDE_SCHEMA).unwrap();
+        let set = parser.type_by_name("outer").from_yaml(yaml).unwrap().to_field_set().unwrap();
+        assert!(set.has(&deep));
+        assert!(!set.has(&leaf));
+
+        let parser = Parser::new(OVERRIDE_SCHEMA)
+            .unwrap()
+            .with_override_scope(OverrideScope::Transitive)
+            .unwrap();
+        let set = parser.type_by_name("outer").from_yaml(yaml).unwrap().to_field_set().unwrap();
+        assert!(!set.has(&deep));
+        assert!(set.has(&leaf));
+    }
+
+    #[test]
+    fn test_override_scope_rejects_contradictions() {
+        let schema = r#"types:
+- name: outer
+  map:
+    fields:
+    - name: name
+      type:
+        namedType: str
+        elementRelationship: atomic
+- name: str
+  scalar: string
+"#;
+        let err = Parser::new(schema)
+            .unwrap()
+            .with_override_scope(OverrideScope::Referenced)
+            .unwrap_err();
+        assert!(err.message.contains("scalar type str"));
+    }
+
+    #[test]
+    fn test_sub_types() {
+        let schema = r#"types:
+- name: io.k8s.api.core.v1.Pod
+  map:
+    fields:
+    - name: spec
+      type:
+        namedType: io.k8s.api.core.v1.PodSpec
+- name: io.k8s.api.core.v1.PodSpec
+  map:
+    fields:
+    - name: containers
+      type:
+        list:
+          elementType:
+            namedType: io.k8s.api.core.v1.Container
+          elementRelationship: associative
+          keys: [name]
+- name: io.k8s.api.core.v1.Container
+  map:
+    fields:
+    - name: name
+      type:
+        scalar: string
+    - name: image
+      type:
+        scalar: string
+"#;
+        use crate::fieldpath::{Path, PathElement};
+
+        let parser = Parser::new(schema).unwrap();
+        let spec = parser.type_by_path("io.k8s.api.core.v1.PodSpec").unwrap();
+        assert_eq!(spec.type_ref.named_type.as_deref(), Some("io.k8s.api.core.v1.PodSpec"));
+        let containers = parser.type_by_path("io.k8s.api.core.v1.Pod.spec.containers").unwrap();
+        assert!(containers.from_yaml(r#"[{"name": "a"}]"#).is_ok());
+        assert!(parser.type_by_path("io.k8s.api.core.v1.Pod.status").is_none());
+        assert!(parser.type_by_path("io.k8s.api.core.v1").is_none());
+
+        // A lone container validates and merges against its own type
+        let path = Path::from_elements(vec![
+            PathElement::field_name("containers"),
+            PathElement::index(0),
+        ]);
+        let container = spec.sub_type(&path).unwrap();
+        assert_eq!(container.type_ref.named_type.as_deref(), Some("io.k8s.api.core.v1.Container"));
+        assert!(container.from_yaml(r#"{"name": "a", "ports": []}"#).is_err());
+        let lhs = container.from_yaml(r#"{"name": "a"}"#).unwrap();
+        let rhs = container.from_yaml(r#"{"image": "nginx"}"#).unwrap();
+        let merged = lhs.merge(&rhs).unwrap();
+        assert_eq!(crate::value::to_json(merged.value()).unwrap(), r#"{"image":"nginx","name":"a"}"#);
+        assert!(spec.sub_type(&path.with(PathElement::field_name("name"))).is_some());
+        assert!(spec.sub_type(&path.with(PathElement::index(0))).is_none());
+    }
+
+    #[test]
+    fn test_parser_from_compiled() {
+        let schema: Schema = serde_yaml::from_str(TEST_SCHEMA).unwrap();
+        let bytes = schema.compile().to_bytes().unwrap();
+        let parser = Parser::from_compiled(CompiledSchema::from_bytes(&bytes).unwrap());
+        assert!(parser.type_names().contains(&"stringPair"));
+
+        let pt = parser.type_by_name("stringPair");
+        let lhs = pt.from_yaml(r#"{"key": "foo"}"#).unwrap();
+        let rhs = pt.from_yaml(r#"{"value": "bar"}"#).unwrap();
+        let merged = lhs.merge(&rhs).unwrap();
+        assert_eq!(crate::value::to_json(merged.value()).unwrap(), r#"{"key":"foo","value":"bar"}"#);
+        assert!(pt.from_yaml(r#"{"other": 1}"#).is_err());
+    }
+
+    #[test]
+    fn test_infer_type() {
+        let parser = Parser::new(
+            r#"types:
+- name: stringPair
+  map:
+    fields:
+    - name: key
+      type:
+        scalar: string
+    - name: value
+      type:
+        scalar: string
+- name: keyOnly
+  map:
+    fields:
+    - name: key
+      type:
+        scalar: string
+- name: strings
+  list:
+    elementType:
+      scalar: string
+    elementRelationship: atomic
+"#,
+        )
+        .unwrap();
+        let value = crate::value::from_json(r#"{"key": "a", "value": "b"}"#).unwrap();
+        assert_eq!(
+            parser.infer_type(&value),
+            vec![("stringPair".to_string(), 1.0), ("keyOnly".to_string(), 2.0 / 3.0)]
+        );
+        assert_eq!(parser.infer_type_among(&value, &["keyOnly", "missing"]), vec![("keyOnly".to_string(), 2.0 / 3.0)]);
+
+        let value = crate::value::from_json(r#"["a", "b"]"#).unwrap();
+        assert_eq!(parser.infer_type(&value), vec![("strings".to_string(), 1.0)]);
+    }
+
     #[test]
     fn test_deduced_parseable_type() {
         let pt = deduced_parseable_type();
@@ -186,4 +578,23 @@ mod tests {
         let tv = pt.from_yaml(r#"{"a": 1, "b": "hello"}"#).unwrap();
         assert!(tv.value().is_map());
     }
+
+    #[test]
+    fn test_embedded_resource_parseable_type() {
+        let pt = embedded_resource_parseable_type();
+        assert!(pt.is_valid());
+        assert!(pt.from_yaml("{}").is_ok());
+        assert!(pt.from_yaml(r#"{"apiVersion": "v1", "spec": {}}"#).is_err());
+
+        let lhs = pt
+            .from_yaml(r#"{"apiVersion": "v1", "kind": "Pod", "metadata": {"labels": {"a": "1"}}, "spec": {"x": 1}}"#)
+            .unwrap();
+        let rhs = pt
+            .from_yaml(r#"{"apiVersion": "v1", "kind": "Pod", "metadata": {"labels": {"b": "2"}}, "spec": {"y": 2}}"#)
+            .unwrap();
+        let expected = pt
+            .from_yaml(r#"{"apiVersion": "v1", "kind": "Pod", "metadata": {"labels": {"a": "1", "b": "2"}}, "spec": {"x": 1, "y": 2}}"#)
+            .unwrap();
+        assert_eq!(lhs.merge(&rhs).unwrap().value(), expected.value());
+    }
 }
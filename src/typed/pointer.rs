@@ -0,0 +1,150 @@
+//! Conversion between field set paths and positional paths, such as those
+//! of JSON Pointers and CEL field paths.
+
+#![cfg_attr(not(test), deny(clippy::indexing_slicing, clippy::string_slice))]
+
+use super::typed_value::TypedValue;
+use crate::fieldpath::{Path, PathElement, SerializeError};
+use crate::value::Value;
+
+impl TypedValue {
+    /// Returns the path with list keys and set values replaced by the
+    /// indexes of the items they address, so that it can be rendered with
+    /// Path::to_json_pointer or Path::to_cel_field_path. Returns None if
+    /// nothing exists at the path.
+    pub fn index_path(&self, path: &Path) -> Option<Path> {
+        self.convert_path(path, false)
+    }
+
+    /// Returns the path with indexes into associative lists replaced by the
+    /// keys or set values of the items, the form field sets use. Returns
+    /// None if nothing exists at the path.
+    pub fn keyed_path(&self, path: &Path) -> Option<Path> {
+        self.convert_path(path, true)
+    }
+
+    /// Parses an RFC 6901 JSON Pointer into the path field sets use,
+    /// telling map keys from list indexes by the object.
+    pub fn path_from_json_pointer(&self, pointer: &str) -> Result<Path, SerializeError> {
+        let error = |message: &str| SerializeError::new(format!("{} in JSON pointer {:?}", message, pointer));
+        if pointer.is_empty() {
+            return Ok(Path::new());
+        }
+        let segments = pointer.strip_prefix('/').ok_or_else(|| error("missing leading '/'"))?;
+
+        let mut path = Path::new();
+        let mut value = self.value();
+        for segment in segments.split('/') {
+            let segment = segment.replace("~1", "/").replace("~0", "~");
+            match value {
+                Value::Map(fields) => {
+                    value = fields.get(&segment).ok_or_else(|| error(&format!("no field {:?}", segment)))?;
+                    path.push(PathElement::FieldName(segment));
+                }
+                Value::List(items) => {
+                    let index: usize = segment.parse().map_err(|_| error(&format!("invalid index {:?}", segment)))?;
+                    value = items.get(index).ok_or_else(|| error(&format!("no item {}", index)))?;
+                    path.push(PathElement::Index(index as i32));
+                }
+                _ => return Err(error(&format!("{:?} is beneath a scalar", segment))),
+            }
+        }
+        self.keyed_path(&path).ok_or_else(|| error("no item matches"))
+    }
+
+    fn convert_path(&self, path: &Path, keyed: bool) -> Option<Path> {
+        let mut out = Path::new();
+        let mut parent = Path::new();
+        for pe in path.iter() {
+            let element = self.element_at(&parent)?;
+            let Value::List(items) = element.value() else {
+                self.element_at(&parent.with(pe.clone()))?;
+                out.push(pe.clone());
+                parent.push(pe.clone());
+                continue;
+            };
+            let index = match pe {
+                PathElement::Index(index) => usize::try_from(*index).ok().filter(|i| *i < items.len())?,
+                _ => {
+                    let item = self.element_at(&parent.with(pe.clone()))?;
+                    items.iter().position(|v| v == item.value())?
+                }
+            };
+            let converted = if keyed {
+                self.children(element.value(), element.type_ref()).into_iter().nth(index)?.0
+            } else {
+                PathElement::Index(index as i32)
+            };
+            out.push(converted);
+            parent.push(pe.clone());
+        }
+        Some(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::typed::Parser;
+
+    #[test]
+    fn test_positional_paths() {
+        let pt = Parser::new(
+            r#"types:
+- name: root
+  map:
+    fields:
+    - name: containers
+      type:
+        list:
+          elementType:
+            map:
+              fields:
+              - name: name
+                type:
+                  scalar: string
+              - name: args
+                type:
+                  list:
+                    elementType:
+                      scalar: string
+                    elementRelationship: atomic
+          elementRelationship: associative
+          keys: [name]
+    - name: annotations
+      type:
+        map:
+          elementType:
+            scalar: string
+"#,
+        )
+        .unwrap()
+        .type_by_name("root");
+        let obj = pt
+            .from_yaml(r#"{"containers": [{"name": "a"}, {"name": "b", "args": ["x", "y"]}], "annotations": {"example.com/role": "web"}}"#)
+            .unwrap();
+
+        let keyed = obj.path_from_json_pointer("/containers/1/args/0").unwrap();
+        assert_eq!(keyed.to_string(), r#".containers[name="b"].args[0]"#);
+        assert!(keyed.to_json_pointer().is_err());
+        let indexed = obj.index_path(&keyed).unwrap();
+        assert_eq!(indexed.to_json_pointer().unwrap(), "/containers/1/args/0");
+        assert_eq!(indexed.to_cel_field_path().unwrap(), ".containers[1].args[0]");
+        assert_eq!(obj.keyed_path(&indexed).unwrap(), keyed);
+
+        let annotation = obj.path_from_json_pointer("/annotations/example.com~1role").unwrap();
+        assert_eq!(annotation.to_json_pointer().unwrap(), "/annotations/example.com~1role");
+        let cel = annotation.to_cel_field_path().unwrap();
+        assert_eq!(cel, ".annotations['example.com/role']");
+        assert_eq!(Path::from_cel_field_path(&cel).unwrap(), annotation);
+        let quoted = Path::from_elements(vec![PathElement::field_name(r"it's\")]);
+        assert_eq!(quoted.to_cel_field_path().unwrap(), r"['it\'s\\']");
+        assert_eq!(Path::from_cel_field_path(r"['it\'s\\']").unwrap(), quoted);
+
+        assert!(obj.path_from_json_pointer("/containers/2").is_err());
+        assert!(obj.path_from_json_pointer("containers").is_err());
+        assert!(obj.index_path(&Path::parse(r#".containers[name="c"]"#).unwrap()).is_none());
+        assert!(Path::from_cel_field_path(".spec[x]").is_err());
+        assert!(Path::from_cel_field_path(".1abc").is_err());
+    }
+}
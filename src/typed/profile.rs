@@ -0,0 +1,140 @@
+//! Parse profiles, bundling the guardrails parsing can apply.
+//!
+//! Services parsing objects from many tenants want every guardrail at once:
+//! limits on the size and nesting of the input, no aliases or merge keys, no
+//! duplicate keys, no unknown fields and no numbers silently losing
+//! precision. ParseProfile::untrusted turns them all on, and applies the
+//! same way to YAML and JSON.
+
+use super::limits::{read_json, InputChecks, ParseLimits};
+use super::parser::{ParseError, ParseableType};
+use super::typed_value::{as_typed_with_field_validation, TypedValue};
+use super::validation::{FieldValidation, ValidationError};
+use super::yaml_policy::{read_yaml_with_policy, YamlPolicy};
+use crate::value::Value;
+
+/// ParseProfile is how strictly input is parsed. The default profile
+/// parses as from_yaml and from_json do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ParseProfile {
+    pub limits: ParseLimits,
+    /// The handling of aliases and merge keys in YAML input.
+    pub yaml: YamlPolicy,
+    /// The handling of unknown fields and duplicate keys of lists.
+    pub field_validation: FieldValidation,
+    /// Reject maps in the input with a key more than once, instead of
+    /// keeping the last value.
+    pub reject_duplicate_map_keys: bool,
+    /// Reject integers that don't fit in 64 bits and infinite or NaN
+    /// floats, instead of reading them as floats.
+    pub reject_numeric_overflow: bool,
+}
+
+impl ParseProfile {
+    /// The profile for untrusted input: untrusted limits, strict YAML and
+    /// field validation, and every input check.
+    pub fn untrusted() -> Self {
+        ParseProfile {
+            limits: ParseLimits::untrusted(),
+            yaml: YamlPolicy::strict(),
+            field_validation: FieldValidation::Strict,
+            reject_duplicate_map_keys: true,
+            reject_numeric_overflow: true,
+        }
+    }
+
+    fn checks(&self) -> InputChecks {
+        InputChecks {
+            duplicate_keys: self.reject_duplicate_map_keys,
+            numeric_overflow: self.reject_numeric_overflow,
+        }
+    }
+}
+
+impl ParseableType {
+    /// Parses a YAML string into a TypedValue as `profile` says. Returns
+    /// the warnings collected if its field validation is Warn.
+    pub fn from_yaml_with_profile(
+        &self,
+        yaml: &str,
+        profile: &ParseProfile,
+    ) -> Result<(TypedValue, Vec<ValidationError>), ParseError> {
+        let value = read_yaml_with_policy(yaml, &profile.yaml, &profile.limits, profile.checks())?;
+        self.typed_with_profile(value, profile)
+    }
+
+    /// Parses a JSON string into a TypedValue as `profile` says. Its YAML
+    /// policy doesn't apply. Returns the warnings collected if its field
+    /// validation is Warn.
+    pub fn from_json_with_profile(
+        &self,
+        json: &str,
+        profile: &ParseProfile,
+    ) -> Result<(TypedValue, Vec<ValidationError>), ParseError> {
+        let value = read_json(json, &profile.limits, profile.checks())?;
+        self.typed_with_profile(value, profile)
+    }
+
+    fn typed_with_profile(
+        &self,
+        value: Value,
+        profile: &ParseProfile,
+    ) -> Result<(TypedValue, Vec<ValidationError>), ParseError> {
+        as_typed_with_field_validation(value, &self.schema, self.type_ref.clone(), profile.field_validation)
+            .map_err(|e| ParseError::new(format!("validation failed: {}", e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::typed::{LimitExceeded, Parser};
+
+    #[test]
+    fn test_parse_profile() {
+        let pt = Parser::new(
+            "types:\n- name: root\n  map:\n    fields:\n    - name: a\n      type:\n        scalar: numeric\n",
+        )
+        .unwrap()
+        .type_by_name("root");
+        let untrusted = ParseProfile::untrusted();
+
+        let (tv, warnings) = pt.from_yaml_with_profile("a: 1", &untrusted).unwrap();
+        assert_eq!(tv.value(), pt.from_yaml("a: 1").unwrap().value());
+        assert!(warnings.is_empty());
+
+        // The default profile lets through what the untrusted one rejects
+        for (yaml, json) in [
+            ("a: 1\na: 2\n", r#"{"a": 1, "a": 2}"#),
+            ("a: 18446744073709551615", r#"{"a": 18446744073709551615}"#),
+        ] {
+            assert!(
+                pt.from_yaml_with_profile(yaml, &ParseProfile::default()).is_ok(),
+                "{}",
+                yaml
+            );
+            assert!(
+                pt.from_json_with_profile(json, &ParseProfile::default()).is_ok(),
+                "{}",
+                json
+            );
+            assert!(pt.from_yaml_with_profile(yaml, &untrusted).is_err(), "{}", yaml);
+            assert!(pt.from_json_with_profile(json, &untrusted).is_err(), "{}", json);
+        }
+        assert!(pt.from_yaml_with_profile("a: .inf", &untrusted).is_err());
+        let err = pt.from_yaml_with_profile("x: &x 1\ny: *x\n", &untrusted).unwrap_err();
+        assert!(err.message.contains("aliases"), "{}", err);
+        assert!(pt.from_json_with_profile(r#"{"b": 1}"#, &untrusted).is_err());
+        let deep = format!("{}1{}", "[".repeat(100), "]".repeat(100));
+        let err = pt.from_json_with_profile(&deep, &untrusted).unwrap_err();
+        assert_eq!(err.limit_exceeded, Some(LimitExceeded::Depth { limit: 64 }));
+
+        let warn = ParseProfile {
+            field_validation: FieldValidation::Warn,
+            ..untrusted
+        };
+        let (tv, warnings) = pt.from_json_with_profile(r#"{"a": 1, "b": 2}"#, &warn).unwrap();
+        assert_eq!(tv.value(), pt.from_yaml("a: 1").unwrap().value());
+        assert_eq!(warnings.len(), 1);
+    }
+}
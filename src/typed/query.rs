@@ -0,0 +1,285 @@
+//! JSONPath-like queries over typed values.
+//!
+//! Queries support a safe subset of JSONPath: an optional leading `$`, field
+//! access (`.name` or `['name']`), list indexes (`[0]`, `[-1]`), wildcards
+//! (`.*` or `[*]`) and equality filters on a field of list items
+//! (`[?(@.name=='nginx')]`). Matches carry their fieldpath::Path, so queries
+//! can select fields for ignore lists and policy rules.
+
+#![cfg_attr(not(test), deny(clippy::indexing_slicing, clippy::string_slice))]
+
+use super::typed_value::TypedValue;
+use crate::fieldpath::Path;
+use crate::schema::TypeRef;
+use crate::value::Value;
+use std::fmt;
+use std::str::FromStr;
+
+/// Query is a parsed JSONPath-like expression.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Query {
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+    Field(String),
+    Index(i64),
+    Wildcard,
+    /// Items whose field at the given sub-path equals the value.
+    Filter(Vec<String>, Value),
+}
+
+/// QueryMatch is a value selected by a query.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryMatch {
+    /// The path of the value.
+    pub path: Path,
+    /// The selected value.
+    pub value: Value,
+}
+
+/// QueryError is returned for malformed query expressions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryError {
+    pub message: String,
+}
+
+impl QueryError {
+    fn new(message: impl Into<String>) -> Self {
+        QueryError {
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid query: {}", self.message)
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+impl Query {
+    /// Parses a query expression.
+    pub fn parse(expr: &str) -> Result<Query, QueryError> {
+        let mut rest = expr.trim();
+        rest = rest.strip_prefix('$').unwrap_or(rest);
+        let mut segments = Vec::new();
+        let mut first = true;
+
+        while !rest.is_empty() {
+            if let Some(r) = rest.strip_prefix('[') {
+                let (selector, after) = split_bracket(r)?;
+                segments.push(parse_bracket(selector.trim())?);
+                rest = after;
+            } else {
+                let r = match rest.strip_prefix('.') {
+                    Some(r) => r,
+                    None if first => rest,
+                    None => return Err(QueryError::new(format!("unexpected {:?}", rest))),
+                };
+                let end = r.find(['.', '[']).unwrap_or(r.len());
+                let (name, after) = r.split_at(end);
+                match name {
+                    "" => return Err(QueryError::new("empty field name")),
+                    "*" => segments.push(Segment::Wildcard),
+                    _ => segments.push(Segment::Field(name.to_string())),
+                }
+                rest = after;
+            }
+            first = false;
+        }
+        Ok(Query { segments })
+    }
+}
+
+impl FromStr for Query {
+    type Err = QueryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Query::parse(s)
+    }
+}
+
+/// Splits at the `]` closing a bracket, skipping quoted text, into the
+/// selector and what follows the bracket.
+fn split_bracket(s: &str) -> Result<(&str, &str), QueryError> {
+    let mut quote = None;
+    for (i, c) in s.char_indices() {
+        match (quote, c) {
+            (None, '\'' | '"') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, ']') => {
+                let (selector, after) = s.split_at(i);
+                return Ok((selector, after.strip_prefix(']').unwrap_or(after)));
+            }
+            _ => {}
+        }
+    }
+    Err(QueryError::new("unterminated bracket"))
+}
+
+fn parse_bracket(s: &str) -> Result<Segment, QueryError> {
+    if s == "*" {
+        return Ok(Segment::Wildcard);
+    }
+    if let Some(name) = unquote(s) {
+        return Ok(Segment::Field(name));
+    }
+    if let Some(filter) = s.strip_prefix("?(").and_then(|f| f.strip_suffix(')')) {
+        return parse_filter(filter.trim());
+    }
+    s.parse()
+        .map(Segment::Index)
+        .map_err(|_| QueryError::new(format!("unsupported selector [{}]", s)))
+}
+
+fn parse_filter(s: &str) -> Result<Segment, QueryError> {
+    let (lhs, rhs) = s
+        .split_once("==")
+        .ok_or_else(|| QueryError::new(format!("unsupported filter {:?}, expected @.field==value", s)))?;
+    let fields: Vec<String> = lhs
+        .trim()
+        .strip_prefix("@.")
+        .ok_or_else(|| QueryError::new(format!("filter must start with @.: {:?}", s)))?
+        .split('.')
+        .map(str::to_string)
+        .collect();
+    if fields.iter().any(String::is_empty) {
+        return Err(QueryError::new(format!("empty field name in filter {:?}", s)));
+    }
+    let rhs = rhs.trim();
+    let value = match unquote(rhs) {
+        Some(text) => Value::String(text),
+        None => crate::value::from_json(rhs).map_err(|_| QueryError::new(format!("invalid literal {}", rhs)))?,
+    };
+    Ok(Segment::Filter(fields, value))
+}
+
+/// Returns the contents of a single or double quoted string.
+fn unquote(s: &str) -> Option<String> {
+    ['\'', '"'].iter().find_map(|&q| {
+        let inner = s.strip_prefix(q)?.strip_suffix(q)?;
+        (!inner.contains(q)).then(|| inner.to_string())
+    })
+}
+
+impl TypedValue {
+    /// Returns the values selected by the query, in document order, along
+    /// with their paths. Items of associative lists are addressed by key.
+    pub fn query(&self, query: &Query) -> Vec<QueryMatch> {
+        let mut current: Vec<(Path, &Value, TypeRef)> = vec![(Path::new(), self.value(), self.type_ref().clone())];
+        for segment in &query.segments {
+            let mut next = Vec::new();
+            for (path, value, type_ref) in current {
+                let children = self.children(value, &type_ref);
+                let len = children.len() as i64;
+                for (i, (pe, child, child_type)) in children.into_iter().enumerate() {
+                    let selected = match segment {
+                        Segment::Field(name) => pe.as_field_name() == Some(name.as_str()),
+                        Segment::Index(n) => {
+                            value.is_list() && (if *n < 0 { len + n } else { *n }) == i as i64
+                        }
+                        Segment::Wildcard => true,
+                        Segment::Filter(fields, expected) => {
+                            value.is_list() && field_at(child, fields) == Some(expected)
+                        }
+                    };
+                    if selected {
+                        next.push((path.with(pe), child, child_type));
+                    }
+                }
+            }
+            current = next;
+        }
+        current
+            .into_iter()
+            .map(|(path, value, _)| QueryMatch {
+                path,
+                value: value.clone(),
+            })
+            .collect()
+    }
+}
+
+fn field_at<'a>(value: &'a Value, fields: &[String]) -> Option<&'a Value> {
+    fields.iter().try_fold(value, |v, name| v.as_map()?.get(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fieldpath::PathElement;
+    use crate::typed::Parser;
+    use crate::value::{Field, FieldList};
+
+    const POD_SCHEMA: &str = r#"types:
+- name: pod
+  map:
+    fields:
+    - name: containers
+      type:
+        list:
+          elementType:
+            namedType: container
+          elementRelationship: associative
+          keys: [name]
+    - name: args
+      type:
+        list:
+          elementType:
+            scalar: string
+          elementRelationship: atomic
+- name: container
+  map:
+    fields:
+    - name: name
+      type:
+        scalar: string
+    - name: image
+      type:
+        scalar: string
+"#;
+
+    #[test]
+    fn test_query() {
+        let tv = Parser::new(POD_SCHEMA)
+            .unwrap()
+            .type_by_name("pod")
+            .from_yaml(
+                r#"{"containers": [{"name": "a", "image": "nginx"}, {"name": "b", "image": "redis"}], "args": ["x", "y"]}"#,
+            )
+            .unwrap();
+        let key = |name: &str| {
+            PathElement::Key(FieldList::with_fields(vec![Field {
+                name: "name".to_string(),
+                value: Value::String(name.into()),
+            }]))
+        };
+        let run = |q: &str| tv.query(&Query::parse(q).unwrap());
+
+        let matches = run("$.containers[?(@.name=='b')].image");
+        assert_eq!(matches.len(), 1);
+        assert_eq!(
+            matches[0].path,
+            Path::from_elements(vec![PathElement::field_name("containers"), key("b"), PathElement::field_name("image")])
+        );
+        assert_eq!(matches[0].value, Value::String("redis".into()));
+
+        let images: Vec<Value> = run("containers[*].image").into_iter().map(|m| m.value).collect();
+        assert_eq!(images, vec![Value::String("nginx".into()), Value::String("redis".into())]);
+
+        let last = run("$['args'][-1]");
+        assert_eq!(last[0].path, Path::from_elements(vec![PathElement::field_name("args"), PathElement::index(1)]));
+        assert!(run("$.missing.*").is_empty());
+    }
+
+    #[test]
+    fn test_query_parse_errors() {
+        for q in ["$.a[", "$..a", "$.a[?(@.b>1)]", "$.a[x]", "$.a[?(@.b==nope)]"] {
+            assert!(Query::parse(q).is_err(), "{} should be rejected", q);
+        }
+    }
+}
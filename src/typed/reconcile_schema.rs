@@ -3,13 +3,14 @@
 //! When schemas change (e.g., from granular to atomic), field sets need to be
 //! reconciled to match the new schema structure.
 
-use crate::fieldpath::{Path, PathElement, Set};
-use crate::schema::{ElementRelationship, Map, TypeRef};
+use crate::fieldpath::{reconcile_set_with_schema, ReconcileOptions, Set};
 use crate::typed::TypedValue;
 
 /// Reconciles a field set with changes to the object's schema.
 ///
-/// Returns the reconciled field set, or None if no changes were made.
+/// Returns the reconciled field set, or None if no changes were made. This
+/// is fieldpath::reconcile_set_with_schema with the schema and type of `tv`
+/// and default options.
 ///
 /// Supports:
 /// - Changing types from atomic to granular
@@ -18,270 +19,14 @@ pub fn reconcile_field_set_with_schema(
     fieldset: &Set,
     tv: &TypedValue,
 ) -> Result<Option<Set>, String> {
-    let mut walker = ReconcileWalker {
-        value: tv,
-        fieldset: fieldset.clone(),
-        type_ref: tv.type_ref().clone(),
-        path: Path::new(),
-        is_atomic: false,
-        to_remove: None,
-        to_add: None,
-    };
-
-    walker.reconcile()?;
-
-    // If there are accumulated changes, apply them
-    if walker.to_add.is_some() || walker.to_remove.is_some() {
-        let mut out = fieldset.clone();
-        if let Some(ref to_remove) = walker.to_remove {
-            out = out.recursive_difference(to_remove);
-        }
-        if let Some(ref to_add) = walker.to_add {
-            out = out.union(to_add);
-        }
-        Ok(Some(out))
-    } else {
-        Ok(None)
-    }
-}
-
-struct ReconcileWalker<'a> {
-    value: &'a TypedValue,
-    fieldset: Set,
-    type_ref: TypeRef,
-    path: Path,
-    is_atomic: bool,
-    to_remove: Option<Set>,
-    to_add: Option<Set>,
-}
-
-impl<'a> ReconcileWalker<'a> {
-    fn reconcile(&mut self) -> Result<(), String> {
-        let atom = match self.value.schema().resolve(&self.type_ref) {
-            Some(a) => a,
-            None => {
-                return Err(format!("could not resolve {:?}", self.type_ref));
-            }
-        };
-
-        // Handle based on atom type
-        if atom.map.is_some() {
-            self.do_map(atom.map.as_ref().unwrap())?;
-        } else if atom.list.is_some() {
-            self.do_list(atom.list.as_ref().unwrap())?;
-        }
-        // Scalars don't need reconciliation
-
-        Ok(())
-    }
-
-    fn do_map(&mut self, map: &Map) -> Result<(), String> {
-        // We don't reconcile deduced types (unstructured CRDs) or maps that contain
-        // only unknown fields since deduced types do not yet support atomic/granular tags.
-        if is_untyped_deduced_map(map) {
-            return Ok(());
-        }
-
-        // Reconcile maps and structs changed from granular to atomic.
-        // Note that migrations from atomic to granular are not recommended and will
-        // be treated as if they were always granular.
-        //
-        // In this case, the manager that owned the previously atomic field (and all subfields),
-        // will now own just the top-level field and none of the subfields.
-        if !self.is_atomic && map.element_relationship == ElementRelationship::Atomic {
-            if self.fieldset.size() > 0 {
-                // Remove all root and children fields
-                let mut to_remove = Set::new();
-                to_remove.insert(&self.path);
-                self.to_remove = Some(to_remove);
-
-                // Add the root of the atomic
-                let mut to_add = Set::new();
-                to_add.insert(&self.path);
-                self.to_add = Some(to_add);
-            }
-            return Ok(());
-        }
-
-        // Visit map items
-        self.visit_map_items(map)?;
-        Ok(())
-    }
-
-    fn do_list(&mut self, list: &crate::schema::List) -> Result<(), String> {
-        // Reconcile lists changed from granular to atomic.
-        if !self.is_atomic && list.element_relationship == ElementRelationship::Atomic {
-            // Remove all root and children fields
-            let mut to_remove = Set::new();
-            to_remove.insert(&self.path);
-            self.to_remove = Some(to_remove);
-
-            // Add the root of the atomic
-            let mut to_add = Set::new();
-            to_add.insert(&self.path);
-            self.to_add = Some(to_add);
-            return Ok(());
-        }
-
-        // Visit list items
-        self.visit_list_items(list)?;
-        Ok(())
-    }
-
-    fn visit_map_items(&mut self, map: &Map) -> Result<(), String> {
-        // Get the fieldset at the current path
-        let current_set = self.get_fieldset_at_path();
-
-        // Iterate through members and children
-        let mut elements_to_visit: Vec<(PathElement, bool)> = Vec::new();
-
-        // Collect children that are not members (intermediate paths)
-        current_set.children_iterate(|pe| {
-            if !current_set.members_has(pe) {
-                elements_to_visit.push((pe.clone(), false));
-            }
-        });
-
-        // Collect members
-        current_set.members_iterate(|pe| {
-            elements_to_visit.push((pe.clone(), true));
-        });
-
-        // Process each element
-        for (pe, is_member) in elements_to_visit {
-            // Get the type ref for this path element
-            if let Some(tr) = type_ref_at_path(map, &pe) {
-                let child_set = current_set.children_get(&pe).cloned().unwrap_or_else(Set::new);
-                let has_children = !child_set.is_empty();
-
-                let mut child_walker = ReconcileWalker {
-                    value: self.value,
-                    fieldset: self.fieldset.clone(), // Pass root fieldset, not child_set
-                    type_ref: tr,
-                    path: self.path.with(pe),
-                    is_atomic: is_member && !has_children,
-                    to_remove: None,
-                    to_add: None,
-                };
-
-                child_walker.reconcile()?;
-
-                // Merge accumulated changes
-                self.merge_changes(&child_walker);
-            }
-        }
-
-        Ok(())
-    }
-
-    fn visit_list_items(&mut self, list: &crate::schema::List) -> Result<(), String> {
-        let current_set = self.get_fieldset_at_path();
-
-        let mut elements_to_visit: Vec<(PathElement, bool)> = Vec::new();
-
-        // Collect children that are not members
-        current_set.children_iterate(|pe| {
-            if !current_set.members_has(pe) {
-                elements_to_visit.push((pe.clone(), false));
-            }
-        });
-
-        // Collect members
-        current_set.members_iterate(|pe| {
-            elements_to_visit.push((pe.clone(), true));
-        });
-
-        // Process each element
-        for (pe, is_member) in elements_to_visit {
-            let child_set = current_set.children_get(&pe).cloned().unwrap_or_else(Set::new);
-            let has_children = !child_set.is_empty();
-
-            let mut child_walker = ReconcileWalker {
-                value: self.value,
-                fieldset: self.fieldset.clone(), // Pass root fieldset, not child_set
-                type_ref: list.element_type.clone(),
-                path: self.path.with(pe),
-                is_atomic: is_member && !has_children,
-                to_remove: None,
-                to_add: None,
-            };
-
-            child_walker.reconcile()?;
-
-            // Merge accumulated changes
-            self.merge_changes(&child_walker);
-        }
-
-        Ok(())
-    }
-
-    fn get_fieldset_at_path(&self) -> Set {
-        let mut current = self.fieldset.clone();
-        for pe in self.path.as_slice() {
-            current = current.children_get(pe).cloned().unwrap_or_else(Set::new);
-        }
-        current
-    }
-
-    fn merge_changes(&mut self, child: &ReconcileWalker) {
-        if let Some(ref child_remove) = child.to_remove {
-            if let Some(ref mut to_remove) = self.to_remove {
-                *to_remove = to_remove.union(child_remove);
-            } else {
-                self.to_remove = Some(child_remove.clone());
-            }
-        }
-        if let Some(ref child_add) = child.to_add {
-            if let Some(ref mut to_add) = self.to_add {
-                *to_add = to_add.union(child_add);
-            } else {
-                self.to_add = Some(child_add.clone());
-            }
-        }
-    }
-}
-
-fn type_ref_at_path(map: &Map, pe: &PathElement) -> Option<TypeRef> {
-    let tr = if let Some(name) = pe.as_field_name() {
-        if let Some(field) = map.find_field(name) {
-            field.field_type.clone()
-        } else {
-            map.element_type.clone()
-        }
-    } else {
-        map.element_type.clone()
-    };
-
-    // Return None if the TypeRef is empty
-    if tr.named_type.is_none()
-        && tr.inlined.scalar.is_none()
-        && tr.inlined.list.is_none()
-        && tr.inlined.map.is_none()
-    {
-        None
-    } else {
-        Some(tr)
-    }
-}
-
-/// Returns true if m has no fields defined, but allows untyped elements.
-fn is_untyped_deduced_map(m: &Map) -> bool {
-    is_untyped_deduced_ref(&m.element_type) && m.fields.is_empty()
-}
-
-fn is_untyped_deduced_ref(t: &TypeRef) -> bool {
-    if let Some(ref name) = t.named_type {
-        return name == "__untyped_deduced_";
-    }
-    if let Some(ref scalar) = t.inlined.scalar {
-        return *scalar == crate::schema::Scalar::Untyped;
-    }
-    false
+    reconcile_set_with_schema(fieldset, None, tv.schema(), tv.type_ref(), &ReconcileOptions::default())
+        .map_err(|e| e.to_string())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::fieldpath::{Path, PathElement};
     use crate::typed::Parser;
 
     fn granular_schema(version: &str) -> String {
@@ -0,0 +1,128 @@
+//! Detection of associative list items whose key changed.
+
+use super::comparison::{Comparison, ItemRename};
+use super::typed_value::TypedValue;
+use super::validation::ValidationErrors;
+use crate::fieldpath::{Path, PathElement, Set};
+use crate::value::Value;
+
+impl TypedValue {
+    /// Compares like compare, and also pairs removed and added items of the
+    /// same keyed associative list into `renamed` when they have an equal,
+    /// non-null value for one of the `hints` fields, such as an image or a
+    /// uid. Only pairs that match no other removed or added item are
+    /// reported.
+    pub fn compare_with_rename_hints(&self, rhs: &TypedValue, hints: &[&str]) -> Result<Comparison, ValidationErrors> {
+        let mut comparison = self.compare(rhs)?;
+        let added = keyed_items(&comparison.added);
+        let mut pairs = Vec::new();
+        for from in keyed_items(&comparison.removed) {
+            let Some(lhs_item) = self.element_at(&from) else {
+                continue;
+            };
+            for to in added.iter().filter(|to| siblings(&from, to)) {
+                if let Some(rhs_item) = rhs.element_at(to) {
+                    if share_hint(lhs_item.value(), rhs_item.value(), hints) {
+                        pairs.push((from.clone(), to.clone()));
+                    }
+                }
+            }
+        }
+        for (from, to) in &pairs {
+            let from_count = pairs.iter().filter(|(f, _)| f == from).count();
+            let to_count = pairs.iter().filter(|(_, t)| t == to).count();
+            if from_count == 1 && to_count == 1 {
+                comparison.renamed.push(ItemRename {
+                    from: from.clone(),
+                    to: to.clone(),
+                });
+            }
+        }
+        Ok(comparison)
+    }
+}
+
+/// Returns the paths of the set that end in an associative list key.
+fn keyed_items(set: &Set) -> Vec<Path> {
+    let mut paths = Vec::new();
+    set.iterate(|path| {
+        if matches!(path.last(), Some(PathElement::Key(_))) {
+            paths.push(path.clone());
+        }
+    });
+    paths
+}
+
+fn siblings(a: &Path, b: &Path) -> bool {
+    let (a, b) = (a.as_slice(), b.as_slice());
+    a.len() == b.len() && a[..a.len() - 1] == b[..b.len() - 1]
+}
+
+fn share_hint(lhs: &Value, rhs: &Value, hints: &[&str]) -> bool {
+    let (Value::Map(lhs), Value::Map(rhs)) = (lhs, rhs) else {
+        return false;
+    };
+    hints.iter().any(|hint| match (lhs.get(hint), rhs.get(hint)) {
+        (Some(l), Some(r)) => l == r && !matches!(l, Value::Null),
+        _ => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::typed::Parser;
+
+    #[test]
+    fn test_compare_with_rename_hints() {
+        let pt = Parser::new(
+            r#"types:
+- name: root
+  map:
+    fields:
+    - name: containers
+      type:
+        list:
+          elementType:
+            namedType: container
+          elementRelationship: associative
+          keys: [name]
+- name: container
+  map:
+    fields:
+    - name: name
+      type:
+        scalar: string
+    - name: image
+      type:
+        scalar: string
+"#,
+        )
+        .unwrap()
+        .type_by_name("root");
+        let lhs = pt
+            .from_yaml(r#"{"containers": [{"name": "web", "image": "nginx"}, {"name": "a", "image": "x"}, {"name": "b", "image": "x"}]}"#)
+            .unwrap();
+        let rhs = pt
+            .from_yaml(r#"{"containers": [{"name": "frontend", "image": "nginx"}, {"name": "c", "image": "x"}]}"#)
+            .unwrap();
+
+        assert!(lhs.compare(&rhs).unwrap().renamed.is_empty());
+        let comparison = lhs.compare_with_rename_hints(&rhs, &["image"]).unwrap();
+        let renamed: Vec<String> = comparison.renamed.iter().map(|r| format!("{} -> {}", r.from, r.to)).collect();
+        // a and b both match c, so neither is paired
+        assert_eq!(renamed, [r#".containers[name="web"] -> .containers[name="frontend"]"#]);
+        let rename = &comparison.renamed[0];
+        assert!(comparison.removed.has(&rename.from));
+        assert!(comparison.added.has(&rename.to));
+
+        // Ownership of the old item carries over to the new one
+        let mut owned = Set::new();
+        owned.insert(&rename.from.with(PathElement::field_name("image")));
+        owned.insert(&Path::from_elements(vec![PathElement::field_name("other")]));
+        let rebased = rename.rebase(&owned);
+        assert!(rebased.has(&rename.to.with(PathElement::field_name("image"))));
+        assert!(rebased.has(&Path::from_elements(vec![PathElement::field_name("other")])));
+        assert!(!rebased.has(&rename.from.with(PathElement::field_name("image"))));
+    }
+}
@@ -0,0 +1,182 @@
+//! Human-readable rendering of the differences between typed values.
+
+use super::typed_value::TypedValue;
+use super::validation::ValidationErrors;
+use crate::fieldpath::Path;
+use crate::value::{self, Value};
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const RESET: &str = "\x1b[0m";
+
+/// DiffLayout selects how changed fields are laid out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum DiffLayout {
+    /// One `-` line with the old value and one `+` line with the new value
+    /// per changed field.
+    #[default]
+    Unified,
+    /// One line per changed field with the path, old and new value in
+    /// aligned columns.
+    SideBySide,
+}
+
+/// DiffStyle controls the output of render_diff.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DiffStyle {
+    pub layout: DiffLayout,
+    /// Color removals red and additions green with ANSI escapes.
+    pub color: bool,
+}
+
+impl DiffStyle {
+    /// Plain unified output.
+    pub fn unified() -> Self {
+        DiffStyle {
+            layout: DiffLayout::Unified,
+            color: false,
+        }
+    }
+
+    /// Plain side-by-side output.
+    pub fn side_by_side() -> Self {
+        DiffStyle {
+            layout: DiffLayout::SideBySide,
+            color: false,
+        }
+    }
+
+    /// Returns this style with colors.
+    pub fn colored(mut self) -> Self {
+        self.color = true;
+        self
+    }
+}
+
+/// Renders the fields that differ between `old` and `new`, one entry per
+/// field path in path order.
+///
+/// Atomic lists and maps are rendered as a whole, since their items have no
+/// paths of their own. Values are rendered as JSON.
+pub fn render_diff(old: &TypedValue, new: &TypedValue, style: DiffStyle) -> Result<String, ValidationErrors> {
+    let comparison = old.compare(new)?;
+    let changed = comparison.removed.union(&comparison.modified).union(&comparison.added);
+
+    let mut paths = Vec::new();
+    changed.iterate(|path| paths.push(path.clone()));
+    paths.sort_by(|a, b| a.as_slice().cmp(b.as_slice()));
+    let rows: Vec<(String, Option<String>, Option<String>)> = paths
+        .iter()
+        .map(|path| (path.to_string(), render_at(old, path), render_at(new, path)))
+        .collect();
+
+    let (red, green, reset) = if style.color { (RED, GREEN, RESET) } else { ("", "", "") };
+    let mut out = String::new();
+    match style.layout {
+        DiffLayout::Unified => {
+            for (path, before, after) in &rows {
+                if let Some(before) = before {
+                    out.push_str(&format!("{}- {}: {}{}\n", red, path, before, reset));
+                }
+                if let Some(after) = after {
+                    out.push_str(&format!("{}+ {}: {}{}\n", green, path, after, reset));
+                }
+            }
+        }
+        DiffLayout::SideBySide => {
+            let path_width = rows.iter().map(|(p, _, _)| p.chars().count()).max().unwrap_or(0);
+            let old_width = rows
+                .iter()
+                .map(|(_, b, _)| b.as_deref().unwrap_or("").chars().count())
+                .max()
+                .unwrap_or(0);
+            for (path, before, after) in &rows {
+                let before = before.as_deref().unwrap_or("");
+                let after = after.as_deref().unwrap_or("");
+                out.push_str(&format!(
+                    "{:pw$} | {}{:ow$}{} | {}{}{}\n",
+                    path,
+                    red,
+                    before,
+                    reset,
+                    green,
+                    after,
+                    reset,
+                    pw = path_width,
+                    ow = old_width
+                ));
+            }
+        }
+    }
+    Ok(out)
+}
+
+fn render_at(tv: &TypedValue, path: &Path) -> Option<String> {
+    let element = tv.element_at(path)?;
+    match element.value() {
+        Value::Null => Some("null".to_string()),
+        v => value::to_json(v).ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::typed::Parser;
+
+    const SCHEMA: &str = r#"types:
+- name: root
+  map:
+    fields:
+    - name: replicas
+      type:
+        scalar: numeric
+    - name: image
+      type:
+        scalar: string
+    - name: args
+      type:
+        list:
+          elementType:
+            scalar: string
+          elementRelationship: atomic
+    - name: labels
+      type:
+        map:
+          elementType:
+            scalar: string
+"#;
+
+    #[test]
+    fn test_render_diff() {
+        let pt = Parser::new(SCHEMA).unwrap().type_by_name("root");
+        let old = pt
+            .from_yaml(r#"{"replicas": 1, "image": "a", "args": ["x"], "labels": {"env": "dev"}}"#)
+            .unwrap();
+        let new = pt
+            .from_yaml(r#"{"replicas": 2, "image": "a", "args": ["x", "y"], "labels": {"tier": "web"}}"#)
+            .unwrap();
+
+        assert_eq!(
+            render_diff(&old, &new, DiffStyle::unified()).unwrap(),
+            "- .args: [\"x\"]\n\
+             + .args: [\"x\",\"y\"]\n\
+             - .labels.env: \"dev\"\n\
+             + .labels.tier: \"web\"\n\
+             - .replicas: 1\n\
+             + .replicas: 2\n"
+        );
+        assert_eq!(
+            render_diff(&old, &new, DiffStyle::side_by_side()).unwrap(),
+            ".args        | [\"x\"] | [\"x\",\"y\"]\n\
+             .labels.env  | \"dev\" | \n\
+             .labels.tier |       | \"web\"\n\
+             .replicas    | 1     | 2\n"
+        );
+
+        let colored = render_diff(&old, &new, DiffStyle::unified().colored()).unwrap();
+        assert!(colored.starts_with("\x1b[31m- .args"));
+        assert_eq!(render_diff(&old, &old, DiffStyle::unified()).unwrap(), "");
+    }
+}
@@ -0,0 +1,70 @@
+//! Buffers that compares reuse across calls.
+
+use super::comparison::Comparison;
+use super::detailed::ValueChanges;
+use super::typed_value::TypedValue;
+use super::validation::ValidationErrors;
+use crate::fieldpath::{Path, PathElement};
+use crate::value::Value;
+use std::collections::HashMap;
+
+/// CompareScratch holds the maps compares index list items with, kept
+/// between compares so that their allocations are reused.
+#[derive(Debug, Default)]
+pub(crate) struct CompareScratch {
+    maps: Vec<HashMap<PathElement, usize>>,
+}
+
+impl CompareScratch {
+    /// Returns an empty map, reusing one given back earlier if any.
+    pub(crate) fn take_map(&mut self) -> HashMap<PathElement, usize> {
+        self.maps.pop().unwrap_or_default()
+    }
+
+    /// Gives a map back for later compares.
+    pub(crate) fn put_map(&mut self, mut map: HashMap<PathElement, usize>) {
+        map.clear();
+        self.maps.push(map);
+    }
+}
+
+/// CompareOutput is what a compare records as it goes.
+pub(crate) struct CompareOutput<'s> {
+    pub(crate) comparison: Comparison,
+    /// The values of the modified fields, if they are kept.
+    pub(crate) changes: Option<ValueChanges>,
+    pub(crate) scratch: &'s mut CompareScratch,
+}
+
+impl<'s> CompareOutput<'s> {
+    /// Returns an empty output that doesn't keep values.
+    pub(crate) fn new(scratch: &'s mut CompareScratch) -> Self {
+        CompareOutput {
+            comparison: Comparison::new(),
+            changes: None,
+            scratch,
+        }
+    }
+
+    /// Records the field at `path` as modified from `lhs` to `rhs`.
+    pub(crate) fn modified(&mut self, path: &Path, lhs: &Value, rhs: &Value) {
+        self.comparison.modified.insert(path);
+        if let Some(changes) = &mut self.changes {
+            changes.record(path, lhs, rhs);
+        }
+    }
+}
+
+impl TypedValue {
+    /// Compares this TypedValue with another as compare does, reusing the
+    /// buffers of `scratch`.
+    pub(crate) fn compare_with_scratch(
+        &self,
+        rhs: &TypedValue,
+        scratch: &mut CompareScratch,
+    ) -> Result<Comparison, ValidationErrors> {
+        let mut out = CompareOutput::new(scratch);
+        self.compare_into(rhs, &mut out)?;
+        Ok(out.comparison)
+    }
+}
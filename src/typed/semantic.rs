@@ -0,0 +1,236 @@
+//! Semantic equality of scalar subtypes.
+//!
+//! Quantities, int-or-strings and times have several representations of the
+//! same value ("1Gi" and "1073741824", 80 and "80"). These are treated as
+//! equal when the schema enables semantic scalar comparison.
+
+#![cfg_attr(not(test), deny(clippy::indexing_slicing, clippy::string_slice))]
+
+use crate::schema::Scalar;
+use crate::value::Value;
+
+/// Returns true if two scalars of the given type denote the same value.
+pub(crate) fn scalars_equivalent(scalar: Scalar, lhs: &Value, rhs: &Value) -> bool {
+    if lhs == rhs {
+        return true;
+    }
+    match scalar {
+        Scalar::Quantity => match (quantity(lhs), quantity(rhs)) {
+            (Some(a), Some(b)) => a == b,
+            _ => false,
+        },
+        Scalar::IntOrString => match (lhs, rhs) {
+            (Value::Int(i), Value::String(s)) | (Value::String(s), Value::Int(i)) => {
+                s.parse::<i64>().ok() == Some(*i)
+            }
+            _ => false,
+        },
+        Scalar::Time => match (lhs.as_str().and_then(parse_time), rhs.as_str().and_then(parse_time)) {
+            (Some(a), Some(b)) => a == b,
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Returns the normalized (mantissa, exponent) form of a quantity value,
+/// with value = mantissa * 10^exponent and no trailing zeros in mantissa.
+pub(crate) fn quantity(v: &Value) -> Option<(i128, i32)> {
+    match v {
+        Value::Int(i) => Some(normalize(i128::from(*i), 0)),
+        Value::Float(f) if f.is_finite() => parse_quantity(&f.to_string()),
+        Value::String(s) => parse_quantity(s),
+        _ => None,
+    }
+}
+
+/// Parses a Kubernetes resource quantity such as "1.5Gi", "100m" or "1e3".
+pub(crate) fn parse_quantity(s: &str) -> Option<(i128, i32)> {
+    let s = s.trim();
+    let (negative, s) = match (s.strip_prefix('-'), s.strip_prefix('+')) {
+        (Some(s), _) => (true, s),
+        (_, Some(s)) => (false, s),
+        _ => (false, s),
+    };
+
+    let number_len = s
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(s.len());
+    let (number, suffix) = s.split_at(number_len);
+    let (int_part, frac_part) = number.split_once('.').unwrap_or((number, ""));
+    if int_part.is_empty() && frac_part.is_empty() {
+        return None;
+    }
+
+    let mut mantissa: i128 = 0;
+    for c in int_part.chars().chain(frac_part.chars()) {
+        mantissa = mantissa
+            .checked_mul(10)?
+            .checked_add(i128::from(c.to_digit(10)?))?;
+    }
+    let mut exponent = -i32::try_from(frac_part.len()).ok()?;
+
+    let (decimal, binary) = match suffix {
+        "" => (0, 0),
+        "n" => (-9, 0),
+        "u" => (-6, 0),
+        "m" => (-3, 0),
+        "k" => (3, 0),
+        "M" => (6, 0),
+        "G" => (9, 0),
+        "T" => (12, 0),
+        "P" => (15, 0),
+        "E" => (18, 0),
+        "Ki" => (0, 1),
+        "Mi" => (0, 2),
+        "Gi" => (0, 3),
+        "Ti" => (0, 4),
+        "Pi" => (0, 5),
+        "Ei" => (0, 6),
+        _ => {
+            let exp = suffix.strip_prefix(['e', 'E'])?;
+            (exp.parse::<i32>().ok()?, 0)
+        }
+    };
+    exponent = exponent.checked_add(decimal)?;
+    for _ in 0..binary {
+        mantissa = mantissa.checked_mul(1024)?;
+    }
+
+    if negative {
+        mantissa = -mantissa;
+    }
+    Some(normalize(mantissa, exponent))
+}
+
+fn normalize(mut mantissa: i128, mut exponent: i32) -> (i128, i32) {
+    if mantissa == 0 {
+        return (0, 0);
+    }
+    while mantissa % 10 == 0 {
+        mantissa /= 10;
+        exponent += 1;
+    }
+    (mantissa, exponent)
+}
+
+/// Parses an RFC 3339 timestamp into seconds and nanoseconds since the epoch.
+pub(crate) fn parse_time(s: &str) -> Option<(i64, u32)> {
+    let b = s.as_bytes();
+    let at = |i: usize| b.get(i).copied();
+    if b.len() < 20
+        || at(4) != Some(b'-')
+        || at(7) != Some(b'-')
+        || !matches!(at(10), Some(b'T' | b't' | b' '))
+        || at(13) != Some(b':')
+        || at(16) != Some(b':')
+    {
+        return None;
+    }
+    let num = |range: std::ops::Range<usize>| -> Option<i64> {
+        let part = s.get(range)?;
+        if !part.bytes().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        part.parse().ok()
+    };
+    let (year, month, day) = (num(0..4)?, num(5..7)?, num(8..10)?);
+    let (hour, minute, second) = (num(11..13)?, num(14..16)?, num(17..19)?);
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+
+    let mut rest = s.get(19..)?;
+    let mut nanos: u32 = 0;
+    if let Some(frac) = rest.strip_prefix('.') {
+        let len = frac.find(|c: char| !c.is_ascii_digit()).unwrap_or(frac.len());
+        if len == 0 {
+            return None;
+        }
+        let (digits, after) = frac.split_at(len);
+        for (i, c) in digits.chars().enumerate() {
+            if i < 9 {
+                nanos = nanos * 10 + c.to_digit(10)?;
+            }
+        }
+        for _ in len..9 {
+            nanos *= 10;
+        }
+        rest = after;
+    }
+
+    let offset = match rest {
+        "Z" | "z" => 0,
+        _ => {
+            let ob = rest.as_bytes();
+            if ob.len() != 6 || ob.get(3) != Some(&b':') {
+                return None;
+            }
+            let sign = match ob.first() {
+                Some(b'+') => 1,
+                Some(b'-') => -1,
+                _ => return None,
+            };
+            let h: i64 = rest.get(1..3)?.parse().ok()?;
+            let m: i64 = rest.get(4..6)?.parse().ok()?;
+            sign * (h * 3600 + m * 60)
+        }
+    };
+
+    let days = days_from_civil(year, month, day);
+    let secs = days * 86400 + hour * 3600 + minute * 60 + second - offset;
+    Some((secs, nanos))
+}
+
+/// Returns the number of days since 1970-01-01 for a proleptic Gregorian date.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quantity_equivalence() {
+        let eq = |a: Value, b: Value| scalars_equivalent(Scalar::Quantity, &a, &b);
+        let s = |v: &str| Value::String(v.into());
+
+        assert!(eq(s("1Gi"), s("1073741824")));
+        assert!(eq(s("1Gi"), Value::Int(1073741824)));
+        assert!(eq(s("1000m"), s("1")));
+        assert!(eq(s("0.5"), s("500m")));
+        assert!(eq(s("1e3"), s("1k")));
+        assert!(eq(Value::Float(1.5), s("1500m")));
+        assert!(!eq(s("1G"), s("1Gi")));
+        assert!(!eq(s("1x"), s("1x ")));
+        assert_eq!(parse_quantity("-2Ki"), Some((-2048, 0)));
+        assert_eq!(parse_quantity("abc"), None);
+    }
+
+    #[test]
+    fn test_int_or_string_equivalence() {
+        let eq = |a: Value, b: Value| scalars_equivalent(Scalar::IntOrString, &a, &b);
+
+        assert!(eq(Value::Int(80), Value::String("80".into())));
+        assert!(!eq(Value::Int(80), Value::String("http".into())));
+        assert!(!eq(Value::Int(80), Value::Int(81)));
+    }
+
+    #[test]
+    fn test_time_equivalence() {
+        let eq = |a: &str, b: &str| scalars_equivalent(Scalar::Time, &Value::String(a.into()), &Value::String(b.into()));
+
+        assert!(eq("2024-01-01T00:00:00Z", "2024-01-01T00:00:00.000Z"));
+        assert!(eq("2024-01-01T02:00:00+02:00", "2024-01-01T00:00:00Z"));
+        assert!(!eq("2024-01-01T00:00:01Z", "2024-01-01T00:00:00Z"));
+        assert!(!eq("yesterday", "yesterday "));
+        assert_eq!(parse_time("1970-01-01T00:00:00Z"), Some((0, 0)));
+    }
+}
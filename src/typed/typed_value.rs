@@ -1,13 +1,20 @@
 //! TypedValue implementation.
 
-use crate::fieldpath::{Path, PathElement, Set};
-use crate::schema::{ElementRelationship, Schema, Scalar, TypeRef};
+use crate::fieldpath::{canonical_numbers, Path, PathElement, Set};
+use crate::schema::{
+    Atom, ElementRelationship, HeterogeneousLists, List, ListOrdering, NumericKeys, Schema, Scalar, TypeRef,
+    EMBEDDED_RESOURCE_TYPE,
+};
 use crate::value::{Field, FieldList, Map, Value};
+use std::borrow::Cow;
 use super::comparison::Comparison;
-use super::validation::{ValidationError, ValidationErrors, ValidationOption};
+use super::duplicates::{duplicated, item_path};
+use super::scratch::{CompareOutput, CompareScratch};
+use super::semantic::scalars_equivalent;
+use super::validation::{FieldValidation, ValidationError, ValidationErrors, ValidationOption};
 
 /// Converts a serde_json::Value to our Value type.
-fn json_value_to_value(json: &serde_json::Value) -> Value {
+pub(crate) fn json_value_to_value(json: &serde_json::Value) -> Value {
     match json {
         serde_json::Value::Null => Value::Null,
         serde_json::Value::Bool(b) => Value::Bool(*b),
@@ -58,6 +65,93 @@ pub fn as_typed(
     Ok(tv)
 }
 
+/// Creates a new TypedValue, relaxing unknown fields and duplicate keys as
+/// `validation` asks. Unless validation is strict, unknown fields are dropped
+/// from the value; the relaxed errors are returned as warnings in Warn mode.
+pub fn as_typed_with_field_validation(
+    value: Value,
+    schema: &Schema,
+    type_ref: TypeRef,
+    validation: FieldValidation,
+) -> Result<(TypedValue, Vec<ValidationError>), ValidationErrors> {
+    let mut tv = as_typed_unvalidated(value, schema, type_ref);
+    let errors = match tv.validate(&[]) {
+        Ok(()) => return Ok((tv, Vec::new())),
+        Err(errors) => errors,
+    };
+    if validation == FieldValidation::Strict {
+        return Err(errors);
+    }
+
+    let (warnings, fatal): (Vec<_>, Vec<_>) = errors.into_iter().partition(ValidationError::is_field_error);
+    if !fatal.is_empty() {
+        let mut errors = ValidationErrors::new();
+        fatal.into_iter().for_each(|e| errors.add(e));
+        return Err(errors);
+    }
+
+    prune_unknown_fields(&tv.schema, &tv.type_ref, &mut tv.value);
+    match validation {
+        FieldValidation::Warn => Ok((tv, warnings)),
+        _ => Ok((tv, Vec::new())),
+    }
+}
+
+/// Removes the fields of maps that their type doesn't declare.
+fn prune_unknown_fields(schema: &Schema, type_ref: &TypeRef, value: &mut Value) {
+    let Some(atom) = schema.resolve(type_ref) else {
+        return;
+    };
+    match value {
+        Value::List(items) => {
+            if let Some(ref list) = atom.list {
+                for item in items.iter_mut() {
+                    prune_unknown_fields(schema, &list.element_type, item);
+                }
+            }
+        }
+        Value::Map(fields) => {
+            if let Some(ref map) = atom.map {
+                fields.fields.retain(|key, _| map.find_field(key).is_some() || allows_unknown_fields(map));
+                for (key, val) in fields.fields.iter_mut() {
+                    let field_type = match map.find_field(key) {
+                        Some(field) => &field.field_type,
+                        None => &map.element_type,
+                    };
+                    prune_unknown_fields(schema, field_type, val);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Returns true if the map has an element type for fields it doesn't declare.
+pub(crate) fn allows_unknown_fields(map: &crate::schema::Map) -> bool {
+    let element_type = &map.element_type;
+    element_type.named_type.is_some()
+        || element_type.inlined.scalar.is_some()
+        || element_type.inlined.list.is_some()
+        || element_type.inlined.map.is_some()
+}
+
+/// Returns true if the list is an atomic list of the deduced schema.
+fn is_deduced_atomic(list: &List) -> bool {
+    list.element_relationship == ElementRelationship::Atomic
+        && list.element_type.named_type.as_deref() == Some("__untyped_atomic_")
+}
+
+/// Returns true if the items mix scalars, lists and maps. Nulls count as
+/// scalars.
+fn is_heterogeneous(items: &[Value]) -> bool {
+    let kind = |item: &Value| match item {
+        Value::List(_) => 1,
+        Value::Map(_) => 2,
+        _ => 0,
+    };
+    items.first().is_some_and(|first| items.iter().any(|item| kind(item) != kind(first)))
+}
+
 /// Creates a new TypedValue without validation.
 /// Use this only when validation has already been done.
 pub fn as_typed_unvalidated(value: Value, schema: &Schema, type_ref: TypeRef) -> TypedValue {
@@ -103,6 +197,11 @@ impl TypedValue {
         &self.schema
     }
 
+    /// Returns a mutable reference to the schema.
+    pub(crate) fn schema_mut(&mut self) -> &mut Schema {
+        &mut self.schema
+    }
+
     /// Validates the value against the schema.
     pub fn validate(&self, opts: &[ValidationOption]) -> Result<(), ValidationErrors> {
         let allow_duplicates = opts.contains(&ValidationOption::AllowDuplicates);
@@ -117,7 +216,7 @@ impl TypedValue {
         }
     }
 
-    fn validate_value(
+    pub(crate) fn validate_value(
         &self,
         value: &Value,
         type_ref: &TypeRef,
@@ -125,6 +224,9 @@ impl TypedValue {
         allow_duplicates: bool,
         errors: &mut ValidationErrors,
     ) {
+        #[cfg(feature = "profiling")]
+        let _timer = crate::profile::timer(crate::profile::Phase::Validate, type_ref, Some(&path));
+
         // Resolve the type reference
         let atom = match self.schema.resolve(type_ref) {
             Some(atom) => atom,
@@ -168,8 +270,16 @@ impl TypedValue {
                     ));
                 }
             }
-            Value::Map(_) => {
+            Value::Map(fields) => {
                 if let Some(ref map) = atom.map {
+                    if type_ref.named_type.as_deref() == Some(EMBEDDED_RESOURCE_TYPE) && !fields.is_empty() {
+                        // Embedded objects must identify their type
+                        for required in ["apiVersion", "kind"] {
+                            if fields.get(required).and_then(Value::as_str).is_none_or(str::is_empty) {
+                                errors.add(ValidationError::missing_field(format!("{}", path), required));
+                            }
+                        }
+                    }
                     self.validate_map(value, map, path, allow_duplicates, errors);
                 } else {
                     errors.add(ValidationError::type_mismatch(
@@ -198,6 +308,9 @@ impl TypedValue {
             Scalar::String => value.is_string(),
             Scalar::Boolean => value.is_bool(),
             Scalar::Untyped => value.is_int() || value.is_float() || value.is_string() || value.is_bool(),
+            Scalar::Quantity => value.is_int() || value.is_float() || value.is_string(),
+            Scalar::IntOrString => value.is_int() || value.is_string(),
+            Scalar::Time => value.is_string(),
         };
 
         if !valid {
@@ -206,6 +319,9 @@ impl TypedValue {
                 Scalar::String => "string",
                 Scalar::Boolean => "boolean",
                 Scalar::Untyped => "scalar",
+                Scalar::Quantity => "quantity",
+                Scalar::IntOrString => "int-or-string",
+                Scalar::Time => "time",
             };
             let actual = match value {
                 Value::Null => "null",
@@ -245,21 +361,41 @@ impl TypedValue {
             }
         };
 
-        // Track keys for duplicate detection in associative lists
-        let mut seen_keys = Vec::new();
+        if self.schema.heterogeneous_lists() == HeterogeneousLists::Reject
+            && is_deduced_atomic(list)
+            && is_heterogeneous(items)
+        {
+            errors.add(ValidationError::invalid_value(
+                path.to_string(),
+                "list mixes scalars, lists and maps",
+            ));
+            return;
+        }
+        let list = self.effective_list(list, &[items]);
+        let list = list.as_ref();
+
+        // Track the indexes of each key for duplicate detection in
+        // associative lists, in order of first occurrence
+        let mut key_indexes: Vec<(String, Vec<usize>)> = Vec::new();
+        let mut seen_keys: std::collections::HashMap<FieldList, usize> = std::collections::HashMap::new();
 
         for (i, item) in items.iter().enumerate() {
             let pe = if list.element_relationship == ElementRelationship::Associative {
                 // For associative lists, compute key
                 match self.list_item_to_key(item, list) {
                     Ok(key) => {
-                        if !allow_duplicates && seen_keys.contains(&key) {
-                            errors.add(ValidationError::duplicate_key(
-                                format!("{}", path),
-                                format!("{:?}", key),
-                            ));
+                        match seen_keys.get(&key) {
+                            Some(&group) => key_indexes[group].1.push(i),
+                            None => {
+                                let rendered = if list.keys.is_empty() {
+                                    PathElement::value(item.clone()).to_string()
+                                } else {
+                                    PathElement::Key(key.clone()).to_string()
+                                };
+                                seen_keys.insert(key.clone(), key_indexes.len());
+                                key_indexes.push((rendered, vec![i]));
+                            }
                         }
-                        seen_keys.push(key.clone());
                         PathElement::Key(key)
                     }
                     Err(e) => {
@@ -274,6 +410,14 @@ impl TypedValue {
             let item_path = path.with(pe);
             self.validate_value(item, &list.element_type, item_path, allow_duplicates, errors);
         }
+
+        if !allow_duplicates {
+            for (key, indexes) in key_indexes {
+                if indexes.len() > 1 {
+                    errors.add(ValidationError::duplicate_key(path.to_string(), key, indexes));
+                }
+            }
+        }
     }
 
     fn validate_map(
@@ -303,11 +447,11 @@ impl TypedValue {
 
             // Find the field type
             let field_type = if let Some(field) = map.find_field(key) {
+                check_size_limits(field, val, &field_path, errors);
                 field.field_type.clone()
             } else {
                 // Check if unknown fields are allowed (element_type is set)
-                if map.element_type.named_type.is_some() || map.element_type.inlined.scalar.is_some()
-                    || map.element_type.inlined.list.is_some() || map.element_type.inlined.map.is_some() {
+                if allows_unknown_fields(map) {
                     map.element_type.clone()
                 } else {
                     errors.add(ValidationError::unknown_field(
@@ -322,79 +466,12 @@ impl TypedValue {
         }
     }
 
-    fn list_item_to_key(
+    pub(crate) fn list_item_to_key(
         &self,
         item: &Value,
         list: &crate::schema::List,
     ) -> Result<FieldList, ValidationError> {
-        if list.keys.is_empty() {
-            // Set semantics - use the value itself
-            return Ok(FieldList::with_fields(vec![Field {
-                name: String::new(),
-                value: item.clone(),
-            }]));
-        }
-
-        // Associative list - extract key fields
-        let map = match item {
-            Value::Map(m) => m,
-            _ => {
-                return Err(ValidationError::invalid_value(
-                    "",
-                    "expected map for associative list item",
-                ));
-            }
-        };
-
-        let mut fields = Vec::new();
-        for key_name in &list.keys {
-            match map.get(key_name) {
-                Some(v) => {
-                    fields.push(Field {
-                        name: key_name.clone(),
-                        value: v.clone(),
-                    });
-                }
-                None => {
-                    // Try to get default value from schema
-                    if let Some(default_val) = self.get_associative_key_default(list, key_name) {
-                        fields.push(Field {
-                            name: key_name.clone(),
-                            value: default_val,
-                        });
-                    }
-                    // If no default, don't add this key to the list
-                    // This allows partial keys where only some key fields have defaults
-                }
-            }
-        }
-
-        // If we have keys defined but couldn't find any key values (even with defaults),
-        // that's an error
-        if !list.keys.is_empty() && fields.is_empty() {
-            return Err(ValidationError::invalid_value(
-                "",
-                format!(
-                    "associative list with keys has an element that omits all key fields {:?} (and doesn't have default values for any key fields)",
-                    list.keys
-                ),
-            ));
-        }
-
-        Ok(FieldList::with_fields(fields))
-    }
-
-    /// Gets the default value for an associative list key field from the schema.
-    fn get_associative_key_default(&self, list: &crate::schema::List, field_name: &str) -> Option<Value> {
-        // Resolve the list's element type to get the map schema
-        let atom = self.schema.resolve(&list.element_type)?;
-        let map_schema = atom.map.as_ref()?;
-
-        // Find the field in the map schema
-        let field = map_schema.find_field(field_name)?;
-
-        // Return the default value if it exists, converting from serde_json::Value to our Value
-        field.default.as_ref().map(json_value_to_value)
+        list_item_key(&self.schema, item, list)
     }
 
     /// Converts the typed value to a field set representing all leaf paths.
@@ -402,7 +479,7 @@ impl TypedValue {
         let mut set = Set::new();
         let mut errors = ValidationErrors::new();
 
-        self.collect_field_set(&self.value, &self.type_ref, Path::new(), &mut set, &mut errors);
+        self.collect_field_set(&self.value, &self.type_ref, Path::new(), false, &mut set, &mut errors);
 
         if errors.is_empty() {
             Ok(set)
@@ -411,12 +488,16 @@ impl TypedValue {
         }
     }
 
+    /// Collects the field set of a value. With `duplicate_indexes`, items of
+    /// associative lists and sets that share their key are recorded under
+    /// their index.
     #[allow(clippy::only_used_in_recursion)]
-    fn collect_field_set(
+    pub(crate) fn collect_field_set(
         &self,
         value: &Value,
         type_ref: &TypeRef,
         path: Path,
+        duplicate_indexes: bool,
         set: &mut Set,
         errors: &mut ValidationErrors,
     ) {
@@ -453,6 +534,10 @@ impl TypedValue {
                             // For regular schemas, only insert if empty (shouldn't happen normally)
                             set.insert(&path);
                         }
+                        if leaf_fields(fields) {
+                            self.collect_leaf_fields(fields, map, &path, set);
+                            return;
+                        }
                         for (key, val) in fields.iter() {
                             let pe = PathElement::field_name(key.clone());
                             let field_path = path.with(pe);
@@ -463,7 +548,14 @@ impl TypedValue {
                                 map.element_type.clone()
                             };
 
-                            self.collect_field_set(val, &field_type, field_path.clone(), set, errors);
+                            self.collect_field_set(
+                                val,
+                                &field_type,
+                                field_path.clone(),
+                                duplicate_indexes,
+                                set,
+                                errors,
+                            );
 
                             // For associative maps with element_type (not explicit fields),
                             // insert each key's path similar to how we handle associative lists
@@ -481,29 +573,30 @@ impl TypedValue {
             }
             Value::List(items) => {
                 if let Some(ref list) = atom.list {
+                    let list = self.effective_list(list, &[items]);
+                    let list = list.as_ref();
                     if list.element_relationship == ElementRelationship::Atomic {
                         // Atomic lists are leaves
                         if !path.is_empty() {
                             set.insert(&path);
                         }
                     } else {
-                        for (i, item) in items.iter().enumerate() {
-                            let pe = if list.element_relationship == ElementRelationship::Associative {
-                                if list.keys.is_empty() {
-                                    // Set semantics - use the value as the path element
-                                    PathElement::value(item.clone())
-                                } else {
-                                    // Keyed associative list
-                                    match self.list_item_to_key(item, list) {
-                                        Ok(key) => PathElement::Key(key),
-                                        Err(_) => PathElement::index(i as i32),
-                                    }
-                                }
-                            } else {
-                                PathElement::index(i as i32)
+                        let elements = self.list_item_elements(items, list);
+                        let duplicated = if duplicate_indexes { duplicated(&elements) } else { Vec::new() };
+                        for (i, (item, pe)) in items.iter().zip(elements).enumerate() {
+                            let pe = match duplicated.get(i) {
+                                Some(true) => PathElement::index(i as i32),
+                                _ => pe,
                             };
                             let item_path = path.with(pe);
-                            self.collect_field_set(item, &list.element_type, item_path.clone(), set, errors);
+                            self.collect_field_set(
+                                item,
+                                &list.element_type,
+                                item_path.clone(),
+                                duplicate_indexes,
+                                set,
+                                errors,
+                            );
                             // For keyed associative lists, also insert the item path itself
                             if list.element_relationship == ElementRelationship::Associative && !list.keys.is_empty() {
                                 set.insert(&item_path);
@@ -526,8 +619,39 @@ impl TypedValue {
         }
     }
 
+    /// Collects the fields of a map whose values are all scalars or nulls.
+    /// The element type is resolved once and the fields are inserted beneath
+    /// the map in one descent.
+    fn collect_leaf_fields(&self, fields: &Map, map: &crate::schema::Map, path: &Path, set: &mut Set) {
+        let element_atom = self.schema.resolve(&map.element_type);
+        let keyed = map.element_relationship == ElementRelationship::Associative
+            && map.fields.is_empty()
+            && map.element_type.named_type.is_some();
+        let takes_leaf =
+            |atom: Option<&Atom>, val: &Value| atom.is_some_and(|atom| val.is_null() || atom.scalar.is_some());
+        let mut members = Vec::with_capacity(fields.len());
+        for (key, val) in fields.iter() {
+            let is_leaf_field = match map.find_field(key) {
+                Some(field) => takes_leaf(self.schema.resolve(&field.field_type).as_ref(), val),
+                None => takes_leaf(element_atom.as_ref(), val),
+            };
+            if is_leaf_field || keyed {
+                members.push(PathElement::field_name(key.clone()));
+            }
+        }
+        set.insert_members(path, members);
+    }
+
     /// Compares this TypedValue with another.
     pub fn compare(&self, rhs: &TypedValue) -> Result<Comparison, ValidationErrors> {
+        let mut scratch = CompareScratch::default();
+        let mut out = CompareOutput::new(&mut scratch);
+        self.compare_into(rhs, &mut out)?;
+        Ok(out.comparison)
+    }
+
+    /// Compares this TypedValue with another, recording into `out`.
+    pub(crate) fn compare_into(&self, rhs: &TypedValue, out: &mut CompareOutput<'_>) -> Result<(), ValidationErrors> {
         // Verify same schema/type
         if self.type_ref != rhs.type_ref {
             return Err(ValidationErrors::from_error(ValidationError::schema_error(
@@ -535,16 +659,8 @@ impl TypedValue {
             )));
         }
 
-        let mut comparison = Comparison::new();
-        self.compare_values(
-            &self.value,
-            &rhs.value,
-            &self.type_ref,
-            Path::new(),
-            &mut comparison,
-        );
-
-        Ok(comparison)
+        self.compare_values(&self.value, &rhs.value, &self.type_ref, Path::new(), out);
+        Ok(())
     }
 
     fn compare_values(
@@ -553,7 +669,7 @@ impl TypedValue {
         rhs: &Value,
         type_ref: &TypeRef,
         path: Path,
-        comparison: &mut Comparison,
+        out: &mut CompareOutput<'_>,
     ) {
         let atom = match self.schema.resolve(type_ref) {
             Some(atom) => atom,
@@ -564,34 +680,34 @@ impl TypedValue {
         match (lhs, rhs) {
             (Value::Map(_), Value::Map(_)) => {
                 if let Some(ref map) = atom.map {
-                    self.compare_maps(lhs, rhs, map, path, comparison);
+                    self.compare_maps(lhs, rhs, map, path, out);
                 } else if lhs != rhs {
-                    comparison.modified.insert(&path);
+                    out.modified(&path, lhs, rhs);
                 }
             }
             (Value::List(_), Value::List(_)) => {
                 if let Some(ref list) = atom.list {
-                    self.compare_lists(lhs, rhs, list, path, comparison);
+                    self.compare_lists(lhs, rhs, list, path, out);
                 } else if lhs != rhs {
-                    comparison.modified.insert(&path);
+                    out.modified(&path, lhs, rhs);
                 }
             }
             _ => {
                 // Type mismatch or scalar comparison
-                if lhs != rhs {
-                    comparison.modified.insert(&path);
+                if lhs != rhs && !self.scalars_equivalent(&atom, lhs, rhs) {
+                    out.modified(&path, lhs, rhs);
 
                     // For type changes, track nested paths as added/removed
                     // If LHS is a map, all its nested paths are "removed"
                     if let Value::Map(_) = lhs {
                         if atom.map.is_some() {
-                            self.collect_all_paths(lhs, type_ref, path.clone(), &mut comparison.removed);
+                            self.collect_all_paths(lhs, type_ref, path.clone(), &mut out.comparison.removed);
                         }
                     }
                     // If RHS is a map, all its nested paths are "added"
                     if let Value::Map(_) = rhs {
                         if atom.map.is_some() {
-                            self.collect_all_paths(rhs, type_ref, path.clone(), &mut comparison.added);
+                            self.collect_all_paths(rhs, type_ref, path.clone(), &mut out.comparison.added);
                         }
                     }
                 }
@@ -599,6 +715,15 @@ impl TypedValue {
         }
     }
 
+    /// Returns true if semantic scalar comparison is enabled and both values
+    /// denote the same scalar of the atom's type.
+    fn scalars_equivalent(&self, atom: &Atom, lhs: &Value, rhs: &Value) -> bool {
+        match atom.scalar {
+            Some(scalar) if self.schema.semantic_scalars() => scalars_equivalent(scalar, lhs, rhs),
+            _ => false,
+        }
+    }
+
     /// Collects all nested paths from a value into a set.
     fn collect_all_paths(
         &self,
@@ -637,6 +762,8 @@ impl TypedValue {
             }
             Value::List(items) => {
                 if let Some(ref list) = atom.list {
+                    let list = self.effective_list(list, &[items]);
+                    let list = list.as_ref();
                     if list.element_relationship == ElementRelationship::Atomic {
                         return; // Atomic lists are leaves
                     }
@@ -669,12 +796,15 @@ impl TypedValue {
         rhs: &Value,
         list: &crate::schema::List,
         path: Path,
-        comparison: &mut Comparison,
+        out: &mut CompareOutput<'_>,
     ) {
+        let list = self.effective_list(list, &[list_items(lhs), list_items(rhs)]);
+        let list = list.as_ref();
+
         // For atomic lists, compare as a whole
         if list.element_relationship == ElementRelationship::Atomic {
             if lhs != rhs {
-                comparison.modified.insert(&path);
+                out.modified(&path, lhs, rhs);
             }
             return;
         }
@@ -690,9 +820,9 @@ impl TypedValue {
             _ => return,
         };
 
-        // Build index maps for associative lists
-        let mut lhs_by_key = std::collections::HashMap::new();
-        let mut rhs_by_key = std::collections::HashMap::new();
+        // Build index maps for associative lists, reusing earlier ones
+        let mut lhs_by_key = out.scratch.take_map();
+        let mut rhs_by_key = out.scratch.take_map();
 
         for (i, item) in lhs_items.iter().enumerate() {
             let pe = if list.element_relationship == ElementRelationship::Associative {
@@ -709,7 +839,7 @@ impl TypedValue {
             } else {
                 PathElement::index(i as i32)
             };
-            lhs_by_key.insert(pe, item);
+            lhs_by_key.insert(pe, i);
         }
 
         for (i, item) in rhs_items.iter().enumerate() {
@@ -727,28 +857,30 @@ impl TypedValue {
             } else {
                 PathElement::index(i as i32)
             };
-            rhs_by_key.insert(pe, item);
+            rhs_by_key.insert(pe, i);
         }
 
         // Find removed items (in lhs but not rhs)
         for pe in lhs_by_key.keys() {
             if !rhs_by_key.contains_key(pe) {
-                comparison.removed.insert(&path.with(pe.clone()));
+                out.comparison.removed.insert(&path.with(pe.clone()));
             }
         }
 
         // Find added items (in rhs but not lhs) and modified items
-        for (pe, rhs_item) in &rhs_by_key {
+        for (pe, &j) in &rhs_by_key {
             match lhs_by_key.get(pe) {
                 None => {
-                    comparison.added.insert(&path.with(pe.clone()));
+                    out.comparison.added.insert(&path.with(pe.clone()));
                 }
-                Some(lhs_item) => {
+                Some(&i) => {
                     let item_path = path.with(pe.clone());
-                    self.compare_values(lhs_item, rhs_item, &list.element_type, item_path, comparison);
+                    self.compare_values(&lhs_items[i], &rhs_items[j], &list.element_type, item_path, out);
                 }
             }
         }
+        out.scratch.put_map(lhs_by_key);
+        out.scratch.put_map(rhs_by_key);
     }
 
     fn compare_maps(
@@ -757,12 +889,12 @@ impl TypedValue {
         rhs: &Value,
         map: &crate::schema::Map,
         path: Path,
-        comparison: &mut Comparison,
+        out: &mut CompareOutput<'_>,
     ) {
         // For atomic maps, compare as a whole
         if map.element_relationship == ElementRelationship::Atomic {
             if lhs != rhs {
-                comparison.modified.insert(&path);
+                out.modified(&path, lhs, rhs);
             }
             return;
         }
@@ -773,7 +905,7 @@ impl TypedValue {
 
         if lhs_is_null != rhs_is_null {
             // One is null and the other is not - this is a modification
-            comparison.modified.insert(&path);
+            out.modified(&path, lhs, rhs);
         }
 
         let lhs_fields = match lhs {
@@ -783,7 +915,7 @@ impl TypedValue {
                 if let Value::Map(rhs_map) = rhs {
                     for (key, _) in rhs_map.iter() {
                         let pe = PathElement::field_name(key.clone());
-                        comparison.added.insert(&path.with(pe));
+                        out.comparison.added.insert(&path.with(pe));
                     }
                 }
                 return;
@@ -796,23 +928,33 @@ impl TypedValue {
                 // lhs must be a map, so all its fields are removed
                 for (key, _) in lhs_fields.iter() {
                     let pe = PathElement::field_name(key.clone());
-                    comparison.removed.insert(&path.with(pe));
+                    out.comparison.removed.insert(&path.with(pe));
                 }
                 return;
             },
             _ => return,
         };
 
+        if leaf_fields(lhs_fields) && leaf_fields(rhs_fields) {
+            self.compare_leaf_maps(lhs_fields, rhs_fields, map, &path, out);
+            return;
+        }
+
         // Find removed fields
         for (key, _) in lhs_fields.iter() {
             if !rhs_fields.has(key) {
                 let pe = PathElement::field_name(key.clone());
-                comparison.removed.insert(&path.with(pe));
+                out.comparison.removed.insert(&path.with(pe));
             }
         }
 
         // Find added and modified fields
         for (key, rhs_val) in rhs_fields.iter() {
+            // Unchanged scalars can't contribute; skip them before building
+            // a path, which is most fields of ConfigMap-like objects
+            if is_leaf(rhs_val) && lhs_fields.get(key) == Some(rhs_val) {
+                continue;
+            }
             let pe = PathElement::field_name(key.clone());
             let field_path = path.with(pe);
 
@@ -824,15 +966,50 @@ impl TypedValue {
 
             match lhs_fields.get(key) {
                 None => {
-                    comparison.added.insert(&field_path);
+                    out.comparison.added.insert(&field_path);
                     // Recursively collect all nested paths from the added field
-                    self.collect_all_paths(rhs_val, &field_type, field_path, &mut comparison.added);
+                    self.collect_all_paths(rhs_val, &field_type, field_path, &mut out.comparison.added);
                 }
                 Some(lhs_val) => {
-                    self.compare_values(lhs_val, rhs_val, &field_type, field_path, comparison);
+                    self.compare_values(lhs_val, rhs_val, &field_type, field_path, out);
+                }
+            }
+        }
+    }
+
+    /// Compares maps whose fields are all scalars or nulls by walking their
+    /// field lists. Removed and added fields are inserted beneath the map in
+    /// one descent, and only fields whose values differ resolve their type.
+    fn compare_leaf_maps(
+        &self,
+        lhs: &Map,
+        rhs: &Map,
+        map: &crate::schema::Map,
+        path: &Path,
+        out: &mut CompareOutput<'_>,
+    ) {
+        let removed = lhs
+            .iter()
+            .filter(|(key, _)| !rhs.has(key))
+            .map(|(key, _)| PathElement::field_name(key.clone()))
+            .collect();
+        let mut added = Vec::new();
+        for (key, rhs_val) in rhs.iter() {
+            match lhs.get(key) {
+                None => added.push(PathElement::field_name(key.clone())),
+                Some(lhs_val) if lhs_val != rhs_val => {
+                    let field_type = match map.find_field(key) {
+                        Some(field) => &field.field_type,
+                        None => &map.element_type,
+                    };
+                    let field_path = path.with(PathElement::field_name(key.clone()));
+                    self.compare_values(lhs_val, rhs_val, field_type, field_path, out);
                 }
+                Some(_) => {}
             }
         }
+        out.comparison.removed.insert_members(path, removed);
+        out.comparison.added.insert_members(path, added);
     }
 
     /// Removes items from the value based on the provided set of paths.
@@ -868,22 +1045,13 @@ impl TypedValue {
             // Handle lists
             Value::List(values) => {
                 if let Some(ref list) = atom.list {
+                    let list = self.effective_list(list, &[values]);
+                    let list = list.as_ref();
                     let mut new_values = Vec::new();
-                    for (i, item) in values.iter().enumerate() {
-                        let pe = if list.element_relationship == ElementRelationship::Associative {
-                            if list.keys.is_empty() {
-                                // Set semantics - use the value as the path element
-                                PathElement::value(item.clone())
-                            } else {
-                                match self.list_item_to_key(item, list) {
-                                    Ok(key) => PathElement::Key(key),
-                                    Err(_) => PathElement::index(i as i32),
-                                }
-                            }
-                        } else {
-                            PathElement::index(i as i32)
-                        };
-                        let item_path = path.with(pe);
+                    let elements = self.list_item_elements(values, list);
+                    let duplicated = duplicated(&elements);
+                    for (i, (item, pe)) in values.iter().zip(elements).enumerate() {
+                        let item_path = item_path(&path, pe, i, duplicated[i], items);
 
                         if !items.has(&item_path) {
                             let new_item = self.remove_items_from_value(item, &list.element_type, items, item_path);
@@ -962,22 +1130,13 @@ impl TypedValue {
 
         // Handle lists
         if let (Some(ref list), Value::List(values)) = (&atom.list, value) {
+            let list = self.effective_list(list, &[values]);
+            let list = list.as_ref();
             let mut new_values = Vec::new();
-            for (i, item) in values.iter().enumerate() {
-                let pe = if list.element_relationship == ElementRelationship::Associative {
-                    if list.keys.is_empty() {
-                        // Set semantics - use the value as the path element
-                        PathElement::value(item.clone())
-                    } else {
-                        match self.list_item_to_key(item, list) {
-                            Ok(key) => PathElement::Key(key),
-                            Err(_) => PathElement::index(i as i32),
-                        }
-                    }
-                } else {
-                    PathElement::index(i as i32)
-                };
-                let item_path = path.with(pe);
+            let elements = self.list_item_elements(values, list);
+            let duplicated = duplicated(&elements);
+            for (i, (item, pe)) in values.iter().zip(elements).enumerate() {
+                let item_path = item_path(&path, pe, i, duplicated[i], items);
 
                 let new_item = self.extract_items_from_value(item, &list.element_type, items, item_path);
                 if !matches!(new_item, Value::Null) {
@@ -1020,7 +1179,8 @@ impl TypedValue {
     ///
     /// The merge strategy is "keep RHS" - if both lhs (self) and rhs have a value
     /// at the same path, the rhs value is used. For maps, fields are recursively
-    /// merged. For atomic lists/maps, they are replaced entirely.
+    /// merged. For atomic lists/maps, they are replaced entirely. Items of
+    /// associative lists are ordered as the schema's ListOrdering specifies.
     pub fn merge(&self, rhs: &TypedValue) -> Result<TypedValue, ValidationErrors> {
         if self.type_ref != rhs.type_ref {
             return Err(ValidationErrors::from_error(ValidationError::schema_error(
@@ -1038,6 +1198,9 @@ impl TypedValue {
     }
 
     fn merge_values(&self, lhs: &Value, rhs: &Value, type_ref: &TypeRef) -> Value {
+        #[cfg(feature = "profiling")]
+        let _timer = crate::profile::timer(crate::profile::Phase::Merge, type_ref, None);
+
         // If rhs is null, it means "delete/clear" - use null
         if matches!(rhs, Value::Null) {
             return Value::Null;
@@ -1067,6 +1230,8 @@ impl TypedValue {
             }
             (Value::List(lhs_items), Value::List(rhs_items)) => {
                 if let Some(ref list) = atom.list {
+                    let list = self.effective_list(list, &[lhs_items, rhs_items]);
+                    let list = list.as_ref();
                     if list.element_relationship == ElementRelationship::Atomic {
                         return rhs.clone();
                     }
@@ -1115,21 +1280,26 @@ impl TypedValue {
                 lhs_by_key.get(k).is_some_and(|v| v.len() > 1)
             });
             let rhs_is_proper_subset = rhs_subset_of_lhs && !lhs_subset_of_rhs;
+            let applied_first = self.schema.list_ordering() == ListOrdering::AppliedFirst;
 
-            if is_set && rhs_is_proper_subset && !lhs_has_rhs_duplicates {
+            if is_set && rhs_is_proper_subset && !lhs_has_rhs_duplicates && !applied_first {
                 // For sets: RHS ⊆ LHS with no duplicates to resolve - preserve LHS
                 Value::List(lhs.to_vec())
             } else {
-                // General case: items only in LHS first, then RHS items in RHS order
+                // General case: items only in LHS and RHS items in RHS order,
+                // in the order the schema asks for
                 let mut result: Vec<Value> = Vec::new();
 
-                // Add LHS items that are NOT in RHS (preserving order and duplicates)
-                for item in lhs {
-                    if let Ok(key) = self.list_item_to_key(item, list) {
-                        if !rhs_key_set.contains(&key) {
-                            result.push(item.clone());
-                        }
-                    }
+                // LHS items that are NOT in RHS (preserving order and duplicates)
+                let lhs_only: Vec<Value> = lhs
+                    .iter()
+                    .filter(|item| {
+                        self.list_item_to_key(item, list).is_ok_and(|key| !rhs_key_set.contains(&key))
+                    })
+                    .cloned()
+                    .collect();
+                if !applied_first {
+                    result.extend(lhs_only.iter().cloned());
                 }
 
                 // Add RHS items in RHS order
@@ -1152,32 +1322,65 @@ impl TypedValue {
                     }
                 }
 
+                if applied_first {
+                    result.extend(lhs_only);
+                }
                 Value::List(result)
             }
         } else {
-            // Non-associative lists - just use rhs entirely
-            Value::List(rhs.to_vec())
+            // Non-associative lists are merged element-wise by index; items only
+            // present in lhs are kept
+            let mut result = Vec::with_capacity(lhs.len().max(rhs.len()));
+            for i in 0..lhs.len().max(rhs.len()) {
+                match (lhs.get(i), rhs.get(i)) {
+                    (Some(l), Some(r)) => result.push(self.merge_values(l, r, &list.element_type)),
+                    (Some(l), None) => result.push(l.clone()),
+                    (None, Some(r)) => result.push(r.clone()),
+                    (None, None) => {}
+                }
+            }
+            Value::List(result)
         }
     }
 
     fn merge_maps(&self, lhs: &crate::value::Map, rhs: &crate::value::Map, map: &crate::schema::Map) -> Value {
-        let mut result = crate::value::Map::new();
+        let mut result = lhs.clone();
 
-        // Copy all lhs fields
-        for (key, val) in lhs.iter() {
-            result.set(key.clone(), val.clone());
+        // Scalars and nulls replace whatever lhs holds, so flat maps of
+        // scalars merge by overlaying the field lists
+        if leaf_fields(rhs) {
+            for (key, rhs_val) in rhs.iter() {
+                result.set(key.clone(), rhs_val.clone());
+            }
+            return Value::Map(result);
         }
 
         // Merge rhs fields
         for (key, rhs_val) in rhs.iter() {
-            let field_type = if let Some(field) = map.find_field(key) {
+            // Leaf fields of mixed maps are replaced without resolving a type
+            if is_leaf(rhs_val) {
+                result.set(key.clone(), rhs_val.clone());
+                continue;
+            }
+            let field = map.find_field(key);
+            let field_type = if let Some(field) = field {
                 field.field_type.clone()
             } else {
                 map.element_type.clone()
             };
 
             let new_val = if let Some(lhs_val) = lhs.get(key) {
-                self.merge_values(lhs_val, rhs_val, &field_type)
+                match (lhs_val, rhs_val) {
+                    (Value::Map(lhs_map), Value::Map(rhs_map)) if field.is_some_and(|f| f.retain_keys) => {
+                        // Only the keys present in rhs survive
+                        let mut retained = crate::value::Map::new();
+                        for (k, v) in lhs_map.iter().filter(|(k, _)| rhs_map.get(k).is_some()) {
+                            retained.set(k.clone(), v.clone());
+                        }
+                        self.merge_values(&Value::Map(retained), rhs_val, &field_type)
+                    }
+                    _ => self.merge_values(lhs_val, rhs_val, &field_type),
+                }
             } else {
                 rhs_val.clone()
             };
@@ -1187,6 +1390,199 @@ impl TypedValue {
         Value::Map(result)
     }
 
+    /// Returns the list schema to use for the given items.
+    ///
+    /// Atomic lists of the deduced schema become associative when every item
+    /// is a map with a distinct value for one of the schema's deduced list keys.
+    /// All sides of a binary operation must qualify for the same key.
+    /// Otherwise, with HeterogeneousLists::PerElement, they become sets when
+    /// a side mixes scalars, lists and maps.
+    pub(crate) fn effective_list<'a>(&self, list: &'a List, sides: &[&[Value]]) -> Cow<'a, List> {
+        if !is_deduced_atomic(list) || sides.iter().all(|items| items.is_empty()) {
+            return Cow::Borrowed(list);
+        }
+
+        let qualifies = |key: &String| {
+            sides.iter().all(|items| {
+                let mut seen = std::collections::HashSet::new();
+                items.iter().all(|item| match item.as_map().and_then(|m| m.get(key)) {
+                    Some(v) if !v.is_null() => seen.insert(v),
+                    _ => false,
+                })
+            })
+        };
+
+        match self.schema.deduced_list_keys().iter().find(|key| qualifies(key)) {
+            Some(key) => Cow::Owned(List {
+                element_type: TypeRef {
+                    named_type: Some("__untyped_deduced_".to_string()),
+                    ..Default::default()
+                },
+                element_relationship: ElementRelationship::Associative,
+                keys: vec![key.clone()],
+            }),
+            None if self.schema.heterogeneous_lists() == HeterogeneousLists::PerElement
+                && sides.iter().any(|items| is_heterogeneous(items)) =>
+            {
+                Cow::Owned(List {
+                    element_type: list.element_type.clone(),
+                    element_relationship: ElementRelationship::Associative,
+                    keys: Vec::new(),
+                })
+            }
+            None => Cow::Borrowed(list),
+        }
+    }
+
+    /// Returns the element at the given path, typed with its schema type.
+    ///
+    /// Field names address map entries, keys and values address items of
+    /// associative lists, and indexes address items of any list by position,
+    /// including atomic ones. Returns None if nothing exists at the path.
+    pub fn element_at(&self, path: &Path) -> Option<TypedValue> {
+        let mut value = &self.value;
+        let mut type_ref = self.type_ref.clone();
+
+        for pe in path {
+            let atom = self.schema.resolve(&type_ref)?;
+            match (pe, value) {
+                (PathElement::FieldName(name), Value::Map(fields)) => {
+                    let map = atom.map.as_ref()?;
+                    type_ref = match map.find_field(name) {
+                        Some(field) => field.field_type.clone(),
+                        None => map.element_type.clone(),
+                    };
+                    value = fields.get(name)?;
+                }
+                (PathElement::Index(i), Value::List(items)) => {
+                    let list = atom.list.as_ref()?;
+                    value = items.get(usize::try_from(*i).ok()?)?;
+                    type_ref = list.element_type.clone();
+                }
+                (PathElement::Key(key), Value::List(items)) => {
+                    let list = self.effective_list(atom.list.as_ref()?, &[items]);
+                    value = items
+                        .iter()
+                        .find(|item| self.list_item_to_key(item, &list).ok().as_ref() == Some(key))?;
+                    type_ref = list.element_type.clone();
+                }
+                (PathElement::Value(v), Value::List(items)) => {
+                    let list = atom.list.as_ref()?;
+                    value = items.iter().find(|item| *item == v)?;
+                    type_ref = list.element_type.clone();
+                }
+                _ => return None,
+            }
+        }
+
+        Some(TypedValue {
+            value: value.clone(),
+            type_ref,
+            schema: self.schema.clone(),
+        })
+    }
+
+    /// Returns the children of `value`, which has type `type_ref`, with their
+    /// path elements and types. Scalars and unresolvable types have none.
+    pub(crate) fn children<'v>(&self, value: &'v Value, type_ref: &TypeRef) -> Vec<(PathElement, &'v Value, TypeRef)> {
+        let Some(atom) = self.schema.resolve(type_ref) else {
+            return Vec::new();
+        };
+        match (value, atom.map.as_ref(), atom.list.as_ref()) {
+            (Value::Map(fields), Some(map), _) => fields
+                .iter()
+                .map(|(key, val)| {
+                    let field_type = match map.find_field(key) {
+                        Some(field) => field.field_type.clone(),
+                        None => map.element_type.clone(),
+                    };
+                    (PathElement::field_name(key.clone()), val, field_type)
+                })
+                .collect(),
+            (Value::List(items), _, Some(list)) => {
+                let list = self.effective_list(list, &[items]);
+                self.list_item_elements(items, &list)
+                    .into_iter()
+                    .zip(items)
+                    .map(|(pe, item)| (pe, item, list.element_type.clone()))
+                    .collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Returns a copy with values rewritten by `f`.
+    ///
+    /// `f` is called with the path of every value, parents before children.
+    /// Returning Some replaces the value and its subtree is not visited further.
+    pub fn transform_values<F>(&self, mut f: F) -> TypedValue
+    where
+        F: FnMut(&Path, &Value) -> Option<Value>,
+    {
+        TypedValue {
+            value: self.transform_value(&self.value, &self.type_ref, Path::new(), &mut f),
+            type_ref: self.type_ref.clone(),
+            schema: self.schema.clone(),
+        }
+    }
+
+    fn transform_value<F>(&self, value: &Value, type_ref: &TypeRef, path: Path, f: &mut F) -> Value
+    where
+        F: FnMut(&Path, &Value) -> Option<Value>,
+    {
+        if let Some(replaced) = f(&path, value) {
+            return replaced;
+        }
+        let atom = match self.schema.resolve(type_ref) {
+            Some(atom) => atom,
+            None => return value.clone(),
+        };
+
+        match value {
+            Value::Map(fields) => {
+                if let Some(ref map) = atom.map {
+                    let mut new_map = crate::value::Map::new();
+                    for (key, val) in fields.iter() {
+                        let field_type = match map.find_field(key) {
+                            Some(field) => &field.field_type,
+                            None => &map.element_type,
+                        };
+                        let field_path = path.with(PathElement::field_name(key.clone()));
+                        new_map.set(key.clone(), self.transform_value(val, field_type, field_path, f));
+                    }
+                    return Value::Map(new_map);
+                }
+            }
+            Value::List(items) => {
+                if let Some(ref list) = atom.list {
+                    let list = self.effective_list(list, &[items]);
+                    let list = list.as_ref();
+                    let new_items = items
+                        .iter()
+                        .enumerate()
+                        .map(|(i, item)| {
+                            let pe = if list.element_relationship != ElementRelationship::Associative {
+                                PathElement::index(i as i32)
+                            } else if list.keys.is_empty() {
+                                PathElement::value(item.clone())
+                            } else {
+                                match self.list_item_to_key(item, list) {
+                                    Ok(key) => PathElement::Key(key),
+                                    Err(_) => PathElement::index(i as i32),
+                                }
+                            };
+                            self.transform_value(item, &list.element_type, path.with(pe), f)
+                        })
+                        .collect();
+                    return Value::List(new_items);
+                }
+            }
+            _ => {}
+        }
+
+        value.clone()
+    }
+
     /// Creates an empty TypedValue with the same schema and type.
     pub fn empty(&self) -> TypedValue {
         TypedValue {
@@ -1197,6 +1593,109 @@ impl TypedValue {
     }
 }
 
+/// Returns the key of an associative list item, filling in schema defaults
+/// for missing key fields and canonicalizing numbers if the schema asks to.
+pub(crate) fn list_item_key(
+    schema: &Schema,
+    item: &Value,
+    list: &crate::schema::List,
+) -> Result<FieldList, ValidationError> {
+    if list.keys.is_empty() {
+        // Set semantics - use the value itself
+        return Ok(FieldList::with_fields(vec![Field {
+            name: String::new(),
+            value: item.clone(),
+        }]));
+    }
+
+    // Associative list - extract key fields
+    let map = match item {
+        Value::Map(m) => m,
+        _ => {
+            return Err(ValidationError::invalid_value(
+                "",
+                "expected map for associative list item",
+            ));
+        }
+    };
+
+    let mut fields = Vec::new();
+    for key_name in &list.keys {
+        match map.get(key_name) {
+            Some(v) => {
+                fields.push(Field {
+                    name: key_name.clone(),
+                    value: v.clone(),
+                });
+            }
+            None => {
+                // Try to get default value from schema
+                if let Some(default_val) = associative_key_default(schema, list, key_name) {
+                    fields.push(Field {
+                        name: key_name.clone(),
+                        value: default_val,
+                    });
+                }
+                // If no default, don't add this key to the list
+                // This allows partial keys where only some key fields have defaults
+            }
+        }
+    }
+
+    // If we have keys defined but couldn't find any key values (even with defaults),
+    // that's an error
+    if !list.keys.is_empty() && fields.is_empty() {
+        return Err(ValidationError::invalid_value(
+            "",
+            format!(
+                "associative list with keys has an element that omits all key fields {:?} (and doesn't have default values for any key fields)",
+                list.keys
+            ),
+        ));
+    }
+
+    if schema.numeric_keys() == NumericKeys::Integral {
+        for field in &mut fields {
+            field.value = canonical_numbers(&field.value);
+        }
+    }
+
+    Ok(FieldList::with_fields(fields))
+}
+
+/// Gets the default value for an associative list key field from the schema.
+fn associative_key_default(schema: &Schema, list: &crate::schema::List, field_name: &str) -> Option<Value> {
+    // Resolve the list's element type to get the map schema
+    let atom = schema.resolve(&list.element_type)?;
+    let map_schema = atom.map.as_ref()?;
+
+    // Find the field in the map schema
+    let field = map_schema.find_field(field_name)?;
+
+    // Return the default value if it exists, converting from serde_json::Value to our Value
+    field.default.as_ref().map(json_value_to_value)
+}
+
+fn list_items(v: &Value) -> &[Value] {
+    match v {
+        Value::List(items) => items,
+        _ => &[],
+    }
+}
+
+/// Reports a field value over its schema's size limits.
+pub(crate) fn check_size_limits(field: &crate::schema::StructField, value: &Value, path: &Path, errors: &mut ValidationErrors) {
+    let (max, actual, unit) = match value {
+        Value::String(s) => (field.max_length, s.chars().count(), "characters"),
+        Value::List(items) => (field.max_items, items.len(), "items"),
+        Value::Map(entries) => (field.max_properties, entries.len(), "entries"),
+        _ => return,
+    };
+    if let Some(max) = max.filter(|max| actual > *max) {
+        errors.add(ValidationError::too_large(format!("{}", path), unit, max, actual));
+    }
+}
+
 fn value_type_name(v: &Value) -> &'static str {
     match v {
         Value::Null => "null",
@@ -1209,6 +1708,16 @@ fn value_type_name(v: &Value) -> &'static str {
     }
 }
 
+/// Returns true for values without children: scalars and null.
+fn is_leaf(value: &Value) -> bool {
+    !value.is_map() && !value.is_list()
+}
+
+/// Returns true for maps whose fields are all leaves.
+fn leaf_fields(fields: &Map) -> bool {
+    fields.iter().all(|(_, value)| is_leaf(value))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1269,6 +1778,244 @@ mod tests {
         assert!(comparison.is_same());
     }
 
+    #[test]
+    fn test_flat_map_merge_compare() {
+        let pt = crate::typed::deduced_parseable_type();
+        let lhs = pt.from_yaml(r#"{"a": "1", "b": "2", "c": {"x": 1}, "d": "4"}"#).unwrap();
+        let rhs = pt.from_yaml(r#"{"a": "1", "b": "3", "c": "flat", "d": null, "e": true}"#).unwrap();
+
+        let merged = lhs.merge(&rhs).unwrap();
+        assert_eq!(
+            crate::value::to_json(merged.value()).unwrap(),
+            r#"{"a":"1","b":"3","c":"flat","d":null,"e":true}"#
+        );
+
+        let comparison = lhs.compare(&rhs).unwrap();
+        let paths = |set: &Set| {
+            let mut paths = Vec::new();
+            set.iterate(|path| paths.push(path.to_string()));
+            paths.sort();
+            paths
+        };
+        assert_eq!(paths(&comparison.modified), [".b", ".c", ".d"]);
+        assert_eq!(paths(&comparison.added), [".e"]);
+        assert_eq!(paths(&comparison.removed), [".c.x"]);
+    }
+
+    #[test]
+    fn test_leaf_map_branch() {
+        let pt = crate::typed::Parser::new(
+            r#"types:
+- name: root
+  map:
+    fields:
+    - name: a
+      type:
+        namedType: str
+    - name: b
+      type:
+        namedType: str
+    - name: c
+      type:
+        namedType: str
+- name: str
+  scalar: string
+"#,
+        )
+        .unwrap()
+        .type_by_name("root");
+        let lhs = pt.from_yaml(r#"{"a": "1", "b": "2"}"#).unwrap();
+        let rhs = pt.from_yaml(r#"{"b": "3", "c": null}"#).unwrap();
+
+        let merged = lhs.merge(&rhs).unwrap();
+        assert_eq!(crate::value::to_json(merged.value()).unwrap(), r#"{"a":"1","b":"3","c":null}"#);
+
+        // The leaf-only branch overlays the field lists without visiting
+        // the field types
+        #[cfg(feature = "profiling")]
+        {
+            let profiling = crate::profile::start();
+            lhs.merge(&rhs).unwrap();
+            let visited: Vec<String> = profiling.finish().top_types(10).into_iter().map(|spot| spot.name).collect();
+            assert!(visited.iter().any(|name| name == "root"), "{:?}", visited);
+            assert!(!visited.iter().any(|name| name == "str"), "{:?}", visited);
+        }
+
+        let paths = |set: &Set| {
+            let mut paths = Vec::new();
+            set.iterate(|path| paths.push(path.to_string()));
+            paths
+        };
+        let comparison = lhs.compare(&rhs).unwrap();
+        assert_eq!(paths(&comparison.removed), [".a"]);
+        assert_eq!(paths(&comparison.added), [".c"]);
+        assert_eq!(paths(&comparison.modified), [".b"]);
+        assert_eq!(paths(&merged.to_field_set().unwrap()), [".a", ".b", ".c"]);
+    }
+
+    #[test]
+    fn test_duplicate_key_indexes() {
+        let pt = crate::typed::Parser::new(
+            r#"types:
+- name: root
+  map:
+    fields:
+    - name: ports
+      type:
+        list:
+          elementType:
+            map:
+              fields:
+              - name: port
+                type:
+                  scalar: numeric
+              - name: protocol
+                type:
+                  scalar: string
+          elementRelationship: associative
+          keys: [port, protocol]
+"#,
+        )
+        .unwrap()
+        .type_by_name("root");
+        let yaml = r#"{"ports": [
+            {"port": 80, "protocol": "TCP"},
+            {"port": 443, "protocol": "TCP"},
+            {"port": 80, "protocol": "UDP"},
+            {"port": 80, "protocol": "TCP"},
+            {"port": 443, "protocol": "TCP"},
+            {"port": 80, "protocol": "TCP"}
+        ]}"#;
+        let errors = pt.from_yaml_with_opts(yaml, &[ValidationOption::AllowDuplicates]).unwrap().validate(&[]).unwrap_err();
+        let duplicates: Vec<(String, String, Vec<usize>)> = errors
+            .iter()
+            .filter_map(|e| match e {
+                ValidationError::DuplicateKey { path, key, indexes } => Some((path.clone(), key.clone(), indexes.clone())),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            duplicates,
+            [
+                (".ports".to_string(), r#"[port=80,protocol="TCP"]"#.to_string(), vec![0, 3, 5]),
+                (".ports".to_string(), r#"[port=443,protocol="TCP"]"#.to_string(), vec![1, 4]),
+            ]
+        );
+        assert_eq!(
+            errors.iter().next().unwrap().to_string(),
+            r#".ports: duplicate key in list: [port=80,protocol="TCP"] at indexes [0, 3, 5]"#
+        );
+    }
+
+    const INDEXED_LIST_SCHEMA: &str = r#"types:
+- name: root
+  map:
+    fields:
+    - name: items
+      type:
+        list:
+          elementType:
+            namedType: item
+          elementRelationship: separable
+    - name: tags
+      type:
+        list:
+          elementType:
+            scalar: string
+          elementRelationship: atomic
+- name: item
+  map:
+    fields:
+    - name: a
+      type:
+        scalar: numeric
+    - name: b
+      type:
+        scalar: numeric
+"#;
+
+    #[test]
+    fn test_index_path_elements() {
+        use crate::typed::Parser;
+
+        let parser = Parser::new(INDEXED_LIST_SCHEMA).unwrap();
+        let pt = parser.type_by_name("root");
+        let lhs = pt.from_yaml(r#"{"items": [{"a": 1}, {"a": 2}], "tags": ["x", "y"]}"#).unwrap();
+        let rhs = pt.from_yaml(r#"{"items": [{"b": 3}]}"#).unwrap();
+
+        let item = |i| Path::from_elements(vec![PathElement::field_name("items"), PathElement::index(i)]);
+
+        // Field sets address separable list items by index, and survive FieldsV1
+        let set = lhs.to_field_set().unwrap();
+        assert!(set.has(&item(1).with(PathElement::field_name("a"))));
+        assert_eq!(Set::from_json(&set.to_json().unwrap()).unwrap(), set);
+
+        // Merge is element-wise and keeps trailing lhs items
+        let merged = lhs.merge(&rhs).unwrap();
+        let first = merged.element_at(&item(0)).unwrap();
+        let item_type = parser.type_by_name("item");
+        assert_eq!(first.value(), item_type.from_yaml(r#"{"a": 1, "b": 3}"#).unwrap().value());
+        assert_eq!(merged.element_at(&item(1)).unwrap().value(), lhs.element_at(&item(1)).unwrap().value());
+        assert!(merged.element_at(&item(2)).is_none());
+
+        // Indexes also address items of atomic lists
+        let tag = Path::from_elements(vec![PathElement::field_name("tags"), PathElement::index(1)]);
+        assert_eq!(lhs.element_at(&tag).unwrap().value(), &Value::String("y".into()));
+
+        // Extracting an index keeps only that item
+        let mut wanted = Set::new();
+        wanted.insert(&item(1));
+        let extracted = lhs.extract_items(&wanted);
+        assert_eq!(extracted.value(), pt.from_yaml(r#"{"items": [{"a": 2}]}"#).unwrap().value());
+    }
+
+    #[test]
+    fn test_validate_size_limits() {
+        let parser = crate::typed::Parser::new(
+            r#"types:
+- name: root
+  map:
+    fields:
+    - name: name
+      type:
+        scalar: string
+      maxLength: 3
+    - name: args
+      type:
+        list:
+          elementType:
+            scalar: string
+          elementRelationship: atomic
+      maxItems: 1
+    - name: labels
+      type:
+        map:
+          elementType:
+            scalar: string
+      maxProperties: 1
+"#,
+        )
+        .unwrap();
+        let pt = parser.type_by_name("root");
+
+        assert!(pt.from_yaml(r#"{"name": "äbc", "args": ["a"], "labels": {"a": "b"}}"#).is_ok());
+
+        let tv = as_typed_unvalidated(
+            serde_yaml::from_str(r#"{"name": "abcd", "args": ["a", "b"], "labels": {"a": "b", "c": "d"}}"#).unwrap(),
+            &pt.schema,
+            pt.type_ref.clone(),
+        );
+        let errors: Vec<String> = tv.validate(&[]).unwrap_err().iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            errors,
+            vec![
+                ".args: has 2 items, more than the maximum of 1",
+                ".labels: has 2 entries, more than the maximum of 1",
+                ".name: has 4 characters, more than the maximum of 3",
+            ]
+        );
+    }
+
     #[test]
     fn test_validate_scalar() {
         let schema = Schema::with_types(vec![TypeDef {
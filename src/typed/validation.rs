@@ -1,42 +1,104 @@
 //! Validation types and errors.
 
+use crate::messages::{render, Message, MessageCatalog};
 use std::fmt;
-use thiserror::Error;
 
 /// ValidationOptions controls validation behavior.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum ValidationOption {
     /// Allow duplicate items in sets and associative lists.
     AllowDuplicates,
 }
 
+/// FieldValidation controls how unknown fields and duplicate keys are
+/// handled while parsing, like the API server's field validation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum FieldValidation {
+    /// Drop unknown fields and keep duplicates without reporting them.
+    Ignore,
+    /// Drop unknown fields and keep duplicates, reporting both as warnings.
+    Warn,
+    /// Fail on unknown fields and duplicate keys.
+    #[default]
+    Strict,
+}
+
 /// ValidationError represents an error during schema validation.
-#[derive(Debug, Clone, Error)]
+///
+/// Errors display their messages in English; see localized for others.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
 pub enum ValidationError {
-    #[error("{path}: type mismatch: expected {expected}, got {actual}")]
     TypeMismatch {
         path: String,
         expected: String,
         actual: String,
     },
 
-    #[error("{path}: unknown field: {field}")]
     UnknownField { path: String, field: String },
 
-    #[error("{path}: missing required field: {field}")]
     MissingField { path: String, field: String },
 
-    #[error("{path}: duplicate key in list: {key}")]
-    DuplicateKey { path: String, key: String },
+    /// Several items of an associative list share a key. `key` is the key
+    /// as rendered in paths and `indexes` the positions of every item with
+    /// it, ascending.
+    DuplicateKey {
+        path: String,
+        key: String,
+        indexes: Vec<usize>,
+    },
 
-    #[error("{path}: {message}")]
     InvalidValue { path: String, message: String },
 
-    #[error("{message}")]
+    TooLarge {
+        path: String,
+        unit: String,
+        max: usize,
+        actual: usize,
+    },
+
     SchemaError { message: String },
 }
 
 impl ValidationError {
+    /// Returns true for errors that FieldValidation can relax: unknown
+    /// fields and duplicate keys.
+    pub fn is_field_error(&self) -> bool {
+        matches!(self, ValidationError::UnknownField { .. } | ValidationError::DuplicateKey { .. })
+    }
+
+    /// Returns the message of the error.
+    pub fn message(&self) -> Message<'_> {
+        match self {
+            ValidationError::TypeMismatch { path, expected, actual } => {
+                Message::TypeMismatch { path, expected, actual }
+            }
+            ValidationError::UnknownField { path, field } => Message::UnknownField { path, field },
+            ValidationError::MissingField { path, field } => Message::MissingField { path, field },
+            ValidationError::DuplicateKey { path, key, indexes } => Message::DuplicateKey { path, key, indexes },
+            ValidationError::InvalidValue { path, message } => Message::InvalidValue { path, message },
+            ValidationError::TooLarge {
+                path,
+                unit,
+                max,
+                actual,
+            } => Message::TooLarge {
+                path,
+                unit,
+                max: *max,
+                actual: *actual,
+            },
+            ValidationError::SchemaError { message } => Message::SchemaError { message },
+        }
+    }
+
+    /// Returns the message of the error as `catalog` words it.
+    pub fn localized(&self, catalog: &dyn MessageCatalog) -> String {
+        render(catalog, &self.message())
+    }
+
     /// Creates a type mismatch error.
     pub fn type_mismatch(path: impl Into<String>, expected: impl Into<String>, actual: impl Into<String>) -> Self {
         ValidationError::TypeMismatch {
@@ -62,11 +124,12 @@ impl ValidationError {
         }
     }
 
-    /// Creates a duplicate key error.
-    pub fn duplicate_key(path: impl Into<String>, key: impl Into<String>) -> Self {
+    /// Creates a duplicate key error for the items at `indexes`.
+    pub fn duplicate_key(path: impl Into<String>, key: impl Into<String>, indexes: Vec<usize>) -> Self {
         ValidationError::DuplicateKey {
             path: path.into(),
             key: key.into(),
+            indexes,
         }
     }
 
@@ -78,6 +141,16 @@ impl ValidationError {
         }
     }
 
+    /// Creates an error for a value with more than `max` units.
+    pub fn too_large(path: impl Into<String>, unit: impl Into<String>, max: usize, actual: usize) -> Self {
+        ValidationError::TooLarge {
+            path: path.into(),
+            unit: unit.into(),
+            max,
+            actual,
+        }
+    }
+
     /// Creates a schema error.
     pub fn schema_error(message: impl Into<String>) -> Self {
         ValidationError::SchemaError {
@@ -86,6 +159,14 @@ impl ValidationError {
     }
 }
 
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message().english())
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
 /// ValidationErrors is a collection of validation errors.
 #[derive(Debug, Clone, Default)]
 pub struct ValidationErrors {
@@ -129,6 +210,13 @@ impl ValidationErrors {
     pub fn iter(&self) -> impl Iterator<Item = &ValidationError> {
         self.errors.iter()
     }
+
+    /// Returns the messages of the errors as `catalog` words them, one per
+    /// line.
+    pub fn localized(&self, catalog: &dyn MessageCatalog) -> String {
+        let messages: Vec<String> = self.errors.iter().map(|err| err.localized(catalog)).collect();
+        messages.join("\n")
+    }
 }
 
 impl IntoIterator for ValidationErrors {
@@ -0,0 +1,320 @@
+//! Handling of YAML anchors, aliases and merge keys.
+
+#![cfg_attr(not(test), deny(clippy::indexing_slicing, clippy::string_slice))]
+
+use super::limits::{check_bytes, read_yaml, InputChecks, ParseLimits};
+use super::parser::{ParseError, ParseableType};
+use super::typed_value::{as_typed, TypedValue};
+use crate::value::{Map, Value};
+
+/// The key of a YAML merge key entry.
+pub const MERGE_KEY: &str = "<<";
+
+/// AliasPolicy says what parsing does with YAML aliases.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum AliasPolicy {
+    /// Aliases are replaced by a copy of their anchored node. Every copy
+    /// counts against the node limit.
+    #[default]
+    Expand,
+    /// Documents with aliases are rejected. Anchors alone are accepted.
+    Reject,
+}
+
+/// MergeKeyPolicy says what parsing does with `<<` keys in maps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum MergeKeyPolicy {
+    /// `<<` is an ordinary key, as from_yaml treats it.
+    #[default]
+    Literal,
+    /// The entries of the map, or list of maps, under `<<` are merged into
+    /// the enclosing map. Keys of the enclosing map win over merged ones,
+    /// and earlier maps in a list win over later ones.
+    Expand,
+    /// Documents with `<<` keys are rejected.
+    Reject,
+}
+
+/// YamlPolicy is the handling of YAML features that make one node appear
+/// in several places, which matters for field ownership: every copy of an
+/// anchored node is owned separately.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct YamlPolicy {
+    pub aliases: AliasPolicy,
+    pub merge_keys: MergeKeyPolicy,
+}
+
+impl YamlPolicy {
+    /// Rejects aliases and merge keys, so every field is written out where
+    /// it applies.
+    pub fn strict() -> Self {
+        YamlPolicy {
+            aliases: AliasPolicy::Reject,
+            merge_keys: MergeKeyPolicy::Reject,
+        }
+    }
+}
+
+impl ParseableType {
+    /// Parses a YAML string into a TypedValue, handling aliases and merge
+    /// keys as `policy` says, within the given limits.
+    pub fn from_yaml_with_policy(
+        &self,
+        yaml: &str,
+        policy: &YamlPolicy,
+        limits: &ParseLimits,
+    ) -> Result<TypedValue, ParseError> {
+        let value = read_yaml_with_policy(yaml, policy, limits, InputChecks::default())?;
+        as_typed(value, &self.schema, self.type_ref.clone(), &[])
+            .map_err(|e| ParseError::new(format!("validation failed: {}", e)))
+    }
+}
+
+/// Reads a YAML document within the given limits, handling aliases and
+/// merge keys as `policy` says.
+pub(super) fn read_yaml_with_policy(
+    yaml: &str,
+    policy: &YamlPolicy,
+    limits: &ParseLimits,
+    checks: InputChecks,
+) -> Result<Value, ParseError> {
+    if policy.aliases == AliasPolicy::Reject {
+        check_bytes(yaml, limits)?;
+        if let Some((anchor, line)) = first_alias(yaml) {
+            return Err(ParseError::new(format!(
+                "YAML aliases are not allowed: *{} at line {}",
+                anchor, line
+            )));
+        }
+    }
+    let mut value = read_yaml(yaml, limits, checks)?;
+    match policy.merge_keys {
+        MergeKeyPolicy::Literal => {}
+        MergeKeyPolicy::Expand => expand_merge_keys(&mut value)?,
+        MergeKeyPolicy::Reject => {
+            if has_merge_key(&value) {
+                return Err(ParseError::new("YAML merge keys are not allowed"));
+            }
+        }
+    }
+    Ok(value)
+}
+
+/// Returns the name and line of the first alias in the document, found by
+/// scanning its text before anything is expanded. Aliases are `*` where a
+/// node starts, outside of quoted and block scalars and comments. Lines
+/// continuing a plain scalar with `*` count too, so the scan errs on the
+/// side of finding aliases.
+fn first_alias(yaml: &str) -> Option<(String, usize)> {
+    let is_space = |c: Option<&u8>| matches!(c, None | Some(b' ' | b'\t' | b'\r'));
+    let is_anchor_char = |c: &u8| !matches!(c, b' ' | b'\t' | b'\r' | b',' | b'[' | b']' | b'{' | b'}');
+    // Lines indented more than this are the content of a block scalar
+    let mut block_indent: Option<usize> = None;
+    // The quote of a quoted scalar continuing on the next line
+    let mut quote: Option<u8> = None;
+    let mut flow_depth = 0usize;
+
+    for (i, line) in yaml.lines().enumerate() {
+        let b = line.as_bytes();
+        let indent = b.iter().take_while(|c| **c == b' ').count();
+        if let Some(n) = block_indent {
+            if line.trim().is_empty() || indent > n {
+                continue;
+            }
+            block_indent = None;
+        }
+        let mut j = 0;
+        let mut at_node = quote.is_none();
+        // The column of the node whose value may be a block scalar
+        let mut node_col = indent;
+        if (line.starts_with("---") || line.starts_with("...")) && is_space(b.get(3)) {
+            j = 3;
+        }
+        while let Some(&c) = b.get(j) {
+            let after = b.get(j + 1..).unwrap_or_default();
+            if let Some(q) = quote {
+                match c {
+                    b'\\' if q == b'"' => j += 1,
+                    b'\'' if q == b'\'' && b.get(j + 1) == Some(&b'\'') => j += 1,
+                    _ if c == q => quote = None,
+                    _ => {}
+                }
+                j += 1;
+                continue;
+            }
+            if is_space(Some(&c)) {
+                j += 1;
+                continue;
+            }
+            if c == b'#' && (j == 0 || is_space(b.get(j - 1))) {
+                break;
+            }
+            let ends_indicator = is_space(b.get(j + 1));
+            if at_node {
+                match c {
+                    b'*' => {
+                        let len = after.iter().take_while(|c| is_anchor_char(c)).count();
+                        if let Some(alias) = after.get(..len).filter(|alias| !alias.is_empty()) {
+                            return Some((String::from_utf8_lossy(alias).into_owned(), i + 1));
+                        }
+                    }
+                    b'"' | b'\'' => {
+                        quote = Some(c);
+                        at_node = false;
+                    }
+                    b'|' | b'>' if flow_depth == 0 => {
+                        block_indent = Some(node_col);
+                        break;
+                    }
+                    b'&' | b'!' => {
+                        // Properties come before the node they belong to
+                        j += 1 + after.iter().take_while(|c| !is_space(Some(c))).count();
+                        continue;
+                    }
+                    b'-' | b'?' if ends_indicator && flow_depth == 0 => {
+                        node_col = j + 1 + after.iter().take_while(|c| **c == b' ').count();
+                    }
+                    b':' if ends_indicator || flow_depth > 0 => {}
+                    b'[' | b'{' => flow_depth += 1,
+                    b']' | b'}' => {
+                        flow_depth = flow_depth.saturating_sub(1);
+                        at_node = false;
+                    }
+                    b',' if flow_depth > 0 => {}
+                    _ => at_node = false,
+                }
+                j += 1;
+                continue;
+            }
+            match c {
+                b':' if ends_indicator || flow_depth > 0 => at_node = true,
+                b',' if flow_depth > 0 => at_node = true,
+                b']' | b'}' if flow_depth > 0 => flow_depth -= 1,
+                _ => {}
+            }
+            j += 1;
+        }
+    }
+    None
+}
+
+fn has_merge_key(value: &Value) -> bool {
+    match value {
+        Value::Map(fields) => fields.get(MERGE_KEY).is_some() || fields.iter().any(|(_, v)| has_merge_key(v)),
+        Value::List(items) => items.iter().any(has_merge_key),
+        _ => false,
+    }
+}
+
+fn expand_merge_keys(value: &mut Value) -> Result<(), ParseError> {
+    match value {
+        Value::Map(fields) => {
+            if let Some(merged) = fields.delete(MERGE_KEY) {
+                let sources = match merged {
+                    Value::Map(source) => vec![source],
+                    Value::List(items) => items
+                        .into_iter()
+                        .map(|item| match item {
+                            Value::Map(source) => Ok(source),
+                            _ => Err(ParseError::new("YAML merge key lists must only hold maps")),
+                        })
+                        .collect::<Result<Vec<Map>, _>>()?,
+                    _ => return Err(ParseError::new("YAML merge keys must refer to a map or list of maps")),
+                };
+                for source in sources {
+                    for (key, val) in source.fields {
+                        if fields.get(&key).is_none() {
+                            fields.set(key, val);
+                        }
+                    }
+                }
+            }
+            for (_, val) in fields.fields.iter_mut() {
+                expand_merge_keys(val)?;
+            }
+        }
+        Value::List(items) => {
+            for item in items {
+                expand_merge_keys(item)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::typed::deduced_parseable_type;
+
+    const YAML: &str = "base: &base {a: 1, b: 2}\nderived:\n  <<: *base\n  b: 3\n";
+
+    #[test]
+    fn test_yaml_policy() {
+        let pt = deduced_parseable_type();
+        let limits = ParseLimits::default();
+        let json = |tv: TypedValue| crate::value::to_json(tv.value()).unwrap();
+
+        // By default aliases expand and merge keys are kept as they are
+        let tv = pt.from_yaml_with_policy(YAML, &YamlPolicy::default(), &limits).unwrap();
+        assert_eq!(json(tv), r#"{"base":{"a":1,"b":2},"derived":{"<<":{"a":1,"b":2},"b":3}}"#);
+        assert_eq!(json(pt.from_yaml(YAML).unwrap()), r#"{"base":{"a":1,"b":2},"derived":{"<<":{"a":1,"b":2},"b":3}}"#);
+
+        let expand = YamlPolicy {
+            merge_keys: MergeKeyPolicy::Expand,
+            ..Default::default()
+        };
+        let tv = pt.from_yaml_with_policy(YAML, &expand, &limits).unwrap();
+        assert_eq!(json(tv), r#"{"base":{"a":1,"b":2},"derived":{"a":1,"b":3}}"#);
+        let lists = "x: &x {a: 1}\ny: &y {a: 2, b: 2}\nz:\n  <<: [*x, *y]\n";
+        let tv = pt.from_yaml_with_policy(lists, &expand, &limits).unwrap();
+        assert_eq!(json(tv), r#"{"x":{"a":1},"y":{"a":2,"b":2},"z":{"a":1,"b":2}}"#);
+        assert!(pt.from_yaml_with_policy("a: {<<: 1}", &expand, &limits).is_err());
+
+        let err = pt.from_yaml_with_policy(YAML, &YamlPolicy::strict(), &limits).unwrap_err();
+        assert_eq!(err.message, "YAML aliases are not allowed: *base at line 3");
+        let reject_merge = YamlPolicy {
+            merge_keys: MergeKeyPolicy::Reject,
+            ..Default::default()
+        };
+        let err = pt.from_yaml_with_policy(YAML, &reject_merge, &limits).unwrap_err();
+        assert_eq!(err.message, "YAML merge keys are not allowed");
+
+        // Anchors without aliases and "*" in strings are fine
+        let plain = "a: &x 1\nb: \"*x\"\nc: 2 * 3\n";
+        assert!(pt.from_yaml_with_policy(plain, &YamlPolicy::strict(), &limits).is_ok());
+        for plain in [
+            "a: 'it''s *x'\nb: \"\\\" *x\"\n",
+            "a: |\n  *x\n  - *y\nb: >-\n  *z\n",
+            "- a: |\n    *x\n  b: 1\n",
+            "a: 1 # *x\n# *y\n",
+            "a: \"multi\n  *line\"\n",
+            "a: [x*, \"*y\"]\n",
+        ] {
+            assert_eq!(first_alias(plain), None, "{}", plain);
+        }
+        for (aliased, anchor, line) in [
+            ("a: &x 1\nb: *x\n", "x", 2),
+            ("a: &x 1\nb: [1, *x]\n", "x", 2),
+            ("a: &x 1\nb: {c: *x}\n", "x", 2),
+            ("- &x 1\n- *x\n", "x", 2),
+            ("a: |\n  text\nb: *x # comment\n", "x", 3),
+            ("- a: |\n    text\n  b: *x\n", "x", 3),
+            ("--- *x\n", "x", 1),
+        ] {
+            assert_eq!(first_alias(aliased), Some((anchor.to_string(), line)), "{}", aliased);
+        }
+
+        // Aliases are rejected before they are expanded
+        let mut laughs = "a: &a [x, x, x, x, x, x, x, x, x, x]\n".to_string();
+        for (name, prev) in ["b", "c", "d", "e", "f", "g"].iter().zip(["a", "b", "c", "d", "e", "f"]) {
+            let items = vec![format!("*{}", prev); 10].join(", ");
+            laughs.push_str(&format!("{}: &{} [{}]\n", name, name, items));
+        }
+        let err = pt.from_yaml_with_policy(&laughs, &YamlPolicy::strict(), &ParseLimits::untrusted()).unwrap_err();
+        assert_eq!(err.message, "YAML aliases are not allowed: *a at line 2");
+    }
+}
@@ -2,7 +2,11 @@
 //!
 //! This module provides type-aware operations on values.
 
+mod serializer;
+mod shared;
 #[allow(clippy::module_inception)]
 mod value;
 
+pub use serializer::*;
+pub use shared::*;
 pub use value::*;
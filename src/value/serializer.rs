@@ -0,0 +1,419 @@
+//! Conversion of serializable data, such as the document trees of other
+//! JSON libraries, into Values.
+
+use super::{Map, Value};
+use serde::ser::{self, Serialize};
+use std::fmt;
+
+/// ToValueError is returned when data has no Value representation, such as
+/// maps with non-string keys or non-finite floats.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToValueError {
+    pub message: String,
+}
+
+impl fmt::Display for ToValueError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ToValueError {}
+
+impl ser::Error for ToValueError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        ToValueError {
+            message: msg.to_string(),
+        }
+    }
+}
+
+/// Converts serializable data into a Value directly, without going through
+/// text or serde_json. This lets the document trees of other JSON
+/// libraries, such as simd_json's OwnedValue or ijson's IValue, feed
+/// Values, as they implement Serialize.
+///
+/// Numbers become ints if they fit in an i64 and floats otherwise, like
+/// numbers parsed from JSON. Enum variants are represented like serde_json
+/// represents them.
+pub fn to_value<T: Serialize + ?Sized>(data: &T) -> Result<Value, ToValueError> {
+    data.serialize(ValueSerializer)
+}
+
+fn error(message: impl Into<String>) -> ToValueError {
+    ToValueError {
+        message: message.into(),
+    }
+}
+
+fn float(f: f64) -> Result<Value, ToValueError> {
+    if f.is_finite() {
+        Ok(Value::Float(f))
+    } else {
+        Err(error(format!("non-finite float {} has no value", f)))
+    }
+}
+
+/// Wraps a value in a single-entry map keyed by an enum variant.
+fn variant(name: &str, value: Value) -> Value {
+    let mut map = Map::new();
+    map.set(name.to_string(), value);
+    Value::Map(map)
+}
+
+struct ValueSerializer;
+
+impl ser::Serializer for ValueSerializer {
+    type Ok = Value;
+    type Error = ToValueError;
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = SeqSerializer;
+    type SerializeTupleStruct = SeqSerializer;
+    type SerializeTupleVariant = SeqSerializer;
+    type SerializeMap = MapSerializer;
+    type SerializeStruct = MapSerializer;
+    type SerializeStructVariant = MapSerializer;
+
+    fn serialize_bool(self, v: bool) -> Result<Value, ToValueError> {
+        Ok(Value::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Value, ToValueError> {
+        Ok(Value::Int(v.into()))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Value, ToValueError> {
+        Ok(Value::Int(v.into()))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Value, ToValueError> {
+        Ok(Value::Int(v.into()))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Value, ToValueError> {
+        Ok(Value::Int(v))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Value, ToValueError> {
+        Ok(i64::try_from(v).map_or(Value::Float(v as f64), Value::Int))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Value, ToValueError> {
+        Ok(Value::Int(v.into()))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Value, ToValueError> {
+        Ok(Value::Int(v.into()))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Value, ToValueError> {
+        Ok(Value::Int(v.into()))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Value, ToValueError> {
+        Ok(i64::try_from(v).map_or(Value::Float(v as f64), Value::Int))
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Value, ToValueError> {
+        Ok(i64::try_from(v).map_or(Value::Float(v as f64), Value::Int))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Value, ToValueError> {
+        float(v.into())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Value, ToValueError> {
+        float(v)
+    }
+
+    fn serialize_char(self, v: char) -> Result<Value, ToValueError> {
+        Ok(Value::String(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Value, ToValueError> {
+        Ok(Value::String(v.to_string()))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value, ToValueError> {
+        Ok(Value::List(v.iter().map(|b| Value::Int((*b).into())).collect()))
+    }
+
+    fn serialize_none(self) -> Result<Value, ToValueError> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Value, ToValueError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Value, ToValueError> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value, ToValueError> {
+        Ok(Value::Null)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Value, ToValueError> {
+        Ok(Value::String(variant.to_string()))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Value, ToValueError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Value, ToValueError> {
+        Ok(variant(name, value.serialize(self)?))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqSerializer, ToValueError> {
+        Ok(SeqSerializer {
+            variant: None,
+            items: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqSerializer, ToValueError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<SeqSerializer, ToValueError> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        name: &'static str,
+        len: usize,
+    ) -> Result<SeqSerializer, ToValueError> {
+        Ok(SeqSerializer {
+            variant: Some(name),
+            items: Vec::with_capacity(len),
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<MapSerializer, ToValueError> {
+        Ok(MapSerializer {
+            variant: None,
+            map: Map::new(),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<MapSerializer, ToValueError> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        name: &'static str,
+        _len: usize,
+    ) -> Result<MapSerializer, ToValueError> {
+        Ok(MapSerializer {
+            variant: Some(name),
+            map: Map::new(),
+            key: None,
+        })
+    }
+}
+
+struct SeqSerializer {
+    variant: Option<&'static str>,
+    items: Vec<Value>,
+}
+
+impl SeqSerializer {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ToValueError> {
+        self.items.push(value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Value {
+        let list = Value::List(self.items);
+        match self.variant {
+            Some(name) => variant(name, list),
+            None => list,
+        }
+    }
+}
+
+impl ser::SerializeSeq for SeqSerializer {
+    type Ok = Value;
+    type Error = ToValueError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ToValueError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, ToValueError> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeTuple for SeqSerializer {
+    type Ok = Value;
+    type Error = ToValueError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ToValueError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, ToValueError> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqSerializer {
+    type Ok = Value;
+    type Error = ToValueError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ToValueError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, ToValueError> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeTupleVariant for SeqSerializer {
+    type Ok = Value;
+    type Error = ToValueError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ToValueError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Value, ToValueError> {
+        Ok(self.finish())
+    }
+}
+
+struct MapSerializer {
+    variant: Option<&'static str>,
+    map: Map,
+    key: Option<String>,
+}
+
+impl MapSerializer {
+    fn insert<T: Serialize + ?Sized>(&mut self, key: String, value: &T) -> Result<(), ToValueError> {
+        self.map.set(key, value.serialize(ValueSerializer)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Value {
+        let map = Value::Map(self.map);
+        match self.variant {
+            Some(name) => variant(name, map),
+            None => map,
+        }
+    }
+}
+
+impl ser::SerializeMap for MapSerializer {
+    type Ok = Value;
+    type Error = ToValueError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), ToValueError> {
+        // Keys are turned into strings the way serde_json does
+        self.key = Some(match key.serialize(ValueSerializer)? {
+            Value::String(s) => s,
+            Value::Int(i) => i.to_string(),
+            Value::Bool(b) => b.to_string(),
+            other => return Err(error(format!("map key {} is not a string", other))),
+        });
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), ToValueError> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| error("map value serialized before its key"))?;
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<Value, ToValueError> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeStruct for MapSerializer {
+    type Ok = Value;
+    type Error = ToValueError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), ToValueError> {
+        self.insert(key.to_string(), value)
+    }
+
+    fn end(self) -> Result<Value, ToValueError> {
+        Ok(self.finish())
+    }
+}
+
+impl ser::SerializeStructVariant for MapSerializer {
+    type Ok = Value;
+    type Error = ToValueError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), ToValueError> {
+        self.insert(key.to_string(), value)
+    }
+
+    fn end(self) -> Result<Value, ToValueError> {
+        Ok(self.finish())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[derive(serde::Serialize)]
+    enum Shape {
+        Point,
+        Circle(f64),
+        Rect { w: u8, h: u8 },
+    }
+
+    #[test]
+    fn test_to_value() {
+        // Another library's document tree converts like its JSON text parses
+        let json = r#"{"a": [1, 2.5, "x", null, true], "b": {"c": 18446744073709551615}}"#;
+        let tree: serde_json::Value = serde_json::from_str(json).unwrap();
+        assert_eq!(to_value(&tree).unwrap(), super::super::from_json(json).unwrap());
+
+        let shapes = vec![Shape::Point, Shape::Circle(1.5), Shape::Rect { w: 1, h: 2 }];
+        let json = serde_json::to_string(&shapes).unwrap();
+        assert_eq!(to_value(&shapes).unwrap(), super::super::from_json(&json).unwrap());
+
+        let keyed: BTreeMap<i32, Option<&str>> = [(1, Some("one")), (2, None)].into();
+        assert_eq!(
+            super::super::to_json(&to_value(&keyed).unwrap()).unwrap(),
+            r#"{"1":"one","2":null}"#
+        );
+
+        assert!(to_value(&f64::NAN).is_err());
+        let unkeyable: BTreeMap<Vec<u8>, u8> = [(vec![1], 1)].into();
+        assert!(to_value(&unkeyable).is_err());
+    }
+}
@@ -0,0 +1,263 @@
+//! Structurally shared values.
+//!
+//! Objects with many repeated subtrees (for example hundreds of identical
+//! container env blocks) can be deduplicated into a SharedValue, where every
+//! distinct subtree is stored once and referenced through an Arc.
+
+use super::{Map, Value};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+/// SharedValue is an immutable value whose subtrees may be shared.
+///
+/// Cloning is cheap, and comparing two subtrees that were deduplicated
+/// together is a pointer comparison.
+#[derive(Clone)]
+pub struct SharedValue {
+    node: Arc<Node>,
+}
+
+struct Node {
+    hash: u64,
+    kind: Kind,
+}
+
+enum Kind {
+    Scalar(Value),
+    List(Vec<SharedValue>),
+    Map(BTreeMap<String, SharedValue>),
+}
+
+impl SharedValue {
+    /// Returns the scalar value, or None for lists and maps.
+    pub fn as_scalar(&self) -> Option<&Value> {
+        match &self.node.kind {
+            Kind::Scalar(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Returns the list items, or None if this is not a list.
+    pub fn as_list(&self) -> Option<&[SharedValue]> {
+        match &self.node.kind {
+            Kind::List(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    /// Returns the map entries, or None if this is not a map.
+    pub fn as_map(&self) -> Option<&BTreeMap<String, SharedValue>> {
+        match &self.node.kind {
+            Kind::Map(fields) => Some(fields),
+            _ => None,
+        }
+    }
+
+    /// Returns the content hash of this subtree.
+    pub fn content_hash(&self) -> u64 {
+        self.node.hash
+    }
+
+    /// Returns true if both values share the same storage.
+    pub fn ptr_eq(&self, other: &SharedValue) -> bool {
+        Arc::ptr_eq(&self.node, &other.node)
+    }
+
+    /// Converts back into an owned Value.
+    pub fn to_value(&self) -> Value {
+        match &self.node.kind {
+            Kind::Scalar(v) => v.clone(),
+            Kind::List(items) => Value::List(items.iter().map(SharedValue::to_value).collect()),
+            Kind::Map(fields) => Value::Map(Map {
+                fields: fields
+                    .iter()
+                    .map(|(k, v)| (k.clone(), v.to_value()))
+                    .collect(),
+            }),
+        }
+    }
+
+    fn new(kind: Kind) -> Self {
+        let mut hasher = DefaultHasher::new();
+        match &kind {
+            Kind::Scalar(v) => v.hash(&mut hasher),
+            Kind::List(items) => {
+                5u8.hash(&mut hasher);
+                items.len().hash(&mut hasher);
+                for item in items {
+                    item.node.hash.hash(&mut hasher);
+                }
+            }
+            Kind::Map(fields) => {
+                6u8.hash(&mut hasher);
+                fields.len().hash(&mut hasher);
+                for (k, v) in fields {
+                    k.hash(&mut hasher);
+                    v.node.hash.hash(&mut hasher);
+                }
+            }
+        }
+        SharedValue {
+            node: Arc::new(Node {
+                hash: hasher.finish(),
+                kind,
+            }),
+        }
+    }
+}
+
+impl PartialEq for SharedValue {
+    fn eq(&self, other: &Self) -> bool {
+        if self.ptr_eq(other) {
+            return true;
+        }
+        if self.node.hash != other.node.hash {
+            return false;
+        }
+        match (&self.node.kind, &other.node.kind) {
+            (Kind::Scalar(a), Kind::Scalar(b)) => a == b,
+            (Kind::List(a), Kind::List(b)) => a == b,
+            (Kind::Map(a), Kind::Map(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for SharedValue {}
+
+impl Hash for SharedValue {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.node.hash.hash(state);
+    }
+}
+
+impl fmt::Debug for SharedValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.node.kind {
+            Kind::Scalar(v) => v.fmt(f),
+            Kind::List(items) => f.debug_list().entries(items).finish(),
+            Kind::Map(fields) => f.debug_map().entries(fields).finish(),
+        }
+    }
+}
+
+impl From<&SharedValue> for Value {
+    fn from(v: &SharedValue) -> Self {
+        v.to_value()
+    }
+}
+
+/// Deduplicator interns subtrees so that identical ones share storage.
+///
+/// A single Deduplicator can be reused across several values to share
+/// subtrees between them as well.
+#[derive(Default)]
+pub struct Deduplicator {
+    seen: HashMap<u64, Vec<SharedValue>>,
+}
+
+impl Deduplicator {
+    /// Creates an empty Deduplicator.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the shared form of a value, reusing previously seen subtrees.
+    pub fn dedup(&mut self, value: &Value) -> SharedValue {
+        let kind = match value {
+            Value::List(items) => Kind::List(items.iter().map(|v| self.dedup(v)).collect()),
+            Value::Map(m) => Kind::Map(
+                m.fields
+                    .iter()
+                    .map(|(k, v)| (k.clone(), self.dedup(v)))
+                    .collect(),
+            ),
+            scalar => Kind::Scalar(scalar.clone()),
+        };
+        self.intern(SharedValue::new(kind))
+    }
+
+    /// Returns the number of distinct subtrees seen so far.
+    pub fn len(&self) -> usize {
+        self.seen.values().map(Vec::len).sum()
+    }
+
+    /// Returns true if no subtrees have been seen.
+    pub fn is_empty(&self) -> bool {
+        self.seen.is_empty()
+    }
+
+    fn intern(&mut self, candidate: SharedValue) -> SharedValue {
+        let bucket = self.seen.entry(candidate.node.hash).or_default();
+        // Children are already interned, so equality here is shallow.
+        if let Some(existing) = bucket.iter().find(|v| **v == candidate) {
+            return existing.clone();
+        }
+        bucket.push(candidate.clone());
+        candidate
+    }
+}
+
+impl Value {
+    /// Returns a structurally shared copy in which identical subtrees are
+    /// stored once.
+    pub fn dedup(&self) -> SharedValue {
+        Deduplicator::new().dedup(self)
+    }
+
+    /// Returns a hash of the value's content.
+    ///
+    /// Equal values always have the same content hash.
+    pub fn content_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.hash(&mut hasher);
+        hasher.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::value::from_yaml;
+
+    #[test]
+    fn test_dedup_shares_identical_subtrees() {
+        let value = from_yaml(
+            r#"
+containers:
+- name: a
+  env: [{name: X, value: "1"}, {name: Y, value: "2"}]
+- name: b
+  env: [{name: X, value: "1"}, {name: Y, value: "2"}]
+"#,
+        )
+        .unwrap();
+
+        let shared = value.dedup();
+        assert_eq!(shared.to_value(), value);
+        assert_eq!(shared.content_hash(), value.dedup().content_hash());
+
+        let containers = shared.as_map().unwrap()["containers"].as_list().unwrap();
+        let env_a = &containers[0].as_map().unwrap()["env"];
+        let env_b = &containers[1].as_map().unwrap()["env"];
+        assert!(env_a.ptr_eq(env_b));
+        assert!(!containers[0].ptr_eq(&containers[1]));
+    }
+
+    #[test]
+    fn test_deduplicator_across_values() {
+        let mut dedup = Deduplicator::new();
+        let a = dedup.dedup(&from_yaml("{spec: {replicas: 3}, name: a}").unwrap());
+        let b = dedup.dedup(&from_yaml("{spec: {replicas: 3}, name: b}").unwrap());
+
+        assert_ne!(a, b);
+        assert!(a.as_map().unwrap()["spec"].ptr_eq(&b.as_map().unwrap()["spec"]));
+        assert_eq!(
+            from_yaml("[1, 2]").unwrap().content_hash(),
+            from_yaml("[1, 2]").unwrap().content_hash()
+        );
+    }
+}
@@ -2,9 +2,13 @@
 
 use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
+use std::fmt;
 
 /// Value represents a JSON/YAML value that can be any of the supported types.
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+///
+/// Values format as JSON: compact with `{}` and `{:?}`, indented with
+/// `{:#}` and `{:#?}`.
+#[derive(Clone, Default, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum Value {
     #[default]
@@ -18,7 +22,7 @@ pub enum Value {
 }
 
 /// Map represents a key-value map where keys are strings.
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Clone, Default, Serialize, Deserialize)]
 #[serde(transparent)]
 pub struct Map {
     #[serde(flatten)]
@@ -108,6 +112,18 @@ impl Value {
             _ => None,
         }
     }
+
+    /// Returns the first list item that is a map whose fields match the
+    /// given key, or None if this is not a list.
+    ///
+    /// Every key field must be present in the item; schema defaults are not
+    /// applied.
+    pub fn find_by_key(&self, key: &FieldList) -> Option<&Value> {
+        self.as_list()?.iter().find(|item| match item {
+            Value::Map(m) => key.iter().all(|f| m.get(&f.name) == Some(&f.value)),
+            _ => false,
+        })
+    }
 }
 
 impl PartialEq for Value {
@@ -346,6 +362,34 @@ impl Ord for FieldList {
     }
 }
 
+/// Writes a value as JSON, indented in alternate mode.
+fn write_json<T: Serialize>(value: &T, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let json = if f.alternate() {
+        serde_json::to_string_pretty(value)
+    } else {
+        serde_json::to_string(value)
+    };
+    f.write_str(&json.map_err(|_| fmt::Error)?)
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_json(self, f)
+    }
+}
+
+impl fmt::Debug for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_json(self, f)
+    }
+}
+
+impl fmt::Debug for Map {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write_json(self, f)
+    }
+}
+
 /// Parse a value from JSON.
 pub fn from_json(json: &str) -> Result<Value, serde_json::Error> {
     serde_json::from_str(json)
@@ -381,6 +425,18 @@ mod tests {
         assert!(Value::Map(Map::new()).is_map());
     }
 
+    #[test]
+    fn test_value_format() {
+        let value = from_json(r#"{"a": [1, "x"], "b": null}"#).unwrap();
+        assert_eq!(format!("{}", value), r#"{"a":[1,"x"],"b":null}"#);
+        assert_eq!(format!("{:?}", value), r#"{"a":[1,"x"],"b":null}"#);
+        assert_eq!(
+            format!("{:#?}", value),
+            "{\n  \"a\": [\n    1,\n    \"x\"\n  ],\n  \"b\": null\n}"
+        );
+        assert_eq!(format!("{:#}", value), format!("{:#?}", value));
+    }
+
     #[test]
     fn test_value_equality() {
         assert_eq!(Value::Null, Value::Null);